@@ -0,0 +1,29 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+
+use mari_formats::unity::{Bundle, assemble_actor};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <body_unity3d_file> [<unity3d_file>...]", args[0]);
+        std::process::exit(1);
+    }
+
+    let mut bundles = Vec::new();
+    for path in &args[1..] {
+        bundles.push(Bundle::new(BufReader::new(File::open(path)?))?);
+    }
+
+    let actor = assemble_actor(&bundles)?;
+    if let Some(skeleton) = &actor.skeleton {
+        println!("{} bones.", skeleton.bones.len());
+    }
+    println!("body: {}", actor.body.repr());
+    for part in &actor.parts {
+        println!("{}: {}", part.name, part.model.repr());
+    }
+
+    Ok(())
+}
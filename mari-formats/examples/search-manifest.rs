@@ -0,0 +1,24 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <octocache_file> <regex>", args[0]);
+        std::process::exit(1);
+    }
+
+    let file = File::open(&args[1])?;
+    let reader = BufReader::new(file);
+
+    let manifest = mari_formats::gakumas::Manifest::new_from_octocache(reader)?;
+    for entry in manifest.search(&args[2])? {
+        println!(
+            "{} ({} bytes, md5 {}, object {})",
+            entry.name, entry.size, entry.md5, entry.object_name
+        );
+    }
+
+    Ok(())
+}
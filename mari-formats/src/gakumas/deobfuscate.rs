@@ -0,0 +1,74 @@
+const UNITYFS_SIGNATURE: &[u8] = b"UnityFS\0";
+/// only this many leading bytes of a bundle are obfuscated
+const HEADER_LEN: usize = 256;
+
+#[derive(Debug)]
+pub enum Error {
+    /// the data is neither UnityFS nor obfuscated UnityFS under the given bundle name
+    NotUnityFS,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// XOR mask derived from the bundle name, applied to the first 256 bytes of the download.
+///
+/// The transform is its own inverse, so the same `Deobfuscator` also obfuscates.
+pub struct Deobfuscator {
+    mask: Vec<u8>,
+}
+
+impl Deobfuscator {
+    /// `bundle_name` is `Entry::name` of the manifest, e.g. `mdl_chr_ttmr-casl-0000_body`
+    pub fn new(bundle_name: &str) -> Self {
+        let key = bundle_name.as_bytes();
+        let mut mask = vec![0; key.len() * 2];
+        let len = mask.len();
+        for (i, b) in key.iter().enumerate() {
+            mask[2 * i] = *b;
+            mask[len - 1 - 2 * i] = !*b;
+        }
+
+        let mut x = 0x9bu8;
+        for b in &mask {
+            x = x.rotate_right(1) ^ b;
+        }
+        for b in &mut mask {
+            *b ^= x;
+        }
+
+        Self { mask }
+    }
+
+    /// `stream_pos` is the offset of `data[0]` in the whole download, for chunked input.
+    pub fn apply(&self, data: &mut [u8], stream_pos: usize) {
+        if self.mask.is_empty() {
+            return;
+        }
+        for (i, b) in data
+            .iter_mut()
+            .take(HEADER_LEN.saturating_sub(stream_pos))
+            .enumerate()
+        {
+            *b ^= self.mask[(stream_pos + i) % self.mask.len()];
+        }
+    }
+}
+
+/// Turn a raw downloaded bundle into UnityFS data. Bundles that are already plain are passed through.
+pub fn deobfuscate_bundle(bundle_name: &str, mut data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    if data.starts_with(UNITYFS_SIGNATURE) {
+        return Ok(data);
+    }
+
+    Deobfuscator::new(bundle_name).apply(&mut data, 0);
+    if !data.starts_with(UNITYFS_SIGNATURE) {
+        return Err(Error::NotUnityFS);
+    }
+    Ok(data)
+}
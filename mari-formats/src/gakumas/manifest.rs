@@ -0,0 +1,230 @@
+use aes::cipher::{BlockDecryptMut, KeyIvInit, block_padding::Pkcs7};
+use md5::{Digest, Md5};
+use std::io::{BufReader, Read};
+
+const OCTOCACHE_KEY: &str = "1nuv9td1bw1udefk";
+const OCTOCACHE_IV: &str = "LvAUtf+tnz";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// ciphertext is not a whole number of blocks, or the padding is broken
+    Decrypt,
+    Checksum,
+    Protobuf(String),
+    Regex(regex::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// One downloadable object, either an asset bundle or a raw resource.
+#[derive(Debug, Clone, Default)]
+pub struct Entry {
+    pub id: i32,
+    /// e.g. `mdl_chr_ttmr-casl-0000_body`, also the deobfuscation key of bundles
+    pub name: String,
+    /// size in bytes of the downloaded object
+    pub size: u64,
+    pub crc: u32,
+    /// hex MD5 of the downloaded object
+    pub md5: String,
+    /// name of the object on the object server
+    pub object_name: String,
+    /// ids of the asset bundles this one depends on
+    pub dependencies: Vec<i32>,
+    pub generation: u64,
+}
+
+/// The asset catalog of the game, i.e. a decrypted `octocacheevai`.
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    pub revision: i32,
+    /// format of object URLs, `{o}` being `Entry::object_name`
+    pub url_format: String,
+    pub asset_bundles: Vec<Entry>,
+    pub resources: Vec<Entry>,
+}
+
+impl Manifest {
+    /// Read a locally saved, encrypted manifest.
+    pub fn new_from_octocache<R: Read>(mut buf: BufReader<R>) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        buf.read_to_end(&mut data).map_err(Error::Io)?;
+        Self::new_from_protobuf(&Self::decrypt(&data)?)
+    }
+
+    /// AES-128-CBC with MD5 of the known key and IV strings, then PKCS#7 unpadding.
+    ///
+    /// The plaintext starts with the MD5 of the protobuf payload that follows; only the payload is
+    /// returned.
+    pub fn decrypt(data: &[u8]) -> Result<Vec<u8>, Error> {
+        let key: [u8; 16] = Md5::digest(OCTOCACHE_KEY.as_bytes()).into();
+        let iv: [u8; 16] = Md5::digest(OCTOCACHE_IV.as_bytes()).into();
+
+        let plain = cbc::Decryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .map_err(|_| Error::Decrypt)?;
+        if plain.len() < 16 {
+            return Err(Error::Checksum);
+        }
+
+        let (hash, payload) = plain.split_at(16);
+        if Md5::digest(payload).as_slice() != hash {
+            return Err(Error::Checksum);
+        }
+        Ok(payload.to_vec())
+    }
+
+    /// Parse a decrypted `Database` protobuf message.
+    pub fn new_from_protobuf(data: &[u8]) -> Result<Self, Error> {
+        let mut manifest = Self::default();
+        let mut msg = Message::new(data);
+        while let Some((field, value)) = msg.next_field()? {
+            match (field, value) {
+                (1, Value::Varint(v)) => manifest.revision = v as i32,
+                (2, Value::Bytes(b)) => manifest.asset_bundles.push(parse_entry(b)?),
+                (4, Value::Bytes(b)) => manifest.resources.push(parse_entry(b)?),
+                (5, Value::Bytes(b)) => manifest.url_format = parse_string(b)?,
+                _ => {}
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Look up an asset bundle or a resource by its exact name.
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries().find(|e| e.name == name)
+    }
+
+    /// All entries whose name matches `pattern` anywhere, e.g. `m.search("mdl.*ttmr.*casl")`.
+    pub fn search(&self, pattern: &str) -> Result<Vec<&Entry>, Error> {
+        let re = regex::Regex::new(pattern).map_err(Error::Regex)?;
+        Ok(self.entries().filter(|e| re.is_match(&e.name)).collect())
+    }
+
+    /// asset bundles first, then resources
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.asset_bundles.iter().chain(self.resources.iter())
+    }
+}
+
+fn parse_entry(data: &[u8]) -> Result<Entry, Error> {
+    let mut entry = Entry::default();
+    let mut msg = Message::new(data);
+    while let Some((field, value)) = msg.next_field()? {
+        match (field, value) {
+            (1, Value::Varint(v)) => entry.id = v as i32,
+            (3, Value::Bytes(b)) => entry.name = parse_string(b)?,
+            (4, Value::Varint(v)) => entry.size = v,
+            (5, Value::Varint(v)) => entry.crc = v as u32,
+            (8, Value::Varint(v)) => entry.dependencies.push(v as i32),
+            (8, Value::Bytes(b)) => {
+                // packed repeated field
+                let mut packed = Message::new(b);
+                while !packed.is_empty() {
+                    entry.dependencies.push(packed.varint()? as i32);
+                }
+            }
+            (10, Value::Bytes(b)) => entry.md5 = parse_string(b)?,
+            (11, Value::Bytes(b)) => entry.object_name = parse_string(b)?,
+            (12, Value::Varint(v)) => entry.generation = v,
+            _ => {}
+        }
+    }
+    Ok(entry)
+}
+
+fn parse_string(data: &[u8]) -> Result<String, Error> {
+    String::from_utf8(data.to_vec()).map_err(|e| Error::Protobuf(e.to_string()))
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Fixed64,
+    Bytes(&'a [u8]),
+    Fixed32,
+}
+
+/// Just enough of the protobuf wire format to walk the fields of a message.
+struct Message<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Message<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = *self.data.get(self.pos).ok_or(Error::Protobuf(format!(
+                "Truncated varint @ byte {}.",
+                self.pos
+            )))?;
+            self.pos += 1;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(Error::Protobuf(format!(
+            "Varint too long @ byte {}.",
+            self.pos
+        )))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(Error::Protobuf(format!(
+                "Field of {} bytes @ byte {} overruns the message.",
+                n, self.pos
+            )))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, Value<'a>)>, Error> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed64
+            }
+            2 => {
+                let n = self.varint()? as usize;
+                Value::Bytes(self.take(n)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed32
+            }
+            t => {
+                return Err(Error::Protobuf(format!(
+                    "Unsupported wire type {} @ byte {}.",
+                    t, self.pos
+                )));
+            }
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
@@ -0,0 +1,9 @@
+//! Gakuen iDOLM@STER specific containers, in place of `reverse-eng/gakumas/GkmasObjectManager`.
+
+mod deobfuscate;
+mod manifest;
+
+pub use deobfuscate::Error as DeobfuscateError;
+pub use deobfuscate::{Deobfuscator, deobfuscate_bundle};
+pub use manifest::Error as ManifestError;
+pub use manifest::{Entry, Manifest};
@@ -0,0 +1,1230 @@
+//! glTF 2.0, as `.gltf` + `.bin` or as a single `.glb`.
+//!
+//! glTF is right-handed with +Y up and its texture coordinates start at the top-left corner, which
+//! is the space of `Model` already, so nothing is flipped either way.
+
+use std::path::Path;
+
+use serde_json::{Value as Json, json};
+
+use std::collections::HashMap;
+
+use crate::math::{
+    MAT4_IDENTITY, Mat4, mat4_from_trs, mat4_inverse, mat4_mul, mat4_to_trs, mat4_transform_point,
+    mat4_transform_vector,
+};
+use crate::{
+    Actor, Bone, Clip, Material, Model, Part, PbrMaterial, Scene, Skeleton, Submesh, TextureError,
+    TextureRGBA8, ToonMaterial, Track,
+};
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_SHORT: u32 = 5123;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const TRIANGLES: u64 = 4;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Png(png::EncodingError),
+    Texture(TextureError),
+    Invalid(String),
+    Unsupported(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn invalid(message: &str) -> Error {
+    Error::Invalid(message.to_string())
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    let b = data.get(at..at.checked_add(4)?)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn decode_base64(s: &str) -> Result<Vec<u8>, Error> {
+    let mut ret = Vec::with_capacity(s.len() / 4 * 3);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in s.bytes().filter(|c| *c != b'=' && !c.is_ascii_whitespace()) {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(invalid("Invalid base64 in a data URI.")),
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            ret.push((acc >> bits) as u8);
+        }
+    }
+    Ok(ret)
+}
+
+/// The rest pose of one glTF skin, bound to the skeleton of an actor.
+struct Skin {
+    /// world transform times inverse bind matrix of every joint
+    matrices: Vec<Mat4>,
+    /// index into `Skeleton::bones` of every joint
+    bones: Vec<u16>,
+}
+
+/// A parsed document with its buffers loaded.
+struct Document {
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Document {
+    fn new(data: &[u8], dir: Option<&Path>) -> Result<Self, Error> {
+        let (json, bin) = if data.starts_with(GLB_MAGIC) {
+            if u32_at(data, 4) != Some(2) {
+                return Err(Error::Unsupported("GLB other than version 2".to_string()));
+            }
+            let mut chunks = Vec::new();
+            let mut at = 12;
+            while let (Some(len), Some(kind)) = (u32_at(data, at), u32_at(data, at + 4)) {
+                let end = (at + 8).checked_add(len as usize);
+                let chunk = end
+                    .and_then(|end| data.get(at + 8..end))
+                    .ok_or(invalid("Truncated GLB chunk."))?;
+                chunks.push((kind, chunk));
+                at += 8 + chunk.len();
+            }
+            let json = chunks
+                .iter()
+                .find(|(kind, _)| *kind == CHUNK_JSON)
+                .ok_or(invalid("GLB without a JSON chunk."))?
+                .1;
+            let bin = chunks.iter().find(|(kind, _)| *kind == CHUNK_BIN);
+            (json, bin.map(|(_, b)| b.to_vec()))
+        } else {
+            (data, None)
+        };
+        let json: Json = serde_json::from_slice(json).map_err(Error::Json)?;
+
+        let mut buffers = Vec::new();
+        let mut bin = bin;
+        for buffer in json["buffers"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            buffers.push(match buffer["uri"].as_str() {
+                None => bin.take().ok_or(invalid("Buffer without data."))?,
+                Some(uri) => load_uri(uri, dir)?,
+            });
+        }
+        Ok(Self { json, buffers })
+    }
+
+    fn view(&self, index: u64) -> Result<(&[u8], usize), Error> {
+        let view = &self.json["bufferViews"][index as usize];
+        let buffer = self
+            .buffers
+            .get(view["buffer"].as_u64().unwrap_or(u64::MAX) as usize)
+            .ok_or(invalid("Buffer view into a missing buffer."))?;
+        let offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let len = view["byteLength"].as_u64().unwrap_or(0) as usize;
+        let data = offset
+            .checked_add(len)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or(invalid("Buffer view overruns its buffer."))?;
+        Ok((data, view["byteStride"].as_u64().unwrap_or(0) as usize))
+    }
+
+    /// all components of an accessor as floats, normalized integers mapped to [0, 1] or [-1, 1]
+    fn accessor(&self, index: &Json) -> Result<(Vec<f32>, usize), Error> {
+        let accessor = index
+            .as_u64()
+            .and_then(|i| self.json["accessors"].get(i as usize))
+            .ok_or(invalid("Missing accessor."))?;
+        if !accessor["sparse"].is_null() {
+            return Err(Error::Unsupported("Sparse accessors".to_string()));
+        }
+        let comps = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(invalid("Accessor of unknown type.")),
+        };
+        let kind = accessor["componentType"].as_u64().unwrap_or(0) as u32;
+        let size = match kind {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | FLOAT => 4,
+            _ => return Err(invalid("Accessor of unknown component type.")),
+        };
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+        let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+
+        let Some(view) = accessor["bufferView"].as_u64() else {
+            return Ok((vec![0.0; count * comps], comps));
+        };
+        let (data, stride) = self.view(view)?;
+        let stride = if stride == 0 { size * comps } else { stride };
+        let offset = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+
+        let mut ret = Vec::with_capacity(count * comps);
+        for i in 0..count {
+            for c in 0..comps {
+                let at = offset + i * stride + c * size;
+                let b = data
+                    .get(at..at + size)
+                    .ok_or(invalid("Accessor overruns its buffer view."))?;
+                let v = match kind {
+                    5120 => b[0] as i8 as f32,
+                    5121 => b[0] as f32,
+                    5122 => i16::from_le_bytes([b[0], b[1]]) as f32,
+                    5123 => u16::from_le_bytes([b[0], b[1]]) as f32,
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                };
+                ret.push(match (normalized, kind) {
+                    (true, 5120) => (v / 127.0).max(-1.0),
+                    (true, 5121) => v / 255.0,
+                    (true, 5122) => (v / 32767.0).max(-1.0),
+                    (true, 5123) => v / 65535.0,
+                    _ => v,
+                });
+            }
+        }
+        Ok((ret, comps))
+    }
+
+    fn local(node: &Json) -> Mat4 {
+        if let Some(m) = node["matrix"].as_array() {
+            let mut ret = [0.0; 16];
+            for (r, v) in ret.iter_mut().zip(m) {
+                *r = v.as_f64().unwrap_or(0.0) as f32;
+            }
+            return ret;
+        }
+        let vector = |name: &str, default: &[f32]| -> Vec<f32> {
+            match node[name].as_array() {
+                Some(a) => a.iter().map(|v| v.as_f64().unwrap_or(0.0) as f32).collect(),
+                None => default.to_vec(),
+            }
+        };
+        let t = vector("translation", &[0.0; 3]);
+        let q = vector("rotation", &[0.0, 0.0, 0.0, 1.0]);
+        let s = vector("scale", &[1.0; 3]);
+        mat4_from_trs(
+            &[t[0], t[1], t[2]],
+            &[q[0], q[1], q[2], q[3]],
+            &[s[0], s[1], s[2]],
+        )
+    }
+
+    /// a mesh baked with the world transform of its node, or skinned in the rest pose of `skin`
+    fn model(&self, mesh: &Json, world: &Mat4, skin: Option<&Skin>) -> Result<Model, Error> {
+        let primitives: Vec<&Json> = mesh["primitives"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter(|p| p["mode"].as_u64().unwrap_or(TRIANGLES) == TRIANGLES)
+            .collect();
+        let has = |attribute: &str| {
+            primitives
+                .iter()
+                .all(|p| !p["attributes"][attribute].is_null())
+        };
+        let (has_normals, has_uvs) = (has("NORMAL"), has("TEXCOORD_0"));
+        let has_colors = has("COLOR_0");
+        let skin = skin.filter(|_| has("JOINTS_0") && has("WEIGHTS_0"));
+
+        let mut model = Model {
+            vertices: Vec::new(),
+            mesh: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
+            submeshes: Vec::new(),
+        };
+        // a mirroring transform turns the winding around
+        let det = world[0] * (world[5] * world[10] - world[9] * world[6])
+            - world[4] * (world[1] * world[10] - world[9] * world[2])
+            + world[8] * (world[1] * world[6] - world[5] * world[2]);
+
+        let mut shared_vertices: Vec<(&Json, (usize, usize))> = Vec::new();
+        for p in primitives {
+            // primitives sharing their attributes share their vertices
+            let shared = shared_vertices
+                .iter()
+                .find(|(attributes, _)| *attributes == &p["attributes"]);
+            let (base, count) = match shared {
+                Some((_, vertices)) => *vertices,
+                None => {
+                    let base = model.vertices.len() / 3;
+                    let (positions, _) = self.accessor(&p["attributes"]["POSITION"])?;
+                    let count = positions.len() / 3;
+                    if base + count > u16::MAX as usize + 1 {
+                        return Err(Error::Unsupported(format!(
+                            "Mesh {} with more than 65536 vertices",
+                            mesh["name"].as_str().unwrap_or_default()
+                        )));
+                    }
+                    // the transform of every vertex and its joints and weights
+                    let mut transforms = vec![*world; count];
+                    if let Some(skin) = skin {
+                        let (joints, _) = self.accessor(&p["attributes"]["JOINTS_0"])?;
+                        let (weights, _) = self.accessor(&p["attributes"]["WEIGHTS_0"])?;
+                        if joints.len() != count * 4 || weights.len() != count * 4 {
+                            return Err(invalid("Joints or weights of the wrong type."));
+                        }
+                        for (i, transform) in transforms.iter_mut().enumerate() {
+                            *transform = [0.0; 16];
+                            for k in i * 4..i * 4 + 4 {
+                                let j = joints[k] as usize;
+                                let matrix =
+                                    skin.matrices.get(j).ok_or(invalid("Joint out of range."))?;
+                                for (t, m) in transform.iter_mut().zip(matrix) {
+                                    *t += weights[k] * m;
+                                }
+                                model.joints.push(skin.bones[j]);
+                                model.weights.push(weights[k]);
+                            }
+                        }
+                    }
+                    for (v, transform) in positions.chunks_exact(3).zip(&transforms) {
+                        model
+                            .vertices
+                            .extend(mat4_transform_point(transform, &[v[0], v[1], v[2]]));
+                    }
+                    if has_normals {
+                        let (normals, _) = self.accessor(&p["attributes"]["NORMAL"])?;
+                        for (n, transform) in normals.chunks_exact(3).zip(&transforms) {
+                            let [x, y, z] = mat4_transform_vector(transform, &[n[0], n[1], n[2]]);
+                            let l = (x * x + y * y + z * z).sqrt();
+                            let l = if l == 0.0 { 1.0 } else { l };
+                            model.normals.extend([x / l, y / l, z / l]);
+                        }
+                    }
+                    if has_colors {
+                        // rgb colors are opaque
+                        let (colors, comps) = self.accessor(&p["attributes"]["COLOR_0"])?;
+                        model.colors.extend(
+                            colors
+                                .chunks_exact(comps)
+                                .flat_map(|c| [c[0], c[1], c[2], c.get(3).copied().unwrap_or(1.0)]),
+                        );
+                    }
+                    if has_uvs {
+                        let (uvs, comps) = self.accessor(&p["attributes"]["TEXCOORD_0"])?;
+                        model
+                            .uvs
+                            .extend(uvs.chunks_exact(comps).flat_map(|uv| [uv[0], uv[1]]));
+                    }
+                    shared_vertices.push((&p["attributes"], (base, count)));
+                    (base, count)
+                }
+            };
+
+            let mut indices: Vec<u16> = if p["indices"].is_null() {
+                (0..count).map(|i| (base + i) as u16).collect()
+            } else {
+                let (indices, _) = self.accessor(&p["indices"])?;
+                if indices.iter().any(|i| *i as usize >= count) {
+                    return Err(invalid("Index past the vertices of its primitive."));
+                }
+                indices
+                    .iter()
+                    .map(|i| (base + *i as usize) as u16)
+                    .collect()
+            };
+            indices.truncate(indices.len() / 3 * 3);
+            if det < 0.0 && skin.is_none() {
+                indices.chunks_exact_mut(3).for_each(|t| t.swap(1, 2));
+            }
+            let material = p["material"]
+                .as_u64()
+                .and_then(|m| self.json["materials"][m as usize]["name"].as_str());
+            model.submeshes.push(Submesh {
+                first: model.mesh.len(),
+                count: indices.len(),
+                material: material.map(str::to_string),
+            });
+            model.mesh.extend(indices);
+        }
+        Ok(model)
+    }
+
+    /// the base color texture of the first material of a mesh
+    fn texture(&self, mesh: &Json, dir: Option<&Path>) -> Result<Option<TextureRGBA8>, Error> {
+        match mesh["primitives"][0]["material"].as_u64() {
+            Some(m) => self.base_texture(&self.json["materials"][m as usize], dir),
+            None => Ok(None),
+        }
+    }
+
+    /// `Material::Unlit` or `Material::Textured` with `KHR_materials_unlit`, `Material::Toon` with
+    /// toon extras, else `Material::Pbr`
+    fn material(&self, material: &Json, dir: Option<&Path>) -> Result<Material, Error> {
+        let pbr = &material["pbrMetallicRoughness"];
+        let factor = |v: &Json, default: f32| v.as_f64().map_or(default, |v| v as f32);
+        let mut base_color = [1.0; 4];
+        if let Some(values) = pbr["baseColorFactor"].as_array() {
+            for (c, v) in base_color.iter_mut().zip(values) {
+                *c = factor(v, 1.0);
+            }
+        }
+        let texture = self.base_texture(material, dir)?;
+        let toon = &material["extras"]["toon"];
+        if toon.is_object() {
+            let required = |texture: Option<TextureRGBA8>, what: &str| {
+                texture.ok_or_else(|| invalid(&format!("Toon material without a {what}.")))
+            };
+            let mut outline_color = [0.0, 0.0, 0.0, 1.0];
+            if let Some(values) = toon["outlineColor"].as_array() {
+                for (c, v) in outline_color.iter_mut().zip(values) {
+                    *c = factor(v, 1.0);
+                }
+            }
+            return Ok(Material::Toon(ToonMaterial {
+                texture: required(texture, "base color texture")?,
+                ramp_texture: required(self.texture_of(&toon["rampTexture"], dir)?, "ramp")?,
+                sdw_texture: required(self.texture_of(&toon["sdwTexture"], dir)?, "shadow map")?,
+                shadow_threshold: factor(&toon["shadowThreshold"], ToonMaterial::SHADOW_THRESHOLD),
+                ramp_scale: factor(&toon["rampScale"], ToonMaterial::RAMP_SCALE),
+                outline_width: factor(&toon["outlineWidth"], 0.0),
+                outline_color,
+            }));
+        }
+        if material["extensions"]["KHR_materials_unlit"].is_object() {
+            return Ok(match texture {
+                Some(texture) => Material::Textured { texture },
+                None => Material::Unlit { color: base_color },
+            });
+        }
+        Ok(Material::Pbr(PbrMaterial {
+            base_color,
+            base_color_texture: texture,
+            metallic: factor(&pbr["metallicFactor"], 1.0),
+            roughness: factor(&pbr["roughnessFactor"], 1.0),
+        }))
+    }
+
+    /// the base color texture of a material
+    fn base_texture(
+        &self,
+        material: &Json,
+        dir: Option<&Path>,
+    ) -> Result<Option<TextureRGBA8>, Error> {
+        self.texture_of(&material["pbrMetallicRoughness"]["baseColorTexture"], dir)
+    }
+
+    /// the texture a texture info refers to
+    fn texture_of(&self, info: &Json, dir: Option<&Path>) -> Result<Option<TextureRGBA8>, Error> {
+        let image = info["index"]
+            .as_u64()
+            .and_then(|t| self.json["textures"][t as usize]["source"].as_u64())
+            .map(|i| &self.json["images"][i as usize]);
+        let Some(image) = image else {
+            return Ok(None);
+        };
+        let data = match (image["bufferView"].as_u64(), image["uri"].as_str()) {
+            (Some(view), _) => self.view(view)?.0.to_vec(),
+            (None, Some(uri)) => load_uri(uri, dir)?,
+            _ => return Err(invalid("Image without data.")),
+        };
+        TextureRGBA8::load(&data).map(Some).map_err(Error::Texture)
+    }
+}
+
+impl Document {
+    /// a skin in the rest pose of the nodes, its joints found among the bones `joints`
+    fn skin(&self, index: u64, worlds: &HashMap<u64, Mat4>, joints: &[u64]) -> Result<Skin, Error> {
+        let skin = &self.json["skins"][index as usize];
+        let nodes: Vec<u64> = skin["joints"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Json::as_u64)
+            .collect();
+        let inverse_binds = match skin["inverseBindMatrices"].is_null() {
+            true => MAT4_IDENTITY.repeat(nodes.len()),
+            false => self.accessor(&skin["inverseBindMatrices"])?.0,
+        };
+        if inverse_binds.len() != nodes.len() * 16 {
+            return Err(invalid("Inverse bind matrices do not match the joints."));
+        }
+        let matrices = nodes
+            .iter()
+            .zip(inverse_binds.chunks_exact(16))
+            .map(|(n, inverse_bind)| mat4_mul(&worlds[n], inverse_bind.try_into().unwrap()))
+            .collect();
+        let bones = nodes
+            .iter()
+            .map(|n| joints.iter().position(|j| j == n).unwrap_or(0) as u16)
+            .collect();
+        Ok(Skin { matrices, bones })
+    }
+
+    /// the channels of an animation targeting joints, resampled at the union of their key times
+    fn clip(&self, animation: &Json, joint_names: &HashMap<u64, String>) -> Result<Clip, Error> {
+        let mut channels = Vec::new();
+        for channel in animation["channels"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            let target = &channel["target"];
+            let Some(bone) = target["node"].as_u64().and_then(|n| joint_names.get(&n)) else {
+                continue;
+            };
+            let width = match target["path"].as_str() {
+                Some("translation") | Some("scale") => 3,
+                Some("rotation") => 4,
+                _ => continue,
+            };
+            let sampler = &animation["samplers"][channel["sampler"].as_u64().unwrap_or(0) as usize];
+            let (times, _) = self.accessor(&sampler["input"])?;
+            let (mut values, _) = self.accessor(&sampler["output"])?;
+            let interpolation = sampler["interpolation"].as_str().unwrap_or("LINEAR");
+            if interpolation == "CUBICSPLINE" {
+                // in-tangent, value and out-tangent per key
+                values = values
+                    .chunks_exact(width * 3)
+                    .flat_map(|k| k[width..width * 2].to_vec())
+                    .collect();
+            }
+            if times.is_empty() || values.len() != times.len() * width {
+                return Err(invalid("Animation sampler with mismatched keys."));
+            }
+            channels.push((
+                bone,
+                target["path"].as_str().unwrap(),
+                times,
+                values,
+                interpolation,
+            ));
+        }
+
+        let mut times: Vec<f32> = channels.iter().flat_map(|c| c.2.clone()).collect();
+        times.sort_by(f32::total_cmp);
+        times.dedup();
+        let mut tracks: Vec<Track> = Vec::new();
+        for (bone, path, keys, values, interpolation) in channels {
+            let width = values.len() / keys.len();
+            let sampled: Vec<f32> = times
+                .iter()
+                .flat_map(|t| sample(&keys, &values, width, interpolation == "STEP", *t))
+                .collect();
+            let track = match tracks.iter_mut().find(|t| t.bone == *bone) {
+                Some(track) => track,
+                None => {
+                    tracks.push(Track {
+                        bone: bone.clone(),
+                        translations: Vec::new(),
+                        rotations: Vec::new(),
+                        scales: Vec::new(),
+                    });
+                    tracks.last_mut().unwrap()
+                }
+            };
+            match path {
+                "translation" => track.translations = sampled,
+                "rotation" => track.rotations = sampled,
+                _ => track.scales = sampled,
+            }
+        }
+        Ok(Clip { times, tracks })
+    }
+}
+
+/// the value of a channel at time `t`, rotations interpolated linearly then normalized
+fn sample(keys: &[f32], values: &[f32], width: usize, step: bool, t: f32) -> Vec<f32> {
+    let at = |k: usize| &values[k * width..k * width + width];
+    let next = keys.partition_point(|k| *k <= t);
+    if next == 0 {
+        return at(0).to_vec();
+    }
+    if next == keys.len() || step {
+        return at(next - 1).to_vec();
+    }
+    let (a, b) = (at(next - 1), at(next));
+    let f = (t - keys[next - 1]) / (keys[next] - keys[next - 1]);
+    // take the shorter way around between quaternions
+    let sign = if width == 4 && a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>() < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+    let mut ret: Vec<f32> = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| a + (sign * b - a) * f)
+        .collect();
+    if width == 4 {
+        let l = ret.iter().map(|v| v * v).sum::<f32>().sqrt();
+        ret.iter_mut().for_each(|v| *v /= l);
+    }
+    ret
+}
+
+fn load_uri(uri: &str, dir: Option<&Path>) -> Result<Vec<u8>, Error> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, data) = data.split_once(";base64,").ok_or(Error::Unsupported(
+            "Data URIs other than base64".to_string(),
+        ))?;
+        return decode_base64(data);
+    }
+    let dir = dir.ok_or(Error::Unsupported(format!(
+        "External file {uri} without a directory"
+    )))?;
+    let path = uri.replace("%20", " ");
+    std::fs::read(dir.join(path)).map_err(Error::Io)
+}
+
+/// Add `texture` to `images` as a PNG, returning its index.
+fn push_png(
+    images: &mut Vec<(String, Vec<u8>)>,
+    name: &str,
+    texture: &TextureRGBA8,
+) -> Result<usize, Error> {
+    let png = texture.encode_png().map_err(Error::Png)?;
+    images.push((name.to_string(), png));
+    Ok(images.len() - 1)
+}
+
+/// `material` as a glTF material, its textures added to `images`.
+///
+/// Unlit and textured materials use `KHR_materials_unlit`. A toon material has its texture as base
+/// color, and its other textures and parameters in `extras.toon` for `new_from_gltf` to read back.
+fn material_json(
+    name: &str,
+    material: &Material,
+    images: &mut Vec<(String, Vec<u8>)>,
+) -> Result<Json, Error> {
+    let mut texture = |suffix: &str, texture: &TextureRGBA8| -> Result<Json, Error> {
+        let index = push_png(images, &format!("{name}{suffix}"), texture)?;
+        Ok(json!({ "index": index }))
+    };
+    let mut ret = json!({ "name": name });
+    match material {
+        Material::Unlit { color } => {
+            ret["pbrMetallicRoughness"] =
+                json!({ "baseColorFactor": color, "metallicFactor": 0.0 });
+            ret["extensions"] = json!({ "KHR_materials_unlit": {} });
+        }
+        Material::Textured { texture: t } => {
+            ret["pbrMetallicRoughness"] = json!({
+                "baseColorTexture": texture("", t)?,
+                "metallicFactor": 0.0,
+            });
+            ret["extensions"] = json!({ "KHR_materials_unlit": {} });
+        }
+        Material::Toon(toon) => {
+            ret["pbrMetallicRoughness"] = json!({
+                "baseColorTexture": texture("", &toon.texture)?,
+                "metallicFactor": 0.0,
+            });
+            ret["extras"] = json!({
+                "toon": {
+                    "rampTexture": texture("_ramp", &toon.ramp_texture)?,
+                    "sdwTexture": texture("_sdw", &toon.sdw_texture)?,
+                    "shadowThreshold": toon.shadow_threshold,
+                    "rampScale": toon.ramp_scale,
+                    "outlineWidth": toon.outline_width,
+                    "outlineColor": toon.outline_color,
+                }
+            });
+        }
+        Material::Pbr(pbr) => {
+            ret["pbrMetallicRoughness"] = json!({
+                "baseColorFactor": pbr.base_color,
+                "metallicFactor": pbr.metallic,
+                "roughnessFactor": pbr.roughness,
+            });
+            if let Some(t) = &pbr.base_color_texture {
+                ret["pbrMetallicRoughness"]["baseColorTexture"] = texture("", t)?;
+            }
+        }
+    }
+    Ok(ret)
+}
+
+impl Scene {
+    /// Read a `.gltf` or `.glb`, resolving external files against `dir`.
+    ///
+    /// Every root node of the default scene with meshes below it becomes an actor named after the
+    /// node. The meshes are baked with their world transform, or skinned in the rest pose, the
+    /// largest is the body and the others are parts. The joints of all skins below a root node make
+    /// up the skeleton of its actor. The base color texture of the body, if PNG, becomes the texture
+    /// of the actor. Named materials become `Material::Pbr`, unlit ones with `KHR_materials_unlit`,
+    /// or toon ones with the toon extras `write_gltf` writes.
+    ///
+    /// Animations become clips resampled at the union of their key times, cubic splines being read
+    /// as linear.
+    pub fn new_from_gltf(data: &[u8], dir: Option<&Path>) -> Result<Self, Error> {
+        let doc = Document::new(data, dir)?;
+        let json = &doc.json;
+        let scene = &json["scenes"][json["scene"].as_u64().unwrap_or(0) as usize];
+        let node_cnt = json["nodes"].as_array().map_or(0, |n| n.len());
+        let roots: Vec<u64> = match scene["nodes"].as_array() {
+            Some(nodes) => nodes.iter().filter_map(Json::as_u64).collect(),
+            None => (0..node_cnt as u64).collect(),
+        };
+        let node_name = |n: u64| {
+            json["nodes"][n as usize]["name"]
+                .as_str()
+                .map_or_else(|| format!("node{n}"), str::to_string)
+        };
+
+        let mut ret = Scene::default();
+        // bone name of every node that is a joint
+        let mut joint_names = HashMap::new();
+        for (i, root) in roots.iter().enumerate() {
+            // depth first, so parents come before their children
+            let mut order = Vec::new();
+            let mut worlds = HashMap::new();
+            let mut parents = HashMap::new();
+            let mut stack = vec![(*root, None)];
+            while let Some((n, parent)) = stack.pop() {
+                let node = json["nodes"]
+                    .get(n as usize)
+                    .ok_or(invalid("Missing node."))?;
+                if order.len() >= node_cnt || worlds.contains_key(&n) {
+                    return Err(invalid("Node cycle."));
+                }
+                let parent_world = parent.map_or(MAT4_IDENTITY, |p| worlds[&p]);
+                worlds.insert(n, mat4_mul(&parent_world, &Document::local(node)));
+                if let Some(p) = parent {
+                    parents.insert(n, p);
+                }
+                order.push(n);
+                let children = node["children"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                for c in children.iter().rev() {
+                    stack.push((c.as_u64().ok_or(invalid("Invalid child."))?, Some(n)));
+                }
+            }
+
+            let skins: Vec<u64> = order
+                .iter()
+                .filter(|n| !json["nodes"][**n as usize]["mesh"].is_null())
+                .filter_map(|n| json["nodes"][*n as usize]["skin"].as_u64())
+                .collect();
+            let mut joints = Vec::new();
+            for skin in &skins {
+                for j in json["skins"][*skin as usize]["joints"]
+                    .as_array()
+                    .ok_or(invalid("Skin without joints."))?
+                {
+                    let j = j.as_u64().ok_or(invalid("Invalid joint."))?;
+                    if !worlds.contains_key(&j) {
+                        return Err(Error::Unsupported(
+                            "Joints outside the root node of their mesh".to_string(),
+                        ));
+                    }
+                    joints.push(j);
+                }
+            }
+            let joints: Vec<u64> = order
+                .iter()
+                .copied()
+                .filter(|n| joints.contains(n))
+                .collect();
+            let skeleton = (!joints.is_empty()).then(|| {
+                let bones = joints
+                    .iter()
+                    .map(|n| {
+                        let mut parent = parents.get(n);
+                        while let Some(p) = parent.filter(|p| !joints.contains(p)) {
+                            parent = parents.get(p);
+                        }
+                        let local = match parent {
+                            Some(p) => mat4_mul(&mat4_inverse(&worlds[p]), &worlds[n]),
+                            None => worlds[n],
+                        };
+                        let (translation, rotation, scale) = mat4_to_trs(&local);
+                        Bone {
+                            name: node_name(*n),
+                            parent: parent.and_then(|p| joints.iter().position(|j| j == p)),
+                            translation,
+                            rotation,
+                            scale,
+                        }
+                    })
+                    .collect();
+                Skeleton { bones }
+            });
+            for j in &joints {
+                joint_names.insert(*j, node_name(*j));
+            }
+
+            let mut models = Vec::new();
+            for n in &order {
+                let node = &json["nodes"][*n as usize];
+                let Some(m) = node["mesh"].as_u64() else {
+                    continue;
+                };
+                let skin = match node["skin"].as_u64() {
+                    Some(skin) => Some(doc.skin(skin, &worlds, &joints)?),
+                    None => None,
+                };
+                let mesh = &json["meshes"][m as usize];
+                let name = node["name"].as_str().or(mesh["name"].as_str());
+                models.push((
+                    name.unwrap_or_default().to_string(),
+                    doc.model(mesh, &worlds[n], skin.as_ref())?,
+                    mesh,
+                ));
+            }
+
+            // the first of equally large meshes
+            let Some(body) = (0..models.len())
+                .rev()
+                .max_by_key(|m| models[*m].1.vertices.len())
+            else {
+                continue;
+            };
+            let (_, body, mesh) = models.remove(body);
+            let mut name = json["nodes"][*root as usize]["name"]
+                .as_str()
+                .map_or_else(|| format!("actor{i}"), str::to_string);
+            if ret.actors.contains_key(&name) {
+                name = format!("{name}{i}");
+            }
+            if let Some(texture) = doc.texture(mesh, dir)? {
+                ret.textures.insert(name.clone(), texture);
+            }
+            let mut actor = Actor::new(body);
+            actor.parts = models
+                .into_iter()
+                .map(|(name, model, _)| Part { name, model })
+                .collect();
+            actor.skeleton = skeleton;
+            ret.actors.insert(name, actor);
+        }
+
+        let animations = json["animations"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for (i, animation) in animations.iter().enumerate() {
+            let clip = doc.clip(animation, &joint_names)?;
+            if !clip.tracks.is_empty() {
+                let name = animation["name"]
+                    .as_str()
+                    .map_or_else(|| format!("animation{i}"), str::to_string);
+                ret.clips.insert(name, clip);
+            }
+        }
+
+        let materials = json["materials"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for material in materials {
+            // submeshes refer to materials by name
+            if let Some(name) = material["name"].as_str() {
+                let material = doc.material(material, dir)?;
+                ret.materials.insert(name.to_string(), material);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// The document and its single buffer.
+    ///
+    /// Every actor is a root node with its bones and its models below it. A skeleton becomes a skin
+    /// bound in the rest pose, and every clip an animation of the bones of the same name of every
+    /// skeleton. Every material of `materials` is written, see `material_json`; a submesh naming
+    /// none of them gets a material with the texture of its actor.
+    fn to_gltf(&self) -> Result<(Json, Vec<u8>), Error> {
+        let mut bin = Vec::new();
+        let mut views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut push_view = |bin: &mut Vec<u8>, bytes: &[u8], target: Option<u32>| {
+            while !bin.len().is_multiple_of(4) {
+                bin.push(0);
+            }
+            let mut view = json!({
+                "buffer": 0,
+                "byteOffset": bin.len(),
+                "byteLength": bytes.len(),
+            });
+            if let Some(target) = target {
+                view["target"] = json!(target);
+            }
+            bin.extend_from_slice(bytes);
+            views.push(view);
+            views.len() - 1
+        };
+        let floats = |v: &[f32]| -> Vec<u8> { v.iter().flat_map(|f| f.to_le_bytes()).collect() };
+
+        let mut names: Vec<&String> = self.actors.keys().collect();
+        names.sort();
+
+        let mut nodes = Vec::new();
+        let mut roots = Vec::new();
+        let mut meshes = Vec::new();
+        let mut materials: Vec<Json> = Vec::new();
+        // actor, `None` for the materials of the scene, and material name of each of `materials`
+        let mut material_keys: Vec<(Option<&str>, String)> = Vec::new();
+        // name and PNG of every image
+        let mut images: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut skins = Vec::new();
+        // skeleton and first joint node of every skinned actor
+        let mut joint_nodes: Vec<(&Skeleton, usize)> = Vec::new();
+        for name in names {
+            let actor = &self.actors[name];
+            let texture = match self.textures.get(name) {
+                Some(texture) => Some(push_png(&mut images, name, texture)?),
+                None => None,
+            };
+
+            // joint nodes first, so that mesh nodes can refer to their skin
+            let mut children = Vec::new();
+            let mut skin = None;
+            if let Some(skeleton) = &actor.skeleton {
+                let base = nodes.len();
+                let mut worlds: Vec<Mat4> = Vec::with_capacity(skeleton.bones.len());
+                for (i, bone) in skeleton.bones.iter().enumerate() {
+                    let local = mat4_from_trs(&bone.translation, &bone.rotation, &bone.scale);
+                    worlds.push(match bone.parent {
+                        Some(p) => mat4_mul(&worlds[p], &local),
+                        None => local,
+                    });
+                    nodes.push(json!({
+                        "name": bone.name,
+                        "translation": bone.translation,
+                        "rotation": bone.rotation,
+                        "scale": bone.scale,
+                    }));
+                    match bone.parent {
+                        Some(p) => match nodes[base + p]["children"].as_array_mut() {
+                            Some(c) => c.push(json!(base + i)),
+                            None => nodes[base + p]["children"] = json!([base + i]),
+                        },
+                        None => children.push(base + i),
+                    }
+                }
+                let inverse_binds: Vec<f32> = worlds.iter().flat_map(mat4_inverse).collect();
+                let view = push_view(&mut bin, &floats(&inverse_binds), None);
+                accessors.push(json!({
+                    "bufferView": view,
+                    "componentType": FLOAT,
+                    "count": worlds.len(),
+                    "type": "MAT4",
+                }));
+                skins.push(json!({
+                    "name": name,
+                    "joints": (base..nodes.len()).collect::<Vec<_>>(),
+                    "inverseBindMatrices": accessors.len() - 1,
+                }));
+                skin = Some(skins.len() - 1);
+                joint_nodes.push((skeleton, base));
+            }
+
+            let models = std::iter::once((name.as_str(), &actor.body))
+                .chain(actor.parts.iter().map(|p| (p.name.as_str(), &p.model)));
+            for (model_name, model) in models {
+                let vertex_cnt = model.vertices.len() / 3;
+                let mut attributes = serde_json::Map::new();
+
+                let view = push_view(&mut bin, &floats(&model.vertices), Some(ARRAY_BUFFER));
+                let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+                for v in model.vertices.chunks_exact(3) {
+                    for c in 0..3 {
+                        min[c] = min[c].min(v[c]);
+                        max[c] = max[c].max(v[c]);
+                    }
+                }
+                accessors.push(json!({
+                    "bufferView": view,
+                    "componentType": FLOAT,
+                    "count": vertex_cnt,
+                    "type": "VEC3",
+                    "min": min,
+                    "max": max,
+                }));
+                attributes.insert("POSITION".to_string(), json!(accessors.len() - 1));
+                if model.normals.len() == model.vertices.len() {
+                    let view = push_view(&mut bin, &floats(&model.normals), Some(ARRAY_BUFFER));
+                    accessors.push(json!({
+                        "bufferView": view,
+                        "componentType": FLOAT,
+                        "count": vertex_cnt,
+                        "type": "VEC3",
+                    }));
+                    attributes.insert("NORMAL".to_string(), json!(accessors.len() - 1));
+                }
+                if model.uvs.len() == vertex_cnt * 2 {
+                    let view = push_view(&mut bin, &floats(&model.uvs), Some(ARRAY_BUFFER));
+                    accessors.push(json!({
+                        "bufferView": view,
+                        "componentType": FLOAT,
+                        "count": vertex_cnt,
+                        "type": "VEC2",
+                    }));
+                    attributes.insert("TEXCOORD_0".to_string(), json!(accessors.len() - 1));
+                }
+                if model.colors.len() == vertex_cnt * 4 {
+                    let view = push_view(&mut bin, &floats(&model.colors), Some(ARRAY_BUFFER));
+                    accessors.push(json!({
+                        "bufferView": view,
+                        "componentType": FLOAT,
+                        "count": vertex_cnt,
+                        "type": "VEC4",
+                    }));
+                    attributes.insert("COLOR_0".to_string(), json!(accessors.len() - 1));
+                }
+                let skinned = skin.is_some()
+                    && model.joints.len() == vertex_cnt * 4
+                    && model.weights.len() == vertex_cnt * 4;
+                if skinned {
+                    let joints: Vec<u8> =
+                        model.joints.iter().flat_map(|j| j.to_le_bytes()).collect();
+                    let view = push_view(&mut bin, &joints, Some(ARRAY_BUFFER));
+                    accessors.push(json!({
+                        "bufferView": view,
+                        "componentType": UNSIGNED_SHORT,
+                        "count": vertex_cnt,
+                        "type": "VEC4",
+                    }));
+                    attributes.insert("JOINTS_0".to_string(), json!(accessors.len() - 1));
+                    let view = push_view(&mut bin, &floats(&model.weights), Some(ARRAY_BUFFER));
+                    accessors.push(json!({
+                        "bufferView": view,
+                        "componentType": FLOAT,
+                        "count": vertex_cnt,
+                        "type": "VEC4",
+                    }));
+                    attributes.insert("WEIGHTS_0".to_string(), json!(accessors.len() - 1));
+                }
+
+                let indices: Vec<u8> = model.mesh.iter().flat_map(|i| i.to_le_bytes()).collect();
+                let index_view = push_view(&mut bin, &indices, Some(ELEMENT_ARRAY_BUFFER));
+                let mut primitives = Vec::new();
+                for s in &model.submeshes {
+                    accessors.push(json!({
+                        "bufferView": index_view,
+                        "byteOffset": s.first * 2,
+                        "componentType": UNSIGNED_SHORT,
+                        "count": s.count,
+                        "type": "SCALAR",
+                    }));
+                    let key = match &s.material {
+                        Some(m) if self.materials.contains_key(m) => (None, m.clone()),
+                        m => (Some(name.as_str()), m.clone().unwrap_or(name.clone())),
+                    };
+                    let material = match material_keys.iter().position(|k| *k == key) {
+                        Some(m) => m,
+                        None => {
+                            let m = match self.materials.get(&key.1).filter(|_| key.0.is_none()) {
+                                Some(material) => material_json(&key.1, material, &mut images)?,
+                                None => {
+                                    let mut m = json!({
+                                        "name": key.1,
+                                        "pbrMetallicRoughness": { "metallicFactor": 0.0 },
+                                    });
+                                    if let Some(t) = texture {
+                                        m["pbrMetallicRoughness"]["baseColorTexture"] =
+                                            json!({ "index": t });
+                                    }
+                                    m
+                                }
+                            };
+                            materials.push(m);
+                            material_keys.push(key);
+                            materials.len() - 1
+                        }
+                    };
+                    primitives.push(json!({
+                        "attributes": attributes,
+                        "indices": accessors.len() - 1,
+                        "material": material,
+                        "mode": TRIANGLES,
+                    }));
+                }
+                meshes.push(json!({ "name": model_name, "primitives": primitives }));
+                nodes.push(json!({ "name": model_name, "mesh": meshes.len() - 1 }));
+                if let Some(skin) = skin.filter(|_| skinned) {
+                    nodes.last_mut().unwrap()["skin"] = json!(skin);
+                }
+                children.push(nodes.len() - 1);
+            }
+            nodes.push(json!({ "name": name, "children": children }));
+            roots.push(nodes.len() - 1);
+        }
+
+        // the materials no submesh refers to
+        let mut material_names: Vec<&String> = self.materials.keys().collect();
+        material_names.sort();
+        for name in material_names {
+            if !material_keys.iter().any(|(a, m)| a.is_none() && m == name) {
+                materials.push(material_json(name, &self.materials[name], &mut images)?);
+            }
+        }
+
+        // every clip plays on the bones of the same name of every skeleton
+        let mut clip_names: Vec<&String> = self.clips.keys().collect();
+        clip_names.sort();
+        let mut animations = Vec::new();
+        for clip_name in clip_names {
+            let clip = &self.clips[clip_name];
+            let (mut min, mut max) = (f32::MAX, f32::MIN);
+            clip.times
+                .iter()
+                .for_each(|t| (min, max) = (min.min(*t), max.max(*t)));
+            let mut input = None;
+            let mut samplers = Vec::new();
+            let mut channels = Vec::new();
+            for track in &clip.tracks {
+                let paths = [
+                    ("translation", &track.translations, 3, "VEC3"),
+                    ("rotation", &track.rotations, 4, "VEC4"),
+                    ("scale", &track.scales, 3, "VEC3"),
+                ];
+                for (path, values, width, kind) in paths {
+                    if values.is_empty() || values.len() != clip.times.len() * width {
+                        continue;
+                    }
+                    let targets: Vec<usize> = joint_nodes
+                        .iter()
+                        .filter_map(|(skeleton, base)| Some(base + skeleton.find(&track.bone)?))
+                        .collect();
+                    if targets.is_empty() {
+                        continue;
+                    }
+                    let input = *input.get_or_insert_with(|| {
+                        let view = push_view(&mut bin, &floats(&clip.times), None);
+                        accessors.push(json!({
+                            "bufferView": view,
+                            "componentType": FLOAT,
+                            "count": clip.times.len(),
+                            "type": "SCALAR",
+                            "min": [min],
+                            "max": [max],
+                        }));
+                        accessors.len() - 1
+                    });
+                    let view = push_view(&mut bin, &floats(values), None);
+                    accessors.push(json!({
+                        "bufferView": view,
+                        "componentType": FLOAT,
+                        "count": clip.times.len(),
+                        "type": kind,
+                    }));
+                    samplers.push(json!({
+                        "input": input,
+                        "output": accessors.len() - 1,
+                        "interpolation": "LINEAR",
+                    }));
+                    for node in targets {
+                        channels.push(json!({
+                            "sampler": samplers.len() - 1,
+                            "target": { "node": node, "path": path },
+                        }));
+                    }
+                }
+            }
+            if !channels.is_empty() {
+                animations.push(json!({
+                    "name": clip_name,
+                    "samplers": samplers,
+                    "channels": channels,
+                }));
+            }
+        }
+
+        let textures: Vec<Json> = (0..images.len())
+            .map(|i| json!({ "source": i, "sampler": 0 }))
+            .collect();
+        let images: Vec<Json> = images
+            .iter()
+            .map(|(name, png)| {
+                let view = push_view(&mut bin, png, None);
+                json!({ "name": name, "bufferView": view, "mimeType": "image/png" })
+            })
+            .collect();
+        let unlit = materials
+            .iter()
+            .any(|m| m["extensions"]["KHR_materials_unlit"].is_object());
+        let mut gltf = json!({
+            "asset": { "version": "2.0", "generator": "mari-formats" },
+            "scene": 0,
+            "scenes": [{ "nodes": roots }],
+            "nodes": nodes,
+            "meshes": meshes,
+            "materials": materials,
+            "accessors": accessors,
+            "bufferViews": views,
+            "buffers": [{ "byteLength": bin.len() }],
+        });
+        if !skins.is_empty() {
+            gltf["skins"] = json!(skins);
+        }
+        if !animations.is_empty() {
+            gltf["animations"] = json!(animations);
+        }
+        if unlit {
+            gltf["extensionsUsed"] = json!(["KHR_materials_unlit"]);
+        }
+        if !images.is_empty() {
+            gltf["images"] = json!(images);
+            gltf["textures"] = json!(textures);
+            gltf["samplers"] = json!([{ "magFilter": 9729, "minFilter": 9729 }]);
+        }
+        Ok((gltf, bin))
+    }
+
+    /// A `.gltf` whose buffer is the returned bytes, to be saved as `bin_uri` next to it.
+    pub fn write_gltf(&self, bin_uri: &str) -> Result<(String, Vec<u8>), Error> {
+        let (mut gltf, bin) = self.to_gltf()?;
+        gltf["buffers"][0]["uri"] = json!(bin_uri);
+        let json = serde_json::to_string_pretty(&gltf).map_err(Error::Json)?;
+        Ok((json, bin))
+    }
+
+    /// A self-contained `.glb`.
+    pub fn write_glb(&self) -> Result<Vec<u8>, Error> {
+        let (gltf, mut bin) = self.to_gltf()?;
+        let mut json = serde_json::to_vec(&gltf).map_err(Error::Json)?;
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+
+        let mut ret = Vec::with_capacity(28 + json.len() + bin.len());
+        ret.extend_from_slice(GLB_MAGIC);
+        ret.extend_from_slice(&2u32.to_le_bytes());
+        ret.extend_from_slice(&((28 + json.len() + bin.len()) as u32).to_le_bytes());
+        ret.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        ret.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+        ret.extend_from_slice(&json);
+        ret.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        ret.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+        ret.extend_from_slice(&bin);
+        Ok(ret)
+    }
+}
@@ -0,0 +1,376 @@
+//! Unity Humanoid ("Mecanim") muscle space.
+//!
+//! Humanoid `AnimationClip`s do not store bone rotations. Every frame is a root position/rotation
+//! (`RootT`/`RootQ`) plus 95 normalized muscle values in [-1, 1]. Turning that back into bone
+//! rotations needs the `Avatar` of the target rig, which carries for every mapped human bone a
+//! pre/post rotation, an axis sign and the muscle limits.
+
+use crate::Skeleton;
+use crate::math::{QUAT_IDENTITY, quat_conj, quat_mul, quat_normalize, quat_rotate};
+
+pub const HUMAN_BONE_COUNT: usize = 55;
+pub const MUSCLE_COUNT: usize = 95;
+
+/// Same order as Unity's `HumanBodyBones`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HumanBone {
+    Hips,
+    LeftUpperLeg,
+    RightUpperLeg,
+    LeftLowerLeg,
+    RightLowerLeg,
+    LeftFoot,
+    RightFoot,
+    Spine,
+    Chest,
+    Neck,
+    Head,
+    LeftShoulder,
+    RightShoulder,
+    LeftUpperArm,
+    RightUpperArm,
+    LeftLowerArm,
+    RightLowerArm,
+    LeftHand,
+    RightHand,
+    LeftToes,
+    RightToes,
+    LeftEye,
+    RightEye,
+    Jaw,
+    LeftThumbProximal,
+    LeftThumbIntermediate,
+    LeftThumbDistal,
+    LeftIndexProximal,
+    LeftIndexIntermediate,
+    LeftIndexDistal,
+    LeftMiddleProximal,
+    LeftMiddleIntermediate,
+    LeftMiddleDistal,
+    LeftRingProximal,
+    LeftRingIntermediate,
+    LeftRingDistal,
+    LeftLittleProximal,
+    LeftLittleIntermediate,
+    LeftLittleDistal,
+    RightThumbProximal,
+    RightThumbIntermediate,
+    RightThumbDistal,
+    RightIndexProximal,
+    RightIndexIntermediate,
+    RightIndexDistal,
+    RightMiddleProximal,
+    RightMiddleIntermediate,
+    RightMiddleDistal,
+    RightRingProximal,
+    RightRingIntermediate,
+    RightRingDistal,
+    RightLittleProximal,
+    RightLittleIntermediate,
+    RightLittleDistal,
+    UpperChest,
+}
+
+/// Rotation axis of a muscle in the bone's Mecanim frame: X is the twist along the bone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dof {
+    X = 0,
+    Y = 1,
+    Z = 2,
+}
+
+struct Muscle {
+    /// attribute name used by the curves of humanoid clips
+    curve: &'static str,
+    bone: HumanBone,
+    dof: Dof,
+}
+
+const fn m(curve: &'static str, bone: HumanBone, dof: Dof) -> Muscle {
+    Muscle { curve, bone, dof }
+}
+
+/// Same order as Unity's `HumanTrait.MuscleName`.
+const MUSCLES: [Muscle; MUSCLE_COUNT] = {
+    use Dof::*;
+    use HumanBone::*;
+    [
+        m("Spine Front-Back", Spine, Z),
+        m("Spine Left-Right", Spine, Y),
+        m("Spine Twist Left-Right", Spine, X),
+        m("Chest Front-Back", Chest, Z),
+        m("Chest Left-Right", Chest, Y),
+        m("Chest Twist Left-Right", Chest, X),
+        m("UpperChest Front-Back", UpperChest, Z),
+        m("UpperChest Left-Right", UpperChest, Y),
+        m("UpperChest Twist Left-Right", UpperChest, X),
+        m("Neck Nod Down-Up", Neck, Z),
+        m("Neck Tilt Left-Right", Neck, Y),
+        m("Neck Turn Left-Right", Neck, X),
+        m("Head Nod Down-Up", Head, Z),
+        m("Head Tilt Left-Right", Head, Y),
+        m("Head Turn Left-Right", Head, X),
+        m("Left Eye Down-Up", LeftEye, Z),
+        m("Left Eye In-Out", LeftEye, Y),
+        m("Right Eye Down-Up", RightEye, Z),
+        m("Right Eye In-Out", RightEye, Y),
+        m("Jaw Close", Jaw, Z),
+        m("Jaw Left-Right", Jaw, Y),
+        m("Left Upper Leg Front-Back", LeftUpperLeg, Z),
+        m("Left Upper Leg In-Out", LeftUpperLeg, Y),
+        m("Left Upper Leg Twist In-Out", LeftUpperLeg, X),
+        m("Left Lower Leg Stretch", LeftLowerLeg, Z),
+        m("Left Lower Leg Twist In-Out", LeftLowerLeg, X),
+        m("Left Foot Up-Down", LeftFoot, Z),
+        m("Left Foot Twist In-Out", LeftFoot, Y),
+        m("Left Toes Up-Down", LeftToes, Z),
+        m("Right Upper Leg Front-Back", RightUpperLeg, Z),
+        m("Right Upper Leg In-Out", RightUpperLeg, Y),
+        m("Right Upper Leg Twist In-Out", RightUpperLeg, X),
+        m("Right Lower Leg Stretch", RightLowerLeg, Z),
+        m("Right Lower Leg Twist In-Out", RightLowerLeg, X),
+        m("Right Foot Up-Down", RightFoot, Z),
+        m("Right Foot Twist In-Out", RightFoot, Y),
+        m("Right Toes Up-Down", RightToes, Z),
+        m("Left Shoulder Down-Up", LeftShoulder, Z),
+        m("Left Shoulder Front-Back", LeftShoulder, Y),
+        m("Left Arm Down-Up", LeftUpperArm, Z),
+        m("Left Arm Front-Back", LeftUpperArm, Y),
+        m("Left Arm Twist In-Out", LeftUpperArm, X),
+        m("Left Forearm Stretch", LeftLowerArm, Z),
+        m("Left Forearm Twist In-Out", LeftLowerArm, X),
+        m("Left Hand Down-Up", LeftHand, Z),
+        m("Left Hand In-Out", LeftHand, Y),
+        m("Right Shoulder Down-Up", RightShoulder, Z),
+        m("Right Shoulder Front-Back", RightShoulder, Y),
+        m("Right Arm Down-Up", RightUpperArm, Z),
+        m("Right Arm Front-Back", RightUpperArm, Y),
+        m("Right Arm Twist In-Out", RightUpperArm, X),
+        m("Right Forearm Stretch", RightLowerArm, Z),
+        m("Right Forearm Twist In-Out", RightLowerArm, X),
+        m("Right Hand Down-Up", RightHand, Z),
+        m("Right Hand In-Out", RightHand, Y),
+        m("LeftHand.Thumb.1 Stretched", LeftThumbProximal, Z),
+        m("LeftHand.Thumb.Spread", LeftThumbProximal, Y),
+        m("LeftHand.Thumb.2 Stretched", LeftThumbIntermediate, Z),
+        m("LeftHand.Thumb.3 Stretched", LeftThumbDistal, Z),
+        m("LeftHand.Index.1 Stretched", LeftIndexProximal, Z),
+        m("LeftHand.Index.Spread", LeftIndexProximal, Y),
+        m("LeftHand.Index.2 Stretched", LeftIndexIntermediate, Z),
+        m("LeftHand.Index.3 Stretched", LeftIndexDistal, Z),
+        m("LeftHand.Middle.1 Stretched", LeftMiddleProximal, Z),
+        m("LeftHand.Middle.Spread", LeftMiddleProximal, Y),
+        m("LeftHand.Middle.2 Stretched", LeftMiddleIntermediate, Z),
+        m("LeftHand.Middle.3 Stretched", LeftMiddleDistal, Z),
+        m("LeftHand.Ring.1 Stretched", LeftRingProximal, Z),
+        m("LeftHand.Ring.Spread", LeftRingProximal, Y),
+        m("LeftHand.Ring.2 Stretched", LeftRingIntermediate, Z),
+        m("LeftHand.Ring.3 Stretched", LeftRingDistal, Z),
+        m("LeftHand.Little.1 Stretched", LeftLittleProximal, Z),
+        m("LeftHand.Little.Spread", LeftLittleProximal, Y),
+        m("LeftHand.Little.2 Stretched", LeftLittleIntermediate, Z),
+        m("LeftHand.Little.3 Stretched", LeftLittleDistal, Z),
+        m("RightHand.Thumb.1 Stretched", RightThumbProximal, Z),
+        m("RightHand.Thumb.Spread", RightThumbProximal, Y),
+        m("RightHand.Thumb.2 Stretched", RightThumbIntermediate, Z),
+        m("RightHand.Thumb.3 Stretched", RightThumbDistal, Z),
+        m("RightHand.Index.1 Stretched", RightIndexProximal, Z),
+        m("RightHand.Index.Spread", RightIndexProximal, Y),
+        m("RightHand.Index.2 Stretched", RightIndexIntermediate, Z),
+        m("RightHand.Index.3 Stretched", RightIndexDistal, Z),
+        m("RightHand.Middle.1 Stretched", RightMiddleProximal, Z),
+        m("RightHand.Middle.Spread", RightMiddleProximal, Y),
+        m("RightHand.Middle.2 Stretched", RightMiddleIntermediate, Z),
+        m("RightHand.Middle.3 Stretched", RightMiddleDistal, Z),
+        m("RightHand.Ring.1 Stretched", RightRingProximal, Z),
+        m("RightHand.Ring.Spread", RightRingProximal, Y),
+        m("RightHand.Ring.2 Stretched", RightRingIntermediate, Z),
+        m("RightHand.Ring.3 Stretched", RightRingDistal, Z),
+        m("RightHand.Little.1 Stretched", RightLittleProximal, Z),
+        m("RightHand.Little.Spread", RightLittleProximal, Y),
+        m("RightHand.Little.2 Stretched", RightLittleIntermediate, Z),
+        m("RightHand.Little.3 Stretched", RightLittleDistal, Z),
+    ]
+};
+
+/// Index of the muscle driven by the curve attribute `name`, e.g. `"Left Arm Down-Up"`.
+pub fn muscle_index(name: &str) -> Option<usize> {
+    MUSCLES.iter().position(|m| m.curve == name)
+}
+
+/// Mecanim frame of one human bone, i.e. `m_Human.m_Skeleton` axes of the `Avatar`.
+#[derive(Debug, Clone)]
+pub struct Axes {
+    /// quaternion x,y,z,w from the parent's frame into the bone's muscle frame
+    pub pre_q: [f32; 4],
+    /// quaternion x,y,z,w from the bone's own frame into the bone's muscle frame
+    pub post_q: [f32; 4],
+    /// per-axis sign applied to the muscle angles
+    pub sgn: [f32; 3],
+    /// per-axis angle reached at muscle value -1, in radians
+    pub limit_min: [f32; 3],
+    /// per-axis angle reached at muscle value 1, in radians
+    pub limit_max: [f32; 3],
+}
+
+impl Axes {
+    /// local rotation of the bone for the muscle values `uvw` of its x,y,z axes
+    pub fn rotation(&self, uvw: &[f32; 3]) -> [f32; 4] {
+        let mut half_tan = [0.0; 3];
+        for i in 0..3 {
+            let angle = if uvw[i] < 0.0 {
+                -uvw[i] * self.limit_min[i]
+            } else {
+                uvw[i] * self.limit_max[i]
+            };
+            half_tan[i] = (0.5 * self.sgn[i] * angle).tan();
+        }
+
+        // twist around X first, then swing by Y and Z ("ZYRoll")
+        let [x, y, z] = half_tan;
+        let q = quat_normalize(&[x, y + x * z, z - x * y, 1.0]);
+
+        quat_normalize(&quat_mul(
+            &self.pre_q,
+            &quat_mul(&q, &quat_conj(&self.post_q)),
+        ))
+    }
+}
+
+/// A human bone of the `Avatar` and the rig bone it drives.
+#[derive(Debug, Clone)]
+pub struct HumanBoneBinding {
+    pub bone: HumanBone,
+    /// index into `Skeleton::bones`
+    pub node: usize,
+    pub axes: Axes,
+}
+
+/// The human description of an `Avatar`, bound to one rig.
+#[derive(Debug, Clone)]
+pub struct Avatar {
+    /// `m_Human.m_Scale`, RootT is expressed in multiples of it
+    pub human_scale: f32,
+    /// hips position relative to the body center, in the body frame of the rest pose
+    pub root_offset: [f32; 3],
+    /// hips rotation relative to the body frame, quaternion x,y,z,w
+    pub root_rotation: [f32; 4],
+    pub bones: Vec<HumanBoneBinding>,
+}
+
+/// One sample of a humanoid clip.
+#[derive(Debug, Clone)]
+pub struct HumanPose {
+    /// body center position, in multiples of `Avatar::human_scale`
+    pub root_t: [f32; 3],
+    /// body orientation, quaternion x,y,z,w
+    pub root_q: [f32; 4],
+    /// normalized muscle values, in `HumanTrait.MuscleName` order
+    pub muscles: [f32; MUSCLE_COUNT],
+}
+
+impl Default for HumanPose {
+    fn default() -> Self {
+        Self {
+            root_t: [0.0; 3],
+            root_q: QUAT_IDENTITY,
+            muscles: [0.0; MUSCLE_COUNT],
+        }
+    }
+}
+
+impl HumanPose {
+    /// set the value of a humanoid curve attribute, e.g. `"RootQ.w"` or `"Jaw Close"`
+    ///
+    /// returns false if `attribute` is not a humanoid curve.
+    pub fn set_curve(&mut self, attribute: &str, value: f32) -> bool {
+        let slot = match attribute {
+            "RootT.x" => &mut self.root_t[0],
+            "RootT.y" => &mut self.root_t[1],
+            "RootT.z" => &mut self.root_t[2],
+            "RootQ.x" => &mut self.root_q[0],
+            "RootQ.y" => &mut self.root_q[1],
+            "RootQ.z" => &mut self.root_q[2],
+            "RootQ.w" => &mut self.root_q[3],
+            _ => match muscle_index(attribute) {
+                Some(i) => &mut self.muscles[i],
+                None => return false,
+            },
+        };
+        *slot = value;
+        true
+    }
+}
+
+/// Local transforms of every bone of a `Skeleton`.
+#[derive(Debug, Clone)]
+pub struct RigPose {
+    /// translation relative to the parent
+    pub translations: Vec<[f32; 3]>,
+    /// rotation relative to the parent, quaternion x,y,z,w
+    pub rotations: Vec<[f32; 4]>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NodeOutOfRange(String),
+    MissingHips,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Avatar {
+    /// Convert a humanoid pose into local bone transforms of `skeleton`.
+    ///
+    /// Bones not driven by the avatar keep their rest transform. The hips are placed in the space
+    /// of their parent, which is assumed to be at the origin like Unity's root motion expects.
+    /// Twist distribution between limb segments is not applied.
+    pub fn pose(&self, skeleton: &Skeleton, pose: &HumanPose) -> Result<RigPose, Error> {
+        let mut translations: Vec<[f32; 3]> =
+            skeleton.bones.iter().map(|b| b.translation).collect();
+        let mut rotations: Vec<[f32; 4]> = skeleton.bones.iter().map(|b| b.rotation).collect();
+
+        let mut uvw = [[0.0f32; 3]; HUMAN_BONE_COUNT];
+        for (muscle, value) in MUSCLES.iter().zip(pose.muscles.iter()) {
+            uvw[muscle.bone as usize][muscle.dof as usize] = *value;
+        }
+
+        let mut hips = None;
+        for binding in &self.bones {
+            if binding.node >= skeleton.bones.len() {
+                return Err(Error::NodeOutOfRange(format!(
+                    "{:?} is bound to bone #{}, but the skeleton has {} bones.",
+                    binding.bone,
+                    binding.node,
+                    skeleton.bones.len()
+                )));
+            }
+            if binding.bone == HumanBone::Hips {
+                hips = Some(binding.node);
+                continue;
+            }
+            rotations[binding.node] = binding.axes.rotation(&uvw[binding.bone as usize]);
+        }
+
+        let hips = hips.ok_or(Error::MissingHips)?;
+        let root_q = quat_normalize(&pose.root_q);
+        let offset = quat_rotate(&root_q, &self.root_offset);
+        translations[hips] = [
+            pose.root_t[0] * self.human_scale + offset[0],
+            pose.root_t[1] * self.human_scale + offset[1],
+            pose.root_t[2] * self.human_scale + offset[2],
+        ];
+        rotations[hips] = quat_normalize(&quat_mul(&root_q, &self.root_rotation));
+
+        Ok(RigPose {
+            translations,
+            rotations,
+        })
+    }
+}
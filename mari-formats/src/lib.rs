@@ -1,8 +1,30 @@
+pub mod gakumas;
+mod gltf;
+mod humanoid;
+mod math;
+mod native;
 mod obj;
+pub mod pmx;
+mod process;
+mod texture;
+pub mod unity;
+pub use gltf::Error as GltfError;
+pub use humanoid::Error as HumanoidError;
+pub use humanoid::{
+    Avatar, Axes, HUMAN_BONE_COUNT, HumanBone, HumanBoneBinding, HumanPose, MUSCLE_COUNT, RigPose,
+    muscle_index,
+};
+pub use native::Error as NativeError;
+pub use native::{
+    ActorView, ClipView, MappedScene, MaterialView, ModelView, PbrMaterialView, SceneView,
+    SubmeshView, TextureView, ToonMaterialView, TrackView,
+};
 pub use obj::Error as ObjError;
+pub use obj::ObjFiles;
+pub use texture::{Channel, ImageFormat, PixelFormat, ResizeFilter, Texture, TextureLayout};
 
 use std::collections::HashMap;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
 pub struct Model {
     /// compact storage of vertex x,y,z
@@ -13,15 +35,81 @@ pub struct Model {
     pub uvs: Vec<f32>,
     /// compact storage of vertex normals, normalized
     pub normals: Vec<f32>,
+    /// compact storage of vertex r,g,b,a, empty if none
+    pub colors: Vec<f32>,
+    /// compact storage of 4 bone indices per vertex into the actor's skeleton, empty if not skinned
+    pub joints: Vec<u16>,
+    /// compact storage of 4 bone weights per vertex, matching `joints`
+    pub weights: Vec<f32>,
+    /// ranges of `mesh` drawn with one material each, covering all of `mesh`
+    pub submeshes: Vec<Submesh>,
+}
+
+pub struct Submesh {
+    /// first index into `Model::mesh`
+    pub first: usize,
+    /// number of indices, a multiple of 3
+    pub count: usize,
+    /// name of the material in the source asset, if any
+    pub material: Option<String>,
+}
+
+pub struct Bone {
+    pub name: String,
+    /// index of the parent in `Skeleton::bones`, `None` for a root
+    pub parent: Option<usize>,
+    /// rest translation relative to the parent
+    pub translation: [f32; 3],
+    /// rest rotation relative to the parent, quaternion x,y,z,w
+    pub rotation: [f32; 4],
+    /// rest scale relative to the parent
+    pub scale: [f32; 3],
+}
+
+pub struct Skeleton {
+    /// parents always come before their children
+    pub bones: Vec<Bone>,
+}
+
+/// A further mesh of an actor, e.g. face or hair, placed in the same space as the body.
+pub struct Part {
+    pub name: String,
+    pub model: Model,
 }
 
 pub struct Actor {
     pub body: Model,
+    pub parts: Vec<Part>,
+    /// bones `Model::joints` of the body and of all parts refer to
+    pub skeleton: Option<Skeleton>,
+}
+
+/// Keyframed local bone transforms, every track sampled at the same times.
+pub struct Clip {
+    /// seconds from the start of the clip, ascending
+    pub times: Vec<f32>,
+    pub tracks: Vec<Track>,
+}
+
+/// One bone's part of a `Clip`. An empty array keeps the rest value of the bone.
+pub struct Track {
+    /// name of the bone in the skeleton the clip is played on
+    pub bone: String,
+    /// compact storage of x,y,z per key
+    pub translations: Vec<f32>,
+    /// compact storage of quaternion x,y,z,w per key
+    pub rotations: Vec<f32>,
+    /// compact storage of x,y,z per key
+    pub scales: Vec<f32>,
 }
 
+#[derive(Default)]
 pub struct Scene {
     pub actors: HashMap<String, Actor>,
     pub textures: HashMap<String, TextureRGBA8>,
+    pub clips: HashMap<String, Clip>,
+    /// keyed by the material names `Submesh::material` refers to
+    pub materials: HashMap<String, Material>,
 }
 
 pub struct TextureRGBA8 {
@@ -29,6 +117,63 @@ pub struct TextureRGBA8 {
     pub data: Vec<u8>,
 }
 
+/// How the submeshes referring to a material are drawn.
+pub enum Material {
+    /// one r,g,b,a color, without lighting
+    Unlit {
+        color: [f32; 4],
+    },
+    /// a texture, without lighting
+    Textured {
+        texture: TextureRGBA8,
+    },
+    Toon(ToonMaterial),
+    /// metallic-roughness parameters, e.g. of glTF, for renderers to approximate
+    Pbr(PbrMaterial),
+}
+
+/// Textures and parameters of the toon look of one material.
+pub struct ToonMaterial {
+    pub texture: TextureRGBA8,
+    pub ramp_texture: TextureRGBA8,
+    pub sdw_texture: TextureRGBA8,
+    /// alpha of `sdw_texture` above which the ramp applies
+    pub shadow_threshold: f32,
+    /// the ramp is sampled at `ramp_scale * (1 + cos)` of the angle between normal and light
+    pub ramp_scale: f32,
+    /// outline width in the units of the source shader, e.g. `_Outline_Width` of Unity's toon
+    /// shaders, 0 for no outline
+    pub outline_width: f32,
+    /// r,g,b,a
+    pub outline_color: [f32; 4],
+}
+
+pub struct PbrMaterial {
+    /// r,g,b,a, multiplied with `base_color_texture`
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<TextureRGBA8>,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+/// the thresholds of the game's shader
+impl ToonMaterial {
+    pub const SHADOW_THRESHOLD: f32 = 0.5;
+    pub const RAMP_SCALE: f32 = 0.35;
+}
+
+impl Material {
+    /// the texture giving the base color, if any
+    pub fn base_texture(&self) -> Option<&TextureRGBA8> {
+        match self {
+            Self::Unlit { .. } => None,
+            Self::Textured { texture } => Some(texture),
+            Self::Toon(toon) => Some(&toon.texture),
+            Self::Pbr(pbr) => pbr.base_color_texture.as_ref(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ModelError {
     Obj(ObjError),
@@ -75,12 +220,37 @@ impl Model {
             mesh,
             uvs: texture,
             normals,
+            materials,
         } = obj;
+
+        // faces before the first `usemtl` have no material
+        let mut submeshes = Vec::new();
+        let first_material = materials.first().map_or(mesh.len(), |(first, _)| *first);
+        if first_material > 0 || materials.is_empty() {
+            submeshes.push(Submesh {
+                first: 0,
+                count: first_material,
+                material: None,
+            });
+        }
+        for (i, (first, name)) in materials.iter().enumerate() {
+            let end = materials.get(i + 1).map_or(mesh.len(), |(next, _)| *next);
+            submeshes.push(Submesh {
+                first: *first,
+                count: end - first,
+                material: Some(name.clone()),
+            });
+        }
+
         Ok(Self {
             vertices,
+            submeshes,
             mesh,
             uvs: texture,
             normals,
+            colors: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
         })
     }
 
@@ -95,18 +265,38 @@ impl Model {
     }
 }
 
+impl Actor {
+    pub fn new(body: Model) -> Self {
+        Self {
+            body,
+            parts: Vec::new(),
+            skeleton: None,
+        }
+    }
+}
+
+impl Skeleton {
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|b| b.name == name)
+    }
+}
+
 impl Scene {
     pub fn new_with_model(model: Model) -> Self {
         Self {
-            actors: HashMap::from([("Temari".to_string(), Actor { body: model })]),
+            actors: HashMap::from([("Temari".to_string(), Actor::new(model))]),
             textures: HashMap::new(),
+            clips: HashMap::new(),
+            materials: HashMap::new(),
         }
     }
 
     pub fn new_with_model_and_texture(model: Model, texture: TextureRGBA8) -> Self {
         Self {
-            actors: HashMap::from([("Temari".to_string(), Actor { body: model })]),
+            actors: HashMap::from([("Temari".to_string(), Actor::new(model))]),
             textures: HashMap::from([("Temari".to_string(), texture)]),
+            clips: HashMap::new(),
+            materials: HashMap::new(),
         }
     }
 }
@@ -114,8 +304,12 @@ impl Scene {
 #[derive(Debug)]
 pub enum TextureError {
     Png(png::DecodingError),
+    #[cfg(feature = "jpeg")]
+    Jpeg(jpeg_decoder::Error),
     WidthTooLarge,
     HeightTooLarge,
+    Invalid(String),
+    Unsupported(String),
 }
 
 impl std::fmt::Display for TextureError {
@@ -127,26 +321,70 @@ impl std::fmt::Display for TextureError {
 impl std::error::Error for TextureError {}
 
 impl TextureRGBA8 {
+    /// Decode any PNG into RGBA8.
+    ///
+    /// Palettes, low bit depths and `tRNS` transparency are expanded, 16-bit samples keep their
+    /// high byte, and grey is replicated into r,g,b. Without an `sRGB` chunk, a `gAMA` chunk is
+    /// converted to the usual display gamma of 2.2. Interlaced images are deinterlaced.
     pub fn new_from_png<R: Read>(buf: BufReader<R>) -> Result<Self, TextureError> {
-        let mut reader = png::Decoder::new(buf)
-            .read_info()
-            .map_err(TextureError::Png)?;
+        let mut decoder = png::Decoder::new(buf);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(TextureError::Png)?;
         let mut data = vec![0; reader.output_buffer_size()];
         let info = reader.next_frame(&mut data).map_err(TextureError::Png)?;
+        data.truncate(info.buffer_size());
 
-        let width: u16 = info
-            .width
-            .try_into()
-            .map_err(|_| TextureError::WidthTooLarge)?;
-        let _: u16 = info
-            .width
-            .try_into()
-            .map_err(|_| TextureError::HeightTooLarge)?;
-        data.resize(info.buffer_size(), 0);
+        let width = texture::texture_size(info.width, info.height)?;
+
+        let mut data = match info.color_type {
+            png::ColorType::Rgba => data,
+            png::ColorType::Rgb => data
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => data.iter().flat_map(|g| [*g, *g, *g, 255]).collect(),
+            png::ColorType::Indexed => unreachable!("palettes are expanded by the decoder"),
+        };
+
+        let png_info = reader.info();
+        if let (None, Some(gamma)) = (png_info.srgb, png_info.source_gamma) {
+            // samples are encoded with `gamma`, re-encode them for a display gamma of 2.2
+            let exponent = 1.0 / (gamma.into_value() * 2.2);
+            if exponent.is_finite() && (exponent - 1.0).abs() > 0.01 {
+                let table: Vec<u8> = (0..=255)
+                    .map(|v| ((v as f32 / 255.0).powf(exponent) * 255.0).round() as u8)
+                    .collect();
+                for p in data.chunks_exact_mut(4) {
+                    for c in &mut p[..3] {
+                        *c = table[*c as usize];
+                    }
+                }
+            }
+        }
 
         Ok(Self { width, data })
     }
 
+    /// Encode as an 8-bit RGBA PNG.
+    pub fn write_png<W: Write>(&self, w: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height() as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()
+    }
+
+    pub(crate) fn encode_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut ret = Vec::new();
+        self.write_png(&mut ret)?;
+        Ok(ret)
+    }
+
     pub fn height(&self) -> u16 {
         (self.data.len() / 4 / self.width as usize) as u16
     }
@@ -0,0 +1,230 @@
+//! Minimal quaternion helpers. Quaternions are stored x,y,z,w like Unity does.
+
+pub type Quat = [f32; 4];
+
+pub const QUAT_IDENTITY: Quat = [0.0, 0.0, 0.0, 1.0];
+
+pub fn quat_mul(a: &Quat, b: &Quat) -> Quat {
+    let [ax, ay, az, aw] = *a;
+    let [bx, by, bz, bw] = *b;
+    [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+}
+
+pub fn quat_conj(q: &Quat) -> Quat {
+    [-q[0], -q[1], -q[2], q[3]]
+}
+
+pub fn quat_normalize(q: &Quat) -> Quat {
+    let l = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+    if l == 0.0 {
+        return QUAT_IDENTITY;
+    }
+    [q[0] / l, q[1] / l, q[2] / l, q[3] / l]
+}
+
+pub fn quat_rotate(q: &Quat, v: &[f32; 3]) -> [f32; 3] {
+    let p = quat_mul(&quat_mul(q, &[v[0], v[1], v[2], 0.0]), &quat_conj(q));
+    [p[0], p[1], p[2]]
+}
+
+/// 4x4 matrix in column-major order.
+pub type Mat4 = [f32; 16];
+
+pub const MAT4_IDENTITY: Mat4 = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+pub fn mat4_from_trs(t: &[f32; 3], q: &Quat, s: &[f32; 3]) -> Mat4 {
+    let [x, y, z, w] = quat_normalize(q);
+    let r = [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y + z * w),
+            2.0 * (x * z - y * w),
+        ],
+        [
+            2.0 * (x * y - z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z + x * w),
+        ],
+        [
+            2.0 * (x * z + y * w),
+            2.0 * (y * z - x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ];
+    let mut m = MAT4_IDENTITY;
+    for c in 0..3 {
+        for row in 0..3 {
+            m[4 * c + row] = r[c][row] * s[c];
+        }
+    }
+    m[12..15].copy_from_slice(t);
+    m
+}
+
+pub fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut m = [0.0; 16];
+    for c in 0..4 {
+        for r in 0..4 {
+            m[4 * c + r] = (0..4).map(|k| a[4 * k + r] * b[4 * c + k]).sum();
+        }
+    }
+    m
+}
+
+pub fn mat4_transform_point(m: &Mat4, p: &[f32; 3]) -> [f32; 3] {
+    let mut ret = [m[12], m[13], m[14]];
+    for (r, v) in ret.iter_mut().enumerate() {
+        *v += m[r] * p[0] + m[4 + r] * p[1] + m[8 + r] * p[2];
+    }
+    ret
+}
+
+pub fn mat4_transform_vector(m: &Mat4, p: &[f32; 3]) -> [f32; 3] {
+    let mut ret = [0.0; 3];
+    for (r, v) in ret.iter_mut().enumerate() {
+        *v = m[r] * p[0] + m[4 + r] * p[1] + m[8 + r] * p[2];
+    }
+    ret
+}
+
+/// general inverse by cofactors, the identity for a singular matrix
+pub fn mat4_inverse(m: &Mat4) -> Mat4 {
+    // the expansion of MESA's gluInvertMatrix, which holds for either storage order
+    let mut inv = [0.0; 16];
+    inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+        + m[9] * m[7] * m[14]
+        + m[13] * m[6] * m[11]
+        - m[13] * m[7] * m[10];
+    inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+        - m[8] * m[7] * m[14]
+        - m[12] * m[6] * m[11]
+        + m[12] * m[7] * m[10];
+    inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+        + m[8] * m[7] * m[13]
+        + m[12] * m[5] * m[11]
+        - m[12] * m[7] * m[9];
+    inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+        - m[8] * m[6] * m[13]
+        - m[12] * m[5] * m[10]
+        + m[12] * m[6] * m[9];
+    inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+        - m[9] * m[3] * m[14]
+        - m[13] * m[2] * m[11]
+        + m[13] * m[3] * m[10];
+    inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+        + m[8] * m[3] * m[14]
+        + m[12] * m[2] * m[11]
+        - m[12] * m[3] * m[10];
+    inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+        - m[8] * m[3] * m[13]
+        - m[12] * m[1] * m[11]
+        + m[12] * m[3] * m[9];
+    inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+        + m[8] * m[2] * m[13]
+        + m[12] * m[1] * m[10]
+        - m[12] * m[2] * m[9];
+    inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+        + m[5] * m[3] * m[14]
+        + m[13] * m[2] * m[7]
+        - m[13] * m[3] * m[6];
+    inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+        - m[4] * m[3] * m[14]
+        - m[12] * m[2] * m[7]
+        + m[12] * m[3] * m[6];
+    inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+        + m[4] * m[3] * m[13]
+        + m[12] * m[1] * m[7]
+        - m[12] * m[3] * m[5];
+    inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+        - m[4] * m[2] * m[13]
+        - m[12] * m[1] * m[6]
+        + m[12] * m[2] * m[5];
+    inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+        - m[5] * m[3] * m[10]
+        - m[9] * m[2] * m[7]
+        + m[9] * m[3] * m[6];
+    inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+        + m[4] * m[3] * m[10]
+        + m[8] * m[2] * m[7]
+        - m[8] * m[3] * m[6];
+    inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+        - m[4] * m[3] * m[9]
+        - m[8] * m[1] * m[7]
+        + m[8] * m[3] * m[5];
+    inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+        + m[4] * m[2] * m[9]
+        + m[8] * m[1] * m[6]
+        - m[8] * m[2] * m[5];
+
+    let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+    if det == 0.0 {
+        return MAT4_IDENTITY;
+    }
+    inv.map(|v| v / det)
+}
+
+/// translation, rotation and scale of an affine matrix without shear
+pub fn mat4_to_trs(m: &Mat4) -> ([f32; 3], Quat, [f32; 3]) {
+    let t = [m[12], m[13], m[14]];
+    let column = |c: usize| [m[4 * c], m[4 * c + 1], m[4 * c + 2]];
+    let length = |v: [f32; 3]| (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    let mut s = [length(column(0)), length(column(1)), length(column(2))];
+    let det = m[0] * (m[5] * m[10] - m[9] * m[6]) - m[4] * (m[1] * m[10] - m[9] * m[2])
+        + m[8] * (m[1] * m[6] - m[5] * m[2]);
+    if det < 0.0 {
+        s[0] = -s[0];
+    }
+    let r = |c: usize, row: usize| {
+        if s[c] == 0.0 {
+            0.0
+        } else {
+            m[4 * c + row] / s[c]
+        }
+    };
+
+    // Shepperd's method on the largest diagonal term
+    let trace = r(0, 0) + r(1, 1) + r(2, 2);
+    let q = if trace > 0.0 {
+        let k = 0.5 / (trace + 1.0).sqrt();
+        [
+            (r(1, 2) - r(2, 1)) * k,
+            (r(2, 0) - r(0, 2)) * k,
+            (r(0, 1) - r(1, 0)) * k,
+            0.25 / k,
+        ]
+    } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+        let k = 0.5 / (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt();
+        [
+            0.25 / k,
+            (r(1, 0) + r(0, 1)) * k,
+            (r(2, 0) + r(0, 2)) * k,
+            (r(1, 2) - r(2, 1)) * k,
+        ]
+    } else if r(1, 1) > r(2, 2) {
+        let k = 0.5 / (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt();
+        [
+            (r(1, 0) + r(0, 1)) * k,
+            0.25 / k,
+            (r(2, 1) + r(1, 2)) * k,
+            (r(2, 0) - r(0, 2)) * k,
+        ]
+    } else {
+        let k = 0.5 / (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt();
+        [
+            (r(2, 0) + r(0, 2)) * k,
+            (r(2, 1) + r(1, 2)) * k,
+            0.25 / k,
+            (r(0, 1) - r(1, 0)) * k,
+        ]
+    };
+    // the one of q and -q with w >= 0
+    let q = if q[3] < 0.0 { q.map(|v| -v) } else { q };
+    (t, quat_normalize(&q), s)
+}
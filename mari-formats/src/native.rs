@@ -0,0 +1,836 @@
+//! The native `.mari` scene container.
+//!
+//! A little-endian file made of a header, a table of contents and one section per actor, texture,
+//! clip or material:
+//!
+//! ```text
+//! header   magic "MARI", u16 major, u16 minor, u32 section count, u32 CRC-32 of the table
+//! table    per section: u32 kind, u32 CRC-32 of the section, u64 offset, u64 size
+//! sections each starting at a multiple of 16
+//! ```
+//!
+//! Inside a section, numbers are u32/f32, strings are a u32 length followed by UTF-8 padded to 4,
+//! and arrays are a u32 element count followed by the elements at the next multiple of 16. Arrays
+//! are therefore aligned whenever the buffer is, as a memory map is, and `SceneView` then borrows
+//! them without parsing. A newer minor version may add section kinds, which older readers skip.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::Path;
+
+use crate::{
+    Actor, Bone, Clip, Material, Model, Part, PbrMaterial, Scene, Skeleton, Submesh, TextureRGBA8,
+    ToonMaterial, Track,
+};
+
+const MAGIC: &[u8; 4] = b"MARI";
+const VERSION_MAJOR: u16 = 1;
+const VERSION_MINOR: u16 = 0;
+
+const HEADER_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 24;
+const ALIGN: usize = 16;
+/// stands for `None` where a length or index is expected
+const NONE: u32 = u32::MAX;
+
+const KIND_ACTOR: u32 = 1;
+const KIND_TEXTURE: u32 = 2;
+const KIND_CLIP: u32 = 3;
+const KIND_MATERIAL: u32 = 4;
+
+/// the variant of a material section, following its name
+const MATERIAL_UNLIT: u32 = 0;
+const MATERIAL_TEXTURED: u32 = 1;
+const MATERIAL_TOON: u32 = 2;
+const MATERIAL_PBR: u32 = 3;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Invalid(String),
+    Unsupported(String),
+    Checksum(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// An array element as stored in the file.
+trait Element: bytemuck::Pod {
+    fn from_le(bytes: &[u8]) -> Self;
+    fn put_le(self, out: &mut Vec<u8>);
+}
+
+impl Element for f32 {
+    fn from_le(bytes: &[u8]) -> Self {
+        f32::from_le_bytes(bytes.try_into().unwrap())
+    }
+
+    fn put_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Element for u16 {
+    fn from_le(bytes: &[u8]) -> Self {
+        u16::from_le_bytes(bytes.try_into().unwrap())
+    }
+
+    fn put_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+fn pad(out: &mut Vec<u8>, align: usize) {
+    out.resize(out.len().next_multiple_of(align), 0);
+}
+
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32s(&mut self, v: &[f32]) {
+        v.iter().for_each(|v| v.put_le(&mut self.data));
+    }
+
+    fn str(&mut self, v: &str) {
+        self.u32(v.len() as u32);
+        self.data.extend_from_slice(v.as_bytes());
+        pad(&mut self.data, 4);
+    }
+
+    fn opt_str(&mut self, v: Option<&str>) {
+        match v {
+            Some(v) => self.str(v),
+            None => self.u32(NONE),
+        }
+    }
+
+    fn array<T: Element>(&mut self, v: &[T]) {
+        self.u32(v.len() as u32);
+        pad(&mut self.data, ALIGN);
+        v.iter().for_each(|v| v.put_le(&mut self.data));
+        pad(&mut self.data, 4);
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        pad(&mut self.data, ALIGN);
+        self.data.extend_from_slice(v);
+        pad(&mut self.data, 4);
+    }
+
+    fn texture(&mut self, texture: &TextureRGBA8) {
+        self.u32(texture.width as u32);
+        self.u32(texture.height() as u32);
+        self.bytes(&texture.data);
+    }
+
+    fn opt_texture(&mut self, texture: Option<&TextureRGBA8>) {
+        match texture {
+            Some(texture) => self.texture(texture),
+            None => self.u32(NONE),
+        }
+    }
+
+    fn material(&mut self, material: &Material) {
+        match material {
+            Material::Unlit { color } => {
+                self.u32(MATERIAL_UNLIT);
+                self.f32s(color);
+            }
+            Material::Textured { texture } => {
+                self.u32(MATERIAL_TEXTURED);
+                self.texture(texture);
+            }
+            Material::Toon(toon) => {
+                self.u32(MATERIAL_TOON);
+                self.f32s(&[toon.shadow_threshold, toon.ramp_scale, toon.outline_width]);
+                self.f32s(&toon.outline_color);
+                self.texture(&toon.texture);
+                self.texture(&toon.ramp_texture);
+                self.texture(&toon.sdw_texture);
+            }
+            Material::Pbr(pbr) => {
+                self.u32(MATERIAL_PBR);
+                self.f32s(&pbr.base_color);
+                self.f32s(&[pbr.metallic, pbr.roughness]);
+                self.opt_texture(pbr.base_color_texture.as_ref());
+            }
+        }
+    }
+
+    fn model(&mut self, model: &Model) {
+        self.array(&model.vertices);
+        self.array(&model.mesh);
+        self.array(&model.uvs);
+        self.array(&model.normals);
+        self.array(&model.colors);
+        self.array(&model.joints);
+        self.array(&model.weights);
+        self.u32(model.submeshes.len() as u32);
+        for submesh in &model.submeshes {
+            self.u32(submesh.first as u32);
+            self.u32(submesh.count as u32);
+            self.opt_str(submesh.material.as_deref());
+        }
+    }
+}
+
+/// Reads one section. Positions are relative to the section, which starts aligned.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    section: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let ret = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| Error::Invalid(format!("Section #{} ends early.", self.section)))?;
+        self.pos += len;
+        Ok(ret)
+    }
+
+    fn align(&mut self, align: usize) -> Result<(), Error> {
+        let len = self.pos.next_multiple_of(align) - self.pos;
+        self.take(len).map(|_| ())
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Result<[f32; N], Error> {
+        let mut ret = [0.0; N];
+        for v in ret.iter_mut() {
+            *v = self.f32()?;
+        }
+        Ok(ret)
+    }
+
+    fn index(&mut self) -> Result<Option<usize>, Error> {
+        Ok(Some(self.u32()?).filter(|v| *v != NONE).map(|v| v as usize))
+    }
+
+    fn str_of_len(&mut self, len: usize) -> Result<&'a str, Error> {
+        let bytes = self.take(len)?;
+        self.align(4)?;
+        std::str::from_utf8(bytes).map_err(|_| {
+            Error::Invalid(format!("Section #{} has a non-UTF-8 string.", self.section))
+        })
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        let len = self.u32()? as usize;
+        self.str_of_len(len)
+    }
+
+    fn opt_str(&mut self) -> Result<Option<&'a str>, Error> {
+        match self.index()? {
+            Some(len) => self.str_of_len(len).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn bytes(&mut self, size: usize) -> Result<&'a [u8], Error> {
+        let cnt = self.u32()? as usize;
+        self.align(ALIGN)?;
+        let ret = self.take(cnt.checked_mul(size).ok_or_else(|| {
+            Error::Invalid(format!("Section #{} has an oversized array.", self.section))
+        })?)?;
+        self.align(4)?;
+        Ok(ret)
+    }
+
+    /// borrowed if the host is little-endian and the buffer aligned, else copied
+    fn array<T: Element>(&mut self) -> Result<Cow<'a, [T]>, Error> {
+        let bytes = self.bytes(size_of::<T>())?;
+        #[cfg(target_endian = "little")]
+        if let Ok(v) = bytemuck::try_cast_slice(bytes) {
+            return Ok(Cow::Borrowed(v));
+        }
+        Ok(Cow::Owned(
+            bytes.chunks_exact(size_of::<T>()).map(T::from_le).collect(),
+        ))
+    }
+
+    fn texture(&mut self) -> Result<TextureView<'a>, Error> {
+        let width = self.u32()?;
+        self.texture_of_width(width)
+    }
+
+    fn opt_texture(&mut self) -> Result<Option<TextureView<'a>>, Error> {
+        match self.u32()? {
+            NONE => Ok(None),
+            width => self.texture_of_width(width).map(Some),
+        }
+    }
+
+    fn texture_of_width(&mut self, width: u32) -> Result<TextureView<'a>, Error> {
+        let height = self.u32()?;
+        let data = self.bytes(1)?;
+        if data.len() != width as usize * height as usize * 4 {
+            return Err(Error::Invalid(format!(
+                "Section #{}: {} bytes do not make a {width}x{height} RGBA8 texture.",
+                self.section,
+                data.len()
+            )));
+        }
+        Ok(TextureView {
+            width,
+            height,
+            data,
+        })
+    }
+
+    fn model(&mut self) -> Result<ModelView<'a>, Error> {
+        let vertices = self.array()?;
+        let mesh = self.array()?;
+        let uvs = self.array()?;
+        let normals = self.array()?;
+        let colors = self.array()?;
+        let joints = self.array()?;
+        let weights = self.array()?;
+        let mut submeshes = Vec::new();
+        for _ in 0..self.u32()? {
+            submeshes.push(SubmeshView {
+                first: self.u32()? as usize,
+                count: self.u32()? as usize,
+                material: self.opt_str()?,
+            });
+        }
+        Ok(ModelView {
+            vertices,
+            mesh,
+            uvs,
+            normals,
+            colors,
+            joints,
+            weights,
+            submeshes,
+        })
+    }
+
+    fn actor(&mut self) -> Result<ActorView<'a>, Error> {
+        let name = self.str()?;
+        let body = self.model()?;
+        let mut parts = Vec::new();
+        for _ in 0..self.u32()? {
+            parts.push((self.str()?, self.model()?));
+        }
+        let skeleton = match self.index()? {
+            Some(cnt) => {
+                let mut bones = Vec::new();
+                for _ in 0..cnt {
+                    bones.push(Bone {
+                        name: self.str()?.to_string(),
+                        parent: self.index()?,
+                        translation: self.f32s()?,
+                        rotation: self.f32s()?,
+                        scale: self.f32s()?,
+                    });
+                }
+                Some(Skeleton { bones })
+            }
+            None => None,
+        };
+        Ok(ActorView {
+            name,
+            body,
+            parts,
+            skeleton,
+        })
+    }
+
+    fn clip(&mut self) -> Result<ClipView<'a>, Error> {
+        let name = self.str()?;
+        let times = self.array()?;
+        let mut tracks = Vec::new();
+        for _ in 0..self.u32()? {
+            tracks.push(TrackView {
+                bone: self.str()?,
+                translations: self.array()?,
+                rotations: self.array()?,
+                scales: self.array()?,
+            });
+        }
+        Ok(ClipView {
+            name,
+            times,
+            tracks,
+        })
+    }
+
+    fn material(&mut self) -> Result<(&'a str, MaterialView<'a>), Error> {
+        let name = self.str()?;
+        let material = match self.u32()? {
+            MATERIAL_UNLIT => MaterialView::Unlit {
+                color: self.f32s()?,
+            },
+            MATERIAL_TEXTURED => MaterialView::Textured {
+                texture: self.texture()?,
+            },
+            MATERIAL_TOON => MaterialView::Toon(ToonMaterialView {
+                shadow_threshold: self.f32()?,
+                ramp_scale: self.f32()?,
+                outline_width: self.f32()?,
+                outline_color: self.f32s()?,
+                texture: self.texture()?,
+                ramp_texture: self.texture()?,
+                sdw_texture: self.texture()?,
+            }),
+            MATERIAL_PBR => MaterialView::Pbr(PbrMaterialView {
+                base_color: self.f32s()?,
+                metallic: self.f32()?,
+                roughness: self.f32()?,
+                base_color_texture: self.opt_texture()?,
+            }),
+            variant => {
+                return Err(Error::Invalid(format!(
+                    "Section #{} is a material of unknown variant {variant}.",
+                    self.section
+                )));
+            }
+        };
+        Ok((name, material))
+    }
+}
+
+/// An RGBA8 texture borrowed from a `.mari` buffer.
+pub struct TextureView<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub data: &'a [u8],
+}
+
+/// A `Submesh` borrowed from a `.mari` buffer.
+pub struct SubmeshView<'a> {
+    pub first: usize,
+    pub count: usize,
+    pub material: Option<&'a str>,
+}
+
+/// A `Model` borrowed from a `.mari` buffer, laid out like `Model`.
+pub struct ModelView<'a> {
+    pub vertices: Cow<'a, [f32]>,
+    pub mesh: Cow<'a, [u16]>,
+    pub uvs: Cow<'a, [f32]>,
+    pub normals: Cow<'a, [f32]>,
+    pub colors: Cow<'a, [f32]>,
+    pub joints: Cow<'a, [u16]>,
+    pub weights: Cow<'a, [f32]>,
+    pub submeshes: Vec<SubmeshView<'a>>,
+}
+
+pub struct ActorView<'a> {
+    pub name: &'a str,
+    pub body: ModelView<'a>,
+    /// part name and model
+    pub parts: Vec<(&'a str, ModelView<'a>)>,
+    pub skeleton: Option<Skeleton>,
+}
+
+pub struct TrackView<'a> {
+    pub bone: &'a str,
+    pub translations: Cow<'a, [f32]>,
+    pub rotations: Cow<'a, [f32]>,
+    pub scales: Cow<'a, [f32]>,
+}
+
+pub struct ClipView<'a> {
+    pub name: &'a str,
+    pub times: Cow<'a, [f32]>,
+    pub tracks: Vec<TrackView<'a>>,
+}
+
+/// A `Material` borrowed from a `.mari` buffer.
+pub enum MaterialView<'a> {
+    Unlit { color: [f32; 4] },
+    Textured { texture: TextureView<'a> },
+    Toon(ToonMaterialView<'a>),
+    Pbr(PbrMaterialView<'a>),
+}
+
+pub struct ToonMaterialView<'a> {
+    pub texture: TextureView<'a>,
+    pub ramp_texture: TextureView<'a>,
+    pub sdw_texture: TextureView<'a>,
+    pub shadow_threshold: f32,
+    pub ramp_scale: f32,
+    pub outline_width: f32,
+    pub outline_color: [f32; 4],
+}
+
+pub struct PbrMaterialView<'a> {
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<TextureView<'a>>,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+/// A `Scene` borrowed from a `.mari` buffer, items in file order.
+pub struct SceneView<'a> {
+    pub version: (u16, u16),
+    pub actors: Vec<ActorView<'a>>,
+    pub textures: Vec<(&'a str, TextureView<'a>)>,
+    pub clips: Vec<ClipView<'a>>,
+    pub materials: Vec<(&'a str, MaterialView<'a>)>,
+}
+
+/// A `.mari` file mapped into memory.
+pub struct MappedScene {
+    map: memmap2::Mmap,
+}
+
+impl MappedScene {
+    /// Map the file at `path`, which must not be modified while mapped.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(Error::Io)?;
+        // SAFETY: the file is only read and the caller keeps it unmodified while mapped
+        let map = unsafe { memmap2::Mmap::map(&file) }.map_err(Error::Io)?;
+        Ok(Self { map })
+    }
+
+    /// Check every section and borrow the scene from the mapping.
+    pub fn view(&self) -> Result<SceneView<'_>, Error> {
+        SceneView::new(&self.map)
+    }
+}
+
+impl<'a> SceneView<'a> {
+    /// Read the table of contents and every section, verifying all checksums.
+    pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+        Self::new_with_verification(data, true)
+    }
+
+    /// Like `new`, but trusting the section checksums, so that pages of arrays that are never
+    /// used are never read either. The table of contents is still verified.
+    pub fn new_unverified(data: &'a [u8]) -> Result<Self, Error> {
+        Self::new_with_verification(data, false)
+    }
+
+    fn new_with_verification(data: &'a [u8], verify: bool) -> Result<Self, Error> {
+        if data.len() < HEADER_SIZE || &data[..4] != MAGIC {
+            return Err(Error::Invalid("Not a .mari file.".to_string()));
+        }
+        let u16_at = |at: usize| u16::from_le_bytes(data[at..at + 2].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let version = (u16_at(4), u16_at(6));
+        if version.0 != VERSION_MAJOR {
+            return Err(Error::Unsupported(format!(
+                "Version {}.{} is not readable by version {VERSION_MAJOR}.{VERSION_MINOR}.",
+                version.0, version.1
+            )));
+        }
+        let section_cnt = u32_at(8) as usize;
+        let table = section_cnt
+            .checked_mul(ENTRY_SIZE)
+            .and_then(|size| data.get(HEADER_SIZE..HEADER_SIZE + size))
+            .ok_or_else(|| Error::Invalid("The table of contents is truncated.".to_string()))?;
+        if crc32fast::hash(table) != u32_at(12) {
+            return Err(Error::Checksum(
+                "The table of contents is corrupted.".to_string(),
+            ));
+        }
+
+        let mut ret = Self {
+            version,
+            actors: Vec::new(),
+            textures: Vec::new(),
+            clips: Vec::new(),
+            materials: Vec::new(),
+        };
+        for (section, entry) in table.chunks_exact(ENTRY_SIZE).enumerate() {
+            let kind = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+            let crc = u32::from_le_bytes(entry[4..8].try_into().unwrap());
+            let offset = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+            let size = u64::from_le_bytes(entry[16..24].try_into().unwrap());
+            let range = usize::try_from(offset)
+                .ok()
+                .zip(usize::try_from(size).ok())
+                .and_then(|(offset, size)| Some(offset..offset.checked_add(size)?));
+            let Some(section_data) = range.and_then(|range| data.get(range)) else {
+                return Err(Error::Invalid(format!(
+                    "Section #{section} lies outside the file."
+                )));
+            };
+            if !(offset as usize).is_multiple_of(ALIGN) {
+                return Err(Error::Invalid(format!(
+                    "Section #{section} is not aligned."
+                )));
+            }
+            if verify && crc32fast::hash(section_data) != crc {
+                return Err(Error::Checksum(format!("Section #{section} is corrupted.")));
+            }
+
+            let mut reader = Reader {
+                data: section_data,
+                pos: 0,
+                section,
+            };
+            match kind {
+                KIND_ACTOR => ret.actors.push(reader.actor()?),
+                KIND_TEXTURE => {
+                    let name = reader.str()?;
+                    ret.textures.push((name, reader.texture()?));
+                }
+                KIND_CLIP => ret.clips.push(reader.clip()?),
+                KIND_MATERIAL => ret.materials.push(reader.material()?),
+                _ if version.1 > VERSION_MINOR => {}
+                _ => {
+                    return Err(Error::Invalid(format!(
+                        "Section #{section} is of unknown kind {kind}."
+                    )));
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Copy everything out into an owned `Scene`.
+    pub fn to_scene(&self) -> Result<Scene, Error> {
+        let mut ret = Scene::default();
+        for actor in &self.actors {
+            ret.actors.insert(actor.name.to_string(), actor.to_actor());
+        }
+        for (name, texture) in &self.textures {
+            ret.textures.insert(name.to_string(), texture.to_texture()?);
+        }
+        for clip in &self.clips {
+            ret.clips.insert(clip.name.to_string(), clip.to_clip());
+        }
+        for (name, material) in &self.materials {
+            ret.materials
+                .insert(name.to_string(), material.to_material()?);
+        }
+        Ok(ret)
+    }
+}
+
+impl TextureView<'_> {
+    pub fn to_texture(&self) -> Result<TextureRGBA8, Error> {
+        let width = self.width.try_into().map_err(|_| {
+            Error::Unsupported(format!("Texture width {} is too large.", self.width))
+        })?;
+        if self.height > u16::MAX as u32 {
+            return Err(Error::Unsupported(format!(
+                "Texture height {} is too large.",
+                self.height
+            )));
+        }
+        Ok(TextureRGBA8 {
+            width,
+            data: self.data.to_vec(),
+        })
+    }
+}
+
+impl ModelView<'_> {
+    pub fn to_model(&self) -> Model {
+        Model {
+            vertices: self.vertices.to_vec(),
+            mesh: self.mesh.to_vec(),
+            uvs: self.uvs.to_vec(),
+            normals: self.normals.to_vec(),
+            colors: self.colors.to_vec(),
+            joints: self.joints.to_vec(),
+            weights: self.weights.to_vec(),
+            submeshes: self
+                .submeshes
+                .iter()
+                .map(|s| Submesh {
+                    first: s.first,
+                    count: s.count,
+                    material: s.material.map(str::to_string),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ActorView<'_> {
+    pub fn to_actor(&self) -> Actor {
+        Actor {
+            body: self.body.to_model(),
+            parts: self
+                .parts
+                .iter()
+                .map(|(name, model)| Part {
+                    name: name.to_string(),
+                    model: model.to_model(),
+                })
+                .collect(),
+            skeleton: self.skeleton.as_ref().map(|s| Skeleton {
+                bones: s
+                    .bones
+                    .iter()
+                    .map(|b| Bone {
+                        name: b.name.clone(),
+                        ..*b
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl ClipView<'_> {
+    pub fn to_clip(&self) -> Clip {
+        Clip {
+            times: self.times.to_vec(),
+            tracks: self
+                .tracks
+                .iter()
+                .map(|t| Track {
+                    bone: t.bone.to_string(),
+                    translations: t.translations.to_vec(),
+                    rotations: t.rotations.to_vec(),
+                    scales: t.scales.to_vec(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MaterialView<'_> {
+    pub fn to_material(&self) -> Result<Material, Error> {
+        Ok(match self {
+            Self::Unlit { color } => Material::Unlit { color: *color },
+            Self::Textured { texture } => Material::Textured {
+                texture: texture.to_texture()?,
+            },
+            Self::Toon(toon) => Material::Toon(ToonMaterial {
+                texture: toon.texture.to_texture()?,
+                ramp_texture: toon.ramp_texture.to_texture()?,
+                sdw_texture: toon.sdw_texture.to_texture()?,
+                shadow_threshold: toon.shadow_threshold,
+                ramp_scale: toon.ramp_scale,
+                outline_width: toon.outline_width,
+                outline_color: toon.outline_color,
+            }),
+            Self::Pbr(pbr) => Material::Pbr(PbrMaterial {
+                base_color: pbr.base_color,
+                base_color_texture: pbr
+                    .base_color_texture
+                    .as_ref()
+                    .map(TextureView::to_texture)
+                    .transpose()?,
+                metallic: pbr.metallic,
+                roughness: pbr.roughness,
+            }),
+        })
+    }
+}
+
+impl Scene {
+    /// Read a `.mari` buffer into an owned scene. See `MappedScene` for borrowing instead.
+    pub fn new_from_native(data: &[u8]) -> Result<Self, Error> {
+        SceneView::new(data)?.to_scene()
+    }
+
+    /// Write the scene as a `.mari` buffer, items sorted by name.
+    pub fn write_native(&self) -> Vec<u8> {
+        fn sorted<T>(map: &std::collections::HashMap<String, T>) -> Vec<(&String, &T)> {
+            let mut ret: Vec<_> = map.iter().collect();
+            ret.sort_by_key(|(name, _)| *name);
+            ret
+        }
+
+        let mut sections = Vec::new();
+        let mut section = |kind: u32, write: &dyn Fn(&mut Writer)| {
+            let mut writer = Writer { data: Vec::new() };
+            write(&mut writer);
+            sections.push((kind, writer.data));
+        };
+        for (name, actor) in sorted(&self.actors) {
+            section(KIND_ACTOR, &|w| {
+                w.str(name);
+                w.model(&actor.body);
+                w.u32(actor.parts.len() as u32);
+                for part in &actor.parts {
+                    w.str(&part.name);
+                    w.model(&part.model);
+                }
+                match &actor.skeleton {
+                    Some(skeleton) => {
+                        w.u32(skeleton.bones.len() as u32);
+                        for bone in &skeleton.bones {
+                            w.str(&bone.name);
+                            w.u32(bone.parent.map_or(NONE, |p| p as u32));
+                            w.f32s(&bone.translation);
+                            w.f32s(&bone.rotation);
+                            w.f32s(&bone.scale);
+                        }
+                    }
+                    None => w.u32(NONE),
+                }
+            });
+        }
+        for (name, texture) in sorted(&self.textures) {
+            section(KIND_TEXTURE, &|w| {
+                w.str(name);
+                w.texture(texture);
+            });
+        }
+        for (name, clip) in sorted(&self.clips) {
+            section(KIND_CLIP, &|w| {
+                w.str(name);
+                w.array(&clip.times);
+                w.u32(clip.tracks.len() as u32);
+                for track in &clip.tracks {
+                    w.str(&track.bone);
+                    w.array(&track.translations);
+                    w.array(&track.rotations);
+                    w.array(&track.scales);
+                }
+            });
+        }
+        for (name, material) in sorted(&self.materials) {
+            section(KIND_MATERIAL, &|w| {
+                w.str(name);
+                w.material(material);
+            });
+        }
+
+        let mut table = Vec::with_capacity(sections.len() * ENTRY_SIZE);
+        let mut offset = (HEADER_SIZE + sections.len() * ENTRY_SIZE).next_multiple_of(ALIGN);
+        for (kind, data) in &sections {
+            table.extend_from_slice(&kind.to_le_bytes());
+            table.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+            table.extend_from_slice(&(offset as u64).to_le_bytes());
+            table.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            offset = (offset + data.len()).next_multiple_of(ALIGN);
+        }
+
+        let mut ret = Vec::with_capacity(offset);
+        ret.extend_from_slice(MAGIC);
+        ret.extend_from_slice(&VERSION_MAJOR.to_le_bytes());
+        ret.extend_from_slice(&VERSION_MINOR.to_le_bytes());
+        ret.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+        ret.extend_from_slice(&crc32fast::hash(&table).to_le_bytes());
+        ret.extend_from_slice(&table);
+        for (_, data) in &sections {
+            pad(&mut ret, ALIGN);
+            ret.extend_from_slice(data);
+        }
+        ret
+    }
+}
@@ -1,12 +1,24 @@
+use std::fmt::Write;
 use std::io::{BufRead, BufReader, Read};
 
+use crate::{Material, Model, Scene};
+
 #[derive(Debug)]
 pub enum Error {
     Io(std::io::Error),
     Invalid(String),
     TooManyVertices,
+    Png(png::EncodingError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
 }
 
+impl std::error::Error for Error {}
+
 pub struct Obj {
     /// compact storage of vertex x,y,z
     pub vertices: Vec<f32>,
@@ -16,6 +28,8 @@ pub struct Obj {
     pub uvs: Vec<f32>,
     /// compact storage of vertex normals, not normalized
     pub normals: Vec<f32>,
+    /// first index into `mesh` and name of each `usemtl`
+    pub materials: Vec<(usize, String)>,
 }
 
 impl Obj {
@@ -24,6 +38,7 @@ impl Obj {
         let mut texture = Vec::<f32>::new();
         let mut mesh = Vec::<u16>::new();
         let mut normals = Vec::<f32>::new();
+        let mut materials = Vec::<(usize, String)>::new();
 
         for (i, line) in buf.lines().enumerate() {
             let line = line.map_err(Error::Io)?;
@@ -124,6 +139,18 @@ impl Obj {
                     }
                 }
 
+                "usemtl" => {
+                    let name = line["usemtl".len()..].trim().to_string();
+                    // a material switch without faces in between replaces the previous one
+                    if materials
+                        .last()
+                        .is_some_and(|(first, _)| *first == mesh.len())
+                    {
+                        materials.pop();
+                    }
+                    materials.push((mesh.len(), name));
+                }
+
                 _ => {}
             }
         }
@@ -133,6 +160,154 @@ impl Obj {
             mesh,
             uvs: texture,
             normals,
+            materials,
         })
     }
 }
+
+/// An OBJ, its MTL and the PNG files the MTL refers to.
+pub struct ObjFiles {
+    pub obj: String,
+    pub mtl: String,
+    /// file name and PNG data
+    pub textures: Vec<(String, Vec<u8>)>,
+}
+
+/// a name usable as a file name
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_alphanumeric() || "-_.".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Append `model` as object `name` whose vertices start at `base`, returning the vertex count.
+///
+/// `default` is the material of submeshes without one.
+fn write_model(
+    out: &mut String,
+    name: &str,
+    model: &Model,
+    base: usize,
+    default: &str,
+    current: &mut Option<String>,
+) -> usize {
+    let vertex_cnt = model.vertices.len() / 3;
+    let has_uvs = model.uvs.len() == vertex_cnt * 2;
+    let has_normals = model.normals.len() == vertex_cnt * 3;
+
+    writeln!(out, "o {name}").unwrap();
+    for v in model.vertices.chunks_exact(3) {
+        writeln!(out, "v {} {} {}", v[0], v[1], v[2]).unwrap();
+    }
+    if has_uvs {
+        for uv in model.uvs.chunks_exact(2) {
+            // flip V back from OpenGL space to OBJ space
+            writeln!(out, "vt {} {}", uv[0], 1.0 - uv[1]).unwrap();
+        }
+    }
+    if has_normals {
+        for n in model.normals.chunks_exact(3) {
+            writeln!(out, "vn {} {} {}", n[0], n[1], n[2]).unwrap();
+        }
+    }
+
+    for submesh in &model.submeshes {
+        let material = submesh.material.as_deref();
+        // faces before the first `usemtl` have no material, later ones need a name
+        if material.is_some() || current.is_some() {
+            let material = material.unwrap_or(default);
+            if current.as_deref() != Some(material) {
+                writeln!(out, "usemtl {material}").unwrap();
+                *current = Some(material.to_string());
+            }
+        }
+        let range = submesh.first..submesh.first + submesh.count;
+        for t in model.mesh[range].chunks_exact(3) {
+            out.push('f');
+            for i in t {
+                let i = base + *i as usize + 1;
+                match (has_uvs, has_normals) {
+                    (true, true) => write!(out, " {i}/{i}/{i}"),
+                    (true, false) => write!(out, " {i}/{i}"),
+                    (false, true) => write!(out, " {i}//{i}"),
+                    (false, false) => write!(out, " {i}"),
+                }
+                .unwrap();
+            }
+            out.push('\n');
+        }
+    }
+    vertex_cnt
+}
+
+impl Model {
+    /// Write as a single OBJ object without a `mtllib`, V flipped back to the OBJ convention.
+    ///
+    /// Submeshes keep their material names through `usemtl`. One without a material that follows
+    /// one with a material gets `default`. Bones are not written.
+    pub fn write_obj(&self) -> String {
+        let mut ret = String::new();
+        write_model(&mut ret, "model", self, 0, "default", &mut None);
+        ret
+    }
+}
+
+impl Scene {
+    /// Write every model of every actor as an OBJ object, referring to an MTL saved as `mtl_name`.
+    ///
+    /// Submeshes without a material use one named after their actor. A material gets the color
+    /// and texture of the scene material of the same name where it has them, else white and the
+    /// texture of its actor.
+    /// Skeletons and clips are not written.
+    pub fn write_obj(&self, mtl_name: &str) -> Result<ObjFiles, Error> {
+        let mut names: Vec<&String> = self.actors.keys().collect();
+        names.sort();
+
+        let mut obj = format!("mtllib {mtl_name}\n");
+        let mut mtl = String::new();
+        let mut textures: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut materials: Vec<String> = Vec::new();
+        let mut base = 0;
+        let mut current = None;
+        for name in names {
+            let actor = &self.actors[name];
+            let models = std::iter::once((name.as_str(), &actor.body))
+                .chain(actor.parts.iter().map(|p| (p.name.as_str(), &p.model)));
+            for (model_name, model) in models {
+                base += write_model(&mut obj, model_name, model, base, name, &mut current);
+
+                for submesh in &model.submeshes {
+                    let material = submesh.material.clone().unwrap_or(name.clone());
+                    if materials.contains(&material) {
+                        continue;
+                    }
+                    let scene_material = self.materials.get(&material);
+                    let [r, g, b, _] = match scene_material {
+                        Some(Material::Unlit { color }) => *color,
+                        Some(Material::Pbr(pbr)) => pbr.base_color,
+                        _ => [1.0; 4],
+                    };
+                    writeln!(mtl, "newmtl {material}\nKd {r} {g} {b}").unwrap();
+                    let texture = match scene_material.and_then(Material::base_texture) {
+                        Some(texture) => Some((&material, texture)),
+                        None => self.textures.get(name).map(|t| (name, t)),
+                    };
+                    if let Some((owner, texture)) = texture {
+                        let file = format!("{}.png", file_name(owner));
+                        if !textures.iter().any(|(f, _)| *f == file) {
+                            textures
+                                .push((file.clone(), texture.encode_png().map_err(Error::Png)?));
+                        }
+                        writeln!(mtl, "map_Kd {file}").unwrap();
+                    }
+                    mtl.push('\n');
+                    materials.push(material);
+                }
+            }
+        }
+        Ok(ObjFiles { obj, mtl, textures })
+    }
+}
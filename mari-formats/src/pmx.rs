@@ -0,0 +1,448 @@
+//! MikuMikuDance PMX 2.0/2.1 models, up to and including the bones.
+
+use std::io::{BufReader, Read};
+
+use crate::{Actor, Model, Skeleton, Submesh};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Invalid(String),
+    Unsupported(String),
+    TooManyVertices,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub struct Material {
+    pub name: String,
+    /// r,g,b,a
+    pub diffuse: [f32; 4],
+    /// index into `Pmx::textures`
+    pub texture: Option<usize>,
+    /// index into `Pmx::textures`
+    pub sphere_texture: Option<usize>,
+    /// index into `Pmx::textures`, `None` for no or a shared toon texture
+    pub toon_texture: Option<usize>,
+    /// r,g,b,a, drawn when the edge flag is set
+    pub edge_color: [f32; 4],
+    pub edge_size: f32,
+    /// number of indices into `Pmx::indices`, following those of the previous material
+    pub index_count: usize,
+}
+
+pub struct Bone {
+    pub name: String,
+    /// absolute rest position
+    pub position: [f32; 3],
+    /// index into `Pmx::bones`
+    pub parent: Option<usize>,
+}
+
+/// A PMX model as stored, in MMD's left-handed space.
+pub struct Pmx {
+    pub name: String,
+    pub comment: String,
+    /// x,y,z per vertex
+    pub positions: Vec<f32>,
+    /// x,y,z per vertex
+    pub normals: Vec<f32>,
+    /// u,v per vertex, v pointing down
+    pub uvs: Vec<f32>,
+    /// 4 indices into `bones` per vertex
+    pub joints: Vec<u16>,
+    /// 4 weights per vertex
+    pub weights: Vec<f32>,
+    /// triangle indices, clockwise as front-facing
+    pub indices: Vec<u32>,
+    /// texture paths relative to the model file
+    pub textures: Vec<String>,
+    pub materials: Vec<Material>,
+    pub bones: Vec<Bone>,
+}
+
+struct Cursor {
+    data: Vec<u8>,
+    pos: usize,
+    utf8: bool,
+}
+
+impl Cursor {
+    fn bytes(&mut self, n: usize) -> Result<&[u8], Error> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(Error::Invalid(format!(
+                "Reading {} bytes @ byte {} overruns the file.",
+                n, self.pos
+            )))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        let b = self.bytes(4)?;
+        Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        let b = self.bytes(4)?;
+        Ok(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Result<[f32; N], Error> {
+        let mut ret = [0.0; N];
+        for r in ret.iter_mut() {
+            *r = self.f32()?;
+        }
+        Ok(ret)
+    }
+
+    fn count(&mut self) -> Result<usize, Error> {
+        let n = self.i32()?;
+        usize::try_from(n).map_err(|_| Error::Invalid(format!("Negative count {n}.")))
+    }
+
+    fn text(&mut self) -> Result<String, Error> {
+        let len = self.count()?;
+        let utf8 = self.utf8;
+        let bytes = self.bytes(len)?;
+        Ok(if utf8 {
+            String::from_utf8_lossy(bytes).into_owned()
+        } else {
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        })
+    }
+
+    /// vertex indices are unsigned unless 4 bytes wide
+    fn vertex_index(&mut self, size: u8) -> Result<u32, Error> {
+        Ok(match size {
+            1 => self.u8()? as u32,
+            2 => self.u16()? as u32,
+            _ => self.i32()? as u32,
+        })
+    }
+
+    /// other indices are signed, -1 for none
+    fn index(&mut self, size: u8) -> Result<Option<usize>, Error> {
+        let i = match size {
+            1 => self.u8()? as i8 as i32,
+            2 => self.u16()? as i16 as i32,
+            _ => self.i32()?,
+        };
+        Ok(usize::try_from(i).ok())
+    }
+}
+
+impl Pmx {
+    pub fn new<R: Read>(mut buf: BufReader<R>) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        buf.read_to_end(&mut data).map_err(Error::Io)?;
+        let mut r = Cursor {
+            data,
+            pos: 0,
+            utf8: false,
+        };
+
+        if r.bytes(4)? != b"PMX " {
+            return Err(Error::Invalid("Not a PMX file.".to_string()));
+        }
+        let version = r.f32()?;
+        if !(2.0..=2.1).contains(&version) {
+            return Err(Error::Unsupported(format!("PMX version {version}")));
+        }
+        let globals = r.u8()? as usize;
+        let globals = r.bytes(globals)?.to_vec();
+        if globals.len() < 8 || globals[2..].iter().any(|s| ![1, 2, 4].contains(s)) {
+            return Err(Error::Invalid("Invalid PMX globals.".to_string()));
+        }
+        r.utf8 = globals[0] == 1;
+        let additional_uvs = globals[1] as usize;
+        let (vertex_size, texture_size, bone_size) = (globals[2], globals[3], globals[5]);
+
+        let name = r.text()?;
+        let _english_name = r.text()?;
+        let comment = r.text()?;
+        let _english_comment = r.text()?;
+
+        let vertex_cnt = r.count()?;
+        let mut positions = Vec::with_capacity(vertex_cnt * 3);
+        let mut normals = Vec::with_capacity(vertex_cnt * 3);
+        let mut uvs = Vec::with_capacity(vertex_cnt * 2);
+        let mut joints = Vec::with_capacity(vertex_cnt * 4);
+        let mut weights = Vec::with_capacity(vertex_cnt * 4);
+        for _ in 0..vertex_cnt {
+            positions.extend(r.f32s::<3>()?);
+            normals.extend(r.f32s::<3>()?);
+            uvs.extend(r.f32s::<2>()?);
+            r.bytes(additional_uvs * 16)?;
+
+            let mut j = [0u16; 4];
+            let mut w = [0.0f32; 4];
+            let bone = |r: &mut Cursor| -> Result<u16, Error> {
+                Ok(r.index(bone_size)?.unwrap_or(0) as u16)
+            };
+            match r.u8()? {
+                // BDEF1
+                0 => {
+                    j[0] = bone(&mut r)?;
+                    w[0] = 1.0;
+                }
+                // BDEF2, and SDEF approximated by it
+                t @ (1 | 3) => {
+                    j[0] = bone(&mut r)?;
+                    j[1] = bone(&mut r)?;
+                    w[0] = r.f32()?;
+                    w[1] = 1.0 - w[0];
+                    if t == 3 {
+                        r.bytes(36)?;
+                    }
+                }
+                // BDEF4, and QDEF approximated by it
+                2 | 4 => {
+                    for j in j.iter_mut() {
+                        *j = bone(&mut r)?;
+                    }
+                    w = r.f32s::<4>()?;
+                }
+                t => return Err(Error::Unsupported(format!("Weight deform type {t}"))),
+            }
+            joints.extend(j);
+            weights.extend(w);
+            let _edge_scale = r.f32()?;
+        }
+
+        let index_cnt = r.count()?;
+        let mut indices = Vec::with_capacity(index_cnt);
+        for _ in 0..index_cnt {
+            let i = r.vertex_index(vertex_size)?;
+            if i as usize >= vertex_cnt {
+                return Err(Error::Invalid(format!(
+                    "Index {i} past the {vertex_cnt} vertices."
+                )));
+            }
+            indices.push(i);
+        }
+
+        let mut textures = Vec::new();
+        for _ in 0..r.count()? {
+            textures.push(r.text()?.replace('\\', "/"));
+        }
+
+        let mut materials = Vec::new();
+        for _ in 0..r.count()? {
+            let name = r.text()?;
+            let _english_name = r.text()?;
+            let diffuse = r.f32s::<4>()?;
+            let _specular = r.f32s::<4>()?;
+            let _ambient = r.f32s::<3>()?;
+            let _flags = r.u8()?;
+            let edge_color = r.f32s::<4>()?;
+            let edge_size = r.f32()?;
+            let texture = r.index(texture_size)?;
+            let sphere_texture = r.index(texture_size)?;
+            let _sphere_mode = r.u8()?;
+            let toon_texture = match r.u8()? {
+                0 => r.index(texture_size)?,
+                _ => {
+                    r.u8()?;
+                    None
+                }
+            };
+            let _memo = r.text()?;
+            materials.push(Material {
+                name,
+                diffuse,
+                texture,
+                sphere_texture,
+                toon_texture,
+                edge_color,
+                edge_size,
+                index_count: r.count()?,
+            });
+        }
+        if materials.iter().map(|m| m.index_count).sum::<usize>() > indices.len() {
+            return Err(Error::Invalid(
+                "Materials cover more than all indices.".to_string(),
+            ));
+        }
+
+        let mut bones = Vec::new();
+        for _ in 0..r.count()? {
+            let name = r.text()?;
+            let _english_name = r.text()?;
+            let position = r.f32s::<3>()?;
+            let parent = r.index(bone_size)?;
+            let _layer = r.i32()?;
+            let flags = r.u16()?;
+            if flags & 0x1 != 0 {
+                r.index(bone_size)?;
+            } else {
+                r.f32s::<3>()?;
+            }
+            if flags & 0x300 != 0 {
+                r.index(bone_size)?;
+                r.f32()?;
+            }
+            if flags & 0x400 != 0 {
+                r.f32s::<3>()?;
+            }
+            if flags & 0x800 != 0 {
+                r.f32s::<6>()?;
+            }
+            if flags & 0x2000 != 0 {
+                r.i32()?;
+            }
+            if flags & 0x20 != 0 {
+                r.index(bone_size)?;
+                r.i32()?;
+                r.f32()?;
+                for _ in 0..r.count()? {
+                    r.index(bone_size)?;
+                    if r.u8()? == 1 {
+                        r.f32s::<6>()?;
+                    }
+                }
+            }
+            bones.push(Bone {
+                name,
+                position,
+                parent,
+            });
+        }
+        if bones.len() > u16::MAX as usize
+            || joints.iter().any(|j| *j as usize >= bones.len().max(1))
+        {
+            return Err(Error::Invalid(
+                "Vertex weighted to a missing bone.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            name,
+            comment,
+            positions,
+            normals,
+            uvs,
+            joints,
+            weights,
+            indices,
+            textures,
+            materials,
+            bones,
+        })
+    }
+
+    /// Convert to an actor in the right-handed space of `Model`, by negating z so that the model
+    /// faces +z like the ones assembled from Unity.
+    pub fn to_actor(&self) -> Result<Actor, Error> {
+        if self.positions.len() / 3 > u16::MAX as usize + 1 {
+            return Err(Error::TooManyVertices);
+        }
+
+        // parents first, as PMX allows children before their parents
+        let mut order = Vec::with_capacity(self.bones.len());
+        let mut placed = vec![None; self.bones.len()];
+        while order.len() < self.bones.len() {
+            let before = order.len();
+            for (i, b) in self.bones.iter().enumerate() {
+                let ready = match b.parent {
+                    Some(p) => p >= self.bones.len() || placed[p].is_some(),
+                    None => true,
+                };
+                if placed[i].is_none() && ready {
+                    placed[i] = Some(order.len());
+                    order.push(i);
+                }
+            }
+            if order.len() == before {
+                // a parent loop, break it at the first bone left
+                let i = placed.iter().position(Option::is_none).unwrap();
+                placed[i] = Some(order.len());
+                order.push(i);
+            }
+        }
+
+        let bones = order
+            .iter()
+            .enumerate()
+            .map(|(new, i)| {
+                let b = &self.bones[*i];
+                let parent = b
+                    .parent
+                    .filter(|p| placed.get(*p).copied().flatten().is_some_and(|p| p < new));
+                let origin = parent.map_or([0.0; 3], |p| self.bones[p].position);
+                crate::Bone {
+                    name: b.name.clone(),
+                    parent: parent.and_then(|p| placed[p]),
+                    translation: [
+                        b.position[0] - origin[0],
+                        b.position[1] - origin[1],
+                        -(b.position[2] - origin[2]),
+                    ],
+                    rotation: [0.0, 0.0, 0.0, 1.0],
+                    scale: [1.0; 3],
+                }
+            })
+            .collect();
+
+        let flip_z = |v: &[f32]| -> Vec<f32> {
+            v.chunks_exact(3)
+                .flat_map(|v| [v[0], v[1], -v[2]])
+                .collect()
+        };
+        let mut submeshes = Vec::new();
+        let mut first = 0;
+        for m in &self.materials {
+            submeshes.push(Submesh {
+                first,
+                count: m.index_count,
+                material: Some(m.name.clone()),
+            });
+            first += m.index_count;
+        }
+
+        let body = Model {
+            vertices: flip_z(&self.positions),
+            // negating z turns clockwise front faces counter-clockwise, indices stay as they are
+            mesh: self.indices.iter().map(|i| *i as u16).collect(),
+            // v already points down like a top-to-bottom texture
+            uvs: self.uvs.clone(),
+            normals: flip_z(&self.normals),
+            colors: Vec::new(),
+            joints: self
+                .joints
+                .iter()
+                .map(|j| placed.get(*j as usize).copied().flatten().unwrap_or(0) as u16)
+                .collect(),
+            weights: self.weights.clone(),
+            submeshes,
+        };
+        Ok(Actor {
+            body,
+            parts: Vec::new(),
+            skeleton: (!self.bones.is_empty()).then_some(Skeleton { bones }),
+        })
+    }
+}
@@ -0,0 +1,231 @@
+//! Mesh and texture clean-up before export.
+
+use std::collections::HashMap;
+
+use crate::Model;
+
+/// Vertex cache size Tipsify optimizes for.
+const CACHE_SIZE: usize = 16;
+
+impl Model {
+    fn vertex_cnt(&self) -> usize {
+        self.vertices.len() / 3
+    }
+
+    /// Keep only the vertices in `order`, vertex `i` becoming the one at `order[i]`.
+    ///
+    /// every vertex referenced by `mesh` must be kept
+    fn reorder_vertices(&mut self, order: &[usize]) {
+        let n = self.vertex_cnt();
+        let mut new_index = vec![u16::MAX; n];
+        for (new, old) in order.iter().enumerate() {
+            new_index[*old] = new as u16;
+        }
+        for i in self.mesh.iter_mut() {
+            *i = new_index[*i as usize];
+        }
+
+        fn pick<T: Copy>(v: &mut Vec<T>, width: usize, n: usize, order: &[usize]) {
+            if v.len() != n * width {
+                return;
+            }
+            *v = order
+                .iter()
+                .flat_map(|o| v[o * width..o * width + width].iter().copied())
+                .collect();
+        }
+        pick(&mut self.vertices, 3, n, order);
+        pick(&mut self.normals, 3, n, order);
+        pick(&mut self.colors, 4, n, order);
+        pick(&mut self.uvs, 2, n, order);
+        pick(&mut self.joints, 4, n, order);
+        pick(&mut self.weights, 4, n, order);
+    }
+
+    /// Drop degenerate triangles and unused vertices, renormalize normals and bone weights.
+    ///
+    /// returns the number of triangles dropped
+    pub fn cleanup(&mut self) -> usize {
+        let mut mesh = Vec::with_capacity(self.mesh.len());
+        for submesh in self.submeshes.iter_mut() {
+            let first = mesh.len();
+            for t in self.mesh[submesh.first..submesh.first + submesh.count].chunks_exact(3) {
+                let p = |i: u16| {
+                    let i = i as usize * 3;
+                    [self.vertices[i], self.vertices[i + 1], self.vertices[i + 2]]
+                };
+                let (a, b, c) = (p(t[0]), p(t[1]), p(t[2]));
+                let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+                let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+                let cross = [
+                    u[1] * v[2] - u[2] * v[1],
+                    u[2] * v[0] - u[0] * v[2],
+                    u[0] * v[1] - u[1] * v[0],
+                ];
+                let distinct = t[0] != t[1] && t[1] != t[2] && t[0] != t[2];
+                if distinct && cross.iter().any(|c| *c != 0.0) {
+                    mesh.extend_from_slice(t);
+                }
+            }
+            submesh.first = first;
+            submesh.count = mesh.len() - first;
+        }
+        let dropped = (self.mesh.len() - mesh.len()) / 3;
+        self.mesh = mesh;
+        self.submeshes.retain(|s| s.count > 0);
+
+        let mut used = vec![false; self.vertex_cnt()];
+        self.mesh.iter().for_each(|i| used[*i as usize] = true);
+        let order: Vec<usize> = (0..used.len()).filter(|i| used[*i]).collect();
+        self.reorder_vertices(&order);
+
+        for n in self.normals.chunks_exact_mut(3) {
+            let l = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            if l > 0.0 {
+                n.iter_mut().for_each(|n| *n /= l);
+            }
+        }
+        for w in self.weights.chunks_exact_mut(4) {
+            let sum: f32 = w.iter().sum();
+            if sum > 0.0 {
+                w.iter_mut().for_each(|w| *w /= sum);
+            }
+        }
+        dropped
+    }
+
+    /// Merge vertices whose positions, quantized to a grid of `epsilon`, fall in the same cell and
+    /// whose other attributes match.
+    ///
+    /// Two positions closer than `epsilon` may still straddle a cell boundary and stay apart.
+    ///
+    /// returns the number of vertices merged away
+    pub fn weld(&mut self, epsilon: f32) -> usize {
+        let n = self.vertex_cnt();
+        let quantize = |v: f32, step: f32| (v / step).round() as i64;
+        let attribute = |v: &[f32], width: usize, i: usize, step: f32| -> Vec<i64> {
+            v.get(i * width..i * width + width)
+                .map(|v| v.iter().map(|v| quantize(*v, step)).collect())
+                .unwrap_or_default()
+        };
+
+        let mut keys: HashMap<Vec<i64>, usize> = HashMap::new();
+        let mut order = Vec::new();
+        let mut remap = vec![0u16; n];
+        for (i, r) in remap.iter_mut().enumerate() {
+            let mut key = attribute(&self.vertices, 3, i, epsilon.max(f32::MIN_POSITIVE));
+            key.extend(attribute(&self.normals, 3, i, 1e-4));
+            key.extend(attribute(&self.uvs, 2, i, 1e-6));
+            key.extend(attribute(&self.colors, 4, i, 1e-4));
+            key.extend(attribute(&self.weights, 4, i, 1e-4));
+            if let Some(j) = self.joints.get(i * 4..i * 4 + 4) {
+                key.extend(j.iter().map(|j| *j as i64));
+            }
+            *r = *keys.entry(key).or_insert_with(|| {
+                order.push(i);
+                order.len() - 1
+            }) as u16;
+        }
+
+        // point the indices at the first vertex of each group, then keep only those
+        for i in self.mesh.iter_mut() {
+            *i = order[remap[*i as usize] as usize] as u16;
+        }
+        self.reorder_vertices(&order);
+        n - order.len()
+    }
+
+    /// Reorder the triangles of every submesh for the post-transform vertex cache (Tipsify), then
+    /// the vertices by first use.
+    pub fn optimize(&mut self) {
+        let n = self.vertex_cnt();
+        for submesh in &self.submeshes {
+            let range = submesh.first..submesh.first + submesh.count;
+            let optimized = tipsify(&self.mesh[range.clone()], n);
+            self.mesh[range].copy_from_slice(&optimized);
+        }
+
+        let mut seen = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        for i in &self.mesh {
+            if !std::mem::replace(&mut seen[*i as usize], true) {
+                order.push(*i as usize);
+            }
+        }
+        order.extend((0..n).filter(|i| !seen[*i]));
+        self.reorder_vertices(&order);
+    }
+}
+
+/// Sander et al., "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw", 2007.
+fn tipsify(indices: &[u16], vertex_cnt: usize) -> Vec<u16> {
+    let triangle_cnt = indices.len() / 3;
+    let mut live = vec![0usize; vertex_cnt];
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); vertex_cnt];
+    for (t, tri) in indices.chunks_exact(3).enumerate() {
+        for v in tri {
+            live[*v as usize] += 1;
+            adjacency[*v as usize].push(t);
+        }
+    }
+
+    let mut cache_time = vec![0usize; vertex_cnt];
+    let mut emitted = vec![false; triangle_cnt];
+    let mut dead_end: Vec<usize> = Vec::new();
+    let mut ret = Vec::with_capacity(indices.len());
+    let mut time = CACHE_SIZE + 1;
+    let mut cursor = 0;
+    let mut fanning = indices.first().map(|i| *i as usize);
+
+    while let Some(f) = fanning {
+        let mut candidates = Vec::new();
+        for t in &adjacency[f] {
+            if std::mem::replace(&mut emitted[*t], true) {
+                continue;
+            }
+            for v in &indices[t * 3..t * 3 + 3] {
+                let v = *v as usize;
+                ret.push(v as u16);
+                dead_end.push(v);
+                candidates.push(v);
+                live[v] -= 1;
+                if time - cache_time[v] > CACHE_SIZE {
+                    cache_time[v] = time;
+                    time += 1;
+                }
+            }
+        }
+
+        // the candidate most likely still in the cache, else a dead end or the next live vertex
+        fanning = candidates
+            .iter()
+            .filter(|v| live[**v] > 0)
+            .map(|v| {
+                let age = time - cache_time[*v];
+                let priority = if age + 2 * live[*v] <= CACHE_SIZE {
+                    age
+                } else {
+                    0
+                };
+                (priority, *v)
+            })
+            .max_by_key(|(p, _)| *p)
+            .map(|(_, v)| v)
+            .or_else(|| {
+                while let Some(d) = dead_end.pop() {
+                    if live[d] > 0 {
+                        return Some(d);
+                    }
+                }
+                while cursor < indices.len() {
+                    let v = indices[cursor] as usize;
+                    cursor += 1;
+                    if live[v] > 0 {
+                        return Some(v);
+                    }
+                }
+                None
+            });
+    }
+    ret
+}
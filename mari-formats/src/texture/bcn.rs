@@ -0,0 +1,455 @@
+//! Block-compressed formats shared by DDS, KTX2 and Unity textures.
+
+use crate::TextureError;
+
+/// BC2 only comes from DDS and KTX2, Unity has no DXT3
+#[cfg_attr(not(any(feature = "dds", feature = "ktx2")), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bc {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4 { signed: bool },
+    Bc5 { signed: bool },
+    Bc7,
+}
+
+impl Bc {
+    pub(crate) fn block_size(self) -> usize {
+        match self {
+            Self::Bc1 | Self::Bc4 { .. } => 8,
+            Self::Bc2 | Self::Bc3 | Self::Bc5 { .. } | Self::Bc7 => 16,
+        }
+    }
+
+    /// bytes of one image of `width` x `height`
+    pub(crate) fn image_size(self, width: usize, height: usize) -> usize {
+        width.div_ceil(4) * height.div_ceil(4) * self.block_size()
+    }
+
+    /// Decode one image into RGBA8 rows, cropping the blocks on the right and bottom edges.
+    pub(crate) fn decode(
+        self,
+        data: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<u8>, TextureError> {
+        let data = data
+            .get(..self.image_size(width, height))
+            .ok_or_else(|| TextureError::Invalid(format!("{self:?}: truncated blocks")))?;
+        let blocks_x = width.div_ceil(4);
+        let mut out = vec![0; width * height * 4];
+        for (i, block) in data.chunks_exact(self.block_size()).enumerate() {
+            let texels = self.decode_block(block);
+            let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+            for (j, texel) in texels.iter().enumerate() {
+                let (x, y) = (bx + j % 4, by + j / 4);
+                if x < width && y < height {
+                    out[(y * width + x) * 4..][..4].copy_from_slice(texel);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn decode_block(self, block: &[u8]) -> [[u8; 4]; 16] {
+        match self {
+            Self::Bc1 => color_block(block, true),
+            Self::Bc2 => {
+                let mut ret = color_block(&block[8..], false);
+                for (j, texel) in ret.iter_mut().enumerate() {
+                    texel[3] = ((block[j / 2] >> (j % 2 * 4)) & 0xf) * 17;
+                }
+                ret
+            }
+            Self::Bc3 => {
+                let mut ret = color_block(&block[8..], false);
+                for (texel, a) in ret.iter_mut().zip(unorm_block(block)) {
+                    texel[3] = a;
+                }
+                ret
+            }
+            Self::Bc4 { signed } => channel_block(block, signed).map(|r| [r, 0, 0, 255]),
+            Self::Bc5 { signed } => {
+                let r = channel_block(block, signed);
+                let g = channel_block(&block[8..], signed);
+                std::array::from_fn(|j| [r[j], g[j], 0, 255])
+            }
+            Self::Bc7 => bc7_block(block),
+        }
+    }
+}
+
+fn rgb565(v: u16) -> [u32; 3] {
+    let r = (v >> 11) as u32 & 0x1f;
+    let g = (v >> 5) as u32 & 0x3f;
+    let b = v as u32 & 0x1f;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// BC1 colors; only BC1 itself has the 3-color mode with transparent black
+fn color_block(block: &[u8], bc1: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u32, wb: u32| {
+        let n = wa + wb;
+        [
+            ((a[0] * wa + b[0] * wb) / n) as u8,
+            ((a[1] * wa + b[1] * wb) / n) as u8,
+            ((a[2] * wa + b[2] * wb) / n) as u8,
+            255,
+        ]
+    };
+    let palette = if c0 > c1 || !bc1 {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, 0]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|j| palette[(indices >> (j * 2)) as usize & 3])
+}
+
+/// 3-bit indices into an 8-value ramp, as used by BC3 alpha and BC4/BC5 channels
+fn ramp_indices(block: &[u8]) -> impl Iterator<Item = usize> {
+    let bits = u64::from_le_bytes([
+        block[2], block[3], block[4], block[5], block[6], block[7], 0, 0,
+    ]);
+    (0..16).map(move |j| (bits >> (j * 3)) as usize & 7)
+}
+
+fn unorm_block(block: &[u8]) -> [u8; 16] {
+    let (e0, e1) = (block[0] as u32, block[1] as u32);
+    let ramp: [u8; 8] = if e0 > e1 {
+        std::array::from_fn(|i| match i {
+            0 => e0 as u8,
+            1 => e1 as u8,
+            _ => ((e0 * (8 - i as u32) + e1 * (i as u32 - 1)) / 7) as u8,
+        })
+    } else {
+        std::array::from_fn(|i| match i {
+            0 => e0 as u8,
+            1 => e1 as u8,
+            6 => 0,
+            7 => 255,
+            _ => ((e0 * (6 - i as u32) + e1 * (i as u32 - 1)) / 5) as u8,
+        })
+    };
+    let mut ret = [0; 16];
+    for (texel, i) in ret.iter_mut().zip(ramp_indices(block)) {
+        *texel = ramp[i];
+    }
+    ret
+}
+
+/// Signed blocks are remapped from -1..1 to 0..255 so they fit RGBA8.
+fn channel_block(block: &[u8], signed: bool) -> [u8; 16] {
+    if !signed {
+        return unorm_block(block);
+    }
+    let e0 = (block[0] as i8).max(-127) as f32 / 127.0;
+    let e1 = (block[1] as i8).max(-127) as f32 / 127.0;
+    let ramp: [f32; 8] = if e0 > e1 {
+        std::array::from_fn(|i| match i {
+            0 => e0,
+            1 => e1,
+            _ => (e0 * (8 - i) as f32 + e1 * (i - 1) as f32) / 7.0,
+        })
+    } else {
+        std::array::from_fn(|i| match i {
+            0 => e0,
+            1 => e1,
+            6 => -1.0,
+            7 => 1.0,
+            _ => (e0 * (6 - i) as f32 + e1 * (i - 1) as f32) / 5.0,
+        })
+    };
+    let mut ret = [0; 16];
+    for (texel, i) in ret.iter_mut().zip(ramp_indices(block)) {
+        *texel = ((ramp[i] * 0.5 + 0.5) * 255.0).round() as u8;
+    }
+    ret
+}
+
+/// Subsets of the 2-subset BC7 partitions, one bit per texel.
+const PARTITIONS2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subsets of the 3-subset BC7 partitions, two bits per texel.
+const PARTITIONS3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Texels whose index has its top bit implied, of the second subset of 2-subset partitions.
+const ANCHORS2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Same for the second and third subsets of 3-subset partitions.
+const ANCHORS3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+/// Interpolation weights, out of 64, for 2, 3 and 4-bit indices.
+const WEIGHTS: [&[u32]; 3] = [
+    &[0, 21, 43, 64],
+    &[0, 9, 18, 27, 37, 46, 55, 64],
+    &[0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64],
+];
+
+/// Layout of a BC7 mode.
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// one p-bit per endpoint
+    endpoint_pbits: bool,
+    /// one p-bit per subset
+    shared_pbits: bool,
+    index_bits: u32,
+    /// bits of the separate alpha indices of modes 4 and 5, else 0
+    index2_bits: u32,
+}
+
+const fn bc7_mode(
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    [color_bits, alpha_bits]: [u32; 2],
+    [endpoint_pbits, shared_pbits]: [bool; 2],
+    [index_bits, index2_bits]: [u32; 2],
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_pbits,
+        shared_pbits,
+        index_bits,
+        index2_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, [4, 0], [true, false], [3, 0]),
+    bc7_mode(2, 6, 0, 0, [6, 0], [false, true], [3, 0]),
+    bc7_mode(3, 6, 0, 0, [5, 0], [false, false], [2, 0]),
+    bc7_mode(2, 6, 0, 0, [7, 0], [true, false], [2, 0]),
+    bc7_mode(1, 0, 2, 1, [5, 6], [false, false], [2, 3]),
+    bc7_mode(1, 0, 2, 0, [7, 8], [false, false], [2, 2]),
+    bc7_mode(1, 0, 0, 0, [7, 7], [true, false], [4, 0]),
+    bc7_mode(2, 6, 0, 0, [5, 5], [true, false], [2, 0]),
+];
+
+/// Fields of a block, least significant bit first.
+struct Bits(u128);
+
+impl Bits {
+    fn take(&mut self, n: u32) -> u32 {
+        let v = (self.0 & ((1 << n) - 1)) as u32;
+        self.0 >>= n;
+        v
+    }
+}
+
+/// the endpoint of `bits`, p-bit included, widened to 8 bits
+fn unquantize(v: u32, bits: u32) -> u32 {
+    let v = v << (8 - bits);
+    v | v >> bits
+}
+
+fn interpolate(e0: u32, e1: u32, index: u32, index_bits: u32) -> u8 {
+    let w = WEIGHTS[index_bits as usize - 2][index as usize];
+    (((64 - w) * e0 + w * e1 + 32) >> 6) as u8
+}
+
+/// Reserved modes decode to transparent black, as D3D does.
+fn bc7_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut bits = Bits(u128::from_le_bytes(block.try_into().unwrap()));
+    let Some(mode) = (0..8).find(|m| block[0] >> m & 1 != 0) else {
+        return [[0; 4]; 16];
+    };
+    bits.take(mode as u32 + 1);
+    let m = &BC7_MODES[mode];
+    let partition = bits.take(m.partition_bits) as usize;
+    let rotation = bits.take(m.rotation_bits);
+    let index_selection = bits.take(m.index_selection_bits);
+
+    // [subset][endpoint][channel]
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for channel in 0..4 {
+        let n = if channel < 3 {
+            m.color_bits
+        } else {
+            m.alpha_bits
+        };
+        for subset in &mut endpoints[..m.subsets] {
+            for endpoint in subset {
+                endpoint[channel] = bits.take(n);
+            }
+        }
+    }
+    let mut pbits = [[0; 2]; 3];
+    if m.endpoint_pbits {
+        for subset in &mut pbits[..m.subsets] {
+            subset[0] = bits.take(1);
+            subset[1] = bits.take(1);
+        }
+    } else if m.shared_pbits {
+        for subset in &mut pbits[..m.subsets] {
+            let p = bits.take(1);
+            *subset = [p, p];
+        }
+    }
+    let has_pbits = m.endpoint_pbits || m.shared_pbits;
+    for (subset, pbits) in endpoints.iter_mut().zip(pbits) {
+        for (endpoint, p) in subset.iter_mut().zip(pbits) {
+            for (channel, v) in endpoint.iter_mut().enumerate() {
+                let n = if channel < 3 {
+                    m.color_bits
+                } else {
+                    m.alpha_bits
+                };
+                *v = match (n, has_pbits) {
+                    (0, _) => 255,
+                    (n, true) => unquantize(*v << 1 | p, n + 1),
+                    (n, false) => unquantize(*v, n),
+                };
+            }
+        }
+    }
+
+    let subset_of = |texel: usize| match m.subsets {
+        2 => (PARTITIONS2[partition] >> texel) as usize & 1,
+        3 => (PARTITIONS3[partition] >> (texel * 2)) as usize & 3,
+        _ => 0,
+    };
+    let is_anchor = |texel: usize| {
+        texel == 0
+            || match m.subsets {
+                2 => ANCHORS2[partition] as usize == texel,
+                3 => ANCHORS3[partition].contains(&(texel as u8)),
+                _ => false,
+            }
+    };
+    let indices = |bits: &mut Bits, n: u32| -> [u32; 16] {
+        std::array::from_fn(|texel| bits.take(if is_anchor(texel) { n - 1 } else { n }))
+    };
+    let primary = indices(&mut bits, m.index_bits);
+    // only single-subset modes have secondary indices, anchored at texel 0 alone
+    let secondary = match m.index2_bits {
+        0 => primary,
+        n => indices(&mut bits, n),
+    };
+
+    std::array::from_fn(|texel| {
+        let [e0, e1] = endpoints[subset_of(texel)];
+        let (mut color_index, mut color_bits) = (primary[texel], m.index_bits);
+        let (mut alpha_index, mut alpha_bits) = (secondary[texel], m.index2_bits.max(m.index_bits));
+        if index_selection == 1 {
+            std::mem::swap(&mut color_index, &mut alpha_index);
+            std::mem::swap(&mut color_bits, &mut alpha_bits);
+        }
+        let mut texel: [u8; 4] = std::array::from_fn(|c| {
+            let (index, bits) = if c < 3 {
+                (color_index, color_bits)
+            } else {
+                (alpha_index, alpha_bits)
+            };
+            interpolate(e0[c], e1[c], index, bits)
+        });
+        if rotation > 0 {
+            texel.swap(rotation as usize - 1, 3);
+        }
+        texel
+    })
+}
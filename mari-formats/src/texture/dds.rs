@@ -0,0 +1,152 @@
+use super::bcn::Bc;
+use super::texture_size;
+use crate::{TextureError, TextureRGBA8};
+
+fn invalid(what: &str) -> TextureError {
+    TextureError::Invalid(format!("DDS: {what}"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, TextureError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| invalid("truncated header"))
+}
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+enum Layout {
+    Bc(Bc),
+    /// bytes per pixel and r,g,b,a masks, a zero mask reads as 0 (or 255 for alpha)
+    Masks(usize, [u32; 4]),
+}
+
+fn dxgi_layout(dxgi: u32) -> Result<Layout, TextureError> {
+    Ok(match dxgi {
+        28 | 29 => Layout::Masks(4, [0xff, 0xff00, 0xff0000, 0xff000000]),
+        87 | 91 => Layout::Masks(4, [0xff0000, 0xff00, 0xff, 0xff000000]),
+        88 | 93 => Layout::Masks(4, [0xff0000, 0xff00, 0xff, 0]),
+        61 => Layout::Masks(1, [0xff, 0, 0, 0]),
+        49 => Layout::Masks(2, [0xff, 0xff00, 0, 0]),
+        65 => Layout::Masks(1, [0, 0, 0, 0xff]),
+        70..=72 => Layout::Bc(Bc::Bc1),
+        73..=75 => Layout::Bc(Bc::Bc2),
+        76..=78 => Layout::Bc(Bc::Bc3),
+        79 | 80 => Layout::Bc(Bc::Bc4 { signed: false }),
+        81 => Layout::Bc(Bc::Bc4 { signed: true }),
+        82 | 83 => Layout::Bc(Bc::Bc5 { signed: false }),
+        84 => Layout::Bc(Bc::Bc5 { signed: true }),
+        97..=99 => Layout::Bc(Bc::Bc7),
+        _ => {
+            return Err(TextureError::Unsupported(format!(
+                "DDS: DXGI format {dxgi}"
+            )));
+        }
+    })
+}
+
+impl TextureRGBA8 {
+    /// Decode the first image of a DDS: BC1 to BC5, BC7 and uncompressed RGB(A), luminance or
+    /// alpha pixel formats, with or without the DX10 header. Further mips, faces and array
+    /// layers are ignored.
+    pub fn new_from_dds(data: &[u8]) -> Result<Self, TextureError> {
+        if !data.starts_with(b"DDS ") || u32_at(data, 4)? != 124 {
+            return Err(invalid("bad magic"));
+        }
+        let height = u32_at(data, 12)?;
+        let width = u32_at(data, 16)?;
+        let texture_width = texture_size(width, height)?;
+        let (width, height) = (width as usize, height as usize);
+
+        let flags = u32_at(data, 80)?;
+        let four_cc = data
+            .get(84..88)
+            .ok_or_else(|| invalid("truncated header"))?;
+        let mut offset = 128;
+        let layout = if flags & DDPF_FOURCC != 0 {
+            match four_cc {
+                b"DXT1" => Layout::Bc(Bc::Bc1),
+                b"DXT2" | b"DXT3" => Layout::Bc(Bc::Bc2),
+                b"DXT4" | b"DXT5" => Layout::Bc(Bc::Bc3),
+                b"ATI1" | b"BC4U" => Layout::Bc(Bc::Bc4 { signed: false }),
+                b"BC4S" => Layout::Bc(Bc::Bc4 { signed: true }),
+                b"ATI2" | b"BC5U" => Layout::Bc(Bc::Bc5 { signed: false }),
+                b"BC5S" => Layout::Bc(Bc::Bc5 { signed: true }),
+                b"DX10" => {
+                    offset = 148;
+                    dxgi_layout(u32_at(data, 128)?)?
+                }
+                _ => {
+                    return Err(TextureError::Unsupported(format!(
+                        "DDS: FourCC {}",
+                        String::from_utf8_lossy(four_cc)
+                    )));
+                }
+            }
+        } else if flags & (DDPF_RGB | DDPF_LUMINANCE | DDPF_ALPHA) != 0 {
+            let bits = u32_at(data, 88)?;
+            if !matches!(bits, 8 | 16 | 24 | 32) {
+                return Err(TextureError::Unsupported(format!("DDS: {bits}-bit pixels")));
+            }
+            let mut masks = [0; 4];
+            for (i, mask) in masks.iter_mut().enumerate() {
+                *mask = u32_at(data, 92 + i * 4)?;
+            }
+            if flags & DDPF_LUMINANCE != 0 {
+                masks[1] = masks[0];
+                masks[2] = masks[0];
+            }
+            if flags & DDPF_ALPHA != 0 && flags & (DDPF_RGB | DDPF_LUMINANCE) == 0 {
+                masks = [0, 0, 0, masks[3]];
+            } else if flags & DDPF_ALPHAPIXELS == 0 {
+                masks[3] = 0;
+            }
+            Layout::Masks(bits as usize / 8, masks)
+        } else {
+            return Err(TextureError::Unsupported("DDS: pixel format".to_string()));
+        };
+
+        let pixels = data
+            .get(offset..)
+            .ok_or_else(|| invalid("truncated pixels"))?;
+        let data = match layout {
+            Layout::Bc(bc) => bc.decode(pixels, width, height)?,
+            Layout::Masks(size, masks) => {
+                let pixels = pixels
+                    .get(..width * height * size)
+                    .ok_or_else(|| invalid("truncated pixels"))?;
+                let alpha_only = masks[..3] == [0, 0, 0];
+                pixels
+                    .chunks_exact(size)
+                    .flat_map(|p| {
+                        let mut bytes = [0; 4];
+                        bytes[..size].copy_from_slice(p);
+                        let v = u32::from_le_bytes(bytes);
+                        let channel = |mask: u32, missing: u8| {
+                            if mask == 0 {
+                                return missing;
+                            }
+                            let max = mask >> mask.trailing_zeros();
+                            (((v & mask) >> mask.trailing_zeros()) * 255 / max) as u8
+                        };
+                        let fill = if alpha_only { 255 } else { 0 };
+                        [
+                            channel(masks[0], fill),
+                            channel(masks[1], fill),
+                            channel(masks[2], fill),
+                            channel(masks[3], 255),
+                        ]
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(Self {
+            width: texture_width,
+            data,
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use super::texture_size;
+use crate::{TextureError, TextureRGBA8};
+
+impl TextureRGBA8 {
+    /// Decode a baseline or progressive JPEG. Grey is replicated into r,g,b and CMYK is
+    /// converted naively, without a color profile.
+    pub fn new_from_jpeg(data: &[u8]) -> Result<Self, TextureError> {
+        let mut decoder = jpeg_decoder::Decoder::new(data);
+        let pixels = decoder.decode().map_err(TextureError::Jpeg)?;
+        let info = decoder
+            .info()
+            .ok_or_else(|| TextureError::Invalid("JPEG: missing frame header".to_string()))?;
+        let width = texture_size(info.width as u32, info.height as u32)?;
+
+        let data = match info.pixel_format {
+            jpeg_decoder::PixelFormat::RGB24 => pixels
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            jpeg_decoder::PixelFormat::L8 => {
+                pixels.iter().flat_map(|g| [*g, *g, *g, 255]).collect()
+            }
+            jpeg_decoder::PixelFormat::L16 => pixels
+                .chunks_exact(2)
+                .flat_map(|p| {
+                    let g = (u16::from_ne_bytes([p[0], p[1]]) >> 8) as u8;
+                    [g, g, g, 255]
+                })
+                .collect(),
+            jpeg_decoder::PixelFormat::CMYK32 => pixels
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let k = 255 - p[3] as u32;
+                    let c = |v: u8| ((255 - v as u32) * k / 255) as u8;
+                    [c(p[0]), c(p[1]), c(p[2]), 255]
+                })
+                .collect(),
+        };
+
+        Ok(Self { width, data })
+    }
+}
@@ -0,0 +1,114 @@
+use super::bcn::Bc;
+use super::texture_size;
+use crate::{TextureError, TextureRGBA8};
+
+fn invalid(what: &str) -> TextureError {
+    TextureError::Invalid(format!("KTX2: {what}"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, TextureError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| invalid("truncated header"))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Result<usize, TextureError> {
+    data.get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid("truncated level index"))
+}
+
+enum Layout {
+    Bc(Bc),
+    /// bytes per pixel and where r,g,b,a sit in them
+    Bytes(usize, [Option<usize>; 4]),
+}
+
+impl TextureRGBA8 {
+    /// Decode the first image of the base level of a KTX2, raw or zstd-supercompressed, in 8-bit
+    /// R/RG/RGB/RGBA/BGRA or BC1 to BC5 and BC7. sRGB and UNORM variants decode alike.
+    pub fn new_from_ktx2(data: &[u8]) -> Result<Self, TextureError> {
+        if !data.starts_with(b"\xabKTX 20\xbb\r\n\x1a\n") {
+            return Err(invalid("bad magic"));
+        }
+        let vk_format = u32_at(data, 12)?;
+        let width = u32_at(data, 20)?;
+        let height = u32_at(data, 24)?.max(1);
+        let supercompression = u32_at(data, 44)?;
+        let texture_width = texture_size(width, height)?;
+        let (width, height) = (width as usize, height as usize);
+
+        let layout = match vk_format {
+            9 | 15 => Layout::Bytes(1, [Some(0), None, None, None]),
+            16 | 22 => Layout::Bytes(2, [Some(0), Some(1), None, None]),
+            23 | 29 => Layout::Bytes(3, [Some(0), Some(1), Some(2), None]),
+            30 | 36 => Layout::Bytes(3, [Some(2), Some(1), Some(0), None]),
+            37 | 43 => Layout::Bytes(4, [Some(0), Some(1), Some(2), Some(3)]),
+            44 | 50 => Layout::Bytes(4, [Some(2), Some(1), Some(0), Some(3)]),
+            131..=134 => Layout::Bc(Bc::Bc1),
+            135 | 136 => Layout::Bc(Bc::Bc2),
+            137 | 138 => Layout::Bc(Bc::Bc3),
+            139 => Layout::Bc(Bc::Bc4 { signed: false }),
+            140 => Layout::Bc(Bc::Bc4 { signed: true }),
+            141 => Layout::Bc(Bc::Bc5 { signed: false }),
+            142 => Layout::Bc(Bc::Bc5 { signed: true }),
+            145 | 146 => Layout::Bc(Bc::Bc7),
+            0 => {
+                return Err(TextureError::Unsupported(
+                    "KTX2: Basis Universal".to_string(),
+                ));
+            }
+            _ => {
+                return Err(TextureError::Unsupported(format!(
+                    "KTX2: VkFormat {vk_format}"
+                )));
+            }
+        };
+
+        let offset = u64_at(data, 80)?;
+        let length = u64_at(data, 88)?;
+        let level = data
+            .get(offset..offset.saturating_add(length))
+            .ok_or_else(|| invalid("truncated level"))?;
+        let decompressed;
+        let level = match supercompression {
+            0 => level,
+            2 => {
+                let mut out = Vec::with_capacity(u64_at(data, 96)?.min(1 << 30));
+                ruzstd::decoding::FrameDecoder::new()
+                    .decode_all_to_vec(level, &mut out)
+                    .map_err(|e| invalid(&format!("zstd: {e}")))?;
+                decompressed = out;
+                &decompressed
+            }
+            _ => {
+                return Err(TextureError::Unsupported(format!(
+                    "KTX2: supercompression scheme {supercompression}"
+                )));
+            }
+        };
+
+        let data = match layout {
+            Layout::Bc(bc) => bc.decode(level, width, height)?,
+            Layout::Bytes(size, channels) => level
+                .get(..width * height * size)
+                .ok_or_else(|| invalid("truncated pixels"))?
+                .chunks_exact(size)
+                .flat_map(|p| {
+                    let [r, g, b, a] = channels.map(|c| c.map(|i| p[i]));
+                    [
+                        r.unwrap_or(0),
+                        g.unwrap_or(0),
+                        b.unwrap_or(0),
+                        a.unwrap_or(255),
+                    ]
+                })
+                .collect(),
+        };
+
+        Ok(Self {
+            width: texture_width,
+            data,
+        })
+    }
+}
@@ -0,0 +1,143 @@
+use super::{linear_to_srgb, srgb_to_linear};
+use crate::TextureRGBA8;
+
+/// for each destination pixel, the source pixels it covers and by how much, summing to 1
+fn box_weights(src: usize, dst: usize) -> Vec<Vec<(usize, f32)>> {
+    let scale = src as f32 / dst as f32;
+    (0..dst)
+        .map(|i| {
+            let (start, end) = (i as f32 * scale, (i + 1) as f32 * scale);
+            (start.floor() as usize..(end.ceil() as usize).min(src))
+                .map(|j| {
+                    let overlap = (end.min(j as f32 + 1.0) - start.max(j as f32)).max(0.0);
+                    (j, overlap / scale)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// share of `alphas` above `cutoff` once scaled by `scale`
+fn coverage(alphas: impl Iterator<Item = f32>, cutoff: f32, scale: f32) -> f32 {
+    let (mut above, mut count) = (0, 0);
+    for a in alphas {
+        above += ((a * scale).min(1.0) > cutoff) as usize;
+        count += 1;
+    }
+    above as f32 / count.max(1) as f32
+}
+
+impl TextureRGBA8 {
+    /// Every mip level from this one, as level 0, down to 1x1, each dimension halving and
+    /// rounding down like OpenGL's.
+    ///
+    /// r,g,b are taken as sRGB and averaged in linear space, weighted by alpha. With
+    /// `alpha_cutoff`, the alpha of each level is scaled so that as many of its pixels pass an
+    /// alpha test at that cutoff as in level 0, so alpha-tested detail doesn't thin out with
+    /// distance.
+    pub fn mip_chain(&self, alpha_cutoff: Option<f32>) -> Vec<Self> {
+        let mut ret = vec![Self {
+            width: self.width,
+            data: self.data.clone(),
+        }];
+        let (mut w, mut h) = (self.width as usize, self.height() as usize);
+        if w == 0 || h == 0 {
+            return ret;
+        }
+
+        let to_linear: Vec<f32> = (0..=255)
+            .map(|v| srgb_to_linear(v as f32 / 255.0))
+            .collect();
+        // premultiplied linear r,g,b and alpha, all 0..1
+        let mut level: Vec<[f32; 4]> = self
+            .data
+            .chunks_exact(4)
+            .map(|p| {
+                let a = p[3] as f32 / 255.0;
+                [
+                    to_linear[p[0] as usize] * a,
+                    to_linear[p[1] as usize] * a,
+                    to_linear[p[2] as usize] * a,
+                    a,
+                ]
+            })
+            .collect();
+        let target = alpha_cutoff.map(|cutoff| {
+            let base = coverage(level.iter().map(|p| p[3]), cutoff, 1.0);
+            (cutoff, base)
+        });
+
+        while w > 1 || h > 1 {
+            let (nw, nh) = ((w / 2).max(1), (h / 2).max(1));
+            let columns = box_weights(w, nw);
+            let rows = box_weights(h, nh);
+            let mut next = Vec::with_capacity(nw * nh);
+            for row in &rows {
+                for column in &columns {
+                    let mut p = [0.0; 4];
+                    for (y, wy) in row {
+                        for (x, wx) in column {
+                            let q = level[y * w + x];
+                            for c in 0..4 {
+                                p[c] += q[c] * wy * wx;
+                            }
+                        }
+                    }
+                    next.push(p);
+                }
+            }
+            (level, w, h) = (next, nw, nh);
+
+            let scale = match target {
+                Some((cutoff, base))
+                    if base > 0.0 && coverage(level.iter().map(|p| p[3]), cutoff, 1.0) != base =>
+                {
+                    // coverage grows with the scale, so bisect for the one matching level 0, on
+                    // the side of 1 it lies, as any scale keeping it is left alone
+                    let above = coverage(level.iter().map(|p| p[3]), cutoff, 1.0) > base;
+                    let (mut low, mut high) = if above { (0.0f32, 1.0) } else { (1.0, 4.0f32) };
+                    for _ in 0..20 {
+                        let mid = (low + high) / 2.0;
+                        if coverage(level.iter().map(|p| p[3]), cutoff, mid) < base {
+                            low = mid;
+                        } else {
+                            high = mid;
+                        }
+                    }
+                    high
+                }
+                _ => 1.0,
+            };
+
+            let data = level
+                .iter()
+                .flat_map(|p| {
+                    let a = p[3];
+                    let color = |c: f32| {
+                        if a > 0.0 {
+                            (linear_to_srgb((c / a).clamp(0.0, 1.0)) * 255.0).round() as u8
+                        } else {
+                            0
+                        }
+                    };
+                    let scaled = (a * scale).min(1.0);
+                    let mut alpha = (scaled * 255.0).round();
+                    // rounding must not move a pixel across the alpha test
+                    if let Some((cutoff, _)) = target {
+                        if scaled > cutoff && alpha / 255.0 <= cutoff {
+                            alpha = (cutoff * 255.0).floor() + 1.0;
+                        } else if scaled <= cutoff && alpha / 255.0 > cutoff {
+                            alpha = (cutoff * 255.0).floor();
+                        }
+                    }
+                    [color(p[0]), color(p[1]), color(p[2]), alpha as u8]
+                })
+                .collect();
+            ret.push(Self {
+                width: w as u16,
+                data,
+            });
+        }
+        ret
+    }
+}
@@ -0,0 +1,315 @@
+//! `Texture` of any pixel format and layout, image containers other than PNG, each behind the
+//! cargo feature of the same name, and `Texture::load` choosing among all of them by magic bytes.
+
+pub(crate) mod bcn;
+#[cfg(feature = "dds")]
+mod dds;
+#[cfg(feature = "jpeg")]
+mod jpeg;
+#[cfg(feature = "ktx2")]
+mod ktx2;
+mod mips;
+mod ops;
+#[cfg(feature = "tga")]
+mod tga;
+
+use std::io::BufReader;
+
+use half::f16;
+
+use crate::{TextureError, TextureRGBA8};
+
+pub use ops::{Channel, ResizeFilter};
+
+/// Pixel formats of `Texture`, all little-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    R8,
+    RG8,
+    RGBA8,
+    /// RGBA8 with r,g,b sRGB-encoded
+    RGBA8Srgb,
+    R16F,
+    RGBA16F,
+    RGBA32F,
+    /// f32 depth
+    Depth,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::R8 => 1,
+            Self::RG8 | Self::R16F => 2,
+            Self::RGBA8 | Self::RGBA8Srgb | Self::Depth => 4,
+            Self::RGBA16F => 8,
+            Self::RGBA32F => 16,
+        }
+    }
+}
+
+/// How the images of a `Texture` are laid out one after another in its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureLayout {
+    Single,
+    /// six faces in the order +x, -x, +y, -y, +z, -z
+    Cube,
+    /// this many layers
+    Array(u32),
+}
+
+impl TextureLayout {
+    pub fn layers(self) -> u32 {
+        match self {
+            Self::Single => 1,
+            Self::Cube => 6,
+            Self::Array(n) => n,
+        }
+    }
+}
+
+/// A texture of any pixel format, e.g. a single-channel mask or an HDR environment map.
+///
+/// Rows go top-down like those of `TextureRGBA8`.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub layout: TextureLayout,
+    pub data: Vec<u8>,
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn unorm8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Texture {
+    /// A texture of zeroes.
+    pub fn new(width: u32, height: u32, format: PixelFormat, layout: TextureLayout) -> Self {
+        let size = width as usize * height as usize * format.bytes_per_pixel();
+        Self {
+            width,
+            height,
+            format,
+            layout,
+            data: vec![0; size * layout.layers() as usize],
+        }
+    }
+
+    /// Decode an image in any enabled container, see `TextureRGBA8::load`.
+    pub fn load(data: &[u8]) -> Result<Self, TextureError> {
+        TextureRGBA8::load(data).map(Self::from)
+    }
+
+    /// bytes of one face or layer
+    pub fn layer_size(&self) -> usize {
+        self.width as usize * self.height as usize * self.format.bytes_per_pixel()
+    }
+
+    pub fn layer(&self, layer: u32) -> &[u8] {
+        let size = self.layer_size();
+        &self.data[layer as usize * size..][..size]
+    }
+
+    /// The pixel at `x`,`y` of `layer` as r,g,b,a; missing channels read as 0 and alpha as 1.
+    ///
+    /// sRGB is decoded to linear. Depth is replicated into r,g,b.
+    pub fn texel(&self, layer: u32, x: u32, y: u32) -> [f32; 4] {
+        let size = self.format.bytes_per_pixel();
+        let index =
+            (layer as usize * self.height as usize + y as usize) * self.width as usize + x as usize;
+        let p = &self.data[index * size..][..size];
+        let f32_at = |i: usize| f32::from_le_bytes(p[i * 4..][..4].try_into().unwrap());
+        let f16_at = |i: usize| f16::from_le_bytes([p[i * 2], p[i * 2 + 1]]).to_f32();
+        let u8_at = |i: usize| p[i] as f32 / 255.0;
+        match self.format {
+            PixelFormat::R8 => [u8_at(0), 0.0, 0.0, 1.0],
+            PixelFormat::RG8 => [u8_at(0), u8_at(1), 0.0, 1.0],
+            PixelFormat::RGBA8 => std::array::from_fn(u8_at),
+            PixelFormat::RGBA8Srgb => std::array::from_fn(|i| match i {
+                3 => u8_at(3),
+                _ => srgb_to_linear(u8_at(i)),
+            }),
+            PixelFormat::R16F => [f16_at(0), 0.0, 0.0, 1.0],
+            PixelFormat::RGBA16F => std::array::from_fn(f16_at),
+            PixelFormat::RGBA32F => std::array::from_fn(f32_at),
+            PixelFormat::Depth => [f32_at(0), f32_at(0), f32_at(0), 1.0],
+        }
+    }
+
+    /// Store r,g,b,a at `x`,`y` of `layer`, dropping the channels the format lacks.
+    ///
+    /// 8-bit formats clamp to 0..1 and sRGB encodes from linear.
+    pub fn set_texel(&mut self, layer: u32, x: u32, y: u32, v: [f32; 4]) {
+        let size = self.format.bytes_per_pixel();
+        let index =
+            (layer as usize * self.height as usize + y as usize) * self.width as usize + x as usize;
+        let p = &mut self.data[index * size..][..size];
+        match self.format {
+            PixelFormat::R8 => p[0] = unorm8(v[0]),
+            PixelFormat::RG8 => p.copy_from_slice(&[unorm8(v[0]), unorm8(v[1])]),
+            PixelFormat::RGBA8 => p.copy_from_slice(&v.map(unorm8)),
+            PixelFormat::RGBA8Srgb => {
+                let [r, g, b, a] = v;
+                let encode = |c: f32| unorm8(linear_to_srgb(c.clamp(0.0, 1.0)));
+                p.copy_from_slice(&[encode(r), encode(g), encode(b), unorm8(a)]);
+            }
+            PixelFormat::R16F => p.copy_from_slice(&f16::from_f32(v[0]).to_le_bytes()),
+            PixelFormat::RGBA16F => {
+                for (c, v) in p.chunks_exact_mut(2).zip(v) {
+                    c.copy_from_slice(&f16::from_f32(v).to_le_bytes());
+                }
+            }
+            PixelFormat::RGBA32F => {
+                for (c, v) in p.chunks_exact_mut(4).zip(v) {
+                    c.copy_from_slice(&v.to_le_bytes());
+                }
+            }
+            PixelFormat::Depth => p.copy_from_slice(&v[0].to_le_bytes()),
+        }
+    }
+
+    /// The same texture in another pixel format, see `texel` and `set_texel`.
+    pub fn convert(&self, format: PixelFormat) -> Self {
+        if format == self.format {
+            return self.clone();
+        }
+        let mut ret = Self::new(self.width, self.height, format, self.layout);
+        for layer in 0..self.layout.layers() {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    ret.set_texel(layer, x, y, self.texel(layer, x, y));
+                }
+            }
+        }
+        ret
+    }
+
+    /// The first face or layer converted to RGBA8, sRGB staying encoded.
+    pub fn to_rgba8(&self) -> Result<TextureRGBA8, TextureError> {
+        let width = texture_size(self.width, self.height)?;
+        let data = match self.format {
+            PixelFormat::RGBA8 | PixelFormat::RGBA8Srgb => self.layer(0).to_vec(),
+            _ => {
+                let first = Self {
+                    layout: TextureLayout::Single,
+                    data: self.layer(0).to_vec(),
+                    ..*self
+                };
+                first.convert(PixelFormat::RGBA8).data
+            }
+        };
+        Ok(TextureRGBA8 { width, data })
+    }
+}
+
+impl From<TextureRGBA8> for Texture {
+    fn from(texture: TextureRGBA8) -> Self {
+        Self {
+            width: texture.width as u32,
+            height: texture.height() as u32,
+            format: PixelFormat::RGBA8,
+            layout: TextureLayout::Single,
+            data: texture.data,
+        }
+    }
+}
+
+/// Image containers `Texture::load` recognizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Tga,
+    Jpeg,
+    Dds,
+    Ktx2,
+}
+
+impl ImageFormat {
+    /// Tell the container by its magic bytes. TGA has none and is recognized by a plausible header.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.starts_with(b"\xff\xd8\xff") {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"DDS ") {
+            Some(Self::Dds)
+        } else if data.starts_with(b"\xabKTX 20\xbb\r\n\x1a\n") {
+            Some(Self::Ktx2)
+        } else if is_tga(data) {
+            Some(Self::Tga)
+        } else {
+            None
+        }
+    }
+}
+
+fn is_tga(data: &[u8]) -> bool {
+    if data.ends_with(b"TRUEVISION-XFILE.\0") {
+        return true;
+    }
+    let Some(header) = data.get(..18) else {
+        return false;
+    };
+    let (color_map, kind, depth) = (header[1], header[2], header[16]);
+    let width = u16::from_le_bytes([header[12], header[13]]);
+    let height = u16::from_le_bytes([header[14], header[15]]);
+    color_map <= 1
+        && matches!(kind, 1 | 2 | 3 | 9 | 10 | 11)
+        && matches!(depth, 8 | 15 | 16 | 24 | 32)
+        && width > 0
+        && height > 0
+}
+
+/// check a decoded size against the limits of `TextureRGBA8`
+pub(crate) fn texture_size(width: u32, height: u32) -> Result<u16, TextureError> {
+    let ret = width.try_into().map_err(|_| TextureError::WidthTooLarge)?;
+    let _: u16 = height
+        .try_into()
+        .map_err(|_| TextureError::HeightTooLarge)?;
+    if width == 0 || height == 0 {
+        return Err(TextureError::Invalid("Empty image.".to_string()));
+    }
+    Ok(ret)
+}
+
+impl TextureRGBA8 {
+    /// Decode an image in any enabled container, told apart by its magic bytes.
+    pub fn load(data: &[u8]) -> Result<Self, TextureError> {
+        match ImageFormat::sniff(data) {
+            Some(ImageFormat::Png) => Self::new_from_png(BufReader::new(data)),
+            #[cfg(feature = "tga")]
+            Some(ImageFormat::Tga) => Self::new_from_tga(data),
+            #[cfg(feature = "jpeg")]
+            Some(ImageFormat::Jpeg) => Self::new_from_jpeg(data),
+            #[cfg(feature = "dds")]
+            Some(ImageFormat::Dds) => Self::new_from_dds(data),
+            #[cfg(feature = "ktx2")]
+            Some(ImageFormat::Ktx2) => Self::new_from_ktx2(data),
+            #[allow(unreachable_patterns)]
+            Some(format) => Err(TextureError::Unsupported(format!(
+                "{format:?}, whose cargo feature is disabled"
+            ))),
+            None => Err(TextureError::Unsupported(
+                "Unknown image format".to_string(),
+            )),
+        }
+    }
+}
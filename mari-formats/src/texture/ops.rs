@@ -0,0 +1,336 @@
+//! CPU image operations to prepare textures and ramps before upload.
+
+use super::{PixelFormat, Texture, TextureLayout};
+use crate::TextureRGBA8;
+
+/// Reconstruction filters of `TextureRGBA8::resize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// averages when shrinking, nearest neighbour when enlarging
+    Box,
+    Bilinear,
+    /// Lanczos with 3 lobes, sharpest but may ring at hard edges
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    fn support(self) -> f32 {
+        match self {
+            Self::Box => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        match self {
+            Self::Box => {
+                if (-0.5..0.5).contains(&x) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Bilinear => (1.0 - x.abs()).max(0.0),
+            Self::Lanczos3 => {
+                if x == 0.0 {
+                    1.0
+                } else if x.abs() < 3.0 {
+                    let px = std::f32::consts::PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// for each destination index, the first source index and the normalized weights from there
+    fn taps(self, src: usize, dst: usize) -> Vec<(usize, Vec<f32>)> {
+        let scale = src as f32 / dst as f32;
+        // widen the kernel when shrinking so every source pixel contributes
+        let filter_scale = scale.max(1.0);
+        let support = self.support() * filter_scale;
+        (0..dst)
+            .map(|i| {
+                let center = (i as f32 + 0.5) * scale;
+                let first = ((center - support).floor().max(0.0)) as usize;
+                let last = ((center + support).ceil() as usize).min(src);
+                let mut weights: Vec<f32> = (first..last)
+                    .map(|j| self.weight((j as f32 + 0.5 - center) / filter_scale))
+                    .collect();
+                let sum: f32 = weights.iter().sum();
+                if sum != 0.0 {
+                    weights.iter_mut().for_each(|w| *w /= sum);
+                } else {
+                    // a box narrower than a pixel may miss every center, take the nearest
+                    let nearest = (center as usize).min(src - 1);
+                    weights = (first..last).map(|j| (j == nearest) as u8 as f32).collect();
+                }
+                (first, weights)
+            })
+            .collect()
+    }
+}
+
+/// Sources of `TextureRGBA8::swizzle` and `TextureRGBA8::extract_channel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+    Zero,
+    /// 255
+    One,
+}
+
+impl Channel {
+    fn read(self, p: &[u8]) -> u8 {
+        match self {
+            Self::R => p[0],
+            Self::G => p[1],
+            Self::B => p[2],
+            Self::A => p[3],
+            Self::Zero => 0,
+            Self::One => 255,
+        }
+    }
+}
+
+fn srgb_table(to_linear: bool) -> Vec<u8> {
+    (0..=255)
+        .map(|v| {
+            let v = v as f32 / 255.0;
+            let v = if to_linear {
+                super::srgb_to_linear(v)
+            } else {
+                super::linear_to_srgb(v)
+            };
+            (v * 255.0).round() as u8
+        })
+        .collect()
+}
+
+impl TextureRGBA8 {
+    /// Resample to `width` x `height`.
+    ///
+    /// Colors are weighted by alpha, so transparent pixels don't darken the edges of opaque ones.
+    pub fn resize(&self, width: u16, height: u16, filter: ResizeFilter) -> Self {
+        let (nw, nh) = (width as usize, height as usize);
+        // `height` divides by the width
+        if self.width == 0 || self.height() == 0 || nw == 0 || nh == 0 {
+            return Self {
+                width,
+                data: vec![0; nw * nh * 4],
+            };
+        }
+        let (w, h) = (self.width as usize, self.height() as usize);
+
+        let premultiplied: Vec<f32> = self
+            .data
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let a = p[3] as f32 / 255.0;
+                [
+                    p[0] as f32 * a,
+                    p[1] as f32 * a,
+                    p[2] as f32 * a,
+                    p[3] as f32,
+                ]
+            })
+            .collect();
+
+        let columns = filter.taps(w, nw);
+        let mut horizontal = vec![0.0; nw * h * 4];
+        for y in 0..h {
+            for (x, (first, weights)) in columns.iter().enumerate() {
+                let out = &mut horizontal[(y * nw + x) * 4..][..4];
+                for (k, weight) in weights.iter().enumerate() {
+                    let p = &premultiplied[(y * w + first + k) * 4..][..4];
+                    for c in 0..4 {
+                        out[c] += p[c] * weight;
+                    }
+                }
+            }
+        }
+
+        let rows = filter.taps(h, nh);
+        let mut data = Vec::with_capacity(nw * nh * 4);
+        for (first, weights) in &rows {
+            for x in 0..nw {
+                let mut p = [0.0f32; 4];
+                for (k, weight) in weights.iter().enumerate() {
+                    let q = &horizontal[((first + k) * nw + x) * 4..][..4];
+                    for c in 0..4 {
+                        p[c] += q[c] * weight;
+                    }
+                }
+                let a = p[3].clamp(0.0, 255.0);
+                let color = |c: f32| {
+                    if a > 0.0 {
+                        (c * 255.0 / a).round().clamp(0.0, 255.0) as u8
+                    } else {
+                        0
+                    }
+                };
+                data.extend([color(p[0]), color(p[1]), color(p[2]), a.round() as u8]);
+            }
+        }
+
+        Self { width, data }
+    }
+
+    /// Mirror top to bottom.
+    pub fn flip_vertical(&self) -> Self {
+        let stride = self.width.max(1) as usize * 4;
+        Self {
+            width: self.width,
+            data: self
+                .data
+                .chunks_exact(stride)
+                .rev()
+                .flatten()
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Mirror left to right.
+    pub fn flip_horizontal(&self) -> Self {
+        let stride = self.width.max(1) as usize * 4;
+        Self {
+            width: self.width,
+            data: self
+                .data
+                .chunks_exact(stride)
+                .flat_map(|row| row.chunks_exact(4).rev().flatten())
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// The `width` x `height` rectangle from `x`,`y`, `None` if it doesn't fit.
+    pub fn crop(&self, x: u16, y: u16, width: u16, height: u16) -> Option<Self> {
+        if x as u32 + width as u32 > self.width as u32
+            || y as u32 + height as u32 > self.height() as u32
+        {
+            return None;
+        }
+        let stride = self.width.max(1) as usize * 4;
+        let data = self
+            .data
+            .chunks_exact(stride)
+            .skip(y as usize)
+            .take(height as usize)
+            .flat_map(|row| &row[x as usize * 4..][..width as usize * 4])
+            .copied()
+            .collect();
+        Some(Self { width, data })
+    }
+
+    /// Multiply r,g,b by alpha.
+    pub fn premultiply_alpha(&mut self) {
+        for p in self.data.chunks_exact_mut(4) {
+            let a = p[3] as u32;
+            for c in &mut p[..3] {
+                *c = ((*c as u32 * a + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// Divide r,g,b by alpha, leaving fully transparent pixels as they are.
+    pub fn unpremultiply_alpha(&mut self) {
+        for p in self.data.chunks_exact_mut(4) {
+            let a = p[3] as u32;
+            if a == 0 {
+                continue;
+            }
+            for c in &mut p[..3] {
+                *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+            }
+        }
+    }
+
+    /// Rearrange channels, e.g. `[B, G, R, A]` to swap red and blue.
+    pub fn swizzle(&self, channels: [Channel; 4]) -> Self {
+        Self {
+            width: self.width,
+            data: self
+                .data
+                .chunks_exact(4)
+                .flat_map(|p| channels.map(|c| c.read(p)))
+                .collect(),
+        }
+    }
+
+    /// One channel as a single-channel texture, e.g. a mask packed into alpha.
+    pub fn extract_channel(&self, channel: Channel) -> Texture {
+        Texture {
+            width: self.width as u32,
+            height: self.height() as u32,
+            format: PixelFormat::R8,
+            layout: TextureLayout::Single,
+            data: self.data.chunks_exact(4).map(|p| channel.read(p)).collect(),
+        }
+    }
+
+    /// Decode sRGB r,g,b to linear in place, alpha being linear already.
+    pub fn srgb_to_linear(&mut self) {
+        self.map_colors(&srgb_table(true));
+    }
+
+    /// Encode linear r,g,b to sRGB in place.
+    pub fn linear_to_srgb(&mut self) {
+        self.map_colors(&srgb_table(false));
+    }
+
+    fn map_colors(&mut self, table: &[u8]) {
+        for p in self.data.chunks_exact_mut(4) {
+            for c in &mut p[..3] {
+                *c = table[*c as usize];
+            }
+        }
+    }
+
+    /// Give fully transparent pixels the average color of their non-transparent neighbours,
+    /// growing outwards up to `radius` pixels, so filtering and mips don't pull the background
+    /// color in at UV seams. Alpha is unchanged.
+    pub fn bleed_alpha(&mut self, radius: u32) {
+        let (w, h) = (self.width as usize, self.height() as usize);
+        let mut known: Vec<bool> = self.data.chunks_exact(4).map(|p| p[3] > 0).collect();
+        for _ in 0..radius {
+            let mut grown = Vec::new();
+            for y in 0..h {
+                for x in 0..w {
+                    if known[y * w + x] {
+                        continue;
+                    }
+                    let mut sum = [0u32; 3];
+                    let mut count = 0;
+                    for ny in y.saturating_sub(1)..(y + 2).min(h) {
+                        for nx in x.saturating_sub(1)..(x + 2).min(w) {
+                            if known[ny * w + nx] {
+                                let p = &self.data[(ny * w + nx) * 4..][..3];
+                                for c in 0..3 {
+                                    sum[c] += p[c] as u32;
+                                }
+                                count += 1;
+                            }
+                        }
+                    }
+                    if count > 0 {
+                        grown.push((y * w + x, sum.map(|s| ((s + count / 2) / count) as u8)));
+                    }
+                }
+            }
+            if grown.is_empty() {
+                break;
+            }
+            for (i, color) in grown {
+                self.data[i * 4..][..3].copy_from_slice(&color);
+                known[i] = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use super::texture_size;
+use crate::{TextureError, TextureRGBA8};
+
+fn invalid(what: &str) -> TextureError {
+    TextureError::Invalid(format!("TGA: {what}"))
+}
+
+/// Expand one 15/16-bit, 24-bit or 32-bit little-endian BGR(A) pixel.
+fn bgra(p: &[u8], alpha_bits: u8) -> [u8; 4] {
+    match p.len() {
+        2 => {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            let five = |s: u16| {
+                let c = ((v >> s) & 0x1f) as u8;
+                (c << 3) | (c >> 2)
+            };
+            let a = if alpha_bits > 0 && v & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            [five(10), five(5), five(0), a]
+        }
+        3 => [p[2], p[1], p[0], 255],
+        _ => [p[2], p[1], p[0], if alpha_bits > 0 { p[3] } else { 255 }],
+    }
+}
+
+impl TextureRGBA8 {
+    /// Decode a Truevision TGA: true-color, grey and color-mapped images, raw or RLE.
+    pub fn new_from_tga(data: &[u8]) -> Result<Self, TextureError> {
+        let header = data.get(..18).ok_or_else(|| invalid("truncated header"))?;
+        let id_len = header[0] as usize;
+        let has_map = header[1];
+        let kind = header[2];
+        let map_first = u16::from_le_bytes([header[3], header[4]]) as usize;
+        let map_len = u16::from_le_bytes([header[5], header[6]]) as usize;
+        let map_depth = header[7];
+        let width = u16::from_le_bytes([header[12], header[13]]) as usize;
+        let height = u16::from_le_bytes([header[14], header[15]]) as usize;
+        let depth = header[16];
+        let descriptor = header[17];
+        let alpha_bits = descriptor & 0x0f;
+
+        let texture_width = texture_size(width as u32, height as u32)?;
+        let mut pos = 18 + id_len;
+
+        let map: Vec<[u8; 4]> = if has_map == 1 {
+            let size = (map_depth as usize).div_ceil(8);
+            if !matches!(size, 2..=4) {
+                return Err(TextureError::Unsupported(format!(
+                    "TGA: {map_depth}-bit color map"
+                )));
+            }
+            let bytes = data
+                .get(pos..pos + map_len * size)
+                .ok_or_else(|| invalid("truncated color map"))?;
+            pos += map_len * size;
+            let map_alpha = if map_depth == 32 || map_depth == 16 {
+                alpha_bits.max(1)
+            } else {
+                0
+            };
+            bytes
+                .chunks_exact(size)
+                .map(|p| bgra(p, map_alpha))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let (pixel_size, rle) = match (kind, depth) {
+            (1 | 9, 8 | 16) if has_map == 1 => ((depth / 8) as usize, kind == 9),
+            (2 | 10, 15 | 16) => (2, kind == 10),
+            (2 | 10, 24 | 32) => ((depth / 8) as usize, kind == 10),
+            (3 | 11, 8 | 16) => ((depth / 8) as usize, kind == 11),
+            _ => {
+                return Err(TextureError::Unsupported(format!(
+                    "TGA: image type {kind} with {depth}-bit pixels"
+                )));
+            }
+        };
+        let pixel = |p: &[u8]| -> Result<[u8; 4], TextureError> {
+            Ok(match kind {
+                1 | 9 => {
+                    let index = if p.len() == 2 {
+                        u16::from_le_bytes([p[0], p[1]]) as usize
+                    } else {
+                        p[0] as usize
+                    };
+                    *index
+                        .checked_sub(map_first)
+                        .and_then(|i| map.get(i))
+                        .ok_or_else(|| invalid("color index out of the map"))?
+                }
+                3 | 11 => {
+                    let a = if p.len() == 2 && alpha_bits > 0 {
+                        p[1]
+                    } else {
+                        255
+                    };
+                    [p[0], p[0], p[0], a]
+                }
+                _ => bgra(p, alpha_bits),
+            })
+        };
+
+        let count = width * height;
+        let mut pixels = Vec::with_capacity(count);
+        if rle {
+            while pixels.len() < count {
+                let packet = *data.get(pos).ok_or_else(|| invalid("truncated pixels"))?;
+                pos += 1;
+                let n = (packet & 0x7f) as usize + 1;
+                if packet & 0x80 != 0 {
+                    let p = data
+                        .get(pos..pos + pixel_size)
+                        .ok_or_else(|| invalid("truncated pixels"))?;
+                    pos += pixel_size;
+                    let p = pixel(p)?;
+                    pixels.extend(std::iter::repeat_n(p, n));
+                } else {
+                    let bytes = data
+                        .get(pos..pos + n * pixel_size)
+                        .ok_or_else(|| invalid("truncated pixels"))?;
+                    pos += n * pixel_size;
+                    for p in bytes.chunks_exact(pixel_size) {
+                        pixels.push(pixel(p)?);
+                    }
+                }
+            }
+            pixels.truncate(count);
+        } else {
+            let bytes = data
+                .get(pos..pos + count * pixel_size)
+                .ok_or_else(|| invalid("truncated pixels"))?;
+            for p in bytes.chunks_exact(pixel_size) {
+                pixels.push(pixel(p)?);
+            }
+        }
+
+        // TGA rows go bottom-up unless the descriptor says otherwise
+        let flip_rows = descriptor & 0x20 == 0;
+        let flip_columns = descriptor & 0x10 != 0;
+        let mut out = Vec::with_capacity(count * 4);
+        for y in 0..height {
+            let row = if flip_rows { height - 1 - y } else { y };
+            for x in 0..width {
+                let column = if flip_columns { width - 1 - x } else { x };
+                out.extend_from_slice(&pixels[row * width + column]);
+            }
+        }
+
+        Ok(Self {
+            width: texture_width,
+            data: out,
+        })
+    }
+}
@@ -0,0 +1,175 @@
+use super::Error;
+use super::reader::Reader;
+use super::serialized::{ObjectInfo, PPtr, SerializedFile};
+
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+
+/// node flag of serialized files inside a bundle
+const NODE_SERIALIZED_FILE: u32 = 0x4;
+
+/// A UnityFS asset bundle, fully decompressed.
+pub struct Bundle {
+    /// e.g. `2022.3.21f1`
+    pub unity_version: String,
+    pub files: Vec<SerializedFile>,
+    /// remaining nodes, e.g. streamed texture data in `.resS`, by node path
+    pub resources: HashMap<String, Vec<u8>>,
+}
+
+struct Block {
+    uncompressed_size: usize,
+    compressed_size: usize,
+    flags: u16,
+}
+
+fn decompress(compression: u32, data: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    match compression {
+        0 => Ok(data.to_vec()),
+        2 | 3 => lz4_flex::block::decompress(data, size)
+            .map_err(|e| Error::Invalid(format!("LZ4: {e}."))),
+        1 => Err(Error::Unsupported("LZMA compressed bundles".to_string())),
+        c => Err(Error::Unsupported(format!("Compression type {c}"))),
+    }
+}
+
+impl Bundle {
+    pub fn new<R: Read>(mut buf: BufReader<R>) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        buf.read_to_end(&mut data).map_err(Error::Io)?;
+        Self::new_from_bytes(&data)
+    }
+
+    pub fn new_from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut r = Reader::new(data, true);
+        let signature = r.cstr()?;
+        if signature != "UnityFS" {
+            return Err(Error::Unsupported(format!("{signature:?} bundles")));
+        }
+        let version = r.u32()?;
+        let _player_version = r.cstr()?;
+        let unity_version = r.cstr()?;
+        let _size = r.i64()?;
+        let compressed_info_size = r.u32()? as usize;
+        let info_size = r.u32()? as usize;
+        let flags = r.u32()?;
+
+        if version >= 7 {
+            r.align(16);
+        }
+        let info = if flags & 0x80 != 0 {
+            // blocks info at the end of the file
+            let start = data
+                .len()
+                .checked_sub(compressed_info_size)
+                .ok_or(Error::Invalid(
+                    "Blocks info larger than the bundle.".to_string(),
+                ))?;
+            &data[start..]
+        } else {
+            r.bytes(compressed_info_size)?
+        };
+        let info = decompress(flags & 0x3f, info, info_size)?;
+        if flags & 0x200 != 0 {
+            r.align(16);
+        }
+
+        let mut ir = Reader::new(&info, true);
+        ir.bytes(16)?; // uncompressed data hash
+        let block_cnt = ir.i32()?;
+        let mut blocks = Vec::new();
+        for _ in 0..block_cnt {
+            blocks.push(Block {
+                uncompressed_size: ir.u32()? as usize,
+                compressed_size: ir.u32()? as usize,
+                flags: ir.u16()?,
+            });
+        }
+
+        let mut stream = Vec::with_capacity(blocks.iter().map(|b| b.uncompressed_size).sum());
+        for block in &blocks {
+            stream.extend(decompress(
+                (block.flags & 0x3f) as u32,
+                r.bytes(block.compressed_size)?,
+                block.uncompressed_size,
+            )?);
+        }
+
+        let mut files = Vec::new();
+        let mut resources = HashMap::new();
+        let node_cnt = ir.i32()?;
+        for _ in 0..node_cnt {
+            let offset = ir.i64()? as usize;
+            let size = ir.i64()? as usize;
+            let flags = ir.u32()?;
+            let path = ir.cstr()?;
+            let node = offset
+                .checked_add(size)
+                .and_then(|end| stream.get(offset..end))
+                .ok_or(Error::Invalid(format!("Node {path} overruns the bundle.")))?;
+
+            if flags & NODE_SERIALIZED_FILE != 0 {
+                files.push(SerializedFile::new(path, node.to_vec())?);
+            } else {
+                resources.insert(path, node.to_vec());
+            }
+        }
+
+        Ok(Self {
+            unity_version,
+            files,
+            resources,
+        })
+    }
+
+    /// Data of a streamed resource, `path` being e.g. `archive:/CAB-…/CAB-….resS`.
+    pub fn resource(&self, path: &str, offset: u64, size: u64) -> Result<&[u8], Error> {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let data = self
+            .resources
+            .get(name)
+            .ok_or(Error::Invalid(format!("Missing resource {path}.")))?;
+        let (offset, size) = (offset as usize, size as usize);
+        offset
+            .checked_add(size)
+            .and_then(|end| data.get(offset..end))
+            .ok_or(Error::Invalid(format!(
+                "{size} bytes @ byte {offset} overruns {path}."
+            )))
+    }
+
+    /// Follow a `PPtr` read from an object of `file`.
+    ///
+    /// returns `None` for null pointers and for objects outside this bundle.
+    pub fn resolve<'a>(
+        &'a self,
+        file: &'a SerializedFile,
+        pptr: &PPtr,
+    ) -> Option<(&'a SerializedFile, &'a ObjectInfo)> {
+        if pptr.is_null() {
+            return None;
+        }
+
+        let target = if pptr.file_id == 0 {
+            file
+        } else {
+            let external = file.externals.get(pptr.file_id as usize - 1)?;
+            let name = external.rsplit('/').next().unwrap_or(external);
+            self.files.iter().find(|f| f.name == name)?
+        };
+        Some((target, target.object(pptr.path_id)?))
+    }
+
+    /// every object of the given class, across all serialized files
+    pub fn objects_of_class(
+        &self,
+        class_id: i32,
+    ) -> impl Iterator<Item = (&SerializedFile, &ObjectInfo)> {
+        self.files.iter().flat_map(move |f| {
+            f.objects
+                .iter()
+                .filter(move |o| o.class_id == class_id)
+                .map(move |o| (f, o))
+        })
+    }
+}
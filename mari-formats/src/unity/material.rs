@@ -0,0 +1,191 @@
+use super::bundle::Bundle;
+use super::serialized::{PPtr, SerializedFile, Value};
+use super::texture::decode_texture2d;
+use super::{CLASS_TEXTURE2D, Error};
+use crate::{TextureRGBA8, ToonMaterial};
+
+/// Base texture properties, most specific first: URP's `_BaseMap` and the built-in pipeline's
+/// `_MainTex`. The names of the toon shader's ramp and shadow textures aren't known, so those
+/// are only found by the suffixes below.
+const BASE_PROPERTIES: &[&str] = &["_BaseMap", "_MainTex"];
+const OUTLINE_WIDTH_PROPERTIES: &[&str] = &["_OutlineWidth", "_Outline_Width", "_OutlineSize"];
+const OUTLINE_COLOR_PROPERTIES: &[&str] = &["_OutlineColor", "_Outline_Color"];
+
+/// Texture name suffixes used by the game's assets, e.g. `t_chr_ttmr-casl-0000_bdy_col`, for
+/// textures not under a known property.
+const BASE_SUFFIX: &str = "_col";
+const RAMP_SUFFIX: &str = "_rmp";
+const SHADOW_SUFFIX: &str = "_sdw";
+
+/// A texture slot of a material.
+#[derive(Debug, Clone)]
+pub struct TexEnv {
+    pub texture: PPtr,
+    pub scale: [f32; 2],
+    pub offset: [f32; 2],
+}
+
+/// The saved properties of a Unity `Material`.
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub shader: Option<PPtr>,
+    pub textures: Vec<(String, TexEnv)>,
+    pub floats: Vec<(String, f32)>,
+    /// r,g,b,a
+    pub colors: Vec<(String, [f32; 4])>,
+}
+
+/// entries of a serialized `map<string, T>`, whose keys are plain or `FastPropertyName` strings
+fn map_entries(map: Option<&Value>) -> impl Iterator<Item = (String, &Value)> {
+    map.and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|pair| {
+            let key = pair.get("first")?;
+            let key = key.as_str().or(key.get("name").and_then(Value::as_str))?;
+            Some((key.to_string(), pair.get("second")?))
+        })
+}
+
+fn vector<const N: usize>(value: Option<&Value>, names: [&str; N]) -> Option<[f32; N]> {
+    let value = value?;
+    let mut ret = [0.0; N];
+    for (r, name) in ret.iter_mut().zip(names) {
+        *r = value.get(name)?.as_f32()?;
+    }
+    Some(ret)
+}
+
+impl Material {
+    pub fn new(value: &Value) -> Result<Self, Error> {
+        let properties = value.get("m_SavedProperties").ok_or(Error::Invalid(
+            "Material without m_SavedProperties.".to_string(),
+        ))?;
+
+        let textures = map_entries(properties.get("m_TexEnvs"))
+            .filter_map(|(name, env)| {
+                Some((
+                    name,
+                    TexEnv {
+                        texture: PPtr::new(env.get("m_Texture")?)?,
+                        scale: vector(env.get("m_Scale"), ["x", "y"])?,
+                        offset: vector(env.get("m_Offset"), ["x", "y"])?,
+                    },
+                ))
+            })
+            .collect();
+        let floats = map_entries(properties.get("m_Floats"))
+            .filter_map(|(name, v)| Some((name, v.as_f32()?)))
+            .collect();
+        let colors = map_entries(properties.get("m_Colors"))
+            .filter_map(|(name, v)| Some((name, vector(Some(v), ["r", "g", "b", "a"])?)))
+            .collect();
+
+        Ok(Self {
+            name: value
+                .get("m_Name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            shader: value.get("m_Shader").and_then(PPtr::new),
+            textures,
+            floats,
+            colors,
+        })
+    }
+
+    pub fn texture(&self, name: &str) -> Option<&TexEnv> {
+        self.textures
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, t)| t)
+    }
+
+    pub fn float(&self, name: &str) -> Option<f32> {
+        self.floats.iter().find(|(n, _)| n == name).map(|(_, f)| *f)
+    }
+
+    pub fn color(&self, name: &str) -> Option<[f32; 4]> {
+        self.colors.iter().find(|(n, _)| n == name).map(|(_, c)| *c)
+    }
+
+    /// non-null texture slots that point into the bundle, with their Texture2D
+    fn slots(&self, bundle: &Bundle, file: &SerializedFile) -> Result<Vec<(&str, Value)>, Error> {
+        let mut slots = Vec::new();
+        for (property, env) in &self.textures {
+            if let Some((f, o)) = bundle.resolve(file, &env.texture)
+                && o.class_id == CLASS_TEXTURE2D
+            {
+                slots.push((property.as_str(), f.read(o)?));
+            }
+        }
+        Ok(slots)
+    }
+
+    /// Resolve the base color texture, `file` being where the material lives.
+    pub fn base_texture(
+        &self,
+        bundle: &Bundle,
+        file: &SerializedFile,
+    ) -> Result<TextureRGBA8, Error> {
+        self.pick(
+            &self.slots(bundle, file)?,
+            bundle,
+            BASE_PROPERTIES,
+            BASE_SUFFIX,
+        )
+    }
+
+    /// Resolve the textures of the game's toon shader, `file` being where the material lives.
+    pub fn to_toon(&self, bundle: &Bundle, file: &SerializedFile) -> Result<ToonMaterial, Error> {
+        let slots = self.slots(bundle, file)?;
+        let pick =
+            |properties: &[&str], suffix: &str| self.pick(&slots, bundle, properties, suffix);
+
+        let outline_width = OUTLINE_WIDTH_PROPERTIES
+            .iter()
+            .find_map(|p| self.float(p))
+            .unwrap_or(0.0);
+        let outline_color = OUTLINE_COLOR_PROPERTIES
+            .iter()
+            .find_map(|p| self.color(p))
+            .unwrap_or([0.0, 0.0, 0.0, 1.0]);
+
+        Ok(ToonMaterial {
+            texture: pick(BASE_PROPERTIES, BASE_SUFFIX)?,
+            ramp_texture: pick(&[], RAMP_SUFFIX)?,
+            sdw_texture: pick(&[], SHADOW_SUFFIX)?,
+            shadow_threshold: ToonMaterial::SHADOW_THRESHOLD,
+            ramp_scale: ToonMaterial::RAMP_SCALE,
+            outline_width,
+            outline_color,
+        })
+    }
+
+    /// the texture of the first of `properties` present, else of the texture named with `suffix`
+    fn pick(
+        &self,
+        slots: &[(&str, Value)],
+        bundle: &Bundle,
+        properties: &[&str],
+        suffix: &str,
+    ) -> Result<TextureRGBA8, Error> {
+        let by_property = properties
+            .iter()
+            .find_map(|p| slots.iter().find(|(property, _)| property == p));
+        let by_name = || {
+            slots.iter().find(|(_, t)| {
+                t.get("m_Name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|n| n.ends_with(suffix))
+            })
+        };
+        let (_, texture) = by_property.or_else(by_name).ok_or(Error::Invalid(format!(
+            "Material {} has no {} texture.",
+            self.name,
+            properties.first().unwrap_or(&suffix)
+        )))?;
+        decode_texture2d(bundle, texture)
+    }
+}
@@ -0,0 +1,299 @@
+use super::Error;
+use super::bundle::Bundle;
+use super::serialized::Value;
+
+/// `m_VertexData` channels since Unity 2019.
+const CHANNEL_POSITION: usize = 0;
+const CHANNEL_NORMAL: usize = 1;
+const CHANNEL_COLOR: usize = 3;
+const CHANNEL_TEXCOORD0: usize = 4;
+const CHANNEL_BLEND_WEIGHT: usize = 12;
+const CHANNEL_BLEND_INDICES: usize = 13;
+
+/// byte size of each `VertexFormat` since Unity 2019
+const FORMAT_SIZES: [usize; 12] = [4, 2, 1, 1, 2, 2, 1, 1, 2, 2, 4, 4];
+
+/// A Unity `Mesh`, still in Unity's left-handed space.
+pub struct Mesh {
+    pub name: String,
+    pub vertex_cnt: usize,
+    /// x,y,z per vertex
+    pub positions: Vec<f32>,
+    /// x,y,z per vertex, empty if absent
+    pub normals: Vec<f32>,
+    /// r,g,b,a per vertex, empty if absent
+    pub colors: Vec<f32>,
+    /// u,v per vertex, empty if absent
+    pub uvs: Vec<f32>,
+    /// 4 indices into the renderer's bones per vertex, empty if not skinned
+    pub joints: Vec<u16>,
+    /// 4 weights per vertex, empty if not skinned
+    pub weights: Vec<f32>,
+    /// triangle indices of each submesh, base vertex applied
+    pub submeshes: Vec<Vec<u32>>,
+    /// column-major inverse bind matrix of each bone
+    pub bind_poses: Vec<[f32; 16]>,
+}
+
+struct Channel {
+    stream: usize,
+    offset: usize,
+    format: usize,
+    dimension: usize,
+}
+
+fn get<'a>(value: &'a Value, name: &str) -> Result<&'a Value, Error> {
+    value
+        .get(name)
+        .ok_or(Error::Invalid(format!("Mesh without {name}.")))
+}
+
+fn get_usize(value: &Value, name: &str) -> Result<usize, Error> {
+    get(value, name)?
+        .as_i64()
+        .filter(|v| *v >= 0)
+        .map(|v| v as usize)
+        .ok_or(Error::Invalid(format!("Mesh with invalid {name}.")))
+}
+
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let frac = (h & 0x3ff) as f32;
+    sign * match exp {
+        0 => frac * 2f32.powi(-24),
+        31 if frac == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + frac / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+/// one component of a vertex attribute, normalized formats mapped to [0, 1] or [-1, 1]
+fn component(format: usize, b: &[u8]) -> f32 {
+    match format {
+        0 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        1 => half_to_f32(u16::from_le_bytes([b[0], b[1]])),
+        2 => b[0] as f32 / 255.0,
+        3 => (b[0] as i8 as f32 / 127.0).max(-1.0),
+        4 => u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0,
+        5 => (i16::from_le_bytes([b[0], b[1]]) as f32 / 32767.0).max(-1.0),
+        6 => b[0] as f32,
+        7 => b[0] as i8 as f32,
+        8 => u16::from_le_bytes([b[0], b[1]]) as f32,
+        9 => i16::from_le_bytes([b[0], b[1]]) as f32,
+        10 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+        _ => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+    }
+}
+
+impl Mesh {
+    pub fn new(bundle: &Bundle, mesh: &Value) -> Result<Self, Error> {
+        let name = mesh
+            .get("m_Name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let vertex_data = get(mesh, "m_VertexData")?;
+        let vertex_cnt = get_usize(vertex_data, "m_VertexCount")?;
+        let mut channels = Vec::new();
+        for c in get(vertex_data, "m_Channels")?
+            .as_array()
+            .unwrap_or_default()
+        {
+            let format = get_usize(c, "format")?;
+            if format >= FORMAT_SIZES.len() {
+                return Err(Error::Unsupported(format!("Vertex format {format}")));
+            }
+            channels.push(Channel {
+                stream: get_usize(c, "stream")?,
+                offset: get_usize(c, "offset")?,
+                format,
+                dimension: get_usize(c, "dimension")? & 0xf,
+            });
+        }
+        if vertex_cnt == 0 && mesh.get("m_CompressedMesh").is_some() {
+            return Err(Error::Unsupported(format!("Compressed mesh {name}")));
+        }
+
+        let mut data = get(vertex_data, "m_DataSize")?
+            .as_bytes()
+            .unwrap_or_default();
+        if data.is_empty() && vertex_cnt > 0 {
+            let stream = get(mesh, "m_StreamData")?;
+            let offset = stream.get("offset").and_then(Value::as_i64).unwrap_or(0);
+            let size = stream.get("size").and_then(Value::as_i64).unwrap_or(0);
+            let path = stream.get("path").and_then(Value::as_str).unwrap_or("");
+            data = bundle.resource(path, offset as u64, size as u64)?;
+        }
+
+        // streams are laid out one after another, each 16-byte aligned
+        let stream_cnt = channels.iter().map(|c| c.stream + 1).max().unwrap_or(0);
+        let mut stream_offsets = Vec::new();
+        let mut strides = Vec::new();
+        let mut offset = 0;
+        for s in 0..stream_cnt {
+            let stride: usize = channels
+                .iter()
+                .filter(|c| c.stream == s && c.dimension > 0)
+                .map(|c| c.dimension * FORMAT_SIZES[c.format])
+                .sum();
+            let end = vertex_cnt
+                .checked_mul(stride)
+                .and_then(|size| size.checked_add(offset))
+                .filter(|end| *end <= data.len())
+                .ok_or(Error::Invalid(format!(
+                    "Vertex data of {name} is truncated."
+                )))?;
+            stream_offsets.push(offset);
+            strides.push(stride);
+            offset = end.div_ceil(16) * 16;
+        }
+
+        let read_channel = |i: usize| -> Result<Option<(usize, Vec<f32>)>, Error> {
+            let Some(c) = channels.get(i).filter(|c| c.dimension > 0) else {
+                return Ok(None);
+            };
+            let size = FORMAT_SIZES[c.format];
+            let mut ret = Vec::with_capacity(vertex_cnt * c.dimension);
+            for v in 0..vertex_cnt {
+                let start = stream_offsets[c.stream] + v * strides[c.stream];
+                let bytes = start
+                    .checked_add(c.offset)
+                    .and_then(|start| data.get(start..start + c.dimension * size))
+                    .ok_or(Error::Invalid(format!(
+                        "Vertex data of {name} is truncated."
+                    )))?;
+                ret.extend(bytes.chunks_exact(size).map(|b| component(c.format, b)));
+            }
+            Ok(Some((c.dimension, ret)))
+        };
+
+        let positions = match read_channel(CHANNEL_POSITION)? {
+            Some((3, p)) => p,
+            Some((4, p)) => p.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect(),
+            _ => return Err(Error::Invalid(format!("Mesh {name} without positions."))),
+        };
+        let normals = match read_channel(CHANNEL_NORMAL)? {
+            Some((3, n)) => n,
+            Some((4, n)) => n.chunks_exact(4).flat_map(|n| [n[0], n[1], n[2]]).collect(),
+            _ => Vec::new(),
+        };
+        let colors = match read_channel(CHANNEL_COLOR)? {
+            Some((4, c)) => c,
+            Some((3, c)) => c
+                .chunks_exact(3)
+                .flat_map(|c| [c[0], c[1], c[2], 1.0])
+                .collect(),
+            _ => Vec::new(),
+        };
+        let uvs = match read_channel(CHANNEL_TEXCOORD0)? {
+            Some((d, uv)) if d >= 2 => uv.chunks_exact(d).flat_map(|uv| [uv[0], uv[1]]).collect(),
+            _ => Vec::new(),
+        };
+
+        let (mut joints, mut weights) = (Vec::new(), Vec::new());
+        if let Some((index_dim, indices)) = read_channel(CHANNEL_BLEND_INDICES)? {
+            let (weight_dim, blend) =
+                read_channel(CHANNEL_BLEND_WEIGHT)?.unwrap_or((0, Vec::new()));
+            for v in 0..vertex_cnt {
+                let mut w = [0.0f32; 4];
+                let mut j = [0u16; 4];
+                for k in 0..index_dim.min(4) {
+                    j[k] = indices[v * index_dim + k] as u16;
+                    w[k] = if k < weight_dim {
+                        blend[v * weight_dim + k]
+                    } else if k == weight_dim {
+                        // the last weight is implied
+                        1.0 - w[..k].iter().sum::<f32>()
+                    } else {
+                        0.0
+                    };
+                }
+                let sum: f32 = w.iter().sum();
+                if sum > 0.0 {
+                    w.iter_mut().for_each(|w| *w /= sum);
+                }
+                joints.extend_from_slice(&j);
+                weights.extend_from_slice(&w);
+            }
+        }
+
+        let index_buffer = get(mesh, "m_IndexBuffer")?.as_bytes().unwrap_or_default();
+        let index_size = if get_usize(mesh, "m_IndexFormat").unwrap_or(0) == 1 {
+            4
+        } else {
+            2
+        };
+        let mut submeshes = Vec::new();
+        for s in get(mesh, "m_SubMeshes")?.as_array().unwrap_or_default() {
+            let first_byte = get_usize(s, "firstByte")?;
+            let index_cnt = get_usize(s, "indexCount")?;
+            let topology = get_usize(s, "topology").unwrap_or(0);
+            let base_vertex = get_usize(s, "baseVertex").unwrap_or(0);
+            if topology != 0 {
+                return Err(Error::Unsupported(format!(
+                    "Topology {topology} in mesh {name}"
+                )));
+            }
+            let bytes = index_cnt
+                .checked_mul(index_size)
+                .and_then(|size| size.checked_add(first_byte))
+                .and_then(|end| index_buffer.get(first_byte..end))
+                .ok_or(Error::Invalid(format!(
+                    "Index buffer of {name} is truncated."
+                )))?;
+            // whole triangles only
+            let triangle_cnt = bytes.len() / index_size / 3;
+            let indices = bytes
+                .chunks_exact(index_size)
+                .take(triangle_cnt * 3)
+                .map(|b| match b {
+                    [a, b] => u16::from_le_bytes([*a, *b]) as usize,
+                    _ => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize,
+                })
+                .map(|i| {
+                    i.checked_add(base_vertex)
+                        .filter(|i| *i < vertex_cnt)
+                        .and_then(|i| u32::try_from(i).ok())
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(Error::Invalid(format!(
+                    "Mesh {name} indexes past its {vertex_cnt} vertices."
+                )))?;
+            submeshes.push(indices);
+        }
+
+        let bind_poses = get(mesh, "m_BindPose")?
+            .as_array()
+            .unwrap_or_default()
+            .iter()
+            .map(matrix)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::Invalid(format!("Invalid bind pose in mesh {name}.")))?;
+
+        Ok(Self {
+            name,
+            vertex_cnt,
+            positions,
+            normals,
+            colors,
+            uvs,
+            joints,
+            weights,
+            submeshes,
+            bind_poses,
+        })
+    }
+}
+
+/// a `Matrix4x4f`, whose fields `eRC` are row R and column C, in column-major order
+pub fn matrix(value: &Value) -> Option<[f32; 16]> {
+    let mut m = [0.0; 16];
+    for c in 0..4 {
+        for r in 0..4 {
+            m[4 * c + r] = value.get(&format!("e{r}{c}"))?.as_f32()?;
+        }
+    }
+    Some(m)
+}
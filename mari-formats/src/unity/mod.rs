@@ -0,0 +1,46 @@
+//! Native reading of Unity asset bundles, in place of AssetStudio.
+//!
+//! Only UnityFS bundles with type trees are supported, which is what the game ships.
+
+mod bundle;
+mod material;
+mod mesh;
+mod prefab;
+mod reader;
+mod serialized;
+mod texture;
+
+pub use bundle::Bundle;
+pub use material::{Material, TexEnv};
+pub use mesh::Mesh;
+pub use prefab::{Hierarchy, Node, assemble_actor};
+pub use serialized::{ObjectInfo, PPtr, SerializedFile, Value};
+pub use texture::{decode_texture2d, texture_format_name};
+
+/// Unity class ids.
+pub const CLASS_TEXTURE2D: i32 = 28;
+pub const CLASS_MATERIAL: i32 = 21;
+pub const CLASS_MONO_BEHAVIOUR: i32 = 114;
+pub const CLASS_GAME_OBJECT: i32 = 1;
+pub const CLASS_TRANSFORM: i32 = 4;
+pub const CLASS_MESH_RENDERER: i32 = 23;
+pub const CLASS_MESH_FILTER: i32 = 33;
+pub const CLASS_MESH: i32 = 43;
+pub const CLASS_SKINNED_MESH_RENDERER: i32 = 137;
+pub const CLASS_RECT_TRANSFORM: i32 = 224;
+pub const CLASS_ANIMATION_CLIP: i32 = 74;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Invalid(String),
+    Unsupported(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+
+use super::bundle::Bundle;
+use super::mesh::Mesh;
+use super::serialized::{ObjectInfo, PPtr, SerializedFile, Value};
+use super::{
+    CLASS_GAME_OBJECT, CLASS_MESH_FILTER, CLASS_MESH_RENDERER, CLASS_RECT_TRANSFORM,
+    CLASS_SKINNED_MESH_RENDERER, CLASS_TRANSFORM, Error,
+};
+use crate::math::{Mat4, mat4_from_trs, mat4_mul, mat4_transform_point, mat4_transform_vector};
+use crate::{Actor, Bone, Model, Part, Skeleton, Submesh};
+
+/// an object identified across the serialized files of a bundle
+type Key = (String, i64);
+
+fn key(file: &SerializedFile, info: &ObjectInfo) -> Key {
+    (file.name.clone(), info.path_id)
+}
+
+fn resolve(bundle: &Bundle, file: &SerializedFile, pptr: Option<&Value>) -> Option<Key> {
+    let (f, o) = bundle.resolve(file, &PPtr::new(pptr?)?)?;
+    Some(key(f, o))
+}
+
+fn vector<const N: usize>(value: Option<&Value>, names: [&str; N]) -> Option<[f32; N]> {
+    let value = value?;
+    let mut ret = [0.0; N];
+    for (r, name) in ret.iter_mut().zip(names) {
+        *r = value.get(name)?.as_f32()?;
+    }
+    Some(ret)
+}
+
+fn name_of(value: &Value) -> String {
+    value
+        .get("m_Name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// A `Transform` of a prefab, in Unity's left-handed space.
+pub struct Node {
+    /// name of the owning `GameObject`
+    pub name: String,
+    /// names from below the root down to this node joined by `/`, empty for a root
+    pub path: String,
+    /// index of the parent in `Hierarchy::nodes`, `None` for a root
+    pub parent: Option<usize>,
+    pub translation: [f32; 3],
+    /// quaternion x,y,z,w
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+/// The `GameObject`/`Transform` tree of a bundle.
+pub struct Hierarchy {
+    /// parents always come before their children
+    pub nodes: Vec<Node>,
+    /// `GameObject` to its node
+    game_objects: HashMap<Key, usize>,
+    /// `Transform` to its node
+    transforms: HashMap<Key, usize>,
+}
+
+impl Hierarchy {
+    pub fn new(bundle: &Bundle) -> Result<Self, Error> {
+        struct Raw {
+            node: Node,
+            game_object: Option<Key>,
+            father: Option<Key>,
+            children: Vec<Key>,
+        }
+
+        let mut raw: HashMap<Key, Raw> = HashMap::new();
+        let mut order = Vec::new();
+        for class_id in [CLASS_TRANSFORM, CLASS_RECT_TRANSFORM] {
+            for (file, info) in bundle.objects_of_class(class_id) {
+                let value = file.read(info)?;
+                let go = value
+                    .get("m_GameObject")
+                    .and_then(PPtr::new)
+                    .and_then(|p| bundle.resolve(file, &p));
+                let name = match go {
+                    Some((f, o)) if o.class_id == CLASS_GAME_OBJECT => name_of(&f.read(o)?),
+                    _ => String::new(),
+                };
+                let game_object = go.map(|(f, o)| key(f, o));
+                let children = value
+                    .get("m_Children")
+                    .and_then(Value::as_array)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|c| resolve(bundle, file, Some(c)))
+                    .collect();
+                let node = Node {
+                    name,
+                    path: String::new(),
+                    parent: None,
+                    translation: vector(value.get("m_LocalPosition"), ["x", "y", "z"])
+                        .unwrap_or([0.0; 3]),
+                    rotation: vector(value.get("m_LocalRotation"), ["x", "y", "z", "w"])
+                        .unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                    scale: vector(value.get("m_LocalScale"), ["x", "y", "z"]).unwrap_or([1.0; 3]),
+                };
+                let k = key(file, info);
+                order.push(k.clone());
+                raw.insert(
+                    k,
+                    Raw {
+                        node,
+                        game_object,
+                        father: resolve(bundle, file, value.get("m_Father")),
+                        children,
+                    },
+                );
+            }
+        }
+
+        // walk down from the roots so that parents come first
+        let mut stack: Vec<(Key, Option<usize>)> = order
+            .iter()
+            .rev()
+            .filter(|k| raw[*k].father.as_ref().is_none_or(|f| !raw.contains_key(f)))
+            .map(|k| (k.clone(), None))
+            .collect();
+        let mut ret = Self {
+            nodes: Vec::new(),
+            game_objects: HashMap::new(),
+            transforms: HashMap::new(),
+        };
+        while let Some((k, parent)) = stack.pop() {
+            let Some(mut r) = raw.remove(&k) else {
+                continue;
+            };
+            r.node.parent = parent;
+            r.node.path = match parent {
+                None => String::new(),
+                Some(p) if ret.nodes[p].path.is_empty() => r.node.name.clone(),
+                Some(p) => format!("{}/{}", ret.nodes[p].path, r.node.name),
+            };
+            let i = ret.nodes.len();
+            ret.nodes.push(r.node);
+            ret.transforms.insert(k, i);
+            if let Some(go) = r.game_object {
+                ret.game_objects.insert(go, i);
+            }
+            stack.extend(r.children.into_iter().rev().map(|c| (c, Some(i))));
+        }
+        if !raw.is_empty() {
+            return Err(Error::Invalid(format!(
+                "{} transforms are not reachable from a root.",
+                raw.len()
+            )));
+        }
+        Ok(ret)
+    }
+
+    pub fn find(&self, path: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.path == path)
+    }
+}
+
+/// A mesh drawn by a `SkinnedMeshRenderer` or a `MeshFilter` + `MeshRenderer` pair.
+struct Renderer {
+    /// node of the owning `GameObject`
+    node: usize,
+    mesh: Mesh,
+    /// nodes the mesh is skinned to, empty if not skinned
+    bones: Vec<usize>,
+    /// material name of each submesh
+    materials: Vec<Option<String>>,
+}
+
+fn renderers(bundle: &Bundle, hierarchy: &Hierarchy) -> Result<Vec<Renderer>, Error> {
+    let node_of = |file: &SerializedFile, value: &Value| {
+        resolve(bundle, file, value.get("m_GameObject"))
+            .and_then(|go| hierarchy.game_objects.get(&go).copied())
+    };
+    let mesh_of = |file: &SerializedFile, pptr: Option<&Value>| -> Result<Option<Mesh>, Error> {
+        match pptr
+            .and_then(PPtr::new)
+            .and_then(|p| bundle.resolve(file, &p))
+        {
+            Some((f, o)) => Ok(Some(Mesh::new(bundle, &f.read(o)?)?)),
+            None => Ok(None),
+        }
+    };
+    let materials_of =
+        |file: &SerializedFile, value: &Value| -> Result<Vec<Option<String>>, Error> {
+            let mut ret = Vec::new();
+            for m in value
+                .get("m_Materials")
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+            {
+                ret.push(match PPtr::new(m).and_then(|p| bundle.resolve(file, &p)) {
+                    Some((f, o)) => Some(name_of(&f.read(o)?)),
+                    None => None,
+                });
+            }
+            Ok(ret)
+        };
+
+    let mut ret = Vec::new();
+    for (file, info) in bundle.objects_of_class(CLASS_SKINNED_MESH_RENDERER) {
+        let value = file.read(info)?;
+        let (Some(node), Some(mesh)) = (node_of(file, &value), mesh_of(file, value.get("m_Mesh"))?)
+        else {
+            continue;
+        };
+        let mut bones = Vec::new();
+        for b in value
+            .get("m_Bones")
+            .and_then(Value::as_array)
+            .unwrap_or_default()
+        {
+            bones.push(
+                resolve(bundle, file, Some(b))
+                    .and_then(|t| hierarchy.transforms.get(&t).copied())
+                    .ok_or(Error::Invalid(format!(
+                        "Bone of mesh {} is outside the hierarchy.",
+                        mesh.name
+                    )))?,
+            );
+        }
+        ret.push(Renderer {
+            node,
+            materials: materials_of(file, &value)?,
+            mesh,
+            bones,
+        });
+    }
+
+    let mut filters = HashMap::new();
+    for (file, info) in bundle.objects_of_class(CLASS_MESH_FILTER) {
+        let value = file.read(info)?;
+        if let (Some(node), Some(mesh)) =
+            (node_of(file, &value), mesh_of(file, value.get("m_Mesh"))?)
+        {
+            filters.insert(node, mesh);
+        }
+    }
+    for (file, info) in bundle.objects_of_class(CLASS_MESH_RENDERER) {
+        let value = file.read(info)?;
+        let Some(node) = node_of(file, &value) else {
+            continue;
+        };
+        if let Some(mesh) = filters.remove(&node) {
+            ret.push(Renderer {
+                node,
+                materials: materials_of(file, &value)?,
+                mesh,
+                bones: Vec::new(),
+            });
+        }
+    }
+    Ok(ret)
+}
+
+/// Unity's left-handed transform in our right-handed space, by negating x.
+fn mirror_trs(node: &Node) -> ([f32; 3], [f32; 4]) {
+    let [tx, ty, tz] = node.translation;
+    let [qx, qy, qz, qw] = node.rotation;
+    ([-tx, ty, tz], [qx, -qy, -qz, qw])
+}
+
+/// S·M·S with S negating x
+fn mirror_matrix(m: &Mat4) -> Mat4 {
+    let mut ret = *m;
+    for c in 0..4 {
+        for r in 0..4 {
+            if (r == 0) != (c == 0) {
+                ret[4 * c + r] = -ret[4 * c + r];
+            }
+        }
+    }
+    ret
+}
+
+/// Bake one renderer in the rest pose of the skeleton, `to_bone` mapping its hierarchy's nodes
+/// to bones.
+fn bake(renderer: &Renderer, world: &[Mat4], to_bone: &[usize]) -> Result<Model, Error> {
+    let mesh = &renderer.mesh;
+    if mesh.vertex_cnt > u16::MAX as usize + 1 {
+        return Err(Error::Unsupported(format!(
+            "Mesh {} with {} vertices",
+            mesh.name, mesh.vertex_cnt
+        )));
+    }
+
+    let skinned = !renderer.bones.is_empty() && !mesh.weights.is_empty();
+    let skin: Vec<Mat4> = if skinned {
+        if mesh.bind_poses.len() < renderer.bones.len() {
+            return Err(Error::Invalid(format!(
+                "Mesh {} has fewer bind poses than bones.",
+                mesh.name
+            )));
+        }
+        renderer
+            .bones
+            .iter()
+            .zip(&mesh.bind_poses)
+            .map(|(b, bind)| mat4_mul(&world[to_bone[*b]], &mirror_matrix(bind)))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let own_bone = to_bone[renderer.node];
+
+    let mut vertices = Vec::with_capacity(mesh.vertex_cnt * 3);
+    let mut normals = Vec::with_capacity(mesh.normals.len());
+    let mut joints = Vec::with_capacity(mesh.vertex_cnt * 4);
+    let mut weights = Vec::with_capacity(mesh.vertex_cnt * 4);
+    for v in 0..mesh.vertex_cnt {
+        let m = if skinned {
+            let mut m = [0.0; 16];
+            for k in 0..4 {
+                let (j, w) = (mesh.joints[4 * v + k] as usize, mesh.weights[4 * v + k]);
+                if w == 0.0 {
+                    joints.push(0);
+                    weights.push(0.0);
+                    continue;
+                }
+                let s = skin.get(j).ok_or(Error::Invalid(format!(
+                    "Vertex #{v} of mesh {} refers to bone #{j}.",
+                    mesh.name
+                )))?;
+                m.iter_mut().zip(s).for_each(|(m, s)| *m += w * s);
+                joints.push(to_bone[renderer.bones[j]] as u16);
+                weights.push(w);
+            }
+            m
+        } else {
+            joints.extend_from_slice(&[own_bone as u16, 0, 0, 0]);
+            weights.extend_from_slice(&[1.0, 0.0, 0.0, 0.0]);
+            world[own_bone]
+        };
+
+        let p = &mesh.positions[3 * v..3 * v + 3];
+        vertices.extend(mat4_transform_point(&m, &[-p[0], p[1], p[2]]));
+        if let Some(n) = mesh.normals.get(3 * v..3 * v + 3) {
+            let [x, y, z] = mat4_transform_vector(&m, &[-n[0], n[1], n[2]]);
+            let l = (x * x + y * y + z * z).sqrt();
+            let l = if l == 0.0 { 1.0 } else { l };
+            normals.extend([x / l, y / l, z / l]);
+        }
+    }
+
+    // flip V to convert from Unity space to OpenGL space
+    let uvs = mesh
+        .uvs
+        .chunks_exact(2)
+        .flat_map(|uv| [uv[0], 1.0 - uv[1]])
+        .collect();
+
+    // negating x turns Unity's clockwise front faces counter-clockwise, indices stay as they are
+    let mut indices = Vec::new();
+    let mut submeshes = Vec::new();
+    for (i, s) in mesh.submeshes.iter().enumerate() {
+        submeshes.push(Submesh {
+            first: indices.len(),
+            count: s.len(),
+            material: renderer.materials.get(i).cloned().flatten(),
+        });
+        indices.extend(s.iter().map(|i| *i as u16));
+    }
+
+    Ok(Model {
+        vertices,
+        mesh: indices,
+        uvs,
+        normals,
+        colors: mesh.colors.clone(),
+        joints,
+        weights,
+        submeshes,
+    })
+}
+
+/// Assemble the prefabs of one character into an actor, e.g. body, face and hair bundles.
+///
+/// The first bundle is the body and its hierarchy becomes the skeleton. Nodes of further bundles
+/// are matched to bones by their path below the root, or else by a unique bone name, and unmatched
+/// ones are added under their parent. Every mesh is baked in the rest pose in the actor's space,
+/// converted to the right-handed space of `Model`. The largest mesh of the first bundle is the
+/// body, all others become parts named after their `GameObject`.
+pub fn assemble_actor(bundles: &[Bundle]) -> Result<Actor, Error> {
+    let mut bones: Vec<Bone> = Vec::new();
+    let mut world: Vec<Mat4> = Vec::new();
+    let mut paths: HashMap<String, usize> = HashMap::new();
+    let mut models: Vec<(String, bool, Model)> = Vec::new();
+
+    for (b, bundle) in bundles.iter().enumerate() {
+        let hierarchy = Hierarchy::new(bundle)?;
+        let mut to_bone = Vec::with_capacity(hierarchy.nodes.len());
+        for node in &hierarchy.nodes {
+            let by_name = || {
+                let mut same = bones
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, b)| b.name == node.name);
+                match (same.next(), same.next()) {
+                    (Some((i, _)), None) if b > 0 => Some(i),
+                    _ => None,
+                }
+            };
+            let i = match paths.get(&node.path).copied().or_else(by_name) {
+                Some(i) => i,
+                None => {
+                    let parent = node.parent.map(|p| to_bone[p]);
+                    let (translation, rotation) = mirror_trs(node);
+                    let local = mat4_from_trs(&translation, &rotation, &node.scale);
+                    world.push(match parent {
+                        Some(p) => mat4_mul(&world[p], &local),
+                        None => local,
+                    });
+                    bones.push(Bone {
+                        name: node.name.clone(),
+                        parent,
+                        translation,
+                        rotation,
+                        scale: node.scale,
+                    });
+                    bones.len() - 1
+                }
+            };
+            paths.entry(node.path.clone()).or_insert(i);
+            to_bone.push(i);
+        }
+        if bones.len() > u16::MAX as usize {
+            return Err(Error::Unsupported(format!("{} bones", bones.len())));
+        }
+
+        for renderer in renderers(bundle, &hierarchy)? {
+            models.push((
+                hierarchy.nodes[renderer.node].name.clone(),
+                b == 0,
+                bake(&renderer, &world, &to_bone)?,
+            ));
+        }
+    }
+
+    let body = models
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, in_body, _))| *in_body)
+        .max_by_key(|(_, (_, _, m))| m.vertices.len())
+        .map(|(i, _)| i)
+        .ok_or(Error::Invalid("The body bundle has no mesh.".to_string()))?;
+    let (_, _, body) = models.remove(body);
+
+    Ok(Actor {
+        body,
+        parts: models
+            .into_iter()
+            .map(|(name, _, model)| Part { name, model })
+            .collect(),
+        skeleton: Some(Skeleton { bones }),
+    })
+}
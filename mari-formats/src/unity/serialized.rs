@@ -0,0 +1,421 @@
+use super::reader::Reader;
+use super::{CLASS_MONO_BEHAVIOUR, Error};
+
+/// Strings shared by all type trees, referenced by offsets with the high bit set.
+const COMMON_STRINGS: &str = "AABB\0AnimationClip\0AnimationCurve\0AnimationState\0Array\0Base\0BitField\0bitset\0bool\0char\0ColorRGBA\0Component\0data\0deque\0double\0dynamic_array\0FastPropertyName\0first\0float\0Font\0GameObject\0Generic Mono\0GradientNEW\0GUID\0GUIStyle\0int\0list\0long long\0map\0Matrix4x4f\0MdFour\0MonoBehaviour\0MonoScript\0m_ByteSize\0m_Curve\0m_EditorClassIdentifier\0m_EditorHideFlags\0m_Enabled\0m_ExtensionPtr\0m_GameObject\0m_Index\0m_IsArray\0m_IsStatic\0m_MetaFlag\0m_Name\0m_ObjectHideFlags\0m_PrefabInternal\0m_PrefabParentObject\0m_Script\0m_StaticEditorFlags\0m_Type\0m_Version\0Object\0pair\0PPtr<Component>\0PPtr<GameObject>\0PPtr<Material>\0PPtr<MonoBehaviour>\0PPtr<MonoScript>\0PPtr<Object>\0PPtr<Prefab>\0PPtr<Sprite>\0PPtr<TextAsset>\0PPtr<Texture>\0PPtr<Texture2D>\0PPtr<Transform>\0Prefab\0Quaternionf\0Rectf\0RectInt\0RectOffset\0second\0set\0short\0size\0SInt16\0SInt32\0SInt64\0SInt8\0staticvector\0string\0TextAsset\0TextMesh\0Texture\0Texture2D\0Transform\0TypelessData\0UInt16\0UInt32\0UInt64\0UInt8\0unsigned int\0unsigned long long\0unsigned short\0vector\0Vector2f\0Vector3f\0Vector4f\0m_ScriptingClassIdentifier\0Gradient\0Type*\0int2_storage\0int3_storage\0BoundsInt\0m_CorrespondingSourceObject\0m_PrefabInstance\0m_PrefabAsset\0FileSize\0Hash128\0RenderingLayerMask\0";
+
+/// meta flag asking for 4-byte alignment after the field
+const ALIGN_BYTES: i32 = 0x4000;
+
+/// A deserialized field of a Unity object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    /// byte arrays and `TypelessData`
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    /// fields in serialization order
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// field of an object
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(v) => Some(*v),
+            Value::UInt(v) => Some(*v as i64),
+            Value::Bool(v) => Some(*v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Value::Float(v) => Some(*v as f32),
+            Value::Int(v) => Some(*v as f32),
+            Value::UInt(v) => Some(*v as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            Value::String(s) => Some(s.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// A reference to an object, possibly in another serialized file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PPtr {
+    /// 0 for the same file, else 1 + index into `SerializedFile::externals`
+    pub file_id: i32,
+    /// 0 for null
+    pub path_id: i64,
+}
+
+impl PPtr {
+    pub fn new(value: &Value) -> Option<Self> {
+        Some(Self {
+            file_id: value.get("m_FileID")?.as_i64()? as i32,
+            path_id: value.get("m_PathID")?.as_i64()?,
+        })
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.path_id == 0
+    }
+}
+
+struct TypeTreeNode {
+    type_name: String,
+    name: String,
+    level: u8,
+    type_flags: u8,
+    meta_flag: i32,
+}
+
+pub struct SerializedType {
+    pub class_id: i32,
+    nodes: Vec<TypeTreeNode>,
+}
+
+pub struct ObjectInfo {
+    pub path_id: i64,
+    pub class_id: i32,
+    byte_start: usize,
+    byte_size: usize,
+    type_index: usize,
+}
+
+/// A serialized file (`CAB-…`) with embedded type trees.
+pub struct SerializedFile {
+    /// node path in the bundle, e.g. `CAB-0123456789abcdef`
+    pub name: String,
+    pub unity_version: String,
+    pub types: Vec<SerializedType>,
+    pub objects: Vec<ObjectInfo>,
+    /// paths of the files `m_FileID` 1, 2, … refer to
+    pub externals: Vec<String>,
+    big_endian: bool,
+    data_offset: usize,
+    data: Vec<u8>,
+}
+
+fn type_tree_string(buf: &[u8], offset: u32) -> String {
+    let (buf, offset) = if offset & 0x8000_0000 != 0 {
+        (COMMON_STRINGS.as_bytes(), (offset & 0x7fff_ffff) as usize)
+    } else {
+        (buf, offset as usize)
+    };
+    let rest = buf.get(offset..).unwrap_or_default();
+    let len = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+    String::from_utf8_lossy(&rest[..len]).into_owned()
+}
+
+impl SerializedFile {
+    pub fn new(name: String, data: Vec<u8>) -> Result<Self, Error> {
+        let mut r = Reader::new(&data, true);
+        r.u32()?; // metadata size
+        let mut file_size = r.u32()? as u64;
+        let version = r.u32()?;
+        let mut data_offset = r.u32()? as u64;
+        if version < 17 {
+            return Err(Error::Unsupported(format!(
+                "Serialized file version {version}"
+            )));
+        }
+        let big_endian = r.u8()? != 0;
+        r.bytes(3)?;
+        if version >= 22 {
+            r.u32()?; // metadata size
+            file_size = r.u64()?;
+            data_offset = r.u64()?;
+            r.u64()?;
+        }
+        if file_size as usize > data.len() {
+            return Err(Error::Invalid(format!(
+                "{name} is truncated to {} of {file_size} bytes.",
+                data.len()
+            )));
+        }
+
+        r.big_endian = big_endian;
+        let unity_version = r.cstr()?;
+        r.i32()?; // target platform
+        let type_tree_enabled = r.u8()? != 0;
+        if !type_tree_enabled {
+            return Err(Error::Unsupported(format!("{name} without type trees")));
+        }
+
+        let type_cnt = r.i32()?;
+        let mut types = Vec::new();
+        for _ in 0..type_cnt {
+            types.push(read_type(&mut r, version)?);
+        }
+
+        let object_cnt = r.i32()?;
+        let mut objects = Vec::new();
+        for _ in 0..object_cnt {
+            r.align(4);
+            let path_id = r.i64()?;
+            let byte_start = if version >= 22 {
+                r.i64()? as usize
+            } else {
+                r.u32()? as usize
+            };
+            let byte_size = r.u32()? as usize;
+            let type_index = r.i32()? as usize;
+            let class_id = types
+                .get(type_index)
+                .ok_or(Error::Invalid(format!(
+                    "Object {path_id} of {name} has unknown type #{type_index}."
+                )))?
+                .class_id;
+            objects.push(ObjectInfo {
+                path_id,
+                class_id,
+                byte_start,
+                byte_size,
+                type_index,
+            });
+        }
+
+        let script_cnt = r.i32()?;
+        for _ in 0..script_cnt {
+            r.i32()?;
+            r.align(4);
+            r.i64()?;
+        }
+
+        let external_cnt = r.i32()?;
+        let mut externals = Vec::new();
+        for _ in 0..external_cnt {
+            r.cstr()?;
+            r.bytes(16)?; // guid
+            r.i32()?; // type
+            externals.push(r.cstr()?);
+        }
+
+        Ok(Self {
+            name,
+            unity_version,
+            types,
+            objects,
+            externals,
+            big_endian,
+            data_offset: data_offset as usize,
+            data,
+        })
+    }
+
+    pub fn object(&self, path_id: i64) -> Option<&ObjectInfo> {
+        self.objects.iter().find(|o| o.path_id == path_id)
+    }
+
+    /// class name from the type tree, e.g. `Texture2D`
+    pub fn type_name(&self, object: &ObjectInfo) -> Option<&str> {
+        let node = self.types.get(object.type_index)?.nodes.first()?;
+        Some(node.type_name.as_str())
+    }
+
+    /// Deserialize an object through its type tree.
+    pub fn read(&self, object: &ObjectInfo) -> Result<Value, Error> {
+        let bytes = self
+            .data_offset
+            .checked_add(object.byte_start)
+            .and_then(|start| Some(start..start.checked_add(object.byte_size)?))
+            .and_then(|range| self.data.get(range))
+            .ok_or(Error::Invalid(format!(
+                "Object {} overruns {}.",
+                object.path_id, self.name
+            )))?;
+
+        let nodes = &self.types[object.type_index].nodes;
+        if nodes.is_empty() {
+            return Err(Error::Invalid(format!(
+                "Object {} of {} has an empty type tree.",
+                object.path_id, self.name
+            )));
+        }
+        let mut r = Reader::new(bytes, self.big_endian);
+        read_value(&mut r, nodes, 0)
+    }
+}
+
+fn read_type(r: &mut Reader, version: u32) -> Result<SerializedType, Error> {
+    let class_id = r.i32()?;
+    r.u8()?; // is stripped
+    r.i16()?; // script type index
+    if class_id == CLASS_MONO_BEHAVIOUR {
+        r.bytes(16)?; // script id
+    }
+    r.bytes(16)?; // old type hash
+
+    let node_cnt = r.i32()?;
+    let string_buffer_size = r.i32()?;
+    let mut raw = Vec::new();
+    for _ in 0..node_cnt {
+        r.u16()?; // version
+        let level = r.u8()?;
+        let type_flags = r.u8()?;
+        let type_offset = r.u32()?;
+        let name_offset = r.u32()?;
+        r.i32()?; // byte size
+        r.i32()?; // index
+        let meta_flag = r.i32()?;
+        if version >= 19 {
+            r.u64()?; // ref type hash
+        }
+        raw.push((level, type_flags, type_offset, name_offset, meta_flag));
+    }
+    let strings = r.bytes(string_buffer_size.max(0) as usize)?;
+    let nodes = raw
+        .into_iter()
+        .map(
+            |(level, type_flags, type_offset, name_offset, meta_flag)| TypeTreeNode {
+                type_name: type_tree_string(strings, type_offset),
+                name: type_tree_string(strings, name_offset),
+                level,
+                type_flags,
+                meta_flag,
+            },
+        )
+        .collect();
+
+    if version >= 21 {
+        let dependency_cnt = r.i32()?;
+        for _ in 0..dependency_cnt {
+            r.i32()?;
+        }
+    }
+
+    Ok(SerializedType { class_id, nodes })
+}
+
+/// indices of the direct children of `nodes[i]`
+fn children(nodes: &[TypeTreeNode], i: usize) -> Vec<usize> {
+    let level = nodes[i].level;
+    let mut ret = Vec::new();
+    for (j, node) in nodes.iter().enumerate().skip(i + 1) {
+        if node.level <= level {
+            break;
+        }
+        if node.level == level + 1 {
+            ret.push(j);
+        }
+    }
+    ret
+}
+
+fn read_value(r: &mut Reader, nodes: &[TypeTreeNode], i: usize) -> Result<Value, Error> {
+    let node = &nodes[i];
+    let mut align = node.meta_flag & ALIGN_BYTES != 0;
+
+    let value = match node.type_name.as_str() {
+        "bool" => Value::Bool(r.u8()? != 0),
+        "SInt8" => Value::Int(r.i8()? as i64),
+        "UInt8" | "char" => Value::UInt(r.u8()? as u64),
+        "SInt16" | "short" => Value::Int(r.i16()? as i64),
+        "UInt16" | "unsigned short" => Value::UInt(r.u16()? as u64),
+        "SInt32" | "int" => Value::Int(r.i32()? as i64),
+        "UInt32" | "unsigned int" | "Type*" => Value::UInt(r.u32()? as u64),
+        "SInt64" | "long long" => Value::Int(r.i64()?),
+        "UInt64" | "unsigned long long" | "FileSize" => Value::UInt(r.u64()?),
+        "float" => Value::Float(r.f32()? as f64),
+        "double" => Value::Float(r.f64()?),
+        "string" => {
+            let n = read_len(r)?;
+            let s = String::from_utf8_lossy(r.bytes(n)?).into_owned();
+            if let Some(array) = children(nodes, i).first() {
+                align |= nodes[*array].meta_flag & ALIGN_BYTES != 0;
+            }
+            Value::String(s)
+        }
+        "TypelessData" => {
+            let n = read_len(r)?;
+            Value::Bytes(r.bytes(n)?.to_vec())
+        }
+        _ => {
+            let children = children(nodes, i);
+            if node.type_flags & 1 != 0 {
+                read_array(r, nodes, i)?
+            } else if children.len() == 1 && nodes[children[0]].type_flags & 1 != 0 {
+                align |= nodes[children[0]].meta_flag & ALIGN_BYTES != 0;
+                read_array(r, nodes, children[0])?
+            } else {
+                let mut fields = Vec::with_capacity(children.len());
+                for c in children {
+                    fields.push((nodes[c].name.clone(), read_value(r, nodes, c)?));
+                }
+                Value::Object(fields)
+            }
+        }
+    };
+
+    if align {
+        r.align(4);
+    }
+    Ok(value)
+}
+
+fn read_len(r: &mut Reader) -> Result<usize, Error> {
+    let n = r.i32()?;
+    if n < 0 || n as usize > r.remaining() {
+        return Err(Error::Invalid(format!(
+            "Invalid length {n} @ byte {}.",
+            r.pos()
+        )));
+    }
+    Ok(n as usize)
+}
+
+/// `nodes[i]` is an `Array` node, whose children are the size and the element type
+fn read_array(r: &mut Reader, nodes: &[TypeTreeNode], i: usize) -> Result<Value, Error> {
+    let children = children(nodes, i);
+    let element = *children.get(1).ok_or(Error::Invalid(format!(
+        "Array {} has no element type.",
+        nodes[i].name
+    )))?;
+    let n = read_len(r)?;
+
+    if matches!(
+        nodes[element].type_name.as_str(),
+        "UInt8" | "SInt8" | "char"
+    ) && nodes[element].meta_flag & ALIGN_BYTES == 0
+    {
+        return Ok(Value::Bytes(r.bytes(n)?.to_vec()));
+    }
+    let mut values = Vec::with_capacity(n);
+    for _ in 0..n {
+        values.push(read_value(r, nodes, element)?);
+    }
+    Ok(Value::Array(values))
+}
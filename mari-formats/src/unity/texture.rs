@@ -0,0 +1,193 @@
+use super::Error;
+use super::bundle::Bundle;
+use super::serialized::Value;
+use crate::texture::bcn::Bc;
+use crate::{TextureError, TextureRGBA8};
+
+/// Unity `TextureFormat` values.
+mod format {
+    pub const ALPHA8: i64 = 1;
+    pub const ARGB4444: i64 = 2;
+    pub const RGB24: i64 = 3;
+    pub const RGBA32: i64 = 4;
+    pub const ARGB32: i64 = 5;
+    pub const RGB565: i64 = 7;
+    pub const R16: i64 = 9;
+    pub const DXT1: i64 = 10;
+    pub const DXT5: i64 = 12;
+    pub const RGBA4444: i64 = 13;
+    pub const BGRA32: i64 = 14;
+    pub const BC7: i64 = 25;
+    pub const BC4: i64 = 26;
+    pub const BC5: i64 = 27;
+    pub const RG16: i64 = 62;
+    pub const R8: i64 = 63;
+}
+
+/// Name of a Unity `TextureFormat`, decodable or not.
+pub fn texture_format_name(format: i64) -> Option<&'static str> {
+    Some(match format {
+        1 => "Alpha8",
+        2 => "ARGB4444",
+        3 => "RGB24",
+        4 => "RGBA32",
+        5 => "ARGB32",
+        7 => "RGB565",
+        9 => "R16",
+        10 => "DXT1",
+        12 => "DXT5",
+        13 => "RGBA4444",
+        14 => "BGRA32",
+        15 => "RHalf",
+        16 => "RGHalf",
+        17 => "RGBAHalf",
+        18 => "RFloat",
+        19 => "RGFloat",
+        20 => "RGBAFloat",
+        22 => "RGB9e5Float",
+        24 => "BC6H",
+        25 => "BC7",
+        26 => "BC4",
+        27 => "BC5",
+        28 => "DXT1Crunched",
+        29 => "DXT5Crunched",
+        34 => "ETC_RGB4",
+        45 => "ETC2_RGB",
+        46 => "ETC2_RGBA1",
+        47 => "ETC2_RGBA8",
+        48 => "ASTC_4x4",
+        49 => "ASTC_5x5",
+        50 => "ASTC_6x6",
+        51 => "ASTC_8x8",
+        52 => "ASTC_10x10",
+        53 => "ASTC_12x12",
+        62 => "RG16",
+        63 => "R8",
+        _ => return None,
+    })
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, Error> {
+    value
+        .get(name)
+        .ok_or(Error::Invalid(format!("Texture2D without {name}.")))
+}
+
+/// Decode the top mip level of a `Texture2D` object.
+///
+/// Unity stores rows bottom to top; the result is top to bottom like `new_from_png`.
+pub fn decode_texture2d(bundle: &Bundle, texture: &Value) -> Result<TextureRGBA8, Error> {
+    let width = field(texture, "m_Width")?.as_i64().unwrap_or(0);
+    let height = field(texture, "m_Height")?.as_i64().unwrap_or(0);
+    let format = field(texture, "m_TextureFormat")?.as_i64().unwrap_or(0);
+    let width: u16 = width
+        .try_into()
+        .map_err(|_| Error::Unsupported(format!("Texture width {width}")))?;
+    let height: u16 = height
+        .try_into()
+        .map_err(|_| Error::Unsupported(format!("Texture height {height}")))?;
+
+    if width == 0 || height == 0 {
+        return Err(Error::Invalid("Empty texture.".to_string()));
+    }
+
+    let mut data = field(texture, "image data")?.as_bytes().unwrap_or_default();
+    if data.is_empty() {
+        let stream = field(texture, "m_StreamData")?;
+        let offset = stream.get("offset").and_then(Value::as_i64).unwrap_or(0);
+        let size = stream.get("size").and_then(Value::as_i64).unwrap_or(0);
+        let path = stream.get("path").and_then(Value::as_str).unwrap_or("");
+        data = bundle.resource(path, offset as u64, size as u64)?;
+    }
+
+    let name = texture.get("m_Name").and_then(Value::as_str).unwrap_or("");
+    let blocks = |bc: Bc| {
+        bc.decode(data, width as usize, height as usize)
+            .map_err(|e| match e {
+                TextureError::Invalid(what) => Error::Invalid(format!("Texture {name}: {what}.")),
+                e => Error::Invalid(format!("Texture {name}: {e}")),
+            })
+    };
+    let rgba = match format {
+        format::DXT1 => blocks(Bc::Bc1)?,
+        format::DXT5 => blocks(Bc::Bc3)?,
+        format::BC4 => blocks(Bc::Bc4 { signed: false })?,
+        format::BC5 => blocks(Bc::Bc5 { signed: false })?,
+        format::BC7 => blocks(Bc::Bc7)?,
+        _ => {
+            // e.g. the ETC2 and ASTC of mobile builds, or crunched DXT
+            let bpp = bytes_per_pixel(format).ok_or_else(|| {
+                Error::Unsupported(match texture_format_name(format) {
+                    Some(f) => format!("Texture {name} in format {f} ({format})"),
+                    None => format!("Texture {name} in format {format}"),
+                })
+            })?;
+            decode_pixels(format, bpp, data, width as usize * height as usize)?
+        }
+    };
+
+    let row = width as usize * 4;
+    let flipped = rgba.chunks_exact(row).rev().flatten().copied().collect();
+    Ok(TextureRGBA8 {
+        width,
+        data: flipped,
+    })
+}
+
+fn bytes_per_pixel(format: i64) -> Option<usize> {
+    Some(match format {
+        format::ALPHA8 | format::R8 => 1,
+        format::ARGB4444 | format::RGB565 | format::R16 | format::RGBA4444 | format::RG16 => 2,
+        format::RGB24 => 3,
+        format::RGBA32 | format::ARGB32 | format::BGRA32 => 4,
+        _ => return None,
+    })
+}
+
+/// RGBA8 of `pixels` pixels of an uncompressed format
+fn decode_pixels(format: i64, bpp: usize, data: &[u8], pixels: usize) -> Result<Vec<u8>, Error> {
+    let data = data.get(..pixels * bpp).ok_or(Error::Invalid(format!(
+        "Texture data has {} bytes, {} expected.",
+        data.len(),
+        pixels * bpp
+    )))?;
+
+    let mut rgba = Vec::with_capacity(pixels * 4);
+    for p in data.chunks_exact(bpp) {
+        let px = match format {
+            format::ALPHA8 => [255, 255, 255, p[0]],
+            format::R8 => [p[0], 0, 0, 255],
+            format::R16 => [p[1], 0, 0, 255],
+            format::RG16 => [p[0], p[1], 0, 255],
+            format::ARGB4444 => {
+                let v = u16::from_le_bytes([p[0], p[1]]);
+                let c = |shift: u16| ((v >> shift) & 0xf) as u8 * 17;
+                [c(8), c(4), c(0), c(12)]
+            }
+            format::RGBA4444 => {
+                let v = u16::from_le_bytes([p[0], p[1]]);
+                let c = |shift: u16| ((v >> shift) & 0xf) as u8 * 17;
+                [c(12), c(8), c(4), c(0)]
+            }
+            format::RGB565 => {
+                let v = u16::from_le_bytes([p[0], p[1]]);
+                let r = (v >> 11) as u8;
+                let g = ((v >> 5) & 0x3f) as u8;
+                let b = (v & 0x1f) as u8;
+                [
+                    (r << 3) | (r >> 2),
+                    (g << 2) | (g >> 4),
+                    (b << 3) | (b >> 2),
+                    255,
+                ]
+            }
+            format::RGB24 => [p[0], p[1], p[2], 255],
+            format::RGBA32 => [p[0], p[1], p[2], p[3]],
+            format::ARGB32 => [p[1], p[2], p[3], p[0]],
+            _ => [p[2], p[1], p[0], p[3]], // BGRA32
+        };
+        rgba.extend_from_slice(&px);
+    }
+
+    Ok(rgba)
+}
@@ -0,0 +1,503 @@
+use std::io::BufReader;
+
+use mari_formats::{
+    Actor, Bone, Clip, GltfError, Material, Model, Part, PbrMaterial, Scene, Skeleton, Submesh,
+    TextureRGBA8, ToonMaterial, Track,
+};
+
+fn assert_close(a: &[f32], b: &[f32]) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    }
+}
+
+/// a unit square split into two submeshes, the second without a material
+fn square(material: &str) -> Model {
+    Model {
+        vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+        mesh: vec![0, 1, 2, 0, 2, 3],
+        uvs: vec![0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0],
+        normals: [0.0, 0.0, 1.0].repeat(4),
+        colors: Vec::new(),
+        joints: Vec::new(),
+        weights: Vec::new(),
+        submeshes: vec![
+            Submesh {
+                first: 0,
+                count: 3,
+                material: Some(material.to_string()),
+            },
+            Submesh {
+                first: 3,
+                count: 3,
+                material: None,
+            },
+        ],
+    }
+}
+
+fn skinned_scene() -> Scene {
+    let mut body = square("skin");
+    body.joints = vec![0, 0, 0, 0, 1, 0, 0, 0, 1, 2, 0, 0, 2, 0, 0, 0];
+    body.weights = vec![
+        1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0,
+    ];
+    body.colors = [1.0, 1.0, 1.0, 0.5].repeat(4);
+    let mut actor = Actor::new(body);
+    actor.parts.push(Part {
+        name: "hat".to_string(),
+        model: square("hat"),
+    });
+    let half = 0.5f32.sqrt();
+    actor.skeleton = Some(Skeleton {
+        bones: vec![
+            Bone {
+                name: "Hips".to_string(),
+                parent: None,
+                translation: [0.0, 1.0, 0.0],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                scale: [1.0; 3],
+            },
+            Bone {
+                name: "Spine".to_string(),
+                parent: Some(0),
+                translation: [0.0, 0.5, 0.0],
+                rotation: [0.0, half, 0.0, half],
+                scale: [1.0; 3],
+            },
+            Bone {
+                name: "Head".to_string(),
+                parent: Some(1),
+                translation: [0.25, 0.5, 0.0],
+                rotation: [half, 0.0, 0.0, half],
+                scale: [2.0; 3],
+            },
+        ],
+    });
+
+    let mut scene = Scene::default();
+    scene.actors.insert("Temari".to_string(), actor);
+    scene.textures.insert(
+        "Temari".to_string(),
+        TextureRGBA8 {
+            width: 2,
+            data: (0..16).map(|i| i * 16).collect(),
+        },
+    );
+    scene.clips.insert(
+        "nod".to_string(),
+        Clip {
+            times: vec![0.0, 0.5, 1.0],
+            tracks: vec![
+                Track {
+                    bone: "Head".to_string(),
+                    translations: Vec::new(),
+                    rotations: vec![
+                        0.0,
+                        0.0,
+                        0.0,
+                        1.0,
+                        0.5,
+                        0.0,
+                        0.0,
+                        0.75f32.sqrt(),
+                        0.0,
+                        0.0,
+                        0.0,
+                        1.0,
+                    ],
+                    scales: Vec::new(),
+                },
+                Track {
+                    bone: "Hips".to_string(),
+                    translations: vec![0.0, 1.0, 0.0, 0.0, 1.1, 0.0, 0.0, 1.0, 0.0],
+                    rotations: Vec::new(),
+                    scales: Vec::new(),
+                },
+                Track {
+                    bone: "Tail".to_string(),
+                    translations: vec![0.0; 9],
+                    rotations: Vec::new(),
+                    scales: Vec::new(),
+                },
+            ],
+        },
+    );
+    scene
+}
+
+#[test]
+fn obj_round_trip() {
+    let model = square("red");
+    let obj = model.write_obj();
+    assert!(obj.contains("vt 0 0\n"), "V is flipped back");
+    let read = Model::new_from_obj(BufReader::new(obj.as_bytes())).unwrap();
+
+    assert_close(&read.vertices, &model.vertices);
+    assert_close(&read.uvs, &model.uvs);
+    assert_close(&read.normals, &model.normals);
+    assert_eq!(read.mesh, model.mesh);
+    let materials: Vec<_> = read
+        .submeshes
+        .iter()
+        .map(|s| s.material.as_deref())
+        .collect();
+    assert_eq!(materials, [Some("red"), Some("default")]);
+}
+
+#[test]
+fn obj_without_uvs_or_normals() {
+    let mut model = square("red");
+    model.uvs.clear();
+    let obj = model.write_obj();
+    assert!(obj.contains("f 1//1 2//2 3//3\n"));
+    model.normals.clear();
+    let obj = model.write_obj();
+    assert!(obj.contains("f 1 2 3\n"));
+    let read = Model::new_from_obj(BufReader::new(obj.as_bytes())).unwrap();
+    assert_eq!(read.mesh, model.mesh);
+}
+
+#[test]
+fn scene_obj_with_materials() {
+    let files = skinned_scene().write_obj("temari.mtl").unwrap();
+    assert!(files.obj.starts_with("mtllib temari.mtl\n"));
+    assert!(files.obj.contains("o Temari\n") && files.obj.contains("o hat\n"));
+    // the hat's indices continue after the body's four vertices
+    assert!(files.obj.contains("f 5/5/5 6/6/6 7/7/7\n"));
+    for material in ["skin", "Temari", "hat"] {
+        assert!(files.mtl.contains(&format!("newmtl {material}\n")));
+    }
+    assert!(files.mtl.contains("map_Kd Temari.png\n"));
+    assert_eq!(files.textures.len(), 1);
+    assert!(files.textures[0].1.starts_with(b"\x89PNG"));
+
+    let read = Model::new_from_obj(BufReader::new(files.obj.as_bytes())).unwrap();
+    assert_eq!(read.vertices.len(), 24);
+    let materials: Vec<_> = read
+        .submeshes
+        .iter()
+        .map(|s| s.material.as_deref())
+        .collect();
+    assert_eq!(
+        materials,
+        [Some("skin"), Some("Temari"), Some("hat"), Some("Temari")]
+    );
+}
+
+#[test]
+fn gltf_round_trip_with_skin_and_animation() {
+    let scene = skinned_scene();
+    let glb = scene.write_glb().unwrap();
+    let read = Scene::new_from_gltf(&glb, None).unwrap();
+
+    let (actor, original) = (&read.actors["Temari"], &scene.actors["Temari"]);
+    assert_close(&actor.body.vertices, &original.body.vertices);
+    assert_close(&actor.body.normals, &original.body.normals);
+    assert_close(&actor.body.uvs, &original.body.uvs);
+    assert_close(&actor.body.colors, &original.body.colors);
+    assert_eq!(actor.body.mesh, original.body.mesh);
+    assert_eq!(actor.body.joints, original.body.joints);
+    assert_close(&actor.body.weights, &original.body.weights);
+    assert_eq!(actor.parts.len(), 1);
+    assert!(actor.parts[0].model.joints.is_empty());
+    assert!(actor.parts[0].model.colors.is_empty());
+
+    let bones = &actor.skeleton.as_ref().unwrap().bones;
+    let original_bones = &original.skeleton.as_ref().unwrap().bones;
+    assert_eq!(bones.len(), original_bones.len());
+    for (a, b) in bones.iter().zip(original_bones) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.parent, b.parent);
+        assert_close(&a.translation, &b.translation);
+        assert_close(&a.rotation, &b.rotation);
+        assert_close(&a.scale, &b.scale);
+    }
+
+    assert_eq!(read.textures["Temari"].data, scene.textures["Temari"].data);
+    let Material::Pbr(skin) = &read.materials["skin"] else {
+        panic!("not a PBR material");
+    };
+    assert_eq!(skin.metallic, 0.0);
+    let texture = skin.base_color_texture.as_ref().unwrap();
+    assert_eq!(texture.data, scene.textures["Temari"].data);
+
+    let clip = &read.clips["nod"];
+    let original = &scene.clips["nod"];
+    assert_close(&clip.times, &original.times);
+    // the track of a bone no skeleton has is dropped
+    assert_eq!(clip.tracks.len(), 2);
+    for track in &clip.tracks {
+        let o = original
+            .tracks
+            .iter()
+            .find(|t| t.bone == track.bone)
+            .unwrap();
+        assert_close(&track.translations, &o.translations);
+        assert_close(&track.rotations, &o.rotations);
+        assert_close(&track.scales, &o.scales);
+    }
+}
+
+#[test]
+fn gltf_keeps_skins_and_animations_out_when_absent() {
+    let mut scene = skinned_scene();
+    scene.clips.clear();
+    scene.actors.get_mut("Temari").unwrap().skeleton = None;
+    let (json, _) = scene.write_gltf("scene.bin").unwrap();
+    assert!(!json.contains("\"skins\"") && !json.contains("\"animations\""));
+    assert!(!json.contains("JOINTS_0"));
+}
+
+#[test]
+fn gltf_rejects_lengths_past_the_data() {
+    // a GLB whose first chunk claims 4 GiB
+    let mut glb = skinned_scene().write_glb().unwrap();
+    glb[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(
+        Scene::new_from_gltf(&glb, None),
+        Err(GltfError::Invalid(_))
+    ));
+
+    // a buffer view starting at the end of the address space
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" }],
+        "bufferViews": [{ "buffer": 0, "byteOffset": 18446744073709551615, "byteLength": 12 }],
+        "buffers": [{ "byteLength": 12, "uri": "data:application/octet-stream;base64,BIN" }]
+    }"#;
+    let json = json.replace("BIN", &base64(&[0; 12]));
+    assert!(matches!(
+        Scene::new_from_gltf(json.as_bytes(), None),
+        Err(GltfError::Invalid(_))
+    ));
+}
+
+#[test]
+fn gltf_resamples_channels_with_different_keys() {
+    // Head keyed at 0 and 1, Hips at 0.25 only
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [
+            { "name": "actor", "children": [1, 2] },
+            { "name": "Hips", "children": [3] },
+            { "mesh": 0, "skin": 0 },
+            { "name": "Head", "translation": [0, 1, 0] }
+        ],
+        "skins": [{ "joints": [1, 3] }],
+        "meshes": [{ "primitives": [{
+            "attributes": { "POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2 }
+        }] }],
+        "animations": [{
+            "samplers": [
+                { "input": 3, "output": 4 },
+                { "input": 5, "output": 6, "interpolation": "STEP" }
+            ],
+            "channels": [
+                { "sampler": 0, "target": { "node": 3, "path": "translation" } },
+                { "sampler": 1, "target": { "node": 1, "path": "scale" } }
+            ]
+        }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5121, "count": 3, "type": "VEC4" },
+            { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" },
+            { "bufferView": 3, "componentType": 5126, "count": 2, "type": "SCALAR" },
+            { "bufferView": 4, "componentType": 5126, "count": 2, "type": "VEC3" },
+            { "bufferView": 5, "componentType": 5126, "count": 1, "type": "SCALAR" },
+            { "bufferView": 6, "componentType": 5126, "count": 1, "type": "VEC3" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 48, "byteLength": 48 },
+            { "buffer": 0, "byteOffset": 96, "byteLength": 8 },
+            { "buffer": 0, "byteOffset": 104, "byteLength": 24 },
+            { "buffer": 0, "byteOffset": 128, "byteLength": 4 },
+            { "buffer": 0, "byteOffset": 132, "byteLength": 12 }
+        ],
+        "buffers": [{ "byteLength": 144, "uri": "data:application/octet-stream;base64,BIN" }]
+    }"#;
+    let mut bin = Vec::new();
+    let mut floats = |v: &[f32]| bin.extend(v.iter().flat_map(|f| f.to_le_bytes()));
+    floats(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    bin.extend([0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+    let mut floats = |v: &[f32]| bin.extend(v.iter().flat_map(|f| f.to_le_bytes()));
+    floats(&[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+    floats(&[0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 3.0, 0.0]);
+    floats(&[0.25, 2.0, 2.0, 2.0]);
+    let json = json.replace("BIN", &base64(&bin));
+
+    let scene = Scene::new_from_gltf(json.as_bytes(), None).unwrap();
+    let actor = &scene.actors["actor"];
+    let bones = &actor.skeleton.as_ref().unwrap().bones;
+    assert_eq!(bones.len(), 2);
+    assert_eq!((bones[1].name.as_str(), bones[1].parent), ("Head", Some(0)));
+    // the third vertex follows the Head joint, one up
+    assert_close(&actor.body.vertices[6..9], &[0.0, 2.0, 0.0]);
+    assert_eq!(actor.body.joints[8], 1);
+
+    let clip = &scene.clips["animation0"];
+    assert_eq!(clip.times, [0.0, 0.25, 1.0]);
+    let head = clip.tracks.iter().find(|t| t.bone == "Head").unwrap();
+    assert_close(
+        &head.translations,
+        &[0.0, 1.0, 0.0, 0.0, 1.5, 0.0, 0.0, 3.0, 0.0],
+    );
+    let hips = clip.tracks.iter().find(|t| t.bone == "Hips").unwrap();
+    assert_close(&hips.scales, &[2.0; 9]);
+    assert!(hips.translations.is_empty() && hips.rotations.is_empty());
+}
+
+#[test]
+fn gltf_materials() {
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "materials": [
+            {
+                "name": "metal",
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [1, 0.5, 0.25, 1],
+                    "roughnessFactor": 0.5
+                }
+            },
+            {
+                "name": "flat",
+                "pbrMetallicRoughness": { "baseColorFactor": [0, 1, 0, 1] },
+                "extensions": { "KHR_materials_unlit": {} }
+            },
+            { "pbrMetallicRoughness": {} }
+        ]
+    }"#;
+    let scene = Scene::new_from_gltf(json.as_bytes(), None).unwrap();
+    // unnamed materials can't be referred to
+    assert_eq!(scene.materials.len(), 2);
+    let Material::Pbr(metal) = &scene.materials["metal"] else {
+        panic!("not a PBR material");
+    };
+    assert_eq!(metal.base_color, [1.0, 0.5, 0.25, 1.0]);
+    assert_eq!((metal.metallic, metal.roughness), (1.0, 0.5));
+    assert!(metal.base_color_texture.is_none());
+    assert!(matches!(
+        scene.materials["flat"],
+        Material::Unlit {
+            color: [0.0, 1.0, 0.0, 1.0]
+        }
+    ));
+}
+
+fn texture(seed: u8) -> TextureRGBA8 {
+    TextureRGBA8 {
+        width: 2,
+        data: (0..16).map(|i| seed.wrapping_add(i * 15)).collect(),
+    }
+}
+
+fn assert_material_eq(a: &Material, b: &Material) {
+    match (a, b) {
+        (Material::Unlit { color: a }, Material::Unlit { color: b }) => assert_eq!(a, b),
+        (Material::Textured { texture: a }, Material::Textured { texture: b }) => {
+            assert_eq!(a.data, b.data);
+        }
+        (Material::Toon(a), Material::Toon(b)) => {
+            assert_eq!(a.texture.data, b.texture.data);
+            assert_eq!(a.ramp_texture.data, b.ramp_texture.data);
+            assert_eq!(a.sdw_texture.data, b.sdw_texture.data);
+            assert_eq!(a.shadow_threshold, b.shadow_threshold);
+            assert_eq!(a.ramp_scale, b.ramp_scale);
+            assert_eq!(a.outline_width, b.outline_width);
+            assert_eq!(a.outline_color, b.outline_color);
+        }
+        (Material::Pbr(a), Material::Pbr(b)) => {
+            assert_eq!(a.base_color, b.base_color);
+            assert_eq!(
+                a.base_color_texture.as_ref().map(|t| &t.data),
+                b.base_color_texture.as_ref().map(|t| &t.data)
+            );
+            assert_eq!((a.metallic, a.roughness), (b.metallic, b.roughness));
+        }
+        _ => panic!("different kinds of material"),
+    }
+}
+
+#[test]
+fn gltf_round_trip_of_materials() {
+    let mut scene = Scene::default();
+    scene.actors.insert(
+        "square".to_string(),
+        Actor {
+            body: square("toon"),
+            parts: Vec::new(),
+            skeleton: None,
+        },
+    );
+    scene.textures.insert("square".to_string(), texture(1));
+    scene.materials.insert(
+        "toon".to_string(),
+        Material::Toon(ToonMaterial {
+            texture: texture(2),
+            ramp_texture: texture(3),
+            sdw_texture: texture(4),
+            shadow_threshold: 0.25,
+            ramp_scale: 0.5,
+            outline_width: 1.5,
+            outline_color: [0.1, 0.2, 0.3, 1.0],
+        }),
+    );
+    scene.materials.insert(
+        "flat".to_string(),
+        Material::Unlit {
+            color: [0.0, 1.0, 0.0, 0.5],
+        },
+    );
+    scene.materials.insert(
+        "decal".to_string(),
+        Material::Textured {
+            texture: texture(5),
+        },
+    );
+    scene.materials.insert(
+        "metal".to_string(),
+        Material::Pbr(PbrMaterial {
+            base_color: [1.0, 0.5, 0.25, 1.0],
+            base_color_texture: Some(texture(6)),
+            metallic: 0.75,
+            roughness: 0.125,
+        }),
+    );
+
+    let glb = scene.write_glb().unwrap();
+    let read = Scene::new_from_gltf(&glb, None).unwrap();
+    // the submesh without a material gets one named after its actor
+    let mut names: Vec<&String> = read.materials.keys().collect();
+    names.sort();
+    assert_eq!(names, ["decal", "flat", "metal", "square", "toon"]);
+    for (name, material) in &scene.materials {
+        assert_material_eq(&read.materials[name], material);
+    }
+    // the body now takes the base color of its material
+    assert_eq!(read.textures["square"].data, texture(2).data);
+
+    let (json, _) = scene.write_gltf("scene.bin").unwrap();
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["extensionsUsed"][0], "KHR_materials_unlit");
+}
+
+fn base64(data: &[u8]) -> String {
+    const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut ret = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            ret.push(DIGITS[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    ret
+}
@@ -0,0 +1,219 @@
+use aes::cipher::{BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+use mari_formats::gakumas::{
+    DeobfuscateError, Deobfuscator, Manifest, ManifestError, deobfuscate_bundle,
+};
+use md5::{Digest, Md5};
+use std::io::BufReader;
+
+const NAME: &str = "mdl_chr_ttmr-casl-0000_body";
+
+/// a bundle header followed by a body long enough to cross the obfuscated region
+fn synthetic_bundle() -> Vec<u8> {
+    let mut data = b"UnityFS\0\0\0\0\x085.x.x\x002022.3.21f1\0".to_vec();
+    data.extend((0..600).map(|i| (i * 7 % 251) as u8));
+    data
+}
+
+#[test]
+fn known_obfuscated_header() {
+    // produced by a Python port of the GkmasObjectManager deobfuscator
+    let obfuscated: Vec<u8> = vec![
+        147, 67, 166, 68, 190, 125, 167, 54, 200, 11, 195, 108, 236, 74, 140, 74, 167, 100, 237,
+        73, 244, 10, 247, 20, 168, 7, 249, 81, 251, 121,
+    ];
+    let plain = deobfuscate_bundle(NAME, obfuscated).unwrap();
+    assert_eq!(plain, b"UnityFS\0\0\0\0\x085.x.x\x002022.3.21f1\0");
+}
+
+#[test]
+fn round_trip_only_touches_header() {
+    let plain = synthetic_bundle();
+    let mut obfuscated = plain.clone();
+    Deobfuscator::new(NAME).apply(&mut obfuscated, 0);
+
+    assert!(!obfuscated.starts_with(b"UnityFS"));
+    assert_ne!(obfuscated[..256], plain[..256]);
+    assert_eq!(obfuscated[256..], plain[256..]);
+    assert_eq!(deobfuscate_bundle(NAME, obfuscated).unwrap(), plain);
+}
+
+#[test]
+fn chunked_matches_whole() {
+    let deobfuscator = Deobfuscator::new(NAME);
+    let mut whole = synthetic_bundle();
+    deobfuscator.apply(&mut whole, 0);
+
+    let mut chunked = synthetic_bundle();
+    for (i, chunk) in chunked.chunks_mut(100).enumerate() {
+        deobfuscator.apply(chunk, i * 100);
+    }
+    assert_eq!(chunked, whole);
+}
+
+#[test]
+fn plain_bundle_passes_through() {
+    let plain = synthetic_bundle();
+    assert_eq!(deobfuscate_bundle(NAME, plain.clone()).unwrap(), plain);
+}
+
+#[test]
+fn wrong_name_is_rejected() {
+    let mut obfuscated = synthetic_bundle();
+    Deobfuscator::new(NAME).apply(&mut obfuscated, 0);
+    assert!(matches!(
+        deobfuscate_bundle("mdl_chr_hski-casl-0000_body", obfuscated),
+        Err(DeobfuscateError::NotUnityFS)
+    ));
+}
+
+fn varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn varint_field(out: &mut Vec<u8>, field: u64, v: u64) {
+    varint(out, field << 3);
+    varint(out, v);
+}
+
+fn bytes_field(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, field << 3 | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// a `Database` with one asset bundle, one resource and fields the parser doesn't know
+fn synthetic_database() -> Vec<u8> {
+    let mut bundle = Vec::new();
+    varint_field(&mut bundle, 1, 7);
+    bytes_field(&mut bundle, 3, b"mdl_chr_ttmr-casl-0000_body");
+    varint_field(&mut bundle, 4, 300_000);
+    varint_field(&mut bundle, 5, 0xdead_beef);
+    // packed dependencies
+    bytes_field(&mut bundle, 8, &[1, 0xac, 0x02]);
+    bytes_field(&mut bundle, 10, b"0123456789abcdef0123456789abcdef");
+    bytes_field(&mut bundle, 11, b"a1b2c3");
+    varint_field(&mut bundle, 12, 1_700_000_000);
+    // fixed64 and fixed32 fields are skipped
+    bundle.push(13 << 3 | 1);
+    bundle.extend_from_slice(&[0xff; 8]);
+    bundle.push(14 << 3 | 5);
+    bundle.extend_from_slice(&[0xff; 4]);
+
+    let mut resource = Vec::new();
+    varint_field(&mut resource, 1, 9);
+    bytes_field(&mut resource, 3, b"sud_vo_ttmr_001");
+    // unpacked dependencies
+    varint_field(&mut resource, 8, 3);
+    varint_field(&mut resource, 8, 4);
+
+    let mut database = Vec::new();
+    varint_field(&mut database, 1, 42);
+    bytes_field(&mut database, 2, &bundle);
+    bytes_field(&mut database, 3, b"unknown");
+    bytes_field(&mut database, 4, &resource);
+    bytes_field(&mut database, 5, b"https://object.example/{o}");
+    database
+}
+
+/// what the game does: prefix the MD5 of the payload, then AES-128-CBC with the MD5 keys
+fn encrypt(payload: &[u8]) -> Vec<u8> {
+    let key: [u8; 16] = Md5::digest(b"1nuv9td1bw1udefk").into();
+    let iv: [u8; 16] = Md5::digest(b"LvAUtf+tnz").into();
+    let mut plain = Md5::digest(payload).to_vec();
+    plain.extend_from_slice(payload);
+    cbc::Encryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(&plain)
+}
+
+#[test]
+fn known_encrypted_manifest() {
+    // a `Database` with only `revision: 42`, encrypted by `openssl enc -aes-128-cbc`
+    let encrypted: Vec<u8> = vec![
+        134, 30, 7, 33, 97, 3, 210, 46, 21, 207, 230, 205, 244, 130, 6, 64, 248, 125, 72, 58, 199,
+        83, 11, 113, 72, 74, 39, 144, 220, 227, 158, 150,
+    ];
+    assert_eq!(Manifest::decrypt(&encrypted).unwrap(), [0x08, 0x2a]);
+    let manifest = Manifest::new_from_octocache(BufReader::new(&encrypted[..])).unwrap();
+    assert_eq!(manifest.revision, 42);
+    assert!(manifest.entries().next().is_none());
+}
+
+#[test]
+fn encrypted_database_round_trip() {
+    let database = synthetic_database();
+    let encrypted = encrypt(&database);
+    assert_eq!(Manifest::decrypt(&encrypted).unwrap(), database);
+
+    let manifest = Manifest::new_from_octocache(BufReader::new(&encrypted[..])).unwrap();
+    assert_eq!(manifest.revision, 42);
+    assert_eq!(manifest.url_format, "https://object.example/{o}");
+    assert_eq!(manifest.asset_bundles.len(), 1);
+    assert_eq!(manifest.resources.len(), 1);
+
+    let bundle = &manifest.asset_bundles[0];
+    assert_eq!(bundle.id, 7);
+    assert_eq!(bundle.name, "mdl_chr_ttmr-casl-0000_body");
+    assert_eq!(bundle.size, 300_000);
+    assert_eq!(bundle.crc, 0xdead_beef);
+    assert_eq!(bundle.dependencies, [1, 300]);
+    assert_eq!(bundle.md5, "0123456789abcdef0123456789abcdef");
+    assert_eq!(bundle.object_name, "a1b2c3");
+    assert_eq!(bundle.generation, 1_700_000_000);
+
+    let resource = &manifest.resources[0];
+    assert_eq!(resource.id, 9);
+    assert_eq!(resource.name, "sud_vo_ttmr_001");
+    assert_eq!(resource.dependencies, [3, 4]);
+}
+
+#[test]
+fn corrupt_manifests_are_rejected() {
+    let database = synthetic_database();
+    let mut encrypted = encrypt(&database);
+    assert!(matches!(
+        Manifest::decrypt(&encrypted[..encrypted.len() - 1]),
+        Err(ManifestError::Decrypt)
+    ));
+    // flipping a bit of the first block garbles the hash, yet the padding still checks out
+    encrypted[0] ^= 1;
+    assert!(matches!(
+        Manifest::decrypt(&encrypted),
+        Err(ManifestError::Checksum)
+    ));
+
+    for truncated in [
+        &database[..database.len() - 1],
+        // a varint that never ends
+        &[0x08, 0x80][..],
+        // wire type 3, a group
+        &[0x0b][..],
+    ] {
+        assert!(matches!(
+            Manifest::new_from_protobuf(truncated),
+            Err(ManifestError::Protobuf(_))
+        ));
+    }
+}
+
+#[test]
+fn search_matches_names_anywhere() {
+    let manifest = Manifest::new_from_protobuf(&synthetic_database()).unwrap();
+    let names = |pattern| -> Vec<_> {
+        let found = manifest.search(pattern).unwrap();
+        found.iter().map(|e| e.name.as_str()).collect()
+    };
+    assert_eq!(
+        names("ttmr"),
+        ["mdl_chr_ttmr-casl-0000_body", "sud_vo_ttmr_001"]
+    );
+    assert_eq!(names("mdl.*casl"), ["mdl_chr_ttmr-casl-0000_body"]);
+    assert_eq!(names("^casl"), Vec::<&str>::new());
+    assert!(matches!(manifest.search("("), Err(ManifestError::Regex(_))));
+
+    assert_eq!(manifest.get("sud_vo_ttmr_001").unwrap().id, 9);
+    assert!(manifest.get("ttmr").is_none());
+}
@@ -0,0 +1,273 @@
+use mari_formats::{
+    Avatar, Axes, Bone, HUMAN_BONE_COUNT, HumanBone, HumanBoneBinding, HumanPose, HumanoidError,
+    MUSCLE_COUNT, Skeleton, muscle_index,
+};
+
+/// in `HumanBodyBones` order, so that bone `i` is bound to node `i`
+const BONES: [HumanBone; HUMAN_BONE_COUNT] = {
+    use HumanBone::*;
+    [
+        Hips,
+        LeftUpperLeg,
+        RightUpperLeg,
+        LeftLowerLeg,
+        RightLowerLeg,
+        LeftFoot,
+        RightFoot,
+        Spine,
+        Chest,
+        Neck,
+        Head,
+        LeftShoulder,
+        RightShoulder,
+        LeftUpperArm,
+        RightUpperArm,
+        LeftLowerArm,
+        RightLowerArm,
+        LeftHand,
+        RightHand,
+        LeftToes,
+        RightToes,
+        LeftEye,
+        RightEye,
+        Jaw,
+        LeftThumbProximal,
+        LeftThumbIntermediate,
+        LeftThumbDistal,
+        LeftIndexProximal,
+        LeftIndexIntermediate,
+        LeftIndexDistal,
+        LeftMiddleProximal,
+        LeftMiddleIntermediate,
+        LeftMiddleDistal,
+        LeftRingProximal,
+        LeftRingIntermediate,
+        LeftRingDistal,
+        LeftLittleProximal,
+        LeftLittleIntermediate,
+        LeftLittleDistal,
+        RightThumbProximal,
+        RightThumbIntermediate,
+        RightThumbDistal,
+        RightIndexProximal,
+        RightIndexIntermediate,
+        RightIndexDistal,
+        RightMiddleProximal,
+        RightMiddleIntermediate,
+        RightMiddleDistal,
+        RightRingProximal,
+        RightRingIntermediate,
+        RightRingDistal,
+        RightLittleProximal,
+        RightLittleIntermediate,
+        RightLittleDistal,
+        UpperChest,
+    ]
+};
+
+/// different on every axis, so an angle tells which axis reached it
+const LIMIT_MIN: [f32; 3] = [-0.2, -0.3, -0.4];
+const LIMIT_MAX: [f32; 3] = [0.5, 0.6, 0.7];
+
+const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+fn axis_angle(axis: usize, angle: f32) -> [f32; 4] {
+    let mut q = [0.0, 0.0, 0.0, (angle / 2.0).cos()];
+    q[axis] = (angle / 2.0).sin();
+    q
+}
+
+fn quat_mul(a: &[f32; 4], b: &[f32; 4]) -> [f32; 4] {
+    [
+        a[3] * b[0] + a[0] * b[3] + a[1] * b[2] - a[2] * b[1],
+        a[3] * b[1] - a[0] * b[2] + a[1] * b[3] + a[2] * b[0],
+        a[3] * b[2] + a[0] * b[1] - a[1] * b[0] + a[2] * b[3],
+        a[3] * b[3] - a[0] * b[0] - a[1] * b[1] - a[2] * b[2],
+    ]
+}
+
+/// equal as rotations, `q` and `-q` being the same
+fn assert_rotation_eq(a: &[f32; 4], b: &[f32; 4]) {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    assert!(dot.abs() > 1.0 - 1e-5, "{a:?} != {b:?}");
+}
+
+fn assert_close(a: &[f32], b: &[f32]) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    }
+}
+
+fn skeleton() -> Skeleton {
+    Skeleton {
+        bones: BONES
+            .iter()
+            .enumerate()
+            .map(|(i, bone)| Bone {
+                name: format!("{bone:?}"),
+                parent: i.checked_sub(1).map(|_| 0),
+                translation: [0.0, 0.1, 0.0],
+                rotation: IDENTITY,
+                scale: [1.0; 3],
+            })
+            .collect(),
+    }
+}
+
+/// every human bone bound to the node of its index, with `pre_q` for all of them
+fn avatar(pre_q: [f32; 4]) -> Avatar {
+    Avatar {
+        human_scale: 1.0,
+        root_offset: [0.0; 3],
+        root_rotation: IDENTITY,
+        bones: BONES
+            .iter()
+            .enumerate()
+            .map(|(node, bone)| HumanBoneBinding {
+                bone: *bone,
+                node,
+                axes: Axes {
+                    pre_q,
+                    post_q: IDENTITY,
+                    sgn: [1.0; 3],
+                    limit_min: LIMIT_MIN,
+                    limit_max: LIMIT_MAX,
+                },
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn zero_muscles_give_the_pre_rotations() {
+    let pre_q = axis_angle(1, 0.8);
+    let pose = avatar(pre_q)
+        .pose(&skeleton(), &HumanPose::default())
+        .unwrap();
+    for rotation in &pose.rotations[1..] {
+        assert_rotation_eq(rotation, &pre_q);
+    }
+    // the post rotation is undone on the way out of the muscle frame
+    let axes = Axes {
+        pre_q,
+        post_q: axis_angle(2, 0.3),
+        sgn: [1.0; 3],
+        limit_min: LIMIT_MIN,
+        limit_max: LIMIT_MAX,
+    };
+    assert_rotation_eq(
+        &axes.rotation(&[0.0; 3]),
+        &quat_mul(&pre_q, &axis_angle(2, -0.3)),
+    );
+}
+
+#[test]
+fn every_muscle_reaches_its_limits_around_one_axis() {
+    let (avatar, skeleton) = (avatar(IDENTITY), skeleton());
+    for muscle in 0..MUSCLE_COUNT {
+        for (value, limits) in [(1.0, LIMIT_MAX), (-1.0, LIMIT_MIN)] {
+            let mut pose = HumanPose::default();
+            pose.muscles[muscle] = value;
+            let rotations = avatar.pose(&skeleton, &pose).unwrap().rotations;
+            let moved: Vec<_> = rotations.iter().filter(|q| q[3] < 1.0 - 1e-6).collect();
+            assert_eq!(moved.len(), 1, "muscle #{muscle} moves one bone");
+            let q = moved[0];
+            let axis = (0..3).max_by(|a, b| q[*a].abs().total_cmp(&q[*b].abs()));
+            let axis = axis.unwrap();
+            assert_rotation_eq(q, &axis_angle(axis, limits[axis]));
+        }
+    }
+}
+
+#[test]
+fn muscles_drive_their_bone_and_axis() {
+    let (avatar, skeleton) = (avatar(IDENTITY), skeleton());
+    for (name, bone, axis) in [
+        ("Spine Front-Back", HumanBone::Spine, 2),
+        ("Spine Left-Right", HumanBone::Spine, 1),
+        ("Head Turn Left-Right", HumanBone::Head, 0),
+        ("Jaw Close", HumanBone::Jaw, 2),
+        ("Left Arm Front-Back", HumanBone::LeftUpperArm, 1),
+        ("Left Arm Twist In-Out", HumanBone::LeftUpperArm, 0),
+        ("Right Forearm Stretch", HumanBone::RightLowerArm, 2),
+        ("Right Foot Twist In-Out", HumanBone::RightFoot, 1),
+        ("RightHand.Index.Spread", HumanBone::RightIndexProximal, 1),
+        ("UpperChest Twist Left-Right", HumanBone::UpperChest, 0),
+    ] {
+        let mut pose = HumanPose::default();
+        assert!(pose.set_curve(name, -1.0));
+        let rotations = avatar.pose(&skeleton, &pose).unwrap().rotations;
+        let node = BONES.iter().position(|b| *b == bone).unwrap();
+        assert_rotation_eq(&rotations[node], &axis_angle(axis, LIMIT_MIN[axis]));
+    }
+    assert_eq!(muscle_index("Spine Front-Back"), Some(0));
+    assert_eq!(muscle_index("Tail Wag"), None);
+}
+
+#[test]
+fn axis_signs_flip_the_angle() {
+    let axes = Axes {
+        pre_q: IDENTITY,
+        post_q: IDENTITY,
+        sgn: [1.0, -1.0, 1.0],
+        limit_min: LIMIT_MIN,
+        limit_max: LIMIT_MAX,
+    };
+    assert_rotation_eq(
+        &axes.rotation(&[0.0, 1.0, 0.0]),
+        &axis_angle(1, -LIMIT_MAX[1]),
+    );
+    // half way is half the angle
+    assert_rotation_eq(
+        &axes.rotation(&[0.0, 0.0, 0.5]),
+        &axis_angle(2, LIMIT_MAX[2] / 2.0),
+    );
+}
+
+#[test]
+fn root_motion_is_scaled_by_the_human_scale() {
+    let mut avatar = avatar(IDENTITY);
+    avatar.human_scale = 0.5;
+    avatar.root_offset = [0.1, 0.0, 0.0];
+    avatar.root_rotation = axis_angle(0, 0.4);
+    let mut pose = HumanPose::default();
+    for (attribute, value) in [("RootT.x", 1.0), ("RootT.y", 2.0), ("RootT.z", 3.0)] {
+        assert!(pose.set_curve(attribute, value));
+    }
+    // a quarter turn around y, as clips store it, not normalized
+    let half = 0.5f32.sqrt();
+    for (attribute, value) in [("RootQ.y", 2.0 * half), ("RootQ.w", 2.0 * half)] {
+        assert!(pose.set_curve(attribute, value));
+    }
+
+    let rig = avatar.pose(&skeleton(), &pose).unwrap();
+    // the offset turns with the body, x going to -z
+    assert_close(&rig.translations[0], &[0.5, 1.0, 1.5 - 0.1]);
+    assert_rotation_eq(
+        &rig.rotations[0],
+        &quat_mul(
+            &axis_angle(1, std::f32::consts::FRAC_PI_2),
+            &axis_angle(0, 0.4),
+        ),
+    );
+    // bones below keep their rest translation
+    assert_close(&rig.translations[1], &[0.0, 0.1, 0.0]);
+}
+
+#[test]
+fn bad_bindings_are_rejected() {
+    let skeleton = skeleton();
+    let mut avatar = avatar(IDENTITY);
+    avatar.bones[5].node = skeleton.bones.len();
+    assert!(matches!(
+        avatar.pose(&skeleton, &HumanPose::default()),
+        Err(HumanoidError::NodeOutOfRange(_))
+    ));
+    avatar.bones.remove(5);
+    avatar.bones.remove(0);
+    assert!(matches!(
+        avatar.pose(&skeleton, &HumanPose::default()),
+        Err(HumanoidError::MissingHips)
+    ));
+}
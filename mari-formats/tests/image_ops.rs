@@ -0,0 +1,263 @@
+use mari_formats::{Channel, PixelFormat, ResizeFilter, TextureRGBA8};
+
+/// 4x2, every pixel opaque with r = x and g = y
+fn grid() -> TextureRGBA8 {
+    TextureRGBA8 {
+        width: 4,
+        data: (0..2)
+            .flat_map(|y| (0..4).flat_map(move |x| [x * 10, y * 10, 0, 255]))
+            .collect(),
+    }
+}
+
+#[test]
+fn box_halving_averages_2x2_blocks() {
+    let texture = TextureRGBA8 {
+        width: 4,
+        data: (0..4 * 4 * 4).map(|i| (i * 7 % 256) as u8 | 1).collect(),
+    };
+    let mut opaque = texture.swizzle([Channel::R, Channel::G, Channel::B, Channel::One]);
+    let resized = opaque.resize(2, 2, ResizeFilter::Box);
+    for (i, p) in resized.data.chunks_exact(4).enumerate() {
+        let (x, y) = (i % 2 * 2, i / 2 * 2);
+        for c in 0..3 {
+            let at = |x: usize, y: usize| opaque.data[(y * 4 + x) * 4 + c] as u32;
+            let sum = at(x, y) + at(x + 1, y) + at(x, y + 1) + at(x + 1, y + 1);
+            assert!(p[c].abs_diff(((sum + 2) / 4) as u8) <= 1, "{p:?} at {i}");
+        }
+    }
+    // a constant image stays constant under every filter
+    opaque
+        .data
+        .chunks_exact_mut(4)
+        .for_each(|p| p.copy_from_slice(&[90, 40, 200, 255]));
+    for filter in [
+        ResizeFilter::Box,
+        ResizeFilter::Bilinear,
+        ResizeFilter::Lanczos3,
+    ] {
+        for (w, h) in [(1, 1), (3, 5), (9, 7)] {
+            let resized = opaque.resize(w, h, filter);
+            assert_eq!(resized.width, w);
+            assert_eq!(resized.height(), h);
+            assert!(
+                resized
+                    .data
+                    .chunks_exact(4)
+                    .all(|p| p == [90, 40, 200, 255])
+            );
+        }
+    }
+}
+
+#[test]
+fn resize_upscale() {
+    let texture = TextureRGBA8 {
+        width: 2,
+        data: vec![0, 0, 0, 255, 200, 200, 200, 255],
+    };
+    // nearest neighbour
+    let boxed = texture.resize(4, 1, ResizeFilter::Box);
+    let reds: Vec<u8> = boxed.data.chunks_exact(4).map(|p| p[0]).collect();
+    assert_eq!(reds, [0, 0, 200, 200]);
+    // edges clamp, the middle interpolates
+    let bilinear = texture.resize(4, 1, ResizeFilter::Bilinear);
+    let reds: Vec<u8> = bilinear.data.chunks_exact(4).map(|p| p[0]).collect();
+    assert_eq!(reds, [0, 50, 150, 200]);
+    // monotonic, if with overshoot clamped away
+    let lanczos = texture.resize(8, 1, ResizeFilter::Lanczos3);
+    let reds: Vec<u8> = lanczos.data.chunks_exact(4).map(|p| p[0]).collect();
+    assert!(reds.windows(2).all(|w| w[0] <= w[1]), "{reds:?}");
+}
+
+#[test]
+fn resize_ignores_transparent_colors() {
+    let texture = TextureRGBA8 {
+        width: 2,
+        data: vec![255, 0, 0, 255, 0, 255, 0, 0],
+    };
+    let resized = texture.resize(1, 1, ResizeFilter::Box);
+    assert_eq!(resized.data, [255, 0, 0, 128]);
+}
+
+#[test]
+fn resize_of_nothing_is_blank() {
+    let empty = TextureRGBA8 {
+        width: 0,
+        data: Vec::new(),
+    };
+    let resized = empty.resize(2, 3, ResizeFilter::Bilinear);
+    assert_eq!((resized.width, resized.height()), (2, 3));
+    assert!(resized.data.iter().all(|c| *c == 0));
+    assert!(grid().resize(0, 2, ResizeFilter::Box).data.is_empty());
+}
+
+#[test]
+fn flips_and_crop() {
+    let texture = grid();
+    let flipped = texture.flip_vertical();
+    assert_eq!(&flipped.data[..4], [0, 10, 0, 255]);
+    assert_eq!(flipped.flip_vertical().data, texture.data);
+    let mirrored = texture.flip_horizontal();
+    assert_eq!(&mirrored.data[..4], [30, 0, 0, 255]);
+    assert_eq!(mirrored.flip_horizontal().data, texture.data);
+
+    let cropped = texture.crop(1, 1, 2, 1).unwrap();
+    assert_eq!(cropped.width, 2);
+    assert_eq!(cropped.data, [10, 10, 0, 255, 20, 10, 0, 255]);
+    assert!(texture.crop(3, 0, 2, 1).is_none());
+    assert!(texture.crop(0, 1, 1, 2).is_none());
+}
+
+#[test]
+fn premultiplied_alpha_round_trip() {
+    let mut texture = TextureRGBA8 {
+        width: 3,
+        data: vec![200, 100, 50, 128, 10, 20, 30, 0, 1, 2, 3, 255],
+    };
+    texture.premultiply_alpha();
+    assert_eq!(texture.data, [100, 50, 25, 128, 0, 0, 0, 0, 1, 2, 3, 255]);
+    texture.unpremultiply_alpha();
+    assert_eq!(texture.data, [199, 100, 50, 128, 0, 0, 0, 0, 1, 2, 3, 255]);
+}
+
+#[test]
+fn swizzle_and_extract() {
+    let texture = TextureRGBA8 {
+        width: 1,
+        data: vec![1, 2, 3, 4],
+    };
+    let swizzled = texture.swizzle([Channel::B, Channel::A, Channel::Zero, Channel::R]);
+    assert_eq!(swizzled.data, [3, 4, 0, 1]);
+    let mask = texture.extract_channel(Channel::A);
+    assert_eq!(mask.format, PixelFormat::R8);
+    assert_eq!((mask.width, mask.height), (1, 1));
+    assert_eq!(mask.data, [4]);
+}
+
+#[test]
+fn srgb_conversion() {
+    let mut texture = TextureRGBA8 {
+        width: 2,
+        data: vec![0, 188, 255, 188, 128, 128, 128, 7],
+    };
+    texture.srgb_to_linear();
+    assert_eq!(texture.data, [0, 128, 255, 188, 55, 55, 55, 7]);
+    texture.linear_to_srgb();
+    assert_eq!(&texture.data[..4], [0, 188, 255, 188]);
+    assert!(texture.data[4].abs_diff(128) <= 2);
+}
+
+#[test]
+fn alpha_bleeding() {
+    // an opaque red pixel at the left of a transparent 4x1 strip
+    let mut texture = TextureRGBA8 {
+        width: 4,
+        data: [[255, 0, 0, 255], [0, 0, 0, 0], [0, 0, 0, 0], [9, 9, 9, 0]].concat(),
+    };
+    texture.bleed_alpha(2);
+    assert_eq!(
+        texture.data,
+        [
+            [255, 0, 0, 255],
+            [255, 0, 0, 0],
+            [255, 0, 0, 0],
+            [9, 9, 9, 0]
+        ]
+        .concat()
+    );
+    texture.bleed_alpha(u32::MAX);
+    assert_eq!(&texture.data[12..], [255, 0, 0, 0]);
+
+    // neighbours average
+    let mut texture = TextureRGBA8 {
+        width: 3,
+        data: [[200, 0, 0, 255], [0, 0, 0, 0], [0, 100, 0, 255]].concat(),
+    };
+    texture.bleed_alpha(1);
+    assert_eq!(&texture.data[4..8], [100, 50, 0, 0]);
+}
+
+fn alphas(texture: &TextureRGBA8) -> Vec<u8> {
+    texture.data.chunks_exact(4).map(|p| p[3]).collect()
+}
+
+#[test]
+fn mip_chain_sizes() {
+    let texture = TextureRGBA8 {
+        width: 5,
+        data: vec![255; 5 * 3 * 4],
+    };
+    let sizes: Vec<(u16, u16)> = texture
+        .mip_chain(None)
+        .iter()
+        .map(|level| (level.width, level.height()))
+        .collect();
+    assert_eq!(sizes, [(5, 3), (2, 1), (1, 1)]);
+    // odd sizes still average every pixel, so a constant stays constant
+    assert!(texture.mip_chain(None)[1].data.iter().all(|v| *v == 255));
+}
+
+#[test]
+fn mips_are_gamma_correct() {
+    // black and white average to linear 0.5, which is 188 in sRGB
+    let texture = TextureRGBA8 {
+        width: 2,
+        data: vec![0, 0, 0, 255, 255, 255, 255, 255],
+    };
+    let chain = texture.mip_chain(None);
+    assert_eq!(chain[1].data, [188, 188, 188, 255]);
+
+    // transparent colors don't leak in
+    let texture = TextureRGBA8 {
+        width: 2,
+        data: vec![255, 0, 0, 255, 0, 255, 0, 0],
+    };
+    assert_eq!(texture.mip_chain(None)[1].data, [255, 0, 0, 128]);
+}
+
+#[test]
+fn mips_preserve_alpha_coverage() {
+    // half of the pixels pass an alpha test at 0.6, none of the averaged ones would
+    let texture = TextureRGBA8 {
+        width: 8,
+        data: [255, 0, 255, 0, 255, 0, 255, 0]
+            .iter()
+            .flat_map(|a| [255, 255, 255, *a])
+            .collect(),
+    };
+    let plain = texture.mip_chain(None);
+    assert!(alphas(&plain[1]).iter().all(|a| *a == 128));
+    let preserved = texture.mip_chain(Some(0.6));
+    assert_eq!(preserved.len(), 4);
+    for level in &preserved[1..] {
+        assert!(
+            alphas(level).iter().all(|a| *a as f32 / 255.0 > 0.6),
+            "{:?}",
+            alphas(level)
+        );
+    }
+
+    let texture = TextureRGBA8 {
+        width: 8,
+        data: [255, 255, 200, 160, 100, 60, 0, 0]
+            .iter()
+            .flat_map(|a| [255, 255, 255, *a])
+            .collect(),
+    };
+    let level = &texture.mip_chain(Some(0.5))[1];
+    let passing = alphas(level)
+        .iter()
+        .filter(|a| **a as f32 / 255.0 > 0.5)
+        .count();
+    assert_eq!(passing, 2);
+
+    // opaque textures stay opaque, coverage being the same at any scale above the cutoff
+    let texture = TextureRGBA8 {
+        width: 4,
+        data: vec![255; 4 * 4 * 4],
+    };
+    for level in texture.mip_chain(Some(0.5)) {
+        assert!(alphas(&level).iter().all(|a| *a == 255));
+    }
+}
@@ -0,0 +1,455 @@
+use mari_formats::{ImageFormat, TextureError, TextureRGBA8};
+
+/// a 3x2 image with a distinct color and alpha per pixel, rows top-down
+fn pixels() -> Vec<[u8; 4]> {
+    vec![
+        [255, 0, 0, 255],
+        [0, 255, 0, 128],
+        [0, 0, 255, 0],
+        [10, 20, 30, 255],
+        [200, 100, 50, 64],
+        [1, 2, 3, 4],
+    ]
+}
+
+fn flat(pixels: &[[u8; 4]]) -> Vec<u8> {
+    pixels.concat()
+}
+
+#[cfg(feature = "tga")]
+fn tga_header(kind: u8, width: u16, height: u16, depth: u8, descriptor: u8) -> Vec<u8> {
+    let mut ret = vec![0, 0, kind, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ret.extend(width.to_le_bytes());
+    ret.extend(height.to_le_bytes());
+    ret.extend([depth, descriptor]);
+    ret
+}
+
+#[cfg(feature = "tga")]
+#[test]
+fn tga_uncompressed_bottom_up() {
+    let mut data = tga_header(2, 3, 2, 24, 0);
+    for row in pixels().chunks(3).rev() {
+        for p in row {
+            data.extend([p[2], p[1], p[0]]);
+        }
+    }
+    let texture = TextureRGBA8::new_from_tga(&data).unwrap();
+    let expected: Vec<[u8; 4]> = pixels().iter().map(|p| [p[0], p[1], p[2], 255]).collect();
+    assert_eq!(texture.width, 3);
+    assert_eq!(texture.height(), 2);
+    assert_eq!(texture.data, flat(&expected));
+}
+
+#[cfg(feature = "tga")]
+#[test]
+fn tga_rle_top_down_with_alpha() {
+    let mut data = tga_header(10, 3, 2, 32, 0x28);
+    let bgra = |p: [u8; 4]| [p[2], p[1], p[0], p[3]];
+    let pixels = pixels();
+    // one raw packet of three, then a run of two copies of pixel 4 and a raw packet of one
+    data.push(2);
+    for p in &pixels[..3] {
+        data.extend(bgra(*p));
+    }
+    data.push(0x81);
+    data.extend(bgra(pixels[4]));
+    data.push(0);
+    data.extend(bgra(pixels[5]));
+
+    let texture = TextureRGBA8::new_from_tga(&data).unwrap();
+    let expected = [&pixels[..3], &[pixels[4], pixels[4], pixels[5]]].concat();
+    assert_eq!(texture.data, flat(&expected));
+}
+
+#[cfg(feature = "tga")]
+#[test]
+fn tga_color_mapped_and_grey() {
+    let mut data = tga_header(1, 2, 1, 8, 0x20);
+    data[1] = 1;
+    data[5..7].copy_from_slice(&2u16.to_le_bytes());
+    data[7] = 24;
+    data.extend([0, 0, 255, 255, 0, 0]);
+    data.extend([1, 0]);
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, [0, 0, 255, 255, 255, 0, 0, 255]);
+
+    let mut data = tga_header(3, 2, 1, 8, 0x20);
+    data.extend([7, 200]);
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, [7, 7, 7, 255, 200, 200, 200, 255]);
+}
+
+#[cfg(feature = "tga")]
+#[test]
+fn tga_truncated() {
+    let mut data = tga_header(2, 3, 2, 24, 0);
+    data.extend([0; 10]);
+    assert!(matches!(
+        TextureRGBA8::new_from_tga(&data),
+        Err(TextureError::Invalid(_))
+    ));
+}
+
+#[cfg(feature = "jpeg")]
+fn jpeg(
+    pixels: &[u8],
+    width: u16,
+    height: u16,
+    color: jpeg_encoder::ColorType,
+    progressive: bool,
+) -> Vec<u8> {
+    let mut ret = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut ret, 100);
+    encoder.set_progressive(progressive);
+    encoder.encode(pixels, width, height, color).unwrap();
+    ret
+}
+
+#[cfg(feature = "jpeg")]
+fn assert_close(actual: &[u8], expected: &[u8], tolerance: u8) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(expected) {
+        assert!(a.abs_diff(*e) <= tolerance, "{a} vs {e}");
+    }
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn jpeg_baseline_and_progressive() {
+    // a smooth gradient survives the DCT nearly unchanged
+    let (width, height) = (16u16, 16u16);
+    let rgb: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).flat_map(move |x| [x as u8 * 8, y as u8 * 8, 128]))
+        .collect();
+    let expected: Vec<u8> = rgb
+        .chunks_exact(3)
+        .flat_map(|p| [p[0], p[1], p[2], 255])
+        .collect();
+    for progressive in [false, true] {
+        let data = jpeg(
+            &rgb,
+            width,
+            height,
+            jpeg_encoder::ColorType::Rgb,
+            progressive,
+        );
+        assert_eq!(ImageFormat::sniff(&data), Some(ImageFormat::Jpeg));
+        let texture = TextureRGBA8::load(&data).unwrap();
+        assert_eq!(texture.width, width);
+        assert_eq!(texture.height(), height);
+        assert_close(&texture.data, &expected, 8);
+    }
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn jpeg_grey() {
+    let grey: Vec<u8> = (0..64).map(|i| 100 + i as u8).collect();
+    let data = jpeg(&grey, 8, 8, jpeg_encoder::ColorType::Luma, false);
+    let texture = TextureRGBA8::new_from_jpeg(&data).unwrap();
+    let expected: Vec<u8> = grey.iter().flat_map(|g| [*g, *g, *g, 255]).collect();
+    assert_close(&texture.data, &expected, 4);
+}
+
+#[cfg(feature = "dds")]
+fn dds_header(
+    width: u32,
+    height: u32,
+    flags: u32,
+    four_cc: &[u8; 4],
+    bits: u32,
+    masks: [u32; 4],
+) -> Vec<u8> {
+    let mut ret = b"DDS ".to_vec();
+    let mut header = [0u32; 31];
+    header[0] = 124;
+    header[1] = 0x1007;
+    header[2] = height;
+    header[3] = width;
+    header[18] = 32;
+    header[19] = flags;
+    header[20] = u32::from_le_bytes(*four_cc);
+    header[21] = bits;
+    header[22..26].copy_from_slice(&masks);
+    header[26] = 0x1000;
+    for v in header {
+        ret.extend(v.to_le_bytes());
+    }
+    ret
+}
+
+#[cfg(any(feature = "dds", feature = "ktx2"))]
+/// a BC1 block with its two 565 endpoints and an index per texel
+fn bc1_block(c0: u16, c1: u16, indices: [u8; 16]) -> Vec<u8> {
+    let mut bits = 0u32;
+    for (j, i) in indices.iter().enumerate() {
+        bits |= (*i as u32) << (j * 2);
+    }
+    [c0.to_le_bytes(), c1.to_le_bytes()]
+        .concat()
+        .into_iter()
+        .chain(bits.to_le_bytes())
+        .collect()
+}
+
+#[cfg(feature = "dds")]
+#[test]
+fn dds_bc1() {
+    // red > blue in 565, so four colors: red, blue, 2/3 red, 1/3 red
+    let mut indices = [0; 16];
+    indices[1] = 1;
+    indices[2] = 2;
+    indices[3] = 3;
+    let mut data = dds_header(2, 1, 0x4, b"DXT1", 0, [0; 4]);
+    data.extend(bc1_block(0xf800, 0x001f, indices));
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.width, 2);
+    assert_eq!(texture.height(), 1);
+    assert_eq!(texture.data, [255, 0, 0, 255, 0, 0, 255, 255]);
+
+    // three-color mode with transparent black
+    let mut data = dds_header(4, 1, 0x4, b"DXT1", 0, [0; 4]);
+    data.extend(bc1_block(0x001f, 0xf800, indices));
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(
+        texture.data,
+        [0, 0, 255, 255, 255, 0, 0, 255, 127, 0, 127, 255, 0, 0, 0, 0]
+    );
+}
+
+#[cfg(feature = "dds")]
+#[test]
+fn dds_bc3_alpha_ramp() {
+    let mut block = vec![255, 0];
+    // texels 0..8 take the ramp entries 0..8 in turn
+    let mut bits = 0u64;
+    for j in 0..16 {
+        bits |= ((j % 8) as u64) << (j * 3);
+    }
+    block.extend(&bits.to_le_bytes()[..6]);
+    block.extend(bc1_block(0xffff, 0xffff, [0; 16]));
+    let mut data = dds_header(4, 4, 0x4, b"DXT5", 0, [0; 4]);
+    data.extend(block);
+    let texture = TextureRGBA8::load(&data).unwrap();
+    let alphas: Vec<u8> = texture.data.chunks_exact(4).map(|p| p[3]).collect();
+    let ramp = [255, 0, 218, 182, 145, 109, 72, 36];
+    assert_eq!(alphas, [ramp, ramp].concat());
+    assert!(texture.data.chunks_exact(4).all(|p| p[..3] == [255; 3]));
+}
+
+#[cfg(feature = "dds")]
+#[test]
+fn dds_uncompressed_masks() {
+    let masks = [0xff0000, 0xff00, 0xff, 0xff000000];
+    let mut data = dds_header(3, 2, 0x41, b"\0\0\0\0", 32, masks);
+    for p in pixels() {
+        data.extend([p[2], p[1], p[0], p[3]]);
+    }
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, flat(&pixels()));
+
+    // luminance
+    let mut data = dds_header(2, 1, 0x20000, b"\0\0\0\0", 8, [0xff, 0, 0, 0]);
+    data.extend([9, 90]);
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, [9, 9, 9, 255, 90, 90, 90, 255]);
+}
+
+#[cfg(feature = "dds")]
+#[test]
+fn dds_dx10() {
+    let mut data = dds_header(2, 1, 0x4, b"DX10", 0, [0; 4]);
+    data.extend(61u32.to_le_bytes());
+    data.extend([3u32, 0, 1, 0].iter().flat_map(|v| v.to_le_bytes()));
+    data.extend([7, 250]);
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, [7, 0, 0, 255, 250, 0, 0, 255]);
+
+    // BC6H
+    let mut data = dds_header(4, 4, 0x4, b"DX10", 0, [0; 4]);
+    data.extend(95u32.to_le_bytes());
+    data.extend([3u32, 0, 1, 0].iter().flat_map(|v| v.to_le_bytes()));
+    data.extend([0; 16]);
+    assert!(matches!(
+        TextureRGBA8::load(&data),
+        Err(TextureError::Unsupported(_))
+    ));
+}
+
+#[cfg(feature = "dds")]
+/// a 4x4 BC7 DDS of one block, given as (bits, value) fields least significant first
+fn dds_bc7(fields: &[(u32, u32)]) -> Vec<[u8; 4]> {
+    let (mut block, mut pos) = (0u128, 0);
+    for (bits, value) in fields {
+        assert!((*value as u64) < 1 << bits);
+        block |= (*value as u128) << pos;
+        pos += bits;
+    }
+    assert_eq!(pos, 128);
+    let mut data = dds_header(4, 4, 0x4, b"DX10", 0, [0; 4]);
+    data.extend(98u32.to_le_bytes());
+    data.extend([3u32, 0, 1, 0].iter().flat_map(|v| v.to_le_bytes()));
+    data.extend(block.to_le_bytes());
+    let texture = TextureRGBA8::load(&data).unwrap();
+    texture
+        .data
+        .chunks_exact(4)
+        .map(|p| p.try_into().unwrap())
+        .collect()
+}
+
+#[cfg(feature = "dds")]
+#[test]
+fn dds_bc7_single_subset() {
+    // mode 6: 7-bit RGBA endpoints with a p-bit each, 4-bit indices
+    let mut fields = vec![(7, 1 << 6)];
+    fields.extend([0x7f, 0, 0, 0x7f, 0x40, 0x40, 0x7f, 0x3f].map(|v| (7, v)));
+    fields.extend([(1, 1), (1, 0)]);
+    // texel 0 is the anchor, one bit short
+    fields.extend([(3, 0), (4, 15), (4, 8)]);
+    fields.extend([(4, 0); 13]);
+    let texels = dds_bc7(&fields);
+    assert_eq!(texels[0], [255, 1, 129, 255]);
+    assert_eq!(texels[1], [0, 254, 128, 126]);
+    // weight 34 of 64
+    assert_eq!(texels[2], [120, 135, 128, 186]);
+    assert!(texels[3..].iter().all(|t| *t == texels[0]));
+
+    // mode 4: 5-bit colors, 6-bit alpha, 2 and 3-bit indices, here swapped by the index
+    // selection bit, then red swapped with alpha by the rotation
+    let mut fields = vec![(5, 1 << 4), (2, 1), (1, 1)];
+    fields.extend([31, 0, 0, 0, 0, 0].map(|v| (5, v)));
+    fields.extend([(6, 0), (6, 63)]);
+    fields.extend([(1, 0), (2, 3), (2, 1)]);
+    fields.extend([(2, 0); 13]);
+    fields.extend([(2, 0), (3, 7), (3, 2)]);
+    fields.extend([(3, 0); 13]);
+    let texels = dds_bc7(&fields);
+    assert_eq!(texels[0], [0, 0, 0, 255]);
+    assert_eq!(texels[1], [255, 0, 0, 0]);
+    // red at weight 18 of the 3-bit ramp, alpha at weight 21 of the 2-bit one
+    assert_eq!(texels[2], [84, 0, 0, 183]);
+    assert!(texels[3..].iter().all(|t| *t == texels[0]));
+}
+
+#[cfg(feature = "dds")]
+#[test]
+fn dds_bc7_partitions() {
+    // mode 1, partition 0: columns 2 and 3 are the second subset, whose anchor is texel 15
+    let mut fields = vec![(2, 1 << 1), (6, 0)];
+    // for each of r, g and b: subset 0 endpoints 0 and 1, then those of subset 1
+    fields.extend([63, 0, 0, 0, 0, 0, 0, 63, 0, 0, 63, 0].map(|v| (6, v)));
+    // shared p-bits
+    fields.extend([(1, 1), (1, 0)]);
+    let mut indices = [0; 16];
+    indices[3] = 7;
+    indices[5] = 7;
+    for (texel, index) in indices.iter().enumerate() {
+        let bits = if texel == 0 || texel == 15 { 2 } else { 3 };
+        fields.push((bits, *index));
+    }
+    let texels = dds_bc7(&fields);
+    for (texel, actual) in texels.iter().enumerate() {
+        let expected = match texel {
+            3 => [0, 253, 0, 255],
+            5 => [2, 2, 2, 255],
+            t if t % 4 < 2 => [255, 2, 2, 255],
+            _ => [0, 0, 253, 255],
+        };
+        assert_eq!(*actual, expected, "texel {texel}");
+    }
+
+    // no mode bit set is reserved, and decodes to transparent black
+    assert!(dds_bc7(&[(32, 0); 4]).iter().all(|t| *t == [0; 4]));
+}
+
+#[cfg(feature = "ktx2")]
+fn ktx2(
+    vk_format: u32,
+    width: u32,
+    height: u32,
+    supercompression: u32,
+    level: &[u8],
+    uncompressed: usize,
+) -> Vec<u8> {
+    let mut ret = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+    for v in [vk_format, 1, width, height, 0, 0, 1, 1, supercompression] {
+        ret.extend(v.to_le_bytes());
+    }
+    // empty DFD, KVD and SGD
+    ret.extend([0u8; 32]);
+    let offset = ret.len() + 24;
+    for v in [offset, level.len(), uncompressed] {
+        ret.extend((v as u64).to_le_bytes());
+    }
+    ret.extend(level);
+    ret
+}
+
+#[cfg(feature = "ktx2")]
+#[test]
+fn ktx2_rgba8_raw_and_zstd() {
+    let level = flat(&pixels());
+    let data = ktx2(43, 3, 2, 0, &level, level.len());
+    assert_eq!(ImageFormat::sniff(&data), Some(ImageFormat::Ktx2));
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, level);
+
+    let compressed = zstd_raw_frame(&level);
+    let data = ktx2(37, 3, 2, 2, &compressed, level.len());
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, level);
+}
+
+#[cfg(feature = "ktx2")]
+/// a zstd frame of one raw block, which every decoder must accept
+fn zstd_raw_frame(data: &[u8]) -> Vec<u8> {
+    let mut ret = vec![0x28, 0xb5, 0x2f, 0xfd];
+    // single segment, 1-byte content size
+    ret.push(0x20);
+    ret.push(data.len() as u8);
+    // last raw block
+    let header = ((data.len() as u32) << 3) | 1;
+    ret.extend(&header.to_le_bytes()[..3]);
+    ret.extend(data);
+    ret
+}
+
+#[cfg(feature = "ktx2")]
+#[test]
+fn ktx2_bc1_and_unsupported() {
+    let data = ktx2(131, 1, 1, 0, &bc1_block(0x07e0, 0, [0; 16]), 8);
+    let texture = TextureRGBA8::load(&data).unwrap();
+    assert_eq!(texture.data, [0, 255, 0, 255]);
+
+    let data = ktx2(0, 4, 4, 1, &[0; 16], 16);
+    assert!(matches!(
+        TextureRGBA8::load(&data),
+        Err(TextureError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn load_sniffs_png_and_rejects_unknown() {
+    let png = TextureRGBA8 {
+        width: 3,
+        data: flat(&pixels()),
+    };
+    let mut encoded = Vec::new();
+    let mut encoder = png::Encoder::new(&mut encoded, 3, 2);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&png.data)
+        .unwrap();
+    assert_eq!(ImageFormat::sniff(&encoded), Some(ImageFormat::Png));
+    assert_eq!(TextureRGBA8::load(&encoded).unwrap().data, png.data);
+
+    assert_eq!(ImageFormat::sniff(b"GIF89a"), None);
+    assert!(matches!(
+        TextureRGBA8::load(b"GIF89a"),
+        Err(TextureError::Unsupported(_))
+    ));
+}
@@ -0,0 +1,324 @@
+use std::borrow::Cow;
+
+use mari_formats::{
+    Actor, Bone, Clip, MappedScene, Material, Model, NativeError, Part, PbrMaterial, Scene,
+    SceneView, Skeleton, Submesh, TextureRGBA8, ToonMaterial, Track,
+};
+
+fn texture(width: u16, height: u16, seed: u8) -> TextureRGBA8 {
+    TextureRGBA8 {
+        width,
+        data: (0..width as usize * height as usize * 4)
+            .map(|i| (i as u8).wrapping_mul(seed))
+            .collect(),
+    }
+}
+
+fn quad(offset: f32, material: Option<&str>) -> Model {
+    Model {
+        vertices: vec![
+            offset, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, offset, 1.0, 0.5,
+        ],
+        mesh: vec![0, 1, 2, 0, 2, 3],
+        uvs: vec![0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0],
+        normals: [0.0, 0.0, 1.0].repeat(4),
+        colors: Vec::new(),
+        joints: vec![0, 1, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0],
+        weights: [0.75, 0.25, 0.0, 0.0].repeat(4),
+        submeshes: vec![
+            Submesh {
+                first: 0,
+                count: 3,
+                material: material.map(str::to_string),
+            },
+            Submesh {
+                first: 3,
+                count: 3,
+                material: None,
+            },
+        ],
+    }
+}
+
+fn scene() -> Scene {
+    let mut scene = Scene::default();
+    let mut actor = Actor::new(quad(0.0, Some("body")));
+    let mut hair = quad(-1.0, Some("hair"));
+    hair.colors = [1.0, 0.5, 0.25, 1.0, 0.0, 0.0, 0.0, 0.5].repeat(2);
+    actor.parts.push(Part {
+        name: "hair".to_string(),
+        model: hair,
+    });
+    actor.skeleton = Some(Skeleton {
+        bones: vec![
+            Bone {
+                name: "Hips".to_string(),
+                parent: None,
+                translation: [0.0, 1.0, 0.0],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                scale: [1.0; 3],
+            },
+            Bone {
+                name: "Spine".to_string(),
+                parent: Some(0),
+                translation: [0.0, 0.125, 0.0],
+                rotation: [0.0, 0.5f32.sqrt(), 0.0, 0.5f32.sqrt()],
+                scale: [1.0, 2.0, 1.0],
+            },
+        ],
+    });
+    scene.actors.insert("Temari".to_string(), actor);
+    scene
+        .actors
+        .insert("prop".to_string(), Actor::new(quad(3.0, None)));
+    scene
+        .textures
+        .insert("Temari".to_string(), texture(3, 2, 7));
+    scene.clips.insert(
+        "wave".to_string(),
+        Clip {
+            times: vec![0.0, 0.5, 1.0],
+            tracks: vec![Track {
+                bone: "Spine".to_string(),
+                translations: Vec::new(),
+                rotations: [0.0, 0.0, 0.0, 1.0].repeat(3),
+                scales: vec![1.0; 9],
+            }],
+        },
+    );
+    scene.materials.insert(
+        "body".to_string(),
+        Material::Toon(ToonMaterial {
+            texture: texture(2, 2, 3),
+            ramp_texture: texture(4, 1, 5),
+            sdw_texture: texture(1, 1, 9),
+            shadow_threshold: 0.4,
+            ramp_scale: 0.3,
+            outline_width: 0.25,
+            outline_color: [0.1, 0.2, 0.3, 1.0],
+        }),
+    );
+    scene.materials.insert(
+        "eyes".to_string(),
+        Material::Unlit {
+            color: [0.9, 0.8, 0.7, 0.5],
+        },
+    );
+    scene.materials.insert(
+        "decal".to_string(),
+        Material::Textured {
+            texture: texture(2, 1, 11),
+        },
+    );
+    for (name, base_color_texture) in [("metal", None), ("plastic", Some(texture(1, 2, 13)))] {
+        scene.materials.insert(
+            name.to_string(),
+            Material::Pbr(PbrMaterial {
+                base_color: [1.0, 0.5, 0.25, 1.0],
+                base_color_texture,
+                metallic: 0.75,
+                roughness: 0.125,
+            }),
+        );
+    }
+    scene
+}
+
+fn assert_material_eq(a: &Material, b: &Material) {
+    match (a, b) {
+        (Material::Unlit { color: a }, Material::Unlit { color: b }) => assert_eq!(a, b),
+        (Material::Textured { texture: a }, Material::Textured { texture: b }) => {
+            assert_texture_eq(a, b);
+        }
+        (Material::Toon(a), Material::Toon(b)) => {
+            assert_texture_eq(&a.texture, &b.texture);
+            assert_texture_eq(&a.ramp_texture, &b.ramp_texture);
+            assert_texture_eq(&a.sdw_texture, &b.sdw_texture);
+            assert_eq!(a.shadow_threshold, b.shadow_threshold);
+            assert_eq!(a.ramp_scale, b.ramp_scale);
+            assert_eq!(a.outline_width, b.outline_width);
+            assert_eq!(a.outline_color, b.outline_color);
+        }
+        (Material::Pbr(a), Material::Pbr(b)) => {
+            assert_eq!(a.base_color, b.base_color);
+            match (&a.base_color_texture, &b.base_color_texture) {
+                (Some(a), Some(b)) => assert_texture_eq(a, b),
+                (a, b) => assert_eq!(a.is_some(), b.is_some()),
+            }
+            assert_eq!(a.metallic, b.metallic);
+            assert_eq!(a.roughness, b.roughness);
+        }
+        _ => panic!("different kinds of material"),
+    }
+}
+
+fn assert_model_eq(a: &Model, b: &Model) {
+    assert_eq!(a.vertices, b.vertices);
+    assert_eq!(a.mesh, b.mesh);
+    assert_eq!(a.uvs, b.uvs);
+    assert_eq!(a.normals, b.normals);
+    assert_eq!(a.colors, b.colors);
+    assert_eq!(a.joints, b.joints);
+    assert_eq!(a.weights, b.weights);
+    assert_eq!(a.submeshes.len(), b.submeshes.len());
+    for (a, b) in a.submeshes.iter().zip(&b.submeshes) {
+        assert_eq!(
+            (a.first, a.count, &a.material),
+            (b.first, b.count, &b.material)
+        );
+    }
+}
+
+fn assert_texture_eq(a: &TextureRGBA8, b: &TextureRGBA8) {
+    assert_eq!(a.width, b.width);
+    assert_eq!(a.data, b.data);
+}
+
+fn assert_scene_eq(a: &Scene, b: &Scene) {
+    let mut names: Vec<_> = a.actors.keys().collect();
+    names.sort();
+    let mut other: Vec<_> = b.actors.keys().collect();
+    other.sort();
+    assert_eq!(names, other);
+    for name in names {
+        let (a, b) = (&a.actors[name], &b.actors[name]);
+        assert_model_eq(&a.body, &b.body);
+        assert_eq!(a.parts.len(), b.parts.len());
+        for (a, b) in a.parts.iter().zip(&b.parts) {
+            assert_eq!(a.name, b.name);
+            assert_model_eq(&a.model, &b.model);
+        }
+        assert_eq!(a.skeleton.is_some(), b.skeleton.is_some());
+        if let (Some(a), Some(b)) = (&a.skeleton, &b.skeleton) {
+            assert_eq!(a.bones.len(), b.bones.len());
+            for (a, b) in a.bones.iter().zip(&b.bones) {
+                assert_eq!(a.name, b.name);
+                assert_eq!(a.parent, b.parent);
+                assert_eq!(a.translation, b.translation);
+                assert_eq!(a.rotation, b.rotation);
+                assert_eq!(a.scale, b.scale);
+            }
+        }
+    }
+
+    assert_eq!(a.textures.len(), b.textures.len());
+    for (name, texture) in &a.textures {
+        assert_texture_eq(texture, &b.textures[name]);
+    }
+
+    assert_eq!(a.clips.len(), b.clips.len());
+    for (name, clip) in &a.clips {
+        let other = &b.clips[name];
+        assert_eq!(clip.times, other.times);
+        assert_eq!(clip.tracks.len(), other.tracks.len());
+        for (a, b) in clip.tracks.iter().zip(&other.tracks) {
+            assert_eq!(a.bone, b.bone);
+            assert_eq!(a.translations, b.translations);
+            assert_eq!(a.rotations, b.rotations);
+            assert_eq!(a.scales, b.scales);
+        }
+    }
+
+    assert_eq!(a.materials.len(), b.materials.len());
+    for (name, material) in &a.materials {
+        assert_material_eq(material, &b.materials[name]);
+    }
+}
+
+#[test]
+fn round_trip() {
+    let scene = scene();
+    let data = scene.write_native();
+    assert_scene_eq(&scene, &Scene::new_from_native(&data).unwrap());
+}
+
+#[test]
+fn writing_is_deterministic() {
+    let data = scene().write_native();
+    let again = Scene::new_from_native(&data).unwrap().write_native();
+    assert_eq!(data, again);
+}
+
+#[test]
+fn empty_scene() {
+    let data = Scene::default().write_native();
+    let view = SceneView::new(&data).unwrap();
+    assert_eq!(view.version, (1, 0));
+    assert!(view.actors.is_empty() && view.textures.is_empty());
+    assert!(view.clips.is_empty() && view.materials.is_empty());
+}
+
+#[test]
+fn mapped_arrays_are_borrowed() {
+    let scene = scene();
+    let path = std::env::temp_dir().join(format!("mari-native-{}.mari", std::process::id()));
+    std::fs::write(&path, scene.write_native()).unwrap();
+
+    let mapped = MappedScene::open(&path).unwrap();
+    let view = mapped.view().unwrap();
+    let actor = view.actors.iter().find(|a| a.name == "Temari").unwrap();
+    assert!(matches!(actor.body.vertices, Cow::Borrowed(_)));
+    assert!(matches!(actor.body.mesh, Cow::Borrowed(_)));
+    assert!(matches!(view.clips[0].times, Cow::Borrowed(_)));
+    assert_eq!(
+        actor.body.vertices.as_ref(),
+        scene.actors["Temari"].body.vertices
+    );
+    assert_eq!(actor.parts[0].0, "hair");
+    assert!(actor.body.colors.is_empty());
+    assert!(matches!(actor.parts[0].1.colors, Cow::Borrowed(_)));
+    assert_scene_eq(&scene, &view.to_scene().unwrap());
+
+    drop(view);
+    drop(mapped);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn misaligned_buffer_is_copied() {
+    let scene = scene();
+    let mut data = vec![0u8; 1];
+    data.extend(scene.write_native());
+    let view = SceneView::new(&data[1..]).unwrap();
+    assert_scene_eq(&scene, &view.to_scene().unwrap());
+}
+
+#[test]
+fn corruption_is_detected() {
+    let mut data = scene().write_native();
+    let last = data.len() - 1;
+    data[last] ^= 0xff;
+    assert!(matches!(
+        SceneView::new(&data),
+        Err(NativeError::Checksum(_))
+    ));
+    // trusting the sections skips their checksums, not the table's
+    assert!(SceneView::new_unverified(&data).is_ok());
+    data[20] ^= 0xff;
+    assert!(matches!(
+        SceneView::new_unverified(&data),
+        Err(NativeError::Checksum(_))
+    ));
+}
+
+#[test]
+fn newer_major_version_is_rejected() {
+    let mut data = scene().write_native();
+    data[4] = 2;
+    assert!(matches!(
+        SceneView::new(&data),
+        Err(NativeError::Unsupported(_))
+    ));
+    assert!(matches!(
+        SceneView::new(b"OBJ\n# not a scene"),
+        Err(NativeError::Invalid(_))
+    ));
+}
+
+#[test]
+fn truncation_is_detected() {
+    let data = scene().write_native();
+    for len in [0, 15, 40, data.len() - 1] {
+        assert!(SceneView::new_unverified(&data[..len]).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::io::BufReader;
+
+use mari_formats::{TextureError, TextureRGBA8};
+
+const GRAY: u8 = 0;
+const RGB: u8 = 2;
+const INDEXED: u8 = 3;
+const GRAY_ALPHA: u8 = 4;
+const RGBA: u8 = 6;
+
+/// (x0, y0, dx, dy) of the seven Adam7 passes
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32fast::hash(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+/// zlib stream of stored blocks
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut ret = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(65535).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        ret.push((i + 1 == blocks.len()) as u8);
+        ret.extend((block.len() as u16).to_le_bytes());
+        ret.extend((!(block.len() as u16)).to_le_bytes());
+        ret.extend(*block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for v in data {
+        a = (a + *v as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    ret.extend(((b << 16) | a).to_be_bytes());
+    ret
+}
+
+/// A PNG whose rows, already packed, are stored unfiltered. `extra` chunks come before IDAT.
+fn png(
+    (width, height): (u32, u32),
+    depth: u8,
+    color: u8,
+    rows: &[Vec<u8>],
+    extra: &[(&[u8; 4], Vec<u8>)],
+    interlaced: bool,
+) -> Vec<u8> {
+    let mut ret = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut ihdr = Vec::new();
+    ihdr.extend(width.to_be_bytes());
+    ihdr.extend(height.to_be_bytes());
+    ihdr.extend([depth, color, 0, 0, interlaced as u8]);
+    chunk(&mut ret, b"IHDR", &ihdr);
+    for (kind, data) in extra {
+        chunk(&mut ret, kind, data);
+    }
+    let raw: Vec<u8> = rows.iter().flat_map(|r| [&[0u8][..], r].concat()).collect();
+    chunk(&mut ret, b"IDAT", &zlib(&raw));
+    chunk(&mut ret, b"IEND", &[]);
+    ret
+}
+
+fn decode(data: &[u8]) -> TextureRGBA8 {
+    TextureRGBA8::new_from_png(BufReader::new(data)).unwrap()
+}
+
+fn assert_texture(texture: &TextureRGBA8, (width, height): (u16, u16), pixels: &[[u8; 4]]) {
+    assert_eq!((texture.width, texture.height()), (width, height));
+    assert_eq!(texture.data, pixels.concat());
+}
+
+#[test]
+fn rgba8() {
+    let data = png((2, 1), 8, RGBA, &[vec![1, 2, 3, 4, 5, 6, 7, 8]], &[], false);
+    assert_texture(&decode(&data), (2, 1), &[[1, 2, 3, 4], [5, 6, 7, 8]]);
+}
+
+#[test]
+fn rgb8() {
+    let data = png((1, 2), 8, RGB, &[vec![1, 2, 3], vec![4, 5, 6]], &[], false);
+    assert_texture(&decode(&data), (1, 2), &[[1, 2, 3, 255], [4, 5, 6, 255]]);
+}
+
+#[test]
+fn gray8() {
+    let data = png((3, 1), 8, GRAY, &[vec![0, 128, 255]], &[], false);
+    assert_texture(
+        &decode(&data),
+        (3, 1),
+        &[[0, 0, 0, 255], [128, 128, 128, 255], [255, 255, 255, 255]],
+    );
+}
+
+#[test]
+fn gray_alpha8() {
+    let data = png((2, 1), 8, GRAY_ALPHA, &[vec![10, 20, 30, 40]], &[], false);
+    assert_texture(
+        &decode(&data),
+        (2, 1),
+        &[[10, 10, 10, 20], [30, 30, 30, 40]],
+    );
+}
+
+#[test]
+fn gray_low_bit_depths() {
+    // 1 bit: 1,0,1 ; 2 bits: 3,1,0 ; 4 bits: 15,8,0
+    for (depth, row, values) in [
+        (1, vec![0b1010_0000], [255, 0, 255]),
+        (2, vec![0b1101_0000], [255, 85, 0]),
+        (4, vec![0xf8, 0x00], [255, 136, 0]),
+    ] {
+        let data = png((3, 1), depth, GRAY, &[row], &[], false);
+        let pixels = values.map(|v| [v, v, v, 255]);
+        assert_texture(&decode(&data), (3, 1), &pixels);
+    }
+}
+
+#[test]
+fn sixteen_bit_keeps_the_high_byte() {
+    let rgb = png(
+        (1, 1),
+        16,
+        RGB,
+        &[vec![0x12, 0x34, 0xab, 0xcd, 0xff, 0x00]],
+        &[],
+        false,
+    );
+    assert_texture(&decode(&rgb), (1, 1), &[[0x12, 0xab, 0xff, 255]]);
+
+    let rgba = png(
+        (1, 1),
+        16,
+        RGBA,
+        &[vec![1, 0, 2, 0, 3, 0, 4, 0]],
+        &[],
+        false,
+    );
+    assert_texture(&decode(&rgba), (1, 1), &[[1, 2, 3, 4]]);
+
+    let gray = png(
+        (1, 1),
+        16,
+        GRAY_ALPHA,
+        &[vec![0x80, 0x01, 0x40, 0x02]],
+        &[],
+        false,
+    );
+    assert_texture(&decode(&gray), (1, 1), &[[0x80, 0x80, 0x80, 0x40]]);
+}
+
+#[test]
+fn palette_with_transparency() {
+    let plte = vec![255, 0, 0, 0, 255, 0, 0, 0, 255];
+    // only the first two entries have alpha, the rest are opaque
+    let trns = vec![0, 128];
+    let data = png(
+        (3, 1),
+        8,
+        INDEXED,
+        &[vec![0, 1, 2]],
+        &[(b"PLTE", plte.clone()), (b"tRNS", trns)],
+        false,
+    );
+    assert_texture(
+        &decode(&data),
+        (3, 1),
+        &[[255, 0, 0, 0], [0, 255, 0, 128], [0, 0, 255, 255]],
+    );
+
+    // 2-bit indices
+    let data = png(
+        (3, 1),
+        2,
+        INDEXED,
+        &[vec![0b1001_0000]],
+        &[(b"PLTE", plte)],
+        false,
+    );
+    assert_texture(
+        &decode(&data),
+        (3, 1),
+        &[[0, 0, 255, 255], [0, 255, 0, 255], [255, 0, 0, 255]],
+    );
+}
+
+#[test]
+fn color_key_transparency() {
+    let gray = png(
+        (2, 1),
+        8,
+        GRAY,
+        &[vec![7, 8]],
+        &[(b"tRNS", vec![0, 7])],
+        false,
+    );
+    assert_texture(&decode(&gray), (2, 1), &[[7, 7, 7, 0], [8, 8, 8, 255]]);
+
+    let rgb = png(
+        (2, 1),
+        8,
+        RGB,
+        &[vec![1, 2, 3, 1, 2, 4]],
+        &[(b"tRNS", vec![0, 1, 0, 2, 0, 3])],
+        false,
+    );
+    assert_texture(&decode(&rgb), (2, 1), &[[1, 2, 3, 0], [1, 2, 4, 255]]);
+}
+
+#[test]
+fn gamma_is_converted_unless_srgb() {
+    // samples stored linearly
+    let linear = (b"gAMA", 100_000u32.to_be_bytes().to_vec());
+    let data = png(
+        (2, 1),
+        8,
+        GRAY_ALPHA,
+        &[vec![128, 128, 255, 255]],
+        std::slice::from_ref(&linear),
+        false,
+    );
+    // (128 / 255) ^ (1 / 2.2) * 255, alpha untouched
+    assert_texture(
+        &decode(&data),
+        (2, 1),
+        &[[186, 186, 186, 128], [255, 255, 255, 255]],
+    );
+
+    let srgb = (b"sRGB", vec![0]);
+    let data = png((1, 1), 8, GRAY, &[vec![128]], &[srgb, linear], false);
+    assert_texture(&decode(&data), (1, 1), &[[128, 128, 128, 255]]);
+
+    // the usual 1 / 2.2 needs no conversion
+    let usual = (b"gAMA", 45_455u32.to_be_bytes().to_vec());
+    let data = png((1, 1), 8, GRAY, &[vec![128]], &[usual], false);
+    assert_texture(&decode(&data), (1, 1), &[[128, 128, 128, 255]]);
+}
+
+#[test]
+fn adam7_interlaced() {
+    let (width, height) = (5usize, 3usize);
+    let pixel = |x: usize, y: usize| [x as u8, y as u8, (x * 10 + y) as u8, 200];
+    let mut rows = Vec::new();
+    for (x0, y0, dx, dy) in ADAM7 {
+        if x0 >= width {
+            continue;
+        }
+        for y in (y0..height).step_by(dy) {
+            rows.push((x0..width).step_by(dx).flat_map(|x| pixel(x, y)).collect());
+        }
+    }
+    let data = png((width as u32, height as u32), 8, RGBA, &rows, &[], true);
+
+    let expected: Vec<[u8; 4]> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| pixel(x, y)))
+        .collect();
+    assert_texture(&decode(&data), (width as u16, height as u16), &expected);
+}
+
+#[test]
+fn oversized_dimensions() {
+    let wide = png((70_000, 1), 1, GRAY, &[vec![0; 8750]], &[], false);
+    assert!(matches!(
+        TextureRGBA8::new_from_png(BufReader::new(wide.as_slice())),
+        Err(TextureError::WidthTooLarge)
+    ));
+    let tall = png((1, 70_000), 1, GRAY, &vec![vec![0]; 70_000], &[], false);
+    assert!(matches!(
+        TextureRGBA8::new_from_png(BufReader::new(tall.as_slice())),
+        Err(TextureError::HeightTooLarge)
+    ));
+}
+
+#[test]
+fn write_png_round_trip() {
+    let texture = TextureRGBA8 {
+        width: 3,
+        data: (0..3 * 2 * 4).map(|i| (i * 11) as u8).collect(),
+    };
+    let mut data = Vec::new();
+    texture.write_png(&mut data).unwrap();
+    assert!(data.starts_with(b"\x89PNG"));
+    let decoded = decode(&data);
+    assert_eq!(decoded.width, 3);
+    assert_eq!(decoded.height(), 2);
+    assert_eq!(decoded.data, texture.data);
+}
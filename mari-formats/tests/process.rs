@@ -0,0 +1,179 @@
+use mari_formats::{Model, Submesh};
+
+fn model(vertices: Vec<f32>, mesh: Vec<u16>, uvs: Vec<f32>) -> Model {
+    let n = vertices.len() / 3;
+    let count = mesh.len();
+    Model {
+        vertices,
+        mesh,
+        uvs,
+        normals: [0.0, 0.0, 1.0].repeat(n),
+        colors: Vec::new(),
+        joints: Vec::new(),
+        weights: Vec::new(),
+        submeshes: vec![Submesh {
+            first: 0,
+            count,
+            material: None,
+        }],
+    }
+}
+
+fn position(model: &Model, i: u16) -> [f32; 3] {
+    let i = i as usize * 3;
+    [
+        model.vertices[i],
+        model.vertices[i + 1],
+        model.vertices[i + 2],
+    ]
+}
+
+/// every triangle by its positions, starting from the smallest so rotations compare equal
+fn triangles(model: &Model, submesh: &Submesh) -> Vec<[[i32; 3]; 3]> {
+    let mut ret: Vec<_> = model.mesh[submesh.first..submesh.first + submesh.count]
+        .chunks_exact(3)
+        .map(|t| {
+            let p = |i: u16| position(model, i).map(|v| (v * 1000.0).round() as i32);
+            let mut t = [p(t[0]), p(t[1]), p(t[2])];
+            let first = (0..3).min_by_key(|i| t[*i]).unwrap();
+            t.rotate_left(first);
+            t
+        })
+        .collect();
+    ret.sort();
+    ret
+}
+
+/// average cache miss ratio, misses per triangle, of a FIFO cache of `size` vertices
+fn acmr(mesh: &[u16], size: usize) -> f32 {
+    let mut cache = std::collections::VecDeque::new();
+    let mut misses = 0;
+    for i in mesh {
+        if !cache.contains(i) {
+            misses += 1;
+            cache.push_back(*i);
+            if cache.len() > size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / (mesh.len() / 3) as f32
+}
+
+#[test]
+fn cleanup_drops_degenerate_triangles_and_unused_vertices() {
+    let mut quad = model(
+        vec![
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 5.0, 5.0, 5.0, 2.0, 2.0,
+            0.0,
+        ],
+        // the third is collinear, the fourth repeats a vertex
+        vec![0, 1, 2, 0, 2, 3, 0, 2, 5, 1, 1, 3],
+        vec![0.0; 12],
+    );
+    quad.normals[3..6].copy_from_slice(&[0.0, 0.0, 2.0]);
+    assert_eq!(quad.cleanup(), 2);
+    assert_eq!(quad.mesh, [0, 1, 2, 0, 2, 3]);
+    assert_eq!(quad.vertices.len(), 4 * 3);
+    assert_eq!(quad.normals[3..6], [0.0, 0.0, 1.0]);
+    assert_eq!(quad.submeshes[0].count, 6);
+}
+
+#[test]
+fn weld_merges_matching_vertices() {
+    // a quad as two triangles of their own, the shared corners off by less than a cell
+    let vertices = vec![
+        0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.001, 0.0, 0.0, 1.0, 1.001, 0.0, 0.0, 1.0,
+        0.0,
+    ];
+    let uvs = vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+    let mut quad = model(vertices.clone(), vec![0, 1, 2, 3, 4, 5], uvs.clone());
+    assert_eq!(quad.weld(0.01), 2);
+    assert_eq!(quad.mesh, [0, 1, 2, 0, 2, 3]);
+    assert_eq!(quad.vertices.len(), 4 * 3);
+    assert_eq!(quad.uvs, [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0]);
+    assert_eq!(position(&quad, 3), [0.0, 1.0, 0.0]);
+
+    // a seam in the uvs keeps a corner apart
+    let mut seam = uvs;
+    seam[6] = 0.5;
+    let mut quad = model(vertices, vec![0, 1, 2, 3, 4, 5], seam);
+    assert_eq!(quad.weld(0.01), 1);
+    assert_eq!(quad.mesh, [0, 1, 2, 3, 2, 4]);
+}
+
+#[test]
+fn weld_quantizes_positions_to_a_grid() {
+    let pair = |a: f32, b: f32| model(vec![a, 0.0, 0.0, b, 0.0, 0.0], Vec::new(), Vec::new());
+    // closer than epsilon but on both sides of a cell boundary, at 0.015
+    assert_eq!(pair(0.0149, 0.0151).weld(0.01), 0);
+    // almost epsilon apart but in the same cell
+    let mut same = pair(0.0151, 0.0249);
+    assert_eq!(same.weld(0.01), 1);
+    assert_eq!(same.vertices, [0.0151, 0.0, 0.0]);
+}
+
+#[test]
+fn optimize_keeps_the_triangles_and_improves_the_cache() {
+    // a 10x10 quad grid, its lower and upper halves as submeshes, quads shuffled in each
+    const N: u16 = 10;
+    let vertices: Vec<f32> = (0..=N)
+        .flat_map(|y| (0..=N).flat_map(move |x| [x as f32, y as f32, 0.0]))
+        .collect();
+    let mut quads: Vec<[u16; 6]> = (0..N)
+        .flat_map(|y| {
+            (0..N).map(move |x| {
+                let i = y * (N + 1) + x;
+                [i, i + 1, i + N + 2, i, i + N + 2, i + N + 1]
+            })
+        })
+        .collect();
+    let mut seed = 1u32;
+    for half in quads.chunks_mut(N as usize * N as usize / 2) {
+        for i in (1..half.len()).rev() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            half.swap(i, (seed >> 16) as usize % (i + 1));
+        }
+    }
+    let mesh: Vec<u16> = quads.concat();
+    let uvs = vertices
+        .chunks_exact(3)
+        .flat_map(|p| [p[0] / N as f32, p[1] / N as f32])
+        .collect();
+    let mut grid = model(vertices, mesh, uvs);
+    let half = grid.mesh.len() / 2;
+    grid.submeshes = vec![
+        Submesh {
+            first: 0,
+            count: half,
+            material: Some("a".to_string()),
+        },
+        Submesh {
+            first: half,
+            count: half,
+            material: Some("b".to_string()),
+        },
+    ];
+    let before: Vec<_> = grid.submeshes.iter().map(|s| triangles(&grid, s)).collect();
+    let shuffled = acmr(&grid.mesh, 16);
+
+    grid.optimize();
+    let after: Vec<_> = grid.submeshes.iter().map(|s| triangles(&grid, s)).collect();
+    assert_eq!(before, after);
+    let optimized = acmr(&grid.mesh, 16);
+    assert!(optimized < shuffled, "{optimized} vs {shuffled}");
+    assert!(optimized < 1.0, "{optimized}");
+
+    // vertices in order of first use, their uvs following them
+    let mut next = 0;
+    for i in &grid.mesh {
+        assert!(*i <= next);
+        if *i == next {
+            next += 1;
+        }
+    }
+    assert_eq!(next as usize, grid.vertices.len() / 3);
+    for (p, uv) in grid.vertices.chunks_exact(3).zip(grid.uvs.chunks_exact(2)) {
+        assert_eq!(uv, [p[0] / N as f32, p[1] / N as f32]);
+    }
+}
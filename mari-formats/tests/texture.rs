@@ -0,0 +1,107 @@
+use mari_formats::{PixelFormat, Texture, TextureError, TextureLayout, TextureRGBA8};
+
+fn rgba8() -> TextureRGBA8 {
+    TextureRGBA8 {
+        width: 2,
+        data: vec![255, 0, 0, 255, 0, 128, 255, 64],
+    }
+}
+
+#[test]
+fn sizes_and_layers() {
+    let texture = Texture::new(4, 2, PixelFormat::RGBA16F, TextureLayout::Cube);
+    assert_eq!(texture.layer_size(), 4 * 2 * 8);
+    assert_eq!(texture.data.len(), 6 * 4 * 2 * 8);
+
+    let texture = Texture::new(3, 1, PixelFormat::RG8, TextureLayout::Array(5));
+    assert_eq!(TextureLayout::Array(5).layers(), 5);
+    assert_eq!(texture.data.len(), 5 * 3 * 2);
+    assert_eq!(texture.layer(4).len(), 6);
+
+    // beyond the u16 limit of `TextureRGBA8`
+    let wide = Texture::new(70_000, 1, PixelFormat::R8, TextureLayout::Single);
+    assert_eq!(wide.data.len(), 70_000);
+    assert!(matches!(wide.to_rgba8(), Err(TextureError::WidthTooLarge)));
+}
+
+#[test]
+fn rgba8_round_trip() {
+    let texture = Texture::from(rgba8());
+    assert_eq!((texture.width, texture.height), (2, 1));
+    assert_eq!(texture.format, PixelFormat::RGBA8);
+    assert_eq!(
+        texture.texel(0, 1, 0),
+        [0.0, 128.0 / 255.0, 1.0, 64.0 / 255.0]
+    );
+    assert_eq!(texture.to_rgba8().unwrap().data, rgba8().data);
+
+    for format in [
+        PixelFormat::RGBA16F,
+        PixelFormat::RGBA32F,
+        PixelFormat::RGBA8Srgb,
+    ] {
+        let back = texture.convert(format).convert(PixelFormat::RGBA8);
+        assert_eq!(back.data, rgba8().data, "{format:?}");
+    }
+}
+
+#[test]
+fn channels_are_dropped_and_filled() {
+    let texture = Texture::from(rgba8()).convert(PixelFormat::RG8);
+    assert_eq!(texture.data, [255, 0, 0, 128]);
+    assert_eq!(
+        texture.to_rgba8().unwrap().data,
+        [255, 0, 0, 255, 0, 128, 0, 255]
+    );
+
+    let texture = Texture::from(rgba8()).convert(PixelFormat::R8);
+    assert_eq!(texture.data, [255, 0]);
+}
+
+#[test]
+fn hdr_values() {
+    let mut texture = Texture::new(1, 1, PixelFormat::RGBA16F, TextureLayout::Single);
+    texture.set_texel(0, 0, 0, [4.5, -1.0, 0.25, 1.0]);
+    assert_eq!(texture.texel(0, 0, 0), [4.5, -1.0, 0.25, 1.0]);
+    // 1.0 in binary16
+    assert_eq!(&texture.data[6..], [0x00, 0x3c]);
+    // 8-bit output clamps
+    assert_eq!(texture.to_rgba8().unwrap().data, [255, 0, 64, 255]);
+
+    let mut texture = Texture::new(2, 1, PixelFormat::R16F, TextureLayout::Single);
+    texture.set_texel(0, 1, 0, [100.0, 7.0, 7.0, 7.0]);
+    assert_eq!(texture.texel(0, 1, 0), [100.0, 0.0, 0.0, 1.0]);
+
+    let mut texture = Texture::new(1, 1, PixelFormat::Depth, TextureLayout::Single);
+    texture.set_texel(0, 0, 0, [0.75, 0.0, 0.0, 0.0]);
+    assert_eq!(texture.data, 0.75f32.to_le_bytes());
+    assert_eq!(texture.texel(0, 0, 0), [0.75, 0.75, 0.75, 1.0]);
+}
+
+#[test]
+fn srgb_is_linear_in_texels() {
+    let mut texture = Texture::new(1, 1, PixelFormat::RGBA8Srgb, TextureLayout::Single);
+    texture.set_texel(0, 0, 0, [0.5, 0.0, 1.0, 0.5]);
+    // linear 0.5 encodes to 188
+    assert_eq!(texture.data, [188, 0, 255, 128]);
+    let linear = texture.texel(0, 0, 0);
+    assert!((linear[0] - 0.5).abs() < 0.01);
+    // RGBA8 output keeps the encoded bytes
+    assert_eq!(texture.to_rgba8().unwrap().data, texture.data);
+    assert_eq!(texture.convert(PixelFormat::RGBA8).data, [128, 0, 255, 128]);
+}
+
+#[test]
+fn layers_convert_independently() {
+    let mut texture = Texture::new(1, 1, PixelFormat::R8, TextureLayout::Cube);
+    for face in 0..6 {
+        texture.set_texel(face, 0, 0, [face as f32 / 5.0, 0.0, 0.0, 0.0]);
+    }
+    let converted = texture.convert(PixelFormat::RGBA32F);
+    assert_eq!(converted.layout, TextureLayout::Cube);
+    for face in 0..6 {
+        assert_eq!(converted.texel(face, 0, 0), texture.texel(face, 0, 0));
+    }
+    // only the first face goes to RGBA8
+    assert_eq!(texture.to_rgba8().unwrap().data, [0, 0, 0, 255]);
+}
@@ -0,0 +1,1168 @@
+use mari_formats::unity::{
+    Bundle, CLASS_GAME_OBJECT, CLASS_MATERIAL, CLASS_MESH, CLASS_MESH_FILTER, CLASS_MESH_RENDERER,
+    CLASS_SKINNED_MESH_RENDERER, CLASS_TEXTURE2D, CLASS_TRANSFORM, Error, Material, Mesh, PPtr,
+    SerializedFile, Value, assemble_actor, decode_texture2d,
+};
+
+/// meta flag asking for 4-byte alignment after the field
+const ALIGN_BYTES: i32 = 0x4000;
+
+/// A field of a synthetic object, giving both its type tree and its serialized value.
+#[derive(Clone)]
+enum Field {
+    Int(&'static str, i32),
+    Long(&'static str, i64),
+    Float(&'static str, f32),
+    Str(&'static str, String),
+    /// `vector<UInt8>`
+    Bytes(&'static str, Vec<u8>),
+    /// `TypelessData`
+    Data(&'static str, Vec<u8>),
+    /// name, type name and fields
+    Object(&'static str, &'static str, Vec<Field>),
+    /// name, the shape of the elements, needed when there are none, and the elements
+    Array(&'static str, Box<Field>, Vec<Field>),
+}
+
+use Field::*;
+
+/// level, type flags, type name, name and meta flag
+type TreeNode = (u8, u8, String, String, i32);
+
+impl Field {
+    /// (level, type flags, type name, name, meta flag) of this field and everything below
+    fn nodes(&self, level: u8, nodes: &mut Vec<TreeNode>) {
+        let node = |level, flags, ty: &str, name: &str, meta| {
+            (level, flags, ty.to_string(), name.to_string(), meta)
+        };
+        match self {
+            Int(n, _) => nodes.push(node(level, 0, "int", n, 0)),
+            Long(n, _) => nodes.push(node(level, 0, "SInt64", n, 0)),
+            Float(n, _) => nodes.push(node(level, 0, "float", n, 0)),
+            Str(n, _) => nodes.push(node(level, 0, "string", n, ALIGN_BYTES)),
+            Data(n, _) => nodes.push(node(level, 0, "TypelessData", n, 0)),
+            Bytes(n, _) => nodes.extend([
+                node(level, 0, "vector", n, 0),
+                node(level + 1, 1, "Array", "Array", ALIGN_BYTES),
+                node(level + 2, 0, "int", "size", 0),
+                node(level + 2, 0, "UInt8", "data", 0),
+            ]),
+            Object(n, ty, fields) => {
+                nodes.push(node(level, 0, ty, n, 0));
+                for f in fields {
+                    f.nodes(level + 1, nodes);
+                }
+            }
+            Array(n, element, _) => {
+                nodes.extend([
+                    node(level, 0, "vector", n, 0),
+                    node(level + 1, 1, "Array", "Array", ALIGN_BYTES),
+                    node(level + 2, 0, "int", "size", 0),
+                ]);
+                element.rename("data").nodes(level + 2, nodes);
+            }
+        }
+    }
+
+    fn rename(&self, name: &'static str) -> Field {
+        match self.clone() {
+            Int(_, v) => Int(name, v),
+            Long(_, v) => Long(name, v),
+            Float(_, v) => Float(name, v),
+            Str(_, v) => Str(name, v),
+            Bytes(_, v) => Bytes(name, v),
+            Data(_, v) => Data(name, v),
+            Object(_, ty, v) => Object(name, ty, v),
+            Array(_, e, v) => Array(name, e, v),
+        }
+    }
+
+    /// little-endian, aligned relative to the start of the object
+    fn write(&self, out: &mut Vec<u8>) {
+        let align = |out: &mut Vec<u8>| out.resize(out.len().div_ceil(4) * 4, 0);
+        match self {
+            Int(_, v) => out.extend(v.to_le_bytes()),
+            Long(_, v) => out.extend(v.to_le_bytes()),
+            Float(_, v) => out.extend(v.to_le_bytes()),
+            Str(_, s) => {
+                out.extend((s.len() as i32).to_le_bytes());
+                out.extend(s.as_bytes());
+                align(out);
+            }
+            Data(_, b) => {
+                out.extend((b.len() as i32).to_le_bytes());
+                out.extend(b);
+            }
+            Bytes(_, b) => {
+                out.extend((b.len() as i32).to_le_bytes());
+                out.extend(b);
+                align(out);
+            }
+            Object(_, _, fields) => fields.iter().for_each(|f| f.write(out)),
+            Array(_, _, items) => {
+                out.extend((items.len() as i32).to_le_bytes());
+                items.iter().for_each(|f| f.write(out));
+                align(out);
+            }
+        }
+    }
+}
+
+fn pptr(name: &'static str, file_id: i32, path_id: i64) -> Field {
+    Object(
+        name,
+        "PPtr<Object>",
+        vec![Int("m_FileID", file_id), Long("m_PathID", path_id)],
+    )
+}
+
+fn string(name: &'static str, s: &str) -> Field {
+    Str(name, s.to_string())
+}
+
+/// an object of a serialized file: path id, class id and its root field
+type SerializedObject = (i64, i32, Field);
+
+/// A version 22 serialized file with a type tree per object.
+fn serialized_file(objects: &[SerializedObject], externals: &[&str]) -> Vec<u8> {
+    let mut meta = b"2022.3.21f1\0".to_vec();
+    meta.extend(13i32.to_le_bytes()); // target platform
+    meta.push(1); // type trees
+    meta.extend((objects.len() as i32).to_le_bytes());
+    for (_, class_id, root) in objects {
+        meta.extend(class_id.to_le_bytes());
+        meta.push(0);
+        meta.extend((-1i16).to_le_bytes());
+        meta.extend([0; 16]);
+        let mut nodes = Vec::new();
+        root.nodes(0, &mut nodes);
+        let mut strings = Vec::new();
+        let mut offset = |s: &str| {
+            let o = strings.len() as u32;
+            strings.extend(s.as_bytes());
+            strings.push(0);
+            o
+        };
+        let mut raw = Vec::new();
+        for (level, flags, ty, name, meta_flag) in &nodes {
+            raw.extend(1u16.to_le_bytes());
+            raw.extend([*level, *flags]);
+            raw.extend(offset(ty).to_le_bytes());
+            raw.extend(offset(name).to_le_bytes());
+            raw.extend([-1i32, 0, *meta_flag].iter().flat_map(|v| v.to_le_bytes()));
+            raw.extend(0u64.to_le_bytes());
+        }
+        meta.extend((nodes.len() as i32).to_le_bytes());
+        meta.extend((strings.len() as i32).to_le_bytes());
+        meta.extend(raw);
+        meta.extend(strings);
+        meta.extend(0i32.to_le_bytes()); // dependencies
+    }
+
+    const HEADER: usize = 48;
+    let mut data = Vec::new();
+    meta.extend((objects.len() as i32).to_le_bytes());
+    for (i, (path_id, _, root)) in objects.iter().enumerate() {
+        meta.resize((HEADER + meta.len()).div_ceil(4) * 4 - HEADER, 0);
+        data.resize(data.len().div_ceil(8) * 8, 0);
+        let start = data.len();
+        root.write(&mut data);
+        meta.extend(path_id.to_le_bytes());
+        meta.extend((start as i64).to_le_bytes());
+        meta.extend(((data.len() - start) as u32).to_le_bytes());
+        meta.extend((i as i32).to_le_bytes());
+    }
+    meta.extend(0i32.to_le_bytes()); // scripts
+    meta.extend((externals.len() as i32).to_le_bytes());
+    for path in externals {
+        meta.push(0);
+        meta.extend([0; 16]);
+        meta.extend(0i32.to_le_bytes());
+        meta.extend(path.as_bytes());
+        meta.push(0);
+    }
+
+    let data_offset = (HEADER + meta.len()).div_ceil(16) * 16;
+    let size = data_offset + data.len();
+    let mut ret = [0u32, 0, 22, 0].map(u32::to_be_bytes).concat();
+    ret.extend([0; 4]); // little-endian, reserved
+    ret.extend((meta.len() as u32).to_be_bytes());
+    for v in [size as u64, data_offset as u64, 0] {
+        ret.extend(v.to_be_bytes());
+    }
+    ret.extend(meta);
+    ret.resize(data_offset, 0);
+    ret.extend(data);
+    ret
+}
+
+/// A UnityFS bundle of one block; serialized files are named `CAB-…`, the rest are resources.
+fn unity_fs(nodes: &[(&str, Vec<u8>)], lz4: bool) -> Vec<u8> {
+    let stream: Vec<u8> = nodes.iter().flat_map(|(_, d)| d.clone()).collect();
+    let (compression, block) = if lz4 {
+        (2u16, lz4_flex::block::compress(&stream))
+    } else {
+        (0, stream.clone())
+    };
+
+    let mut info = vec![0; 16];
+    info.extend(1i32.to_be_bytes());
+    info.extend((stream.len() as u32).to_be_bytes());
+    info.extend((block.len() as u32).to_be_bytes());
+    info.extend(compression.to_be_bytes());
+    info.extend((nodes.len() as i32).to_be_bytes());
+    let mut offset = 0;
+    for (path, data) in nodes {
+        info.extend((offset as i64).to_be_bytes());
+        info.extend((data.len() as i64).to_be_bytes());
+        let flags: u32 = if path.starts_with("CAB-") && !path.contains('.') {
+            4
+        } else {
+            0
+        };
+        info.extend(flags.to_be_bytes());
+        info.extend(path.as_bytes());
+        info.push(0);
+        offset += data.len();
+    }
+    let compressed_info = if lz4 {
+        lz4_flex::block::compress(&info)
+    } else {
+        info.clone()
+    };
+
+    // version 8 aligns after the header; LZ4 bundles keep their blocks info at the end
+    let mut ret = b"UnityFS\0".to_vec();
+    ret.extend(8u32.to_be_bytes());
+    ret.extend(b"5.x.x\x002022.3.21f1\0");
+    ret.extend(0i64.to_be_bytes());
+    ret.extend((compressed_info.len() as u32).to_be_bytes());
+    ret.extend((info.len() as u32).to_be_bytes());
+    let flags = if lz4 {
+        0x80 | 0x200 | compression as u32
+    } else {
+        0
+    };
+    ret.extend(flags.to_be_bytes());
+    ret.resize(ret.len().div_ceil(16) * 16, 0);
+    if !lz4 {
+        ret.extend(&compressed_info);
+    }
+    ret.extend(block);
+    if lz4 {
+        ret.extend(compressed_info);
+    }
+    ret
+}
+
+/// `m_StreamData` of a texture or mesh
+fn stream_data(offset: i64, size: i64, path: &str) -> Field {
+    Object(
+        "m_StreamData",
+        "StreamingInfo",
+        vec![
+            Long("offset", offset),
+            Int("size", size as i32),
+            string("path", path),
+        ],
+    )
+}
+
+fn texture2d(name: &str, width: i32, height: i32, format: i32, data: Vec<u8>) -> Field {
+    Object(
+        "Base",
+        "Texture2D",
+        vec![
+            string("m_Name", name),
+            Int("m_Width", width),
+            Int("m_Height", height),
+            Int("m_TextureFormat", format),
+            Data("image data", data),
+            stream_data(0, 0, ""),
+        ],
+    )
+}
+
+/// rows bottom to top, as Unity stores them
+const PIXELS: [[u8; 4]; 4] = [
+    [1, 2, 3, 4],
+    [5, 6, 7, 8],
+    [9, 10, 11, 12],
+    [13, 14, 15, 16],
+];
+
+#[test]
+fn bundle_layouts() {
+    let file = serialized_file(
+        &[(1, CLASS_TEXTURE2D, texture2d("t", 2, 2, 4, PIXELS.concat()))],
+        &[],
+    );
+    let resource: Vec<u8> = (0..32).collect();
+    for lz4 in [false, true] {
+        let data = unity_fs(
+            &[
+                ("CAB-0123", file.clone()),
+                ("CAB-0123.resS", resource.clone()),
+            ],
+            lz4,
+        );
+        let bundle = Bundle::new_from_bytes(&data).unwrap();
+        assert_eq!(bundle.unity_version, "2022.3.21f1");
+        assert_eq!(bundle.files.len(), 1);
+        assert_eq!(bundle.files[0].name, "CAB-0123");
+        assert_eq!(bundle.files[0].unity_version, "2022.3.21f1");
+        assert_eq!(
+            bundle
+                .resource("archive:/CAB-0123/CAB-0123.resS", 4, 3)
+                .unwrap(),
+            [4, 5, 6]
+        );
+        assert!(matches!(
+            bundle.resource("archive:/CAB-0123/CAB-0123.resS", 30, 3),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            bundle.resource("archive:/CAB-0123/CAB-4567.resS", 0, 1),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    let mut data = unity_fs(&[("CAB-0123", file.clone())], false);
+    data[..8].copy_from_slice(b"UnityWeb");
+    assert!(matches!(
+        Bundle::new_from_bytes(&data),
+        Err(Error::Unsupported(_))
+    ));
+    // a truncated block
+    let data = unity_fs(&[("CAB-0123", file.clone())], false);
+    let truncated = &data[..data.len() - 1];
+    assert!(matches!(
+        Bundle::new_from_bytes(truncated),
+        Err(Error::Invalid(_))
+    ));
+}
+
+#[test]
+fn values_follow_the_type_tree() {
+    let element = Object(
+        "data",
+        "pair",
+        vec![string("first", ""), Float("second", 0.0)],
+    );
+    let root = Object(
+        "Base",
+        "Thing",
+        vec![
+            // odd lengths, so that alignment matters
+            string("m_Name", "abc"),
+            Bytes("m_Bytes", vec![1, 2, 3, 4, 5]),
+            Int("m_Int", -7),
+            Long("m_Long", 1 << 40),
+            Array(
+                "m_Pairs",
+                Box::new(element),
+                vec![
+                    Object(
+                        "data",
+                        "pair",
+                        vec![string("first", "x"), Float("second", 0.5)],
+                    ),
+                    Object(
+                        "data",
+                        "pair",
+                        vec![string("first", "yz"), Float("second", -2.0)],
+                    ),
+                ],
+            ),
+            Array("m_Empty", Box::new(Int("data", 0)), vec![]),
+            pptr("m_Other", 1, 5),
+        ],
+    );
+    let data = serialized_file(&[(3, 1000, root)], &["archive:/CAB-other/CAB-other"]);
+    let file = SerializedFile::new("CAB-0123".to_string(), data.clone()).unwrap();
+    assert_eq!(file.externals, ["archive:/CAB-other/CAB-other"]);
+    let object = file.object(3).unwrap();
+    assert_eq!(object.class_id, 1000);
+    assert_eq!(file.type_name(object), Some("Thing"));
+    assert!(file.object(4).is_none());
+
+    let value = file.read(object).unwrap();
+    let pair = |first: &str, second| {
+        Value::Object(vec![
+            ("first".to_string(), Value::String(first.to_string())),
+            ("second".to_string(), Value::Float(second)),
+        ])
+    };
+    let expected = Value::Object(vec![
+        ("m_Name".to_string(), Value::String("abc".to_string())),
+        ("m_Bytes".to_string(), Value::Bytes(vec![1, 2, 3, 4, 5])),
+        ("m_Int".to_string(), Value::Int(-7)),
+        ("m_Long".to_string(), Value::Int(1 << 40)),
+        (
+            "m_Pairs".to_string(),
+            Value::Array(vec![pair("x", 0.5), pair("yz", -2.0)]),
+        ),
+        ("m_Empty".to_string(), Value::Array(vec![])),
+        (
+            "m_Other".to_string(),
+            Value::Object(vec![
+                ("m_FileID".to_string(), Value::Int(1)),
+                ("m_PathID".to_string(), Value::Int(5)),
+            ]),
+        ),
+    ]);
+    assert_eq!(value, expected);
+    assert_eq!(
+        PPtr::new(value.get("m_Other").unwrap()),
+        Some(PPtr {
+            file_id: 1,
+            path_id: 5
+        })
+    );
+
+    // the pointer leads into the other file of the bundle
+    let other = serialized_file(&[(5, 1001, Object("Base", "Other", vec![]))], &[]);
+    let data = unity_fs(&[("CAB-0123", data), ("CAB-other", other)], true);
+    let bundle = Bundle::new_from_bytes(&data).unwrap();
+    let (file, object) = bundle.objects_of_class(1000).next().unwrap();
+    let pptr = PPtr::new(file.read(object).unwrap().get("m_Other").unwrap()).unwrap();
+    let (target, object) = bundle.resolve(file, &pptr).unwrap();
+    assert_eq!((target.name.as_str(), object.class_id), ("CAB-other", 1001));
+    assert!(
+        bundle
+            .resolve(
+                file,
+                &PPtr {
+                    file_id: 0,
+                    path_id: 0
+                }
+            )
+            .is_none()
+    );
+    assert!(
+        bundle
+            .resolve(
+                file,
+                &PPtr {
+                    file_id: 2,
+                    path_id: 5
+                }
+            )
+            .is_none()
+    );
+}
+
+#[test]
+fn bad_serialized_files_are_rejected() {
+    let root = Object("Base", "Thing", vec![string("m_Name", "abc")]);
+    let data = serialized_file(&[(1, 1000, root)], &[]);
+
+    let mut old = data.clone();
+    old[8..12].copy_from_slice(&15u32.to_be_bytes());
+    assert!(matches!(
+        SerializedFile::new("CAB-0123".to_string(), old),
+        Err(Error::Unsupported(_))
+    ));
+    assert!(matches!(
+        SerializedFile::new("CAB-0123".to_string(), data[..data.len() - 1].to_vec()),
+        Err(Error::Invalid(_))
+    ));
+    // a string longer than the object
+    let mut long = data.clone();
+    let len = long.len();
+    long[len - 8..len - 4].copy_from_slice(&100i32.to_le_bytes());
+    let file = SerializedFile::new("CAB-0123".to_string(), long).unwrap();
+    assert!(matches!(
+        file.read(file.object(1).unwrap()),
+        Err(Error::Invalid(_))
+    ));
+    // an object starting past the end of the address space, before the scripts and externals
+    let mut far = data.clone();
+    let end = 48 + u32::from_be_bytes(data[20..24].try_into().unwrap()) as usize;
+    far[end - 24..end - 16].copy_from_slice(&(-1i64).to_le_bytes());
+    let file = SerializedFile::new("CAB-0123".to_string(), far).unwrap();
+    assert!(matches!(
+        file.read(file.object(1).unwrap()),
+        Err(Error::Invalid(_))
+    ));
+}
+
+fn tex_env(property: &'static str, path_id: i64) -> Field {
+    let vector2 = |name, v: f32| Object(name, "Vector2f", vec![Float("x", v), Float("y", v)]);
+    Object(
+        "data",
+        "pair",
+        vec![
+            string("first", property),
+            Object(
+                "second",
+                "UnityTexEnv",
+                vec![
+                    pptr("m_Texture", 0, path_id),
+                    vector2("m_Scale", 1.0),
+                    vector2("m_Offset", 0.0),
+                ],
+            ),
+        ],
+    )
+}
+
+/// `m_SavedProperties` whose map keys are `FastPropertyName`s, as in some Unity versions
+fn fast_property(pair: Field) -> Field {
+    let Object(_, _, mut fields) = pair else {
+        unreachable!()
+    };
+    let Str(_, name) = fields.remove(0) else {
+        unreachable!()
+    };
+    fields.insert(
+        0,
+        Object("first", "FastPropertyName", vec![Str("name", name)]),
+    );
+    Object("data", "pair", fields)
+}
+
+fn material_field(
+    name: &str,
+    tex_envs: Vec<Field>,
+    floats: &[(&str, f32)],
+    colors: &[(&str, [f32; 4])],
+) -> Field {
+    let float = |(name, v): &(&str, f32)| {
+        Object(
+            "data",
+            "pair",
+            vec![string("first", name), Float("second", *v)],
+        )
+    };
+    let color = |(name, c): &(&str, [f32; 4])| {
+        let [r, g, b, a] = *c;
+        Object(
+            "data",
+            "pair",
+            vec![
+                string("first", name),
+                Object(
+                    "second",
+                    "ColorRGBA",
+                    vec![Float("r", r), Float("g", g), Float("b", b), Float("a", a)],
+                ),
+            ],
+        )
+    };
+    let element = tex_envs.first().cloned().unwrap_or(tex_env("", 0));
+    Object(
+        "Base",
+        "Material",
+        vec![
+            string("m_Name", name),
+            pptr("m_Shader", 1, 99),
+            Object(
+                "m_SavedProperties",
+                "UnityPropertySheet",
+                vec![
+                    Array("m_TexEnvs", Box::new(element), tex_envs),
+                    Array(
+                        "m_Floats",
+                        Box::new(float(&("", 0.0))),
+                        floats.iter().map(float).collect(),
+                    ),
+                    Array(
+                        "m_Colors",
+                        Box::new(color(&("", [0.0; 4]))),
+                        colors.iter().map(color).collect(),
+                    ),
+                ],
+            ),
+        ],
+    )
+}
+
+#[test]
+fn materials_resolve_their_textures() {
+    // the ramp is streamed from the resource, flipped rows and all
+    let mut ramp = texture2d("t_bdy_rmp", 2, 2, 4, vec![]);
+    if let Object(_, _, fields) = &mut ramp {
+        fields[5] = stream_data(8, 16, "archive:/CAB-0123/CAB-0123.resS");
+    }
+    let mut resource = vec![0; 8];
+    resource.extend(PIXELS.concat());
+
+    let objects = [
+        (
+            1,
+            CLASS_MATERIAL,
+            material_field(
+                "m_body",
+                vec![
+                    tex_env("_BaseMap", 2),
+                    // ramp and shadow textures are found by the names of their textures
+                    fast_property(tex_env("_RampMap", 3)),
+                    tex_env("_Foo", 4),
+                    // a shade color map, not the shadow mask
+                    tex_env("_ShadeMap", 2),
+                    // null
+                    tex_env("_BumpMap", 0),
+                ],
+                &[("_OutlineWidth", 0.5), ("_Cutoff", 0.1)],
+                &[("_OutlineColor", [0.1, 0.2, 0.3, 1.0])],
+            ),
+        ),
+        (
+            2,
+            CLASS_TEXTURE2D,
+            texture2d("t_col", 2, 2, 4, PIXELS.concat()),
+        ),
+        (3, CLASS_TEXTURE2D, ramp),
+        (
+            4,
+            CLASS_TEXTURE2D,
+            texture2d("t_bdy_sdw", 1, 1, 63, vec![200]),
+        ),
+    ];
+    let data = unity_fs(
+        &[
+            ("CAB-0123", serialized_file(&objects, &[])),
+            ("CAB-0123.resS", resource),
+        ],
+        true,
+    );
+    let bundle = Bundle::new_from_bytes(&data).unwrap();
+    let (file, object) = bundle.objects_of_class(CLASS_MATERIAL).next().unwrap();
+    let material = Material::new(&file.read(object).unwrap()).unwrap();
+
+    assert_eq!(material.name, "m_body");
+    assert_eq!(
+        material.shader,
+        Some(PPtr {
+            file_id: 1,
+            path_id: 99
+        })
+    );
+    assert_eq!(material.textures.len(), 5);
+    assert_eq!(material.texture("_RampMap").unwrap().texture.path_id, 3);
+    assert_eq!(material.texture("_BaseMap").unwrap().scale, [1.0, 1.0]);
+    assert_eq!(material.float("_Cutoff"), Some(0.1));
+    assert_eq!(material.color("_OutlineColor"), Some([0.1, 0.2, 0.3, 1.0]));
+    assert!(material.float("_Missing").is_none());
+
+    let flipped = [PIXELS[2], PIXELS[3], PIXELS[0], PIXELS[1]].concat();
+    let base = material.base_texture(&bundle, file).unwrap();
+    assert_eq!((base.width, base.data.clone()), (2, flipped.clone()));
+
+    let toon = material.to_toon(&bundle, file).unwrap();
+    assert_eq!(toon.texture.data, flipped);
+    assert_eq!(toon.ramp_texture.data, flipped);
+    assert_eq!(toon.sdw_texture.data, [200, 0, 0, 255]);
+    assert_eq!(toon.outline_width, 0.5);
+    assert_eq!(toon.outline_color, [0.1, 0.2, 0.3, 1.0]);
+
+    // without a ramp texture, by property or by name
+    let objects = [
+        (
+            1,
+            CLASS_MATERIAL,
+            material_field("m_face", vec![tex_env("_BaseMap", 2)], &[], &[]),
+        ),
+        objects[1].clone(),
+    ];
+    let data = unity_fs(&[("CAB-0123", serialized_file(&objects, &[]))], false);
+    let bundle = Bundle::new_from_bytes(&data).unwrap();
+    let (file, object) = bundle.objects_of_class(CLASS_MATERIAL).next().unwrap();
+    let material = Material::new(&file.read(object).unwrap()).unwrap();
+    assert!(material.base_texture(&bundle, file).is_ok());
+    assert!(matches!(
+        material.to_toon(&bundle, file),
+        Err(Error::Invalid(_))
+    ));
+}
+
+/// decode a 4x4 texture of one block
+fn decode(format: i32, block: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let objects = [(1, CLASS_TEXTURE2D, texture2d("t_col", 4, 4, format, block))];
+    let data = unity_fs(&[("CAB-0123", serialized_file(&objects, &[]))], false);
+    let bundle = Bundle::new_from_bytes(&data).unwrap();
+    let (file, object) = bundle.objects_of_class(CLASS_TEXTURE2D).next().unwrap();
+    Ok(decode_texture2d(&bundle, &file.read(object)?)?.data)
+}
+
+#[test]
+fn block_compressed_textures() {
+    // DXT1 of red and blue endpoints, the bottom row blue
+    let mut block = [0x00, 0xf8, 0x1f, 0x00].to_vec();
+    block.extend([0, 0, 0, 0x55]);
+    let data = decode(10, block).unwrap();
+    let texels: Vec<&[u8]> = data.chunks_exact(4).collect();
+    assert!(texels[..4].iter().all(|t| *t == [0, 0, 255, 255]));
+    assert!(texels[4..].iter().all(|t| *t == [255, 0, 0, 255]));
+
+    // BC4, one value all over
+    let data = decode(26, [90, 90, 0, 0, 0, 0, 0, 0].to_vec()).unwrap();
+    assert!(data.chunks_exact(4).all(|t| t == [90, 0, 0, 255]));
+
+    // BC7 mode 6 with equal endpoints of 0x40 and p-bit 1
+    let mut bits = 1u128 << 6;
+    for i in 0..8 {
+        bits |= 0x40u128 << (7 + i * 7);
+    }
+    bits |= 0b11 << 63;
+    let data = decode(25, bits.to_le_bytes().to_vec()).unwrap();
+    assert!(data.chunks_exact(4).all(|t| t == [129; 4]));
+
+    assert!(matches!(decode(10, vec![0; 7]), Err(Error::Invalid(_))));
+
+    // undecodable formats are named
+    for (format, name) in [(47, "ETC2_RGBA8"), (48, "ASTC_4x4"), (29, "DXT5Crunched")] {
+        match decode(format, vec![0; 16]) {
+            Err(Error::Unsupported(what)) => {
+                assert!(what.contains(name) && what.contains("t_col"), "{what}")
+            }
+            _ => panic!("format {format} decoded"),
+        }
+    }
+}
+
+/// `VertexFormat` values
+const FLOAT32: i32 = 0;
+const FLOAT16: i32 = 1;
+const UNORM8: i32 = 2;
+const UINT8: i32 = 6;
+const UINT32: i32 = 10;
+
+/// (channel, stream, offset, format, dimension) of the vertex channels present
+type Channel = (usize, i32, i32, i32, i32);
+
+fn vector_field(name: &'static str, ty: &'static str, v: &[f32]) -> Field {
+    let names = ["x", "y", "z", "w"];
+    Object(
+        name,
+        ty,
+        v.iter().zip(names).map(|(v, n)| Float(n, *v)).collect(),
+    )
+}
+
+fn matrix_field(m: [f32; 16]) -> Field {
+    const NAMES: [&str; 16] = [
+        "e00", "e10", "e20", "e30", "e01", "e11", "e21", "e31", "e02", "e12", "e22", "e32", "e03",
+        "e13", "e23", "e33",
+    ];
+    Object(
+        "data",
+        "Matrix4x4f",
+        NAMES.iter().zip(m).map(|(n, v)| Float(n, v)).collect(),
+    )
+}
+
+fn translation(t: [f32; 3]) -> [f32; 16] {
+    let mut m = [0.0; 16];
+    m[0] = 1.0;
+    m[5] = 1.0;
+    m[10] = 1.0;
+    m[15] = 1.0;
+    m[12..15].copy_from_slice(&t);
+    m
+}
+
+/// A 16-bit indexed `Mesh`, `submeshes` being (first index, index count, base vertex) and
+/// `vertex_data` empty when it is streamed.
+fn mesh_field(
+    name: &str,
+    vertex_cnt: i64,
+    channels: &[Channel],
+    vertex_data: Vec<u8>,
+    indices: &[u16],
+    submeshes: &[(i32, i64, i64)],
+    bind_poses: &[[f32; 16]],
+) -> Field {
+    let channel = |(stream, offset, format, dimension): (i32, i32, i32, i32)| {
+        Object(
+            "data",
+            "ChannelInfo",
+            vec![
+                Int("stream", stream),
+                Int("offset", offset),
+                Int("format", format),
+                Int("dimension", dimension),
+            ],
+        )
+    };
+    let all_channels = (0..14)
+        .map(|i| {
+            let c = channels.iter().find(|c| c.0 == i);
+            channel(c.map_or((0, 0, 0, 0), |c| (c.1, c.2, c.3, c.4)))
+        })
+        .collect();
+    let submesh = |(first, count, base): (i32, i64, i64)| {
+        Object(
+            "data",
+            "SubMesh",
+            vec![
+                Int("firstByte", first * 2),
+                Long("indexCount", count),
+                Int("topology", 0),
+                Long("baseVertex", base),
+            ],
+        )
+    };
+    Object(
+        "Base",
+        "Mesh",
+        vec![
+            string("m_Name", name),
+            Array(
+                "m_SubMeshes",
+                Box::new(submesh((0, 0, 0))),
+                submeshes.iter().copied().map(submesh).collect(),
+            ),
+            Array(
+                "m_BindPose",
+                Box::new(matrix_field([0.0; 16])),
+                bind_poses.iter().copied().map(matrix_field).collect(),
+            ),
+            Int("m_IndexFormat", 0),
+            Bytes(
+                "m_IndexBuffer",
+                indices.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            ),
+            Object(
+                "m_VertexData",
+                "VertexData",
+                vec![
+                    Long("m_VertexCount", vertex_cnt),
+                    Array("m_Channels", Box::new(channel((0, 0, 0, 0))), all_channels),
+                    Bytes("m_DataSize", vertex_data),
+                ],
+            ),
+            stream_data(0, 0, ""),
+        ],
+    )
+}
+
+fn read_mesh(mesh: Field, resource: Vec<u8>) -> Result<Mesh, Error> {
+    let data = unity_fs(
+        &[
+            ("CAB-0123", serialized_file(&[(1, CLASS_MESH, mesh)], &[])),
+            ("CAB-0123.resS", resource),
+        ],
+        false,
+    );
+    let bundle = Bundle::new_from_bytes(&data).unwrap();
+    let (file, object) = bundle.objects_of_class(CLASS_MESH).next().unwrap();
+    Mesh::new(&bundle, &file.read(object)?)
+}
+
+#[test]
+fn mesh_channels_and_submeshes() {
+    // stream 0: position, normal and color, stream 1: UV, stream 2: one weight and two joints
+    let channels = [
+        (0, 0, 0, FLOAT32, 3),
+        (1, 0, 12, FLOAT32, 3),
+        (3, 0, 24, UNORM8, 4),
+        (4, 1, 0, FLOAT16, 2),
+        (12, 2, 0, FLOAT32, 1),
+        (13, 2, 4, UINT8, 2),
+    ];
+    let mut data = Vec::new();
+    for v in 0..4 {
+        let f = v as f32;
+        for x in [f, 0.0, -f, 0.0, 1.0, 0.0] {
+            data.extend(x.to_le_bytes());
+        }
+        data.extend([255, 51, 0, 255]);
+    }
+    // streams start 16-byte aligned
+    data.resize(112, 0);
+    for _ in 0..4 {
+        // 0.5 and 1.0 as halves
+        data.extend([0x00, 0x38, 0x00, 0x3c]);
+    }
+    for v in 0..4u8 {
+        data.extend(0.25f32.to_le_bytes());
+        data.extend([v, 7]);
+    }
+    let indices = [0, 1, 2, 0, 1, 2, 2];
+    let submeshes = [(0, 3, 0), (3, 4, 1)];
+    let bind_poses = [translation([1.0, 2.0, 3.0])];
+
+    for streamed in [false, true] {
+        let mut mesh = mesh_field(
+            "m",
+            4,
+            &channels,
+            data.clone(),
+            &indices,
+            &submeshes,
+            &bind_poses,
+        );
+        let mut resource = Vec::new();
+        if streamed {
+            let Object(_, _, fields) = &mut mesh else {
+                unreachable!()
+            };
+            let Object(_, _, vertex_data) = &mut fields[5] else {
+                unreachable!()
+            };
+            vertex_data[2] = Bytes("m_DataSize", vec![]);
+            fields[6] = stream_data(4, data.len() as i64, "archive:/CAB-0123/CAB-0123.resS");
+            resource = [vec![0; 4], data.clone()].concat();
+        }
+        let mesh = read_mesh(mesh, resource).unwrap();
+        assert_eq!(mesh.name, "m");
+        assert_eq!(mesh.vertex_cnt, 4);
+        assert_eq!(&mesh.positions[6..9], [2.0, 0.0, -2.0]);
+        assert_eq!(&mesh.normals[..3], [0.0, 1.0, 0.0]);
+        assert_eq!(&mesh.colors[..4], [1.0, 0.2, 0.0, 1.0]);
+        assert_eq!(&mesh.uvs[..2], [0.5, 1.0]);
+        // the second weight is implied
+        assert_eq!(&mesh.joints[4..8], [1, 7, 0, 0]);
+        assert_eq!(&mesh.weights[4..8], [0.25, 0.75, 0.0, 0.0]);
+        // base vertex applied, the dangling index dropped
+        assert_eq!(mesh.submeshes, [vec![0, 1, 2], vec![1, 2, 3]]);
+        assert_eq!(mesh.bind_poses, bind_poses);
+    }
+}
+
+#[test]
+fn bad_meshes_are_rejected() {
+    let channels = [(0, 0, 0, FLOAT32, 3)];
+    let data = vec![0; 36];
+    let mesh = |vertex_cnt, data: &[u8], submesh| {
+        read_mesh(
+            mesh_field(
+                "m",
+                vertex_cnt,
+                &channels,
+                data.to_vec(),
+                &[0, 1, 2],
+                &[submesh],
+                &[],
+            ),
+            vec![],
+        )
+    };
+    assert!(mesh(3, &data, (0, 3, 0)).is_ok());
+    for (vertex_cnt, data, submesh) in [
+        // vertex data
+        (3, &data[..35], (0, 3, 0)),
+        (i64::MAX, &data[..], (0, 3, 0)),
+        // index buffer
+        (3, &data[..], (0, 6, 0)),
+        (3, &data[..], (0, i64::MAX, 0)),
+        (3, &data[..], (1, i64::MAX / 2, 0)),
+        // indices
+        (3, &data[..], (0, 3, 1)),
+        (3, &data[..], (0, 3, i64::MAX)),
+    ] {
+        assert!(
+            matches!(mesh(vertex_cnt, data, submesh), Err(Error::Invalid(_))),
+            "{vertex_cnt} vertices, {} bytes, submesh {submesh:?}",
+            data.len()
+        );
+    }
+}
+
+/// A character prefab: a `GameObject` and `Transform` per node, `nodes` being (name, parent,
+/// local position), then a renderer per mesh. GameObject `i` is path id `100 + i`, its Transform
+/// `200 + i`.
+struct Prefab {
+    objects: Vec<SerializedObject>,
+}
+
+impl Prefab {
+    fn new(nodes: &[(&'static str, Option<usize>, [f32; 3])]) -> Self {
+        let mut objects = Vec::new();
+        for (i, (name, parent, position)) in nodes.iter().enumerate() {
+            let i = i as i64;
+            let children = nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, p, _))| *p == Some(i as usize))
+                .map(|(c, _)| pptr("data", 0, 200 + c as i64))
+                .collect();
+            objects.push((
+                100 + i,
+                CLASS_GAME_OBJECT,
+                Object("Base", "GameObject", vec![string("m_Name", name)]),
+            ));
+            objects.push((
+                200 + i,
+                CLASS_TRANSFORM,
+                Object(
+                    "Base",
+                    "Transform",
+                    vec![
+                        pptr("m_GameObject", 0, 100 + i),
+                        vector_field("m_LocalRotation", "Quaternionf", &[0.0, 0.0, 0.0, 1.0]),
+                        vector_field("m_LocalPosition", "Vector3f", position),
+                        vector_field("m_LocalScale", "Vector3f", &[1.0; 3]),
+                        Array("m_Children", Box::new(pptr("data", 0, 0)), children),
+                        pptr("m_Father", 0, parent.map_or(0, |p| 200 + p as i64)),
+                    ],
+                ),
+            ));
+        }
+        Self { objects }
+    }
+
+    /// a mesh whose first three vertices make a triangle, each vertex skinned to one of `bones`
+    /// in turn, or not skinned if there are none
+    fn mesh(&mut self, node: i64, positions: &[[f32; 3]], bones: &[(i64, [f32; 3])]) {
+        let id = 300 + self.objects.len() as i64;
+        let mut data: Vec<u8> = positions
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let mut channels = vec![(0, 0, 0, FLOAT32, 3)];
+        let vertex_cnt = positions.len();
+        if !bones.is_empty() {
+            data.resize(data.len().div_ceil(16) * 16, 0);
+            for v in 0..vertex_cnt {
+                data.extend(1.0f32.to_le_bytes());
+                data.extend(((v % bones.len()) as u32).to_le_bytes());
+            }
+            channels.extend([(12, 1, 0, FLOAT32, 1), (13, 1, 4, UINT32, 1)]);
+        }
+        // bind poses are the inverse rest transforms in the renderer's space
+        let bind_poses: Vec<_> = bones
+            .iter()
+            .map(|(_, world)| translation(world.map(|t| -t)))
+            .collect();
+        let mesh = mesh_field(
+            "m",
+            vertex_cnt as i64,
+            &channels,
+            data,
+            &[0, 1, 2],
+            &[(0, 3, 0)],
+            &bind_poses,
+        );
+        self.objects.push((id, CLASS_MESH, mesh));
+        self.objects.push((
+            id + 1,
+            CLASS_MATERIAL,
+            material_field("m_mat", vec![], &[], &[]),
+        ));
+        let materials = Array(
+            "m_Materials",
+            Box::new(pptr("data", 0, 0)),
+            vec![pptr("data", 0, id + 1)],
+        );
+        if bones.is_empty() {
+            let filter = vec![pptr("m_GameObject", 0, 100 + node), pptr("m_Mesh", 0, id)];
+            let renderer = vec![pptr("m_GameObject", 0, 100 + node), materials];
+            self.objects.extend([
+                (
+                    id + 2,
+                    CLASS_MESH_FILTER,
+                    Object("Base", "MeshFilter", filter),
+                ),
+                (
+                    id + 3,
+                    CLASS_MESH_RENDERER,
+                    Object("Base", "MeshRenderer", renderer),
+                ),
+            ]);
+        } else {
+            let bones = bones
+                .iter()
+                .map(|(b, _)| pptr("data", 0, 200 + b))
+                .collect();
+            let renderer = vec![
+                pptr("m_GameObject", 0, 100 + node),
+                materials,
+                pptr("m_Mesh", 0, id),
+                Array("m_Bones", Box::new(pptr("data", 0, 0)), bones),
+            ];
+            self.objects.push((
+                id + 2,
+                CLASS_SKINNED_MESH_RENDERER,
+                Object("Base", "SkinnedMeshRenderer", renderer),
+            ));
+        }
+    }
+
+    fn bundle(&self) -> Bundle {
+        let data = unity_fs(&[("CAB-0123", serialized_file(&self.objects, &[]))], true);
+        Bundle::new_from_bytes(&data).unwrap()
+    }
+}
+
+fn assert_close(a: &[f32], b: &[f32]) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-5, "{a:?} != {b:?}");
+    }
+}
+
+#[test]
+fn parts_are_placed_on_the_bones_of_the_body() {
+    // Unity space, x is negated in the actor
+    let (hips, head) = ([0.0, 1.0, 0.0], [0.25, 1.5, 0.0]);
+    let mut body = Prefab::new(&[
+        ("chr", None, [0.0; 3]),
+        ("Root", Some(0), [0.0; 3]),
+        ("Hips", Some(1), hips),
+        ("Head", Some(2), [0.25, 0.5, 0.0]),
+        ("body", Some(0), [0.0; 3]),
+    ]);
+    // one more vertex than the face, so that it is the body
+    let positions = [[0.0, 1.0, 0.0], [0.25, 1.5, 0.0], [0.0, 1.2, 0.1], [0.0; 3]];
+    body.mesh(4, &positions, &[(2, hips), (3, head)]);
+
+    // the face's copy of the skeleton matches by path
+    let mut face = Prefab::new(&[
+        ("chr_face", None, [0.0; 3]),
+        ("Root", Some(0), [0.0; 3]),
+        ("Hips", Some(1), hips),
+        ("Head", Some(2), [0.25, 0.5, 0.0]),
+        ("face", Some(3), [0.0; 3]),
+    ]);
+    let positions = [[0.25, 1.6, 0.0], [0.3, 1.6, 0.0], [0.25, 1.7, 0.1]];
+    face.mesh(4, &positions, &[(3, head)]);
+
+    // the hair hangs directly off a node named like the head bone, and isn't skinned
+    let mut hair = Prefab::new(&[
+        ("chr_hair", None, [0.0; 3]),
+        ("Head", Some(0), head),
+        ("hair", Some(1), [0.0, 0.1, 0.0]),
+    ]);
+    hair.mesh(2, &[[0.0; 3], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]], &[]);
+
+    let actor = assemble_actor(&[body.bundle(), face.bundle(), hair.bundle()]).unwrap();
+    let bones = actor.skeleton.unwrap().bones;
+    let names: Vec<_> = bones.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["chr", "Root", "Hips", "Head", "body", "face", "hair"]
+    );
+    let parents: Vec<_> = bones.iter().map(|b| b.parent).collect();
+    assert_eq!(
+        parents,
+        [None, Some(0), Some(1), Some(2), Some(0), Some(3), Some(3)]
+    );
+    assert_eq!(bones[3].translation, [-0.25, 0.5, 0.0]);
+
+    assert_eq!(actor.body.vertices.len(), 4 * 3);
+    assert_close(&actor.body.vertices[..3], &[0.0, 1.0, 0.0]);
+    assert_close(&actor.body.vertices[3..6], &[-0.25, 1.5, 0.0]);
+    assert_eq!(&actor.body.joints[..8], [2, 0, 0, 0, 3, 0, 0, 0]);
+    assert_eq!(actor.body.submeshes[0].material.as_deref(), Some("m_mat"));
+
+    let parts: Vec<_> = actor.parts.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(parts, ["face", "hair"]);
+    // skinned at rest, the face stays where it was modelled, on the head bone
+    let face = &actor.parts[0].model;
+    assert_close(
+        &face.vertices,
+        &[-0.25, 1.6, 0.0, -0.3, 1.6, 0.0, -0.25, 1.7, 0.1],
+    );
+    assert!(face.joints.chunks_exact(4).all(|j| j == [3, 0, 0, 0]));
+    // the hair follows the transforms of its own prefab, and its own bone
+    let hair = &actor.parts[1].model;
+    assert_close(
+        &hair.vertices,
+        &[-0.25, 1.6, 0.0, -1.25, 1.6, 0.0, -0.25, 1.6, 1.0],
+    );
+    assert!(hair.joints.chunks_exact(4).all(|j| j == [6, 0, 0, 0]));
+}
@@ -36,7 +36,8 @@ impl Stage {
             mari_renderers::DefaultInitParams {
                 model: &scene.actors.values().nth(0).unwrap().body,
             },
-        );
+        )
+        .unwrap();
 
         Stage { renderer, ctx }
     }
@@ -52,15 +53,19 @@ impl EventHandler for Stage {
             stencil: None,
         });
 
-        self.renderer.render(
-            &mut self.ctx,
-            &(glam::Mat4::from_translation(glam::Vec3 {
+        let transforms = mari_renderers::Transforms {
+            model: glam::Mat4::from_translation(glam::Vec3 {
                 x: 0.0,
                 y: -1.0,
                 z: 0.0,
-            }) * glam::Mat4::from_scale(glam::Vec3::from_array([1.0, 1.0, -1.0])))
-            .to_cols_array(),
-        );
+            }) * glam::Mat4::from_scale(glam::Vec3::from_array([1.0, 1.0, -1.0])),
+            ..Default::default()
+        };
+        let frame = mari_renderers::FrameContext {
+            viewport: window::screen_size().into(),
+            ..Default::default()
+        };
+        self.renderer.render(&mut self.ctx, &transforms, &frame);
 
         self.ctx.end_render_pass();
 
@@ -0,0 +1,139 @@
+use glam::*;
+use miniquad::*;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use std::env;
+    use std::path::Path;
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <mari_file|gltf_file|glb_file>", args[0]);
+        std::process::exit(1);
+    }
+
+    let path = Path::new(&args[1]);
+    let data = std::fs::read(path)?;
+    let scene = match path.extension().and_then(|e| e.to_str()) {
+        Some("gltf" | "glb") => mari_formats::Scene::new_from_gltf(&data, path.parent())?,
+        _ => mari_formats::Scene::new_from_native(&data)?,
+    };
+
+    miniquad::start(conf::Conf::default(), move || Box::new(Stage::new(scene)));
+
+    Ok(())
+}
+struct Stage {
+    cam_pos: Vec3,
+    cam_dir: Vec3,
+    renderer: mari_renderers::SceneRenderer,
+    ctx: Box<dyn RenderingBackend>,
+    /// save one at the end of the next frame
+    screenshot: bool,
+}
+
+impl Stage {
+    pub fn new(scene: mari_formats::Scene) -> Stage {
+        let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+
+        let mut cache = mari_renderers::ResourceCache::new();
+        let registry = mari_renderers::Registry::default();
+        let mut renderer =
+            mari_renderers::SceneRenderer::new(&mut ctx, &scene, &mut cache, &registry).unwrap();
+        println!("{} actors in {} draws.", scene.actors.len(), renderer.len());
+
+        // side by side along x, in name order
+        let mut names: Vec<&String> = scene.actors.keys().collect();
+        names.sort_unstable();
+        let offset = (names.len() as f32 - 1.0) / 2.0;
+        for (i, name) in names.into_iter().enumerate() {
+            let x = i as f32 - offset;
+            renderer.set_transform(name, Mat4::from_translation(vec3(x, -1.2, 0.0)));
+        }
+
+        Stage {
+            cam_pos: Vec3::Z * 2.0,
+            cam_dir: -Vec3::Z,
+            renderer,
+            ctx,
+            screenshot: false,
+        }
+    }
+}
+
+impl EventHandler for Stage {
+    fn update(&mut self) {}
+
+    fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+        if keycode == KeyCode::F12 {
+            self.screenshot = true;
+            return;
+        }
+
+        let true_right = self.cam_dir.cross(Vec3::Y);
+        match keycode {
+            KeyCode::Up => {
+                self.cam_dir = Mat3::from_axis_angle(true_right, 0.05) * self.cam_dir;
+                return;
+            }
+            KeyCode::Down => {
+                self.cam_dir = Mat3::from_axis_angle(true_right, -0.05) * self.cam_dir;
+                return;
+            }
+            KeyCode::Left => {
+                self.cam_dir = Mat3::from_axis_angle(Vec3::Y, 0.05) * self.cam_dir;
+                return;
+            }
+            KeyCode::Right => {
+                self.cam_dir = Mat3::from_axis_angle(Vec3::Y, -0.05) * self.cam_dir;
+                return;
+            }
+            _ => {}
+        };
+
+        let true_front = Vec3::Y.cross(true_right);
+        self.cam_pos += 0.05
+            * match keycode {
+                KeyCode::W => true_front,
+                KeyCode::S => -true_front,
+                KeyCode::A => -true_right,
+                KeyCode::D => true_right,
+                _ => Vec3::ZERO,
+            };
+    }
+
+    fn draw(&mut self) {
+        self.ctx.begin_default_pass(PassAction::Clear {
+            color: Some((0.0, 0.0, 0.0, 1.0)),
+            depth: Some(1.0),
+            stencil: None,
+        });
+
+        let v = Mat4::look_to_rh(self.cam_pos, self.cam_dir, Vec3::Y);
+        let p = Mat4::perspective_rh_gl(
+            60.0f32.to_radians(),
+            window::screen_size().0 / window::screen_size().1,
+            0.01,
+            10.0,
+        );
+
+        let frame = mari_renderers::FrameContext {
+            time: date::now() as f32,
+            viewport: window::screen_size().into(),
+            lights: &[],
+            camera_pos: self.cam_pos,
+        };
+        self.renderer.render(&mut self.ctx, v, p, &frame);
+
+        self.ctx.end_render_pass();
+
+        if std::mem::take(&mut self.screenshot) {
+            let path = format!("screenshot-{}.png", date::now() as u64);
+            match mari_renderers::save_screenshot(&mut self.ctx, &path) {
+                Ok(()) => println!("Saved {path}."),
+                Err(e) => eprintln!("Failed to save {path}: {e}"),
+            }
+        }
+
+        self.ctx.commit_frame();
+    }
+}
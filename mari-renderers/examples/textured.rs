@@ -33,6 +33,8 @@ struct Stage {
     cam_dir: Vec3,
     renderer: mari_renderers::Textured,
     ctx: Box<dyn RenderingBackend>,
+    /// save one at the end of the next frame
+    screenshot: bool,
 }
 
 impl Stage {
@@ -44,14 +46,17 @@ impl Stage {
             mari_renderers::TexturedInitParams {
                 model: &scene.actors.values().nth(0).unwrap().body,
                 texture: scene.textures.values().nth(0).unwrap(),
+                sampler: mari_renderers::Sampler::default(),
             },
-        );
+        )
+        .unwrap();
 
         Stage {
             cam_pos: Vec3::Z,
             cam_dir: -Vec3::Z,
             renderer,
             ctx,
+            screenshot: false,
         }
     }
 }
@@ -60,6 +65,11 @@ impl EventHandler for Stage {
     fn update(&mut self) {}
 
     fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+        if keycode == KeyCode::F12 {
+            self.screenshot = true;
+            return;
+        }
+
         let true_right = self.cam_dir.cross(Vec3::Y);
         match keycode {
             KeyCode::Up => {
@@ -112,11 +122,29 @@ impl EventHandler for Stage {
             5.0,
         );
 
-        self.renderer
-            .render(&mut self.ctx, &(p * v * m).to_cols_array());
+        let transforms = mari_renderers::Transforms {
+            model: m,
+            view: v,
+            projection: p,
+        };
+        let frame = mari_renderers::FrameContext {
+            time: date::now() as f32,
+            viewport: window::screen_size().into(),
+            lights: &[],
+            camera_pos: self.cam_pos,
+        };
+        self.renderer.render(&mut self.ctx, &transforms, &frame);
 
         self.ctx.end_render_pass();
 
+        if std::mem::take(&mut self.screenshot) {
+            let path = format!("screenshot-{}.png", date::now() as u64);
+            match mari_renderers::save_screenshot(&mut self.ctx, &path) {
+                Ok(()) => println!("Saved {path}."),
+                Err(e) => eprintln!("Failed to save {path}: {e}"),
+            }
+        }
+
         self.ctx.commit_frame();
     }
 }
@@ -1,5 +1,3 @@
-use mari_renderers::Renderer;
-
 use glam::*;
 use miniquad::*;
 
@@ -9,9 +7,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     use std::io::BufReader;
 
     let args: Vec<String> = env::args().collect();
-    if args.len() < 5 {
+    if args.len() != 3 && args.len() < 5 {
         eprintln!(
-            "Usage: {} <obj_file> <tex_file> <rmp_tex_file> <sdw_tex_file>",
+            "Usage: {0} <obj_file> <tex_file> <rmp_tex_file> <sdw_tex_file>\n       {0} <obj_file> <unity3d_file>",
             args[0]
         );
         std::process::exit(1);
@@ -19,87 +17,80 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let obj_file = File::open(&args[1])?;
     let obj_reader = BufReader::new(obj_file);
-    let tex_file = File::open(&args[2])?;
-    let tex_reader = BufReader::new(tex_file);
-    let rmp_tex_file = File::open(&args[3])?;
-    let rmp_tex_reader = BufReader::new(rmp_tex_file);
-    let rmp_tex = mari_formats::TextureRGBA8::new_from_png(rmp_tex_reader)?;
-    let sdw_tex_file = File::open(&args[4])?;
-    let sdw_tex_reader = BufReader::new(sdw_tex_file);
-    let sdw_tex = mari_formats::TextureRGBA8::new_from_png(sdw_tex_reader)?;
-
-    let scene = mari_formats::Scene::new_with_model_and_texture(
-        mari_formats::Model::new_from_obj(obj_reader)?,
-        mari_formats::TextureRGBA8::new_from_png(tex_reader)?,
-    );
-
-    miniquad::start(conf::Conf::default(), move || {
-        Box::new(Stage::new(scene, &rmp_tex, &sdw_tex))
-    });
+    let mut model = mari_formats::Model::new_from_obj(obj_reader)?;
+
+    let material = if args.len() == 3 {
+        let bundle_file = File::open(&args[2])?;
+        let bundle = mari_formats::unity::Bundle::new(BufReader::new(bundle_file))?;
+        bundle
+            .objects_of_class(mari_formats::unity::CLASS_MATERIAL)
+            .find_map(|(file, object)| {
+                let material = mari_formats::unity::Material::new(&file.read(object).ok()?).ok()?;
+                let toon = material.to_toon(&bundle, file).ok()?;
+                println!("Using material {}.", material.name);
+                Some(toon)
+            })
+            .ok_or("No toon material in the bundle.")?
+    } else {
+        let tex_file = File::open(&args[2])?;
+        let tex_reader = BufReader::new(tex_file);
+        let rmp_tex_file = File::open(&args[3])?;
+        let rmp_tex_reader = BufReader::new(rmp_tex_file);
+        let sdw_tex_file = File::open(&args[4])?;
+        let sdw_tex_reader = BufReader::new(sdw_tex_file);
+        mari_formats::ToonMaterial {
+            texture: mari_formats::TextureRGBA8::new_from_png(tex_reader)?,
+            ramp_texture: mari_formats::TextureRGBA8::new_from_png(rmp_tex_reader)?,
+            sdw_texture: mari_formats::TextureRGBA8::new_from_png(sdw_tex_reader)?,
+            shadow_threshold: mari_formats::ToonMaterial::SHADOW_THRESHOLD,
+            ramp_scale: mari_formats::ToonMaterial::RAMP_SCALE,
+            // a thin ink line in the units of Unity's toon shaders, as the PNGs carry no outline
+            outline_width: 1.5,
+            outline_color: [0.2, 0.1, 0.1, 1.0],
+        }
+    };
+
+    // the whole model in the one material
+    for submesh in &mut model.submeshes {
+        submesh.material = Some("toon".to_string());
+    }
+    let mut scene = mari_formats::Scene::new_with_model(model);
+    scene
+        .materials
+        .insert("toon".to_string(), mari_formats::Material::Toon(material));
+
+    miniquad::start(conf::Conf::default(), move || Box::new(Stage::new(scene)));
 
     Ok(())
 }
 struct Stage {
     cam_pos: Vec3,
     cam_dir: Vec3,
-    m: Mat4,
-    vp: Mat4,
-    renderer: mari_renderers::Toon,
+    renderer: mari_renderers::SceneRenderer,
     ctx: Box<dyn RenderingBackend>,
+    /// save one at the end of the next frame
+    screenshot: bool,
 }
 
 impl Stage {
-    pub fn new(
-        scene: mari_formats::Scene,
-        ramp_texture: &mari_formats::TextureRGBA8,
-        sdw_texture: &mari_formats::TextureRGBA8,
-    ) -> Stage {
+    pub fn new(scene: mari_formats::Scene) -> Stage {
         let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
 
-        let renderer = mari_renderers::Toon::new(
-            &mut ctx,
-            mari_renderers::ToonInitParams {
-                model: &scene.actors.values().nth(0).unwrap().body,
-                texture: scene.textures.values().nth(0).unwrap(),
-                ramp_texture,
-                sdw_texture,
-            },
-        );
+        let mut cache = mari_renderers::ResourceCache::new();
+        let registry = mari_renderers::Registry::default();
+        let mut renderer =
+            mari_renderers::SceneRenderer::new(&mut ctx, &scene, &mut cache, &registry).unwrap();
+        for name in scene.actors.keys() {
+            renderer.set_transform(name, Mat4::from_translation(vec3(0.0, -1.2, 0.0)));
+        }
 
-        let mut new_self = Stage {
+        Stage {
             cam_pos: Vec3::Z,
             cam_dir: -Vec3::Z,
-            m: Mat4::from_translation(glam::Vec3 {
-                x: 0.0,
-                y: -1.2,
-                z: 0.0,
-            }),
-            vp: Mat4::IDENTITY,
             renderer,
             ctx,
-        };
-
-        new_self.update_cam_pos();
-        new_self
-    }
-
-    /// call when `self.cam_dir` changed
-    fn update_vp(&mut self) {
-        self.vp = Mat4::perspective_rh_gl(
-            60.0f32.to_radians(),
-            window::screen_size().0 / window::screen_size().1,
-            0.01,
-            5.0,
-        ) * Mat4::look_to_rh(self.cam_pos, self.cam_dir, Vec3::Y);
-    }
-
-    /// call when `self.cam_pos` changed
-    fn update_cam_pos(&mut self) {
-        self.update_vp();
-
-        let cam_pos_in_model_space = (self.m.inverse() * self.cam_pos.extend(1.0)).xyz();
-        self.renderer
-            .set_light_pos(&cam_pos_in_model_space.to_array());
+            screenshot: false,
+        }
     }
 }
 
@@ -107,6 +98,11 @@ impl EventHandler for Stage {
     fn update(&mut self) {}
 
     fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+        if keycode == KeyCode::F12 {
+            self.screenshot = true;
+            return;
+        }
+
         let true_right = self.cam_dir.cross(Vec3::Y);
         if match keycode {
             KeyCode::Up => {
@@ -127,7 +123,6 @@ impl EventHandler for Stage {
             }
             _ => false,
         } {
-            self.update_vp();
             return;
         }
 
@@ -140,7 +135,6 @@ impl EventHandler for Stage {
                 KeyCode::D => true_right,
                 _ => Vec3::ZERO,
             };
-        self.update_cam_pos();
     }
 
     fn draw(&mut self) {
@@ -150,11 +144,32 @@ impl EventHandler for Stage {
             stencil: None,
         });
 
-        self.renderer
-            .render(&mut self.ctx, &(self.vp * self.m).to_cols_array());
+        let v = Mat4::look_to_rh(self.cam_pos, self.cam_dir, Vec3::Y);
+        let p = Mat4::perspective_rh_gl(
+            60.0f32.to_radians(),
+            window::screen_size().0 / window::screen_size().1,
+            0.01,
+            5.0,
+        );
+        // without lights, the toon shading is lit from the camera
+        let frame = mari_renderers::FrameContext {
+            time: date::now() as f32,
+            viewport: window::screen_size().into(),
+            lights: &[],
+            camera_pos: self.cam_pos,
+        };
+        self.renderer.render(&mut self.ctx, v, p, &frame);
 
         self.ctx.end_render_pass();
 
+        if std::mem::take(&mut self.screenshot) {
+            let path = format!("screenshot-{}.png", date::now() as u64);
+            match mari_renderers::save_screenshot(&mut self.ctx, &path) {
+                Ok(()) => println!("Saved {path}."),
+                Err(e) => eprintln!("Failed to save {path}: {e}"),
+            }
+        }
+
         self.ctx.commit_frame();
     }
 }
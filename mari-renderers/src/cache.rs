@@ -0,0 +1,278 @@
+//! GPU meshes and textures shared between renderers.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::rc::Rc;
+
+use miniquad::*;
+
+use crate::{RendererError, Sampler, TextureData, resources};
+
+struct Mesh {
+    vertices: usize,
+    positions: BufferId,
+    uvs: Option<BufferId>,
+    normals: Option<BufferId>,
+    /// white where the model has none
+    colors: BufferId,
+    index_buffer: BufferId,
+}
+
+/// A vertex buffer of `MeshHandle` a shader reads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Attribute {
+    Positions,
+    Uvs,
+    Normals,
+    Colors,
+}
+
+impl Attribute {
+    pub(crate) fn format(self) -> VertexFormat {
+        match self {
+            Self::Positions | Self::Normals => VertexFormat::Float3,
+            Self::Uvs => VertexFormat::Float2,
+            Self::Colors => VertexFormat::Float4,
+        }
+    }
+}
+
+/// A reference-counted model on the GPU, one vertex buffer per attribute, and the range of
+/// its indices to draw.
+///
+/// The buffers are deleted when the last handle is released, by `release`, a renderer's
+/// `destroy` or `ResourceCache::collect`. Dropping the last handle instead leaks them.
+#[derive(Clone)]
+pub struct MeshHandle {
+    mesh: Rc<Mesh>,
+    first: usize,
+    count: usize,
+}
+
+impl MeshHandle {
+    /// Upload `model`, with uvs and normals if it has them, and its colors or white.
+    pub fn upload(
+        ctx: &mut Box<dyn RenderingBackend>,
+        model: &mari_formats::Model,
+    ) -> Result<Self, RendererError> {
+        let (uvs, normals) = (!model.uvs.is_empty(), !model.normals.is_empty());
+        crate::error::check_model(model, uvs, normals)?;
+        let mut buffer = |data: &[f32]| {
+            resources::new_buffer(ctx, BufferType::VertexBuffer, BufferSource::slice(data))
+        };
+        let positions = buffer(&model.vertices);
+        let uvs = uvs.then(|| buffer(&model.uvs));
+        let normals = normals.then(|| buffer(&model.normals));
+        let colors = match model.colors.is_empty() {
+            true => buffer(&[1.0; 4].repeat(model.vertices.len() / 3)),
+            false => buffer(&model.colors),
+        };
+        let index_buffer = resources::new_buffer(
+            ctx,
+            BufferType::IndexBuffer,
+            BufferSource::slice(&model.mesh),
+        );
+        Ok(Self {
+            mesh: Rc::new(Mesh {
+                vertices: model.vertices.len() / 3,
+                positions,
+                uvs,
+                normals,
+                colors,
+                index_buffer,
+            }),
+            first: 0,
+            count: model.mesh.len(),
+        })
+    }
+
+    /// The same buffers drawing only `submesh`, which must be of the uploaded model.
+    pub fn submesh(&self, submesh: &mari_formats::Submesh) -> Self {
+        Self {
+            mesh: self.mesh.clone(),
+            first: submesh.first,
+            count: submesh.count,
+        }
+    }
+
+    /// the first index and the number of indices drawn
+    pub fn range(&self) -> (usize, usize) {
+        (self.first, self.count)
+    }
+
+    /// Bindings of `attributes` in order, failing if the mesh lacks one.
+    pub(crate) fn bindings(
+        &self,
+        attributes: &[Attribute],
+        images: Vec<TextureId>,
+    ) -> Result<Bindings, RendererError> {
+        let mesh = &self.mesh;
+        let mut vertex_buffers = Vec::with_capacity(attributes.len());
+        for attribute in attributes {
+            let (attribute, buffer, size) = match attribute {
+                Attribute::Positions => ("vertices", Some(mesh.positions), 3),
+                Attribute::Uvs => ("uvs", mesh.uvs, 2),
+                Attribute::Normals => ("normals", mesh.normals, 3),
+                Attribute::Colors => ("colors", Some(mesh.colors), 4),
+            };
+            vertex_buffers.push(buffer.ok_or(RendererError::AttributeLength {
+                attribute,
+                len: 0,
+                expected: mesh.vertices * size,
+            })?);
+        }
+        Ok(Bindings {
+            vertex_buffers,
+            index_buffer: mesh.index_buffer,
+            images,
+        })
+    }
+
+    /// Drop this handle, deleting the buffers if it is the last.
+    pub fn release(self, ctx: &mut Box<dyn RenderingBackend>) {
+        if let Ok(mesh) = Rc::try_unwrap(self.mesh) {
+            let buffers = [
+                Some(mesh.positions),
+                mesh.uvs,
+                mesh.normals,
+                Some(mesh.colors),
+            ];
+            for buffer in buffers.into_iter().flatten() {
+                resources::delete_buffer(ctx, buffer);
+            }
+            resources::delete_buffer(ctx, mesh.index_buffer);
+        }
+    }
+}
+
+/// A reference-counted texture on the GPU, released like `MeshHandle`.
+#[derive(Clone)]
+pub struct TextureHandle(Rc<TextureId>);
+
+impl TextureHandle {
+    pub fn upload(
+        ctx: &mut Box<dyn RenderingBackend>,
+        texture: &dyn TextureData,
+        sampler: Sampler,
+    ) -> Result<Self, RendererError> {
+        Ok(Self(Rc::new(texture.upload(ctx, sampler)?)))
+    }
+
+    pub fn id(&self) -> TextureId {
+        *self.0
+    }
+
+    /// Drop this handle, deleting the texture if it is the last.
+    pub fn release(self, ctx: &mut Box<dyn RenderingBackend>) {
+        if let Ok(texture) = Rc::try_unwrap(self.0) {
+            resources::delete_textures(ctx, &[texture]);
+        }
+    }
+}
+
+/// a hash of everything `MeshHandle::upload` sends to the GPU
+fn model_hash(model: &mari_formats::Model) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for values in [&model.vertices, &model.uvs, &model.normals, &model.colors] {
+        values.len().hash(&mut hasher);
+        values.iter().for_each(|v| v.to_bits().hash(&mut hasher));
+    }
+    model.mesh.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Meshes and textures keyed by a hash of their contents, so renderers drawing the same asset
+/// share one upload whatever it is named.
+#[derive(Default)]
+pub struct ResourceCache {
+    meshes: HashMap<u64, MeshHandle>,
+    /// one upload per sampler, as sampler state belongs to the texture
+    textures: HashMap<u64, Vec<(Sampler, TextureHandle)>>,
+}
+
+impl ResourceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The mesh of `model`, uploading it the first time. `model` is checked either way, so its
+    /// submeshes may be drawn from the mesh.
+    pub fn mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        model: &mari_formats::Model,
+    ) -> Result<MeshHandle, RendererError> {
+        let key = model_hash(model);
+        if let Some(mesh) = self.meshes.get(&key) {
+            let (uvs, normals) = (!model.uvs.is_empty(), !model.normals.is_empty());
+            crate::error::check_model(model, uvs, normals)?;
+            // a colliding model of other counts gets an upload of its own
+            let same = mesh.mesh.vertices == model.vertices.len() / 3
+                && mesh.count == model.mesh.len()
+                && mesh.mesh.uvs.is_some() == uvs
+                && mesh.mesh.normals.is_some() == normals;
+            if same {
+                return Ok(mesh.clone());
+            }
+            return MeshHandle::upload(ctx, model);
+        }
+        let mesh = MeshHandle::upload(ctx, model)?;
+        self.meshes.insert(key, mesh.clone());
+        Ok(mesh)
+    }
+
+    /// The upload of `texture` with `sampler`, made the first time.
+    pub fn texture(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        texture: &dyn TextureData,
+        sampler: Sampler,
+    ) -> Result<TextureHandle, RendererError> {
+        let uploads = self.textures.entry(texture.content_hash()).or_default();
+        if let Some((_, handle)) = uploads.iter().find(|(s, _)| *s == sampler) {
+            return Ok(handle.clone());
+        }
+        let handle = TextureHandle::upload(ctx, texture, sampler)?;
+        uploads.push((sampler, handle.clone()));
+        Ok(handle)
+    }
+
+    /// Delete the entries no renderer holds anymore, returning how many there were.
+    pub fn collect(&mut self, ctx: &mut Box<dyn RenderingBackend>) -> usize {
+        let mut collected = 0;
+        let unused: Vec<u64> = self
+            .meshes
+            .iter()
+            .filter(|(_, mesh)| Rc::strong_count(&mesh.mesh) == 1)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in unused {
+            self.meshes.remove(&key).unwrap().release(ctx);
+            collected += 1;
+        }
+        for uploads in self.textures.values_mut() {
+            let (unused, used) = std::mem::take(uploads)
+                .into_iter()
+                .partition(|(_, handle)| Rc::strong_count(&handle.0) == 1);
+            *uploads = used;
+            for (_, handle) in unused {
+                handle.release(ctx);
+                collected += 1;
+            }
+        }
+        self.textures.retain(|_, uploads| !uploads.is_empty());
+        collected
+    }
+
+    /// Release every entry, deleting those no renderer holds.
+    pub fn clear(&mut self, ctx: &mut Box<dyn RenderingBackend>) {
+        for (_, mesh) in self.meshes.drain() {
+            mesh.release(ctx);
+        }
+        for (_, uploads) in self.textures.drain() {
+            for (_, handle) in uploads {
+                handle.release(ctx);
+            }
+        }
+    }
+}
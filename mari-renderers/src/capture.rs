@@ -0,0 +1,84 @@
+use mari_formats::TextureRGBA8;
+use miniquad::*;
+
+/// missing from `miniquad::gl`
+const GL_READ_FRAMEBUFFER_BINDING: u32 = 0x8CAA;
+
+/// OpenGL reads rows bottom-up, `TextureRGBA8` keeps them top-down.
+fn flip_rows(data: &mut [u8], width: usize) {
+    let stride = width * 4;
+    let height = data.len() / stride;
+    for y in 0..height / 2 {
+        let (top, bottom) = data.split_at_mut((height - 1 - y) * stride);
+        top[y * stride..][..stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
+
+/// Read the default framebuffer back, e.g. after the last pass of a frame and before
+/// `commit_frame`. Its size is `window::screen_size()`.
+///
+/// Only the OpenGL backend supports this, `None` elsewhere or for sizes beyond `TextureRGBA8`.
+pub fn read_framebuffer(ctx: &mut Box<dyn RenderingBackend>) -> Option<TextureRGBA8> {
+    if ctx.info().backend != Backend::OpenGl {
+        return None;
+    }
+    let (width, height) = window::screen_size();
+    let (width, height): (u16, u16) = (
+        (width as u32).try_into().ok()?,
+        (height as u32).try_into().ok()?,
+    );
+    let mut data = vec![0; width as usize * height as usize * 4];
+    unsafe {
+        let mut bound = 0;
+        gl::glGetIntegerv(GL_READ_FRAMEBUFFER_BINDING, &mut bound);
+        gl::glBindFramebuffer(gl::GL_READ_FRAMEBUFFER, 0);
+        gl::glReadPixels(
+            0,
+            0,
+            width as _,
+            height as _,
+            gl::GL_RGBA,
+            gl::GL_UNSIGNED_BYTE,
+            data.as_mut_ptr() as _,
+        );
+        gl::glBindFramebuffer(gl::GL_READ_FRAMEBUFFER, bound as _);
+    }
+    flip_rows(&mut data, width as usize);
+    Some(TextureRGBA8 { width, data })
+}
+
+/// Read back an RGBA8 texture, e.g. the color attachment of an offscreen pass.
+///
+/// `None` for other formats or sizes beyond `TextureRGBA8`.
+pub fn read_texture(
+    ctx: &mut Box<dyn RenderingBackend>,
+    texture: TextureId,
+) -> Option<TextureRGBA8> {
+    let params = ctx.texture_params(texture);
+    if params.format != TextureFormat::RGBA8 {
+        return None;
+    }
+    let width: u16 = params.width.try_into().ok()?;
+    let _: u16 = params.height.try_into().ok()?;
+    let mut data = vec![0; params.width as usize * params.height as usize * 4];
+    ctx.texture_read_pixels(texture, &mut data);
+    if ctx.info().backend == Backend::OpenGl {
+        flip_rows(&mut data, width as usize);
+    }
+    Some(TextureRGBA8 { width, data })
+}
+
+/// Write the default framebuffer to `path` as a PNG, read as by `read_framebuffer`.
+///
+/// An `Unsupported` error on backends other than OpenGL.
+pub fn save_screenshot(
+    ctx: &mut Box<dyn RenderingBackend>,
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<()> {
+    let texture = read_framebuffer(ctx).ok_or(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Screenshots need the OpenGL backend.",
+    ))?;
+    let file = std::fs::File::create(path)?;
+    Ok(texture.write_png(std::io::BufWriter::new(file))?)
+}
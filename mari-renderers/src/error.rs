@@ -0,0 +1,147 @@
+use miniquad::{ShaderError, ShaderType};
+
+/// Errors of renderer construction.
+#[derive(Debug)]
+pub enum RendererError {
+    /// the driver rejected a shader
+    ShaderCompile {
+        stage: ShaderType,
+        log: String,
+        /// the first line the log points at, 1-based, and its source
+        line: Option<(usize, String)>,
+    },
+    ShaderLink(String),
+    /// `attribute` has `len` floats where the model's vertex count needs `expected`
+    AttributeLength {
+        attribute: &'static str,
+        len: usize,
+        expected: usize,
+    },
+    /// the mesh refers to vertex `index` of `vertices`
+    IndexOutOfRange {
+        index: u16,
+        vertices: usize,
+    },
+    /// the mesh has `len` indices, which isn't a whole number of triangles
+    NotTriangles {
+        len: usize,
+    },
+    /// a submesh draws indices `first..first + count` of a mesh of `len`
+    SubmeshOutOfRange {
+        first: usize,
+        count: usize,
+        len: usize,
+    },
+    /// the texture exceeds the `max` width or height of the backend
+    TextureTooLarge {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    /// no renderer is registered under the name
+    UnknownRenderer(String),
+    /// the renderer needs a texture in the slot
+    MissingTexture(&'static str),
+}
+
+impl std::fmt::Display for RendererError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:#?}")
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+/// The line number of the first `0:LINE` (Mesa, ANGLE, Apple) or `0(LINE)` (NVIDIA) in a
+/// driver log.
+fn log_line(log: &str) -> Option<usize> {
+    log.match_indices("0:")
+        .chain(log.match_indices("0("))
+        .filter(|(i, _)| {
+            // a source string number, not the end of some larger number
+            !log[..*i].ends_with(|c: char| c.is_ascii_digit())
+        })
+        .filter_map(|(i, _)| {
+            let rest = &log[i + 2..];
+            let end = rest.find(|c: char| !c.is_ascii_digit())?;
+            Some((i, rest[..end].parse().ok()?))
+        })
+        .min_by_key(|(i, _)| *i)
+        .map(|(_, line)| line)
+}
+
+impl RendererError {
+    pub(crate) fn from_shader(error: ShaderError, vertex: &str, fragment: &str) -> Self {
+        match error {
+            ShaderError::CompilationError {
+                shader_type,
+                error_message,
+            } => {
+                let source = match shader_type {
+                    ShaderType::Vertex => vertex,
+                    ShaderType::Fragment => fragment,
+                };
+                let line = log_line(&error_message).and_then(|n| {
+                    let text = source.lines().nth(n.checked_sub(1)?)?;
+                    Some((n, text.trim().to_string()))
+                });
+                Self::ShaderCompile {
+                    stage: shader_type,
+                    log: error_message,
+                    line,
+                }
+            }
+            ShaderError::LinkError(log) => Self::ShaderLink(log),
+            ShaderError::FFINulError(e) => Self::ShaderLink(e.to_string()),
+        }
+    }
+}
+
+/// Check that `model` has `uvs` or `normals` for each vertex where asked, and colors if any,
+/// and that its mesh is triangles of existing vertices that its submeshes lie within.
+pub(crate) fn check_model(
+    model: &mari_formats::Model,
+    uvs: bool,
+    normals: bool,
+) -> Result<(), RendererError> {
+    let vertices = model.vertices.len() / 3;
+    let attributes = [
+        ("vertices", &model.vertices, 3, true),
+        ("uvs", &model.uvs, 2, uvs),
+        ("normals", &model.normals, 3, normals),
+        ("colors", &model.colors, 4, !model.colors.is_empty()),
+    ];
+    for (attribute, values, size, needed) in attributes {
+        if needed && values.len() != vertices * size {
+            return Err(RendererError::AttributeLength {
+                attribute,
+                len: values.len(),
+                expected: vertices * size,
+            });
+        }
+    }
+    if !model.mesh.len().is_multiple_of(3) {
+        return Err(RendererError::NotTriangles {
+            len: model.mesh.len(),
+        });
+    }
+    let len = model.mesh.len();
+    if let Some(submesh) = model
+        .submeshes
+        .iter()
+        .find(|s| s.first.checked_add(s.count).is_none_or(|end| end > len))
+    {
+        return Err(RendererError::SubmeshOutOfRange {
+            first: submesh.first,
+            count: submesh.count,
+            len,
+        });
+    }
+    match model.mesh.iter().find(|i| **i as usize >= vertices) {
+        Some(index) => Err(RendererError::IndexOutOfRange {
+            index: *index,
+            vertices,
+        }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,53 @@
+use glam::{Mat4, Vec3};
+
+/// The transforms of one draw, which renderers combine into the spaces they need.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Transforms {
+    /// model to world
+    pub model: Mat4,
+    /// world to view
+    pub view: Mat4,
+    /// view to clip space, where nearer vertices shall have smaller z values as depth test is
+    /// LESS
+    pub projection: Mat4,
+}
+
+impl Transforms {
+    pub fn mvp(&self) -> Mat4 {
+        self.projection * self.view * self.model
+    }
+
+    /// a world-space point in model space
+    pub fn to_model_space(&self, p: Vec3) -> Vec3 {
+        self.model.inverse().transform_point3(p)
+    }
+}
+
+/// A point light.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Light {
+    /// in world space
+    pub position: Vec3,
+}
+
+/// What stays the same for all draws of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameContext<'a> {
+    /// seconds since some fixed start
+    pub time: f32,
+    /// width and height in pixels
+    pub viewport: [f32; 2],
+    /// `Toon` is lit by the first, or from the camera if there is none
+    pub lights: &'a [Light],
+    /// in world space
+    pub camera_pos: Vec3,
+}
+
+impl FrameContext<'_> {
+    /// the position of the main light in world space
+    pub(crate) fn light_pos(&self) -> Vec3 {
+        self.lights
+            .first()
+            .map_or(self.camera_pos, |light| light.position)
+    }
+}
@@ -1,7 +1,18 @@
+mod cache;
+mod capture;
+mod error;
+mod frame;
+mod registry;
 mod renderers;
+mod resources;
+mod scene;
+mod software;
+mod texture;
 
 use miniquad::*;
 
+pub use glam;
+
 pub use renderers::Default;
 pub use renderers::DefaultInitParams;
 
@@ -10,13 +21,53 @@ pub use renderers::TexturedInitParams;
 
 pub use renderers::Toon;
 pub use renderers::ToonInitParams;
+pub use renderers::ToonParams;
+pub use renderers::ToonTextures;
+
+pub use cache::{MeshHandle, ResourceCache, TextureHandle};
+pub use capture::{read_framebuffer, read_texture, save_screenshot};
+pub use error::RendererError;
+pub use frame::{FrameContext, Light, Transforms};
+pub use registry::{Constructor, DynRenderer, MaterialDesc, Registry};
+pub use resources::{GpuObjects, live_gpu_objects};
+pub use scene::SceneRenderer;
+pub use software::{Framebuffer, Software};
+pub use texture::{Filter, Sampler, TextureData, upload_format};
 
 pub trait Renderer<'init> {
     type InitParams;
 
-    fn new(ctx: &mut Box<dyn RenderingBackend>, params: Self::InitParams) -> Self;
-    /// `mvp` is a mat4 in column-major order
-    ///
-    /// depth test is LESS, so after `mvp`, nearer vertices shall have smaller z values.
-    fn render(&self, ctx: &mut Box<dyn RenderingBackend>, mvp: &[f32; 16]);
+    fn new(
+        ctx: &mut Box<dyn RenderingBackend>,
+        params: Self::InitParams,
+    ) -> Result<Self, RendererError>
+    where
+        Self: Sized;
+    fn render(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        transforms: &Transforms,
+        frame: &FrameContext,
+    );
+    /// Replace the model, keeping the textures. On error the renderer is unchanged.
+    fn set_model(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        model: &mari_formats::Model,
+    ) -> Result<(), RendererError> {
+        let mesh = MeshHandle::upload(ctx, model)?;
+        self.set_mesh(ctx, mesh)
+    }
+    /// `set_model` with a mesh shared e.g. through `ResourceCache`. On error `mesh` is
+    /// released and the renderer is unchanged.
+    fn set_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<(), RendererError>;
+    /// Delete the GPU objects of the renderer and release its handles, which are leaked if it
+    /// is dropped instead.
+    fn destroy(self, ctx: &mut Box<dyn RenderingBackend>)
+    where
+        Self: Sized;
 }
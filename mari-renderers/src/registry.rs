@@ -0,0 +1,186 @@
+//! Renderers chosen by name at runtime.
+
+use std::collections::HashMap;
+
+use miniquad::*;
+
+use crate::{
+    FrameContext, MeshHandle, Renderer, RendererError, TextureHandle, ToonParams, ToonTextures,
+    Transforms,
+};
+
+/// The object-safe part of `Renderer`, for renderers picked at runtime.
+pub trait DynRenderer {
+    fn render(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        transforms: &Transforms,
+        frame: &FrameContext,
+    );
+    fn set_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<(), RendererError>;
+    fn destroy(self: Box<Self>, ctx: &mut Box<dyn RenderingBackend>);
+}
+
+impl<T: for<'init> Renderer<'init>> DynRenderer for T {
+    fn render(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        transforms: &Transforms,
+        frame: &FrameContext,
+    ) {
+        Renderer::render(self, ctx, transforms, frame);
+    }
+
+    fn set_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<(), RendererError> {
+        Renderer::set_mesh(self, ctx, mesh)
+    }
+
+    fn destroy(self: Box<Self>, ctx: &mut Box<dyn RenderingBackend>) {
+        Renderer::destroy(*self, ctx);
+    }
+}
+
+/// What any renderer may draw with, each taking the parts it needs.
+pub struct MaterialDesc {
+    pub mesh: MeshHandle,
+    /// by slot, `texture`, `ramp_texture` (uploaded with `Sampler::LOOKUP`) or `sdw_texture`
+    pub textures: HashMap<&'static str, TextureHandle>,
+    /// r,g,b,a of renderers drawing one color
+    pub color: [f32; 4],
+    pub toon: ToonParams,
+}
+
+impl MaterialDesc {
+    /// without textures, with the default color and parameters
+    pub fn new(mesh: MeshHandle) -> Self {
+        Self {
+            mesh,
+            textures: HashMap::new(),
+            color: crate::Default::COLOR,
+            toon: ToonParams::default(),
+        }
+    }
+
+    pub fn release(self, ctx: &mut Box<dyn RenderingBackend>) {
+        self.mesh.release(ctx);
+        for (_, texture) in self.textures {
+            texture.release(ctx);
+        }
+    }
+
+    /// Take the textures of `slots`, or release everything if one is missing.
+    fn take<const N: usize>(
+        mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        slots: [&'static str; N],
+    ) -> Result<(MeshHandle, [TextureHandle; N]), RendererError> {
+        if let Some(slot) = slots.iter().find(|s| !self.textures.contains_key(*s)) {
+            self.release(ctx);
+            return Err(RendererError::MissingTexture(slot));
+        }
+        let textures = slots.map(|slot| self.textures.remove(slot).unwrap());
+        // release the slots the renderer doesn't use
+        for (_, texture) in self.textures {
+            texture.release(ctx);
+        }
+        Ok((self.mesh, textures))
+    }
+}
+
+/// Builds a renderer from a description. On error the handles of the description are released.
+pub type Constructor = Box<
+    dyn Fn(
+        &mut Box<dyn RenderingBackend>,
+        MaterialDesc,
+    ) -> Result<Box<dyn DynRenderer>, RendererError>,
+>;
+
+/// Renderers by name. `Registry::default()` has `default`, `textured` and `toon`.
+pub struct Registry {
+    constructors: HashMap<String, Constructor>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            "default",
+            Box::new(|ctx, desc| {
+                let color = desc.color;
+                let (mesh, []) = desc.take(ctx, [])?;
+                let mut renderer = crate::Default::new_shared(ctx, mesh)?;
+                renderer.set_color(color);
+                Ok(Box::new(renderer))
+            }),
+        );
+        registry.register(
+            "textured",
+            Box::new(|ctx, desc| {
+                let (mesh, [texture]) = desc.take(ctx, ["texture"])?;
+                Ok(Box::new(crate::Textured::new_shared(ctx, mesh, texture)?))
+            }),
+        );
+        registry.register(
+            "toon",
+            Box::new(|ctx, desc| {
+                let params = desc.toon;
+                let (mesh, [texture, ramp_texture, sdw_texture]) =
+                    desc.take(ctx, ["texture", "ramp_texture", "sdw_texture"])?;
+                let textures = ToonTextures {
+                    texture,
+                    ramp_texture,
+                    sdw_texture,
+                };
+                Ok(Box::new(crate::Toon::new_shared(
+                    ctx, mesh, textures, params,
+                )?))
+            }),
+        );
+        registry
+    }
+}
+
+impl Registry {
+    /// without any renderer
+    pub fn new() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Add or replace the renderer under `name`.
+    pub fn register(&mut self, name: &str, constructor: Constructor) {
+        self.constructors.insert(name.to_string(), constructor);
+    }
+
+    /// the registered names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.constructors.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Build the renderer under `name`. On error the handles of `desc` are released.
+    pub fn build(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        name: &str,
+        desc: MaterialDesc,
+    ) -> Result<Box<dyn DynRenderer>, RendererError> {
+        match self.constructors.get(name) {
+            Some(constructor) => constructor(ctx, desc),
+            None => {
+                desc.release(ctx);
+                Err(RendererError::UnknownRenderer(name.to_string()))
+            }
+        }
+    }
+}
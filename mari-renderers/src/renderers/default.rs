@@ -1,75 +1,93 @@
 use miniquad::*;
 
+use super::program::Program;
+use crate::MeshHandle;
+use crate::cache::Attribute;
+
 pub struct InitParams<'a> {
     pub model: &'a mari_formats::Model,
 }
 
 pub struct Default {
-    index_cnt: usize,
-    bindings: Bindings,
-    pipeline: Pipeline,
+    program: Program,
+    /// r,g,b,a
+    color: [f32; 4],
 }
 
 impl<'init> crate::Renderer<'init> for Default {
     type InitParams = InitParams<'init>;
 
-    fn new(ctx: &mut Box<dyn RenderingBackend>, params: InitParams) -> Self {
-        let model = params.model;
+    fn new(
+        ctx: &mut Box<dyn RenderingBackend>,
+        params: InitParams,
+    ) -> Result<Self, crate::RendererError> {
+        let mesh = MeshHandle::upload(ctx, params.model)?;
+        Self::new_shared(ctx, mesh)
+    }
 
-        let vertex_buffer = ctx.new_buffer(
-            BufferType::VertexBuffer,
-            BufferUsage::Immutable,
-            BufferSource::slice(&model.vertices),
-        );
-        let index_buffer = ctx.new_buffer(
-            BufferType::IndexBuffer,
-            BufferUsage::Immutable,
-            BufferSource::slice(&model.mesh),
-        );
-        let bindings = Bindings {
-            vertex_buffers: vec![vertex_buffer],
-            index_buffer,
-            images: vec![],
-        };
+    fn render(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        transforms: &crate::Transforms,
+        _frame: &crate::FrameContext,
+    ) {
+        let mut uniform = [0.0; 20];
+        uniform[..16].copy_from_slice(&transforms.mvp().to_cols_array());
+        uniform[16..].copy_from_slice(&self.color);
+        self.program.draw(ctx, &uniform);
+    }
 
-        let shader = ctx
-            .new_shader(
-                ShaderSource::Glsl {
-                    vertex: include_str!("shaders/default-vert.glsl"),
-                    fragment: include_str!("shaders/default-frag.glsl"),
-                },
-                ShaderMeta {
-                    images: vec![],
-                    uniforms: UniformBlockLayout {
-                        uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
-                    },
-                },
-            )
-            .unwrap();
+    fn set_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<(), crate::RendererError> {
+        self.program.set_mesh(ctx, mesh)
+    }
 
-        let pipeline = ctx.new_pipeline(
-            &[BufferLayout::default()],
-            &[VertexAttribute::new("in_pos", VertexFormat::Float3)],
-            shader,
-            PipelineParams {
-                cull_face: CullFace::Back,
-                depth_test: Comparison::Less,
-                depth_write: true,
-                ..PipelineParams::default()
-            },
-        );
+    fn destroy(self, ctx: &mut Box<dyn RenderingBackend>) {
+        self.program.destroy(ctx);
+    }
+}
 
-        Self {
-            index_cnt: model.mesh.len(),
-            bindings,
-            pipeline,
-        }
+impl Default {
+    /// the color drawn until `set_color`
+    pub const COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+
+    /// Draw a mesh shared e.g. through `ResourceCache`. On error `mesh` is released.
+    pub fn new_shared(
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<Self, crate::RendererError> {
+        let program = Program::new(
+            ctx,
+            (
+                include_str!("shaders/default-vert.glsl"),
+                include_str!("shaders/default-frag.glsl"),
+            ),
+            ShaderMeta {
+                images: vec![],
+                uniforms: UniformBlockLayout {
+                    uniforms: vec![
+                        UniformDesc::new("mvp", UniformType::Mat4),
+                        UniformDesc::new("color", UniformType::Float4),
+                    ],
+                },
+            },
+            &[("in_pos", Attribute::Positions)],
+            CullFace::Back,
+            false,
+            mesh,
+            vec![],
+        )?;
+        Ok(Self {
+            program,
+            color: Self::COLOR,
+        })
     }
 
-    fn render(&self, ctx: &mut Box<dyn RenderingBackend>, mvp: &[f32; 16]) {
-        ctx.apply_pipeline(&self.pipeline);
-        ctx.apply_bindings(&self.bindings);
-        ctx.apply_uniforms(UniformsSource::table(mvp));
-        ctx.draw(0, self.index_cnt as i32, 1);
+    /// Draw with `color`, r,g,b,a, the alpha being ignored.
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.color = color;
     }
 }
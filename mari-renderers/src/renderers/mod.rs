@@ -1,4 +1,5 @@
 mod default;
+mod program;
 mod textured;
 mod toon;
 
@@ -9,4 +10,6 @@ pub use textured::InitParams as TexturedInitParams;
 pub use textured::Textured;
 
 pub use toon::InitParams as ToonInitParams;
+pub use toon::Params as ToonParams;
+pub use toon::Textures as ToonTextures;
 pub use toon::Toon;
@@ -0,0 +1,131 @@
+use miniquad::*;
+
+use crate::cache::Attribute;
+use crate::{MeshHandle, RendererError, TextureHandle, resources};
+
+/// The GPU state every renderer holds: its shader and pipeline, and the handles it draws.
+pub(crate) struct Program {
+    shader: ShaderId,
+    pipeline: Pipeline,
+    /// in the order of the shader inputs
+    attributes: Vec<Attribute>,
+    mesh: MeshHandle,
+    textures: Vec<TextureHandle>,
+    bindings: Bindings,
+}
+
+fn release_all(
+    ctx: &mut Box<dyn RenderingBackend>,
+    mesh: MeshHandle,
+    textures: Vec<TextureHandle>,
+) {
+    mesh.release(ctx);
+    for texture in textures {
+        texture.release(ctx);
+    }
+}
+
+impl Program {
+    /// `attributes` name the shader input of each buffer the shader reads, and `textures` go in
+    /// the order of `meta.images`. On error the handles are released.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        ctx: &mut Box<dyn RenderingBackend>,
+        (vertex, fragment): (&str, &str),
+        meta: ShaderMeta,
+        attributes: &[(&'static str, Attribute)],
+        cull_face: CullFace,
+        blend: bool,
+        mesh: MeshHandle,
+        textures: Vec<TextureHandle>,
+    ) -> Result<Self, RendererError> {
+        let (names, attributes): (Vec<_>, Vec<_>) = attributes.iter().copied().unzip();
+        let images = textures.iter().map(TextureHandle::id).collect();
+        let bindings = match mesh.bindings(&attributes, images) {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                release_all(ctx, mesh, textures);
+                return Err(e);
+            }
+        };
+        let shader = match resources::new_shader(ctx, vertex, fragment, meta) {
+            Ok(shader) => shader,
+            Err(e) => {
+                release_all(ctx, mesh, textures);
+                return Err(e);
+            }
+        };
+        let formats: Vec<_> = names
+            .into_iter()
+            .zip(attributes.iter().map(|a| a.format()))
+            .collect();
+        let pipeline = resources::new_pipeline(ctx, &formats, shader, cull_face, blend);
+        Ok(Self {
+            shader,
+            pipeline,
+            attributes,
+            mesh,
+            textures,
+            bindings,
+        })
+    }
+
+    pub(crate) fn draw<T>(&self, ctx: &mut Box<dyn RenderingBackend>, uniforms: &T) {
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.apply_uniforms(UniformsSource::table(uniforms));
+        let (first, count) = self.mesh.range();
+        ctx.draw(first as i32, count as i32, 1);
+    }
+
+    /// On error `mesh` is released and the program is unchanged.
+    pub(crate) fn set_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<(), RendererError> {
+        match self.mesh_bindings(&mesh) {
+            Ok(bindings) => {
+                self.replace_mesh(ctx, mesh, bindings);
+                Ok(())
+            }
+            Err(e) => {
+                mesh.release(ctx);
+                Err(e)
+            }
+        }
+    }
+
+    /// The bindings drawing `mesh`, for `replace_mesh` once every program of a renderer took it.
+    pub(crate) fn mesh_bindings(&self, mesh: &MeshHandle) -> Result<Bindings, RendererError> {
+        mesh.bindings(&self.attributes, self.bindings.images.clone())
+    }
+
+    /// Draw `mesh` through `bindings` from `mesh_bindings`, releasing the previous mesh.
+    pub(crate) fn replace_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+        bindings: Bindings,
+    ) {
+        self.bindings = bindings;
+        std::mem::replace(&mut self.mesh, mesh).release(ctx);
+    }
+
+    /// `textures` in the order of the shader images
+    pub(crate) fn set_textures(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        textures: Vec<TextureHandle>,
+    ) {
+        self.bindings.images = textures.iter().map(TextureHandle::id).collect();
+        for texture in std::mem::replace(&mut self.textures, textures) {
+            texture.release(ctx);
+        }
+    }
+
+    pub(crate) fn destroy(self, ctx: &mut Box<dyn RenderingBackend>) {
+        resources::delete_program(ctx, self.pipeline, self.shader);
+        release_all(ctx, self.mesh, self.textures);
+    }
+}
@@ -1,3 +1,5 @@
+uniform vec4 color;
+
 void main() {
-    gl_FragColor = vec4(0.7, 0.7, 0.7, 1.0);
+    gl_FragColor = color;
 }
\ No newline at end of file
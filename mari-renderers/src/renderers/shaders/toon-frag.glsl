@@ -4,10 +4,11 @@ varying float rmp;
 uniform sampler2D tex;
 uniform sampler2D rmp_tex;
 uniform sampler2D sdw_tex;
+uniform float shadowThreshold;
 
 void main() {
   vec3 rmpCoeff;
-  if(texture2D(sdw_tex, uv).a > 0.5) {
+  if(texture2D(sdw_tex, uv).a > shadowThreshold) {
     rmpCoeff = texture2D(rmp_tex, vec2(rmp, 0)).rgb;
   } else {
     rmpCoeff = vec3(1.0, 1.0, 1.0);
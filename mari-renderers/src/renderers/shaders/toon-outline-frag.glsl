@@ -0,0 +1,5 @@
+uniform vec4 outlineColor;
+
+void main() {
+  gl_FragColor = vec4(outlineColor.rgb, 1);
+}
@@ -0,0 +1,21 @@
+attribute vec4 in_pos;
+attribute vec3 in_norm;
+attribute vec4 in_color;
+
+uniform mat4 mvp;
+uniform vec2 viewport;
+uniform float outlineWidth;
+uniform float outlineOffset;
+
+void main() {
+  // pushed out along the normal in model space first, as Unity's toon shaders do
+  vec4 pos = mvp * (in_pos + vec4(in_norm * outlineOffset * in_color.a, 0));
+  // the normal on screen, in pixels
+  vec2 dir = (mvp * vec4(in_norm, 0)).xy * viewport;
+  if(dot(dir, dir) > 0.0) {
+    dir = normalize(dir);
+  }
+  // back to clip space, where the offset grows with w to stay the same on screen
+  vec2 offset = dir * outlineWidth * in_color.a * 2.0 / viewport;
+  gl_Position = vec4(pos.xy + offset * pos.w, pos.zw);
+}
@@ -4,12 +4,13 @@ attribute vec3 in_norm;
 
 uniform mat4 mvp;
 uniform vec3 lightPosModelSpace;
+uniform float rampScale;
 
 varying vec2 uv;
 varying float rmp;
 
 void main() {
   uv = in_uv;
-  rmp = 0.35 - 0.35 * dot(in_norm, normalize(in_pos.xyz - lightPosModelSpace));
+  rmp = rampScale - rampScale * dot(in_norm, normalize(in_pos.xyz - lightPosModelSpace));
   gl_Position = mvp * in_pos;
 }
@@ -1,86 +1,95 @@
 use miniquad::*;
 
+use super::program::Program;
+use crate::cache::Attribute;
+use crate::{MeshHandle, TextureHandle};
+
 pub struct InitParams<'a> {
     pub model: &'a mari_formats::Model,
-    pub texture: &'a mari_formats::TextureRGBA8,
+    pub texture: &'a dyn crate::TextureData,
+    pub sampler: crate::Sampler,
 }
 pub struct Textured {
-    index_cnt: usize,
-    bindings: Bindings,
-    pipeline: Pipeline,
+    program: Program,
 }
 
 impl<'init> crate::Renderer<'init> for Textured {
     type InitParams = InitParams<'init>;
 
-    fn new(ctx: &mut Box<dyn RenderingBackend>, params: InitParams) -> Self {
-        let InitParams { model, texture } = params;
-
-        let mut interleaved_buffer =
-            Vec::<f32>::with_capacity(model.vertices.len() + model.uvs.len());
-        for i in 0..model.vertices.len() / 3 {
-            interleaved_buffer.extend_from_slice(&model.vertices[3 * i..3 * i + 3]);
-            interleaved_buffer.extend_from_slice(&model.uvs[2 * i..2 * i + 2]);
-        }
-        let texture = ctx.new_texture_from_rgba8(texture.width, texture.height(), &texture.data);
+    fn new(
+        ctx: &mut Box<dyn RenderingBackend>,
+        params: InitParams,
+    ) -> Result<Self, crate::RendererError> {
+        let InitParams {
+            model,
+            texture,
+            sampler,
+        } = params;
 
-        let vertex_buffer = ctx.new_buffer(
-            BufferType::VertexBuffer,
-            BufferUsage::Immutable,
-            BufferSource::slice(&interleaved_buffer),
-        );
-        let index_buffer = ctx.new_buffer(
-            BufferType::IndexBuffer,
-            BufferUsage::Immutable,
-            BufferSource::slice(&model.mesh),
-        );
-        let bindings = Bindings {
-            vertex_buffers: vec![vertex_buffer],
-            index_buffer,
-            images: vec![texture],
+        let mesh = MeshHandle::upload(ctx, model)?;
+        let texture = match TextureHandle::upload(ctx, texture, sampler) {
+            Ok(texture) => texture,
+            Err(e) => {
+                mesh.release(ctx);
+                return Err(e);
+            }
         };
+        Self::new_shared(ctx, mesh, texture)
+    }
 
-        let shader = ctx
-            .new_shader(
-                ShaderSource::Glsl {
-                    vertex: include_str!("shaders/textured-vert.glsl"),
-                    fragment: include_str!("shaders/textured-frag.glsl"),
-                },
-                ShaderMeta {
-                    images: vec!["tex".to_string()],
-                    uniforms: UniformBlockLayout {
-                        uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
-                    },
-                },
-            )
-            .unwrap();
+    fn render(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        transforms: &crate::Transforms,
+        _frame: &crate::FrameContext,
+    ) {
+        self.program.draw(ctx, &transforms.mvp().to_cols_array());
+    }
 
-        let pipeline = ctx.new_pipeline(
-            &[BufferLayout::default()],
-            &[
-                VertexAttribute::new("in_pos", VertexFormat::Float3),
-                VertexAttribute::new("in_uv", VertexFormat::Float2),
-            ],
-            shader,
-            PipelineParams {
-                cull_face: CullFace::Back,
-                depth_test: Comparison::Less,
-                depth_write: true,
-                ..PipelineParams::default()
-            },
-        );
+    fn set_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<(), crate::RendererError> {
+        self.program.set_mesh(ctx, mesh)
+    }
+
+    fn destroy(self, ctx: &mut Box<dyn RenderingBackend>) {
+        self.program.destroy(ctx);
+    }
+}
 
-        Self {
-            index_cnt: model.mesh.len(),
-            bindings,
-            pipeline,
-        }
+impl Textured {
+    /// Draw a mesh and texture shared e.g. through `ResourceCache`. On error the handles are
+    /// released.
+    pub fn new_shared(
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+        texture: TextureHandle,
+    ) -> Result<Self, crate::RendererError> {
+        let program = Program::new(
+            ctx,
+            (
+                include_str!("shaders/textured-vert.glsl"),
+                include_str!("shaders/textured-frag.glsl"),
+            ),
+            ShaderMeta {
+                images: vec!["tex".to_string()],
+                uniforms: UniformBlockLayout {
+                    uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
+                },
+            },
+            &[("in_pos", Attribute::Positions), ("in_uv", Attribute::Uvs)],
+            CullFace::Back,
+            true,
+            mesh,
+            vec![texture],
+        )?;
+        Ok(Self { program })
     }
 
-    fn render(&self, ctx: &mut Box<dyn RenderingBackend>, mvp: &[f32; 16]) {
-        ctx.apply_pipeline(&self.pipeline);
-        ctx.apply_bindings(&self.bindings);
-        ctx.apply_uniforms(UniformsSource::table(mvp));
-        ctx.draw(0, self.index_cnt as i32, 1);
+    /// Replace the texture, e.g. with one uploaded by `TextureHandle::upload`.
+    pub fn set_texture(&mut self, ctx: &mut Box<dyn RenderingBackend>, texture: TextureHandle) {
+        self.program.set_textures(ctx, vec![texture]);
     }
 }
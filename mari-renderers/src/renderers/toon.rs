@@ -1,125 +1,312 @@
 use miniquad::*;
 
+use super::program::Program;
+use crate::cache::Attribute;
+use crate::{MeshHandle, TextureHandle};
+
 pub struct InitParams<'a> {
     pub model: &'a mari_formats::Model,
-    pub texture: &'a mari_formats::TextureRGBA8,
-    pub ramp_texture: &'a mari_formats::TextureRGBA8,
-    pub sdw_texture: &'a mari_formats::TextureRGBA8,
+    pub texture: &'a dyn crate::TextureData,
+    pub ramp_texture: &'a dyn crate::TextureData,
+    pub sdw_texture: &'a dyn crate::TextureData,
+    /// for `texture` and `sdw_texture`, the ramp is always sampled with `Sampler::LOOKUP`
+    pub sampler: crate::Sampler,
+    pub params: Params,
+}
+
+/// The parameters of a `mari_formats::ToonMaterial` besides its textures, by default those of
+/// the game's shader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    /// alpha of the shadow mask above which the ramp applies
+    pub shadow_threshold: f32,
+    /// the ramp is sampled at `ramp_scale * (1 + cos)` of the angle between normal and light
+    pub ramp_scale: f32,
+    /// in pixels, scaled by the alpha of each vertex color, 0 for no outline
+    pub outline_width: f32,
+    /// in model units along the normal, like `outline_width` scaled by the vertex alpha and
+    /// added to it
+    pub outline_offset: f32,
+    /// r,g,b,a, the alpha being ignored
+    pub outline_color: [f32; 4],
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            shadow_threshold: mari_formats::ToonMaterial::SHADOW_THRESHOLD,
+            ramp_scale: mari_formats::ToonMaterial::RAMP_SCALE,
+            outline_width: 0.0,
+            outline_offset: 0.0,
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+impl Params {
+    /// Model units per unit of `ToonMaterial::outline_width`. Unity's toon shaders push the
+    /// outline out by `_Outline_Width * 0.001` along the object space normal, so the outline is
+    /// up to `outline_width * 0.001 * f / w` pixels wide, for normals across the view, `f` being
+    /// the focal length in pixels and `w` the depth of the vertex.
+    pub const OUTLINE_UNIT: f32 = 0.001;
+
+    /// the parameters of `material`
+    pub fn from_material(material: &mari_formats::ToonMaterial) -> Self {
+        Self {
+            shadow_threshold: material.shadow_threshold,
+            ramp_scale: material.ramp_scale,
+            outline_width: 0.0,
+            outline_offset: material.outline_width * Self::OUTLINE_UNIT,
+            outline_color: material.outline_color,
+        }
+    }
+
+    /// whether there is an outline to draw
+    pub fn outlined(&self) -> bool {
+        self.outline_width > 0.0 || self.outline_offset > 0.0
+    }
+}
+
+/// The uploaded textures of `Toon`.
+#[derive(Clone)]
+pub struct Textures {
+    pub texture: TextureHandle,
+    /// uploaded with `Sampler::LOOKUP`
+    pub ramp_texture: TextureHandle,
+    pub sdw_texture: TextureHandle,
 }
-pub struct Toon {
-    index_cnt: usize,
-    bindings: Bindings,
-    pipeline: Pipeline,
 
-    light_pos_in_model_space: [f32; 3],
+impl Textures {
+    /// Upload the textures of `params`. On error those done are released.
+    pub fn upload(
+        ctx: &mut Box<dyn RenderingBackend>,
+        params: &InitParams,
+    ) -> Result<Self, crate::RendererError> {
+        let texture = TextureHandle::upload(ctx, params.texture, params.sampler)?;
+        let ramp_texture =
+            match TextureHandle::upload(ctx, params.ramp_texture, crate::Sampler::LOOKUP) {
+                Ok(ramp_texture) => ramp_texture,
+                Err(e) => {
+                    texture.release(ctx);
+                    return Err(e);
+                }
+            };
+        match TextureHandle::upload(ctx, params.sdw_texture, params.sampler) {
+            Ok(sdw_texture) => Ok(Self {
+                texture,
+                ramp_texture,
+                sdw_texture,
+            }),
+            Err(e) => {
+                texture.release(ctx);
+                ramp_texture.release(ctx);
+                Err(e)
+            }
+        }
+    }
+
+    /// in the order of the shader images
+    fn into_vec(self) -> Vec<TextureHandle> {
+        vec![self.texture, self.ramp_texture, self.sdw_texture]
+    }
+}
+
+/// Cel shading through the ramp texture where the shadow mask allows, and an ink outline drawn
+/// by extruding the back faces of the mesh along their normals.
+pub struct Toon {
+    program: Program,
+    outline: Program,
+    params: Params,
 }
 
 impl<'init> crate::Renderer<'init> for Toon {
     type InitParams = InitParams<'init>;
 
-    fn new(ctx: &mut Box<dyn RenderingBackend>, params: InitParams) -> Self {
-        let InitParams {
-            model,
-            texture,
-            ramp_texture,
-            sdw_texture,
-        } = params;
-
-        let mut interleaved_buffer =
-            Vec::<f32>::with_capacity(model.vertices.len() + model.uvs.len() + model.normals.len());
-        for i in 0..model.vertices.len() / 3 {
-            interleaved_buffer.extend_from_slice(&model.vertices[3 * i..3 * i + 3]);
-            interleaved_buffer.extend_from_slice(&model.uvs[2 * i..2 * i + 2]);
-            interleaved_buffer.extend_from_slice(&model.normals[3 * i..3 * i + 3]);
+    fn new(
+        ctx: &mut Box<dyn RenderingBackend>,
+        params: InitParams,
+    ) -> Result<Self, crate::RendererError> {
+        let mesh = MeshHandle::upload(ctx, params.model)?;
+        let textures = match Textures::upload(ctx, &params) {
+            Ok(textures) => textures,
+            Err(e) => {
+                mesh.release(ctx);
+                return Err(e);
+            }
+        };
+        Self::new_shared(ctx, mesh, textures, params.params)
+    }
+
+    fn render(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        transforms: &crate::Transforms,
+        frame: &crate::FrameContext,
+    ) {
+        let light_pos_in_model_space = transforms.to_model_space(frame.light_pos());
+        let mut uniform = [0.0; 21];
+        uniform[..16].copy_from_slice(&transforms.mvp().to_cols_array());
+        uniform[16..19].copy_from_slice(&light_pos_in_model_space.to_array());
+        uniform[19] = self.params.ramp_scale;
+        uniform[20] = self.params.shadow_threshold;
+        self.program.draw(ctx, &uniform);
+
+        if self.params.outlined() && frame.viewport.iter().all(|v| *v > 0.0) {
+            let mut uniform = [0.0; 24];
+            uniform[..16].copy_from_slice(&transforms.mvp().to_cols_array());
+            uniform[16..20].copy_from_slice(&self.params.outline_color);
+            uniform[20..22].copy_from_slice(&frame.viewport);
+            uniform[22] = self.params.outline_width;
+            uniform[23] = self.params.outline_offset;
+            self.outline.draw(ctx, &uniform);
         }
-        let texture = ctx.new_texture_from_rgba8(texture.width, texture.height(), &texture.data);
-        let ramp_texture = ctx.new_texture_from_rgba8(
-            ramp_texture.width,
-            ramp_texture.height(),
-            &ramp_texture.data,
-        );
-        let sdw_texture =
-            ctx.new_texture_from_rgba8(sdw_texture.width, sdw_texture.height(), &sdw_texture.data);
+    }
 
-        let vertex_buffer = ctx.new_buffer(
-            BufferType::VertexBuffer,
-            BufferUsage::Immutable,
-            BufferSource::slice(&interleaved_buffer),
-        );
-        let index_buffer = ctx.new_buffer(
-            BufferType::IndexBuffer,
-            BufferUsage::Immutable,
-            BufferSource::slice(&model.mesh),
-        );
-        let bindings = Bindings {
-            vertex_buffers: vec![vertex_buffer],
-            index_buffer,
-            images: vec![texture, ramp_texture, sdw_texture],
-        };
+    fn set_mesh(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+    ) -> Result<(), crate::RendererError> {
+        // both passes take the mesh or neither does
+        let bindings = self
+            .program
+            .mesh_bindings(&mesh)
+            .and_then(|program| Ok((program, self.outline.mesh_bindings(&mesh)?)));
+        match bindings {
+            Ok((program, outline)) => {
+                self.program.replace_mesh(ctx, mesh.clone(), program);
+                self.outline.replace_mesh(ctx, mesh, outline);
+                Ok(())
+            }
+            Err(e) => {
+                mesh.release(ctx);
+                Err(e)
+            }
+        }
+    }
 
-        let shader = ctx
-            .new_shader(
-                ShaderSource::Glsl {
-                    vertex: include_str!("shaders/toon-vert.glsl"),
-                    fragment: include_str!("shaders/toon-frag.glsl"),
-                },
-                ShaderMeta {
-                    images: vec![
-                        "tex".to_string(),
-                        "rmp_tex".to_string(),
-                        "sdw_tex".to_string(),
+    fn destroy(self, ctx: &mut Box<dyn RenderingBackend>) {
+        self.program.destroy(ctx);
+        self.outline.destroy(ctx);
+    }
+}
+
+impl Toon {
+    /// Draw a mesh and textures shared e.g. through `ResourceCache`. On error the handles are
+    /// released.
+    pub fn new_shared(
+        ctx: &mut Box<dyn RenderingBackend>,
+        mesh: MeshHandle,
+        textures: Textures,
+        params: Params,
+    ) -> Result<Self, crate::RendererError> {
+        let outline = Program::new(
+            ctx,
+            (
+                include_str!("shaders/toon-outline-vert.glsl"),
+                include_str!("shaders/toon-outline-frag.glsl"),
+            ),
+            ShaderMeta {
+                images: vec![],
+                uniforms: UniformBlockLayout {
+                    uniforms: vec![
+                        UniformDesc::new("mvp", UniformType::Mat4),
+                        UniformDesc::new("outlineColor", UniformType::Float4),
+                        UniformDesc::new("viewport", UniformType::Float2),
+                        UniformDesc::new("outlineWidth", UniformType::Float1),
+                        UniformDesc::new("outlineOffset", UniformType::Float1),
                     ],
-                    uniforms: UniformBlockLayout {
-                        uniforms: vec![
-                            UniformDesc::new("mvp", UniformType::Mat4),
-                            UniformDesc::new("lightPosModelSpace", UniformType::Float3),
-                        ],
-                    },
                 },
-            )
-            .unwrap();
-
-        let pipeline = ctx.new_pipeline(
-            &[BufferLayout::default()],
+            },
             &[
-                VertexAttribute::new("in_pos", VertexFormat::Float3),
-                VertexAttribute::new("in_uv", VertexFormat::Float2),
-                VertexAttribute::new("in_norm", VertexFormat::Float3),
+                ("in_pos", Attribute::Positions),
+                ("in_norm", Attribute::Normals),
+                ("in_color", Attribute::Colors),
             ],
-            shader,
-            PipelineParams {
-                cull_face: CullFace::Back,
-                depth_test: Comparison::Less,
-                depth_write: true,
-                ..PipelineParams::default()
+            // the inside of the hull, behind the mesh but around its silhouette
+            CullFace::Front,
+            false,
+            mesh.clone(),
+            vec![],
+        );
+        let outline = match outline {
+            Ok(outline) => outline,
+            Err(e) => {
+                mesh.release(ctx);
+                for texture in textures.into_vec() {
+                    texture.release(ctx);
+                }
+                return Err(e);
+            }
+        };
+        let program = Program::new(
+            ctx,
+            (
+                include_str!("shaders/toon-vert.glsl"),
+                include_str!("shaders/toon-frag.glsl"),
+            ),
+            ShaderMeta {
+                images: vec![
+                    "tex".to_string(),
+                    "rmp_tex".to_string(),
+                    "sdw_tex".to_string(),
+                ],
+                uniforms: UniformBlockLayout {
+                    uniforms: vec![
+                        UniformDesc::new("mvp", UniformType::Mat4),
+                        UniformDesc::new("lightPosModelSpace", UniformType::Float3),
+                        UniformDesc::new("rampScale", UniformType::Float1),
+                        UniformDesc::new("shadowThreshold", UniformType::Float1),
+                    ],
+                },
             },
+            &[
+                ("in_pos", Attribute::Positions),
+                ("in_uv", Attribute::Uvs),
+                ("in_norm", Attribute::Normals),
+            ],
+            CullFace::Back,
+            false,
+            mesh,
+            textures.into_vec(),
         );
-
-        Self {
-            index_cnt: model.mesh.len(),
-            bindings,
-            pipeline,
-
-            light_pos_in_model_space: [0.0, 0.0, 1.0],
+        match program {
+            Ok(program) => Ok(Self {
+                program,
+                outline,
+                params,
+            }),
+            Err(e) => {
+                outline.destroy(ctx);
+                Err(e)
+            }
         }
     }
 
-    fn render(&self, ctx: &mut Box<dyn RenderingBackend>, mvp: &[f32; 16]) {
-        ctx.apply_pipeline(&self.pipeline);
-        ctx.apply_bindings(&self.bindings);
-
-        let mut uniform = [0.0; 19];
-        uniform[..16].copy_from_slice(mvp);
-        uniform[16..].copy_from_slice(&self.light_pos_in_model_space);
-        ctx.apply_uniforms(UniformsSource::table(&uniform));
+    /// Replace the textures, e.g. to change costumes.
+    pub fn set_textures(&mut self, ctx: &mut Box<dyn RenderingBackend>, textures: Textures) {
+        self.program.set_textures(ctx, textures.into_vec());
+    }
 
-        ctx.draw(0, self.index_cnt as i32, 1);
+    pub fn set_params(&mut self, params: Params) {
+        self.params = params;
     }
 }
 
-impl Toon {
-    /// set light pos in the MODEL space
-    pub fn set_light_pos(&mut self, p: &[f32; 3]) {
-        self.light_pos_in_model_space = *p;
+impl<'a> InitParams<'a> {
+    /// textures and parameters of a material loaded e.g. with
+    /// `mari_formats::unity::Material::to_toon`
+    pub fn from_material(
+        model: &'a mari_formats::Model,
+        material: &'a mari_formats::ToonMaterial,
+    ) -> Self {
+        Self {
+            model,
+            texture: &material.texture,
+            ramp_texture: &material.ramp_texture,
+            sdw_texture: &material.sdw_texture,
+            sampler: crate::Sampler::default(),
+            params: Params::from_material(material),
+        }
     }
 }
@@ -0,0 +1,129 @@
+//! Creation and deletion of the GPU objects of the renderers, counted to find leaks.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use miniquad::*;
+
+static BUFFERS: AtomicUsize = AtomicUsize::new(0);
+static TEXTURES: AtomicUsize = AtomicUsize::new(0);
+static SHADERS: AtomicUsize = AtomicUsize::new(0);
+static PIPELINES: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts of live GPU objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GpuObjects {
+    pub buffers: usize,
+    pub textures: usize,
+    pub shaders: usize,
+    pub pipelines: usize,
+}
+
+impl GpuObjects {
+    pub fn total(&self) -> usize {
+        self.buffers + self.textures + self.shaders + self.pipelines
+    }
+}
+
+/// The GPU objects created by this crate and not destroyed yet, over all contexts.
+pub fn live_gpu_objects() -> GpuObjects {
+    GpuObjects {
+        buffers: BUFFERS.load(Ordering::Relaxed),
+        textures: TEXTURES.load(Ordering::Relaxed),
+        shaders: SHADERS.load(Ordering::Relaxed),
+        pipelines: PIPELINES.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn new_buffer(
+    ctx: &mut Box<dyn RenderingBackend>,
+    type_: BufferType,
+    data: BufferSource,
+) -> BufferId {
+    BUFFERS.fetch_add(1, Ordering::Relaxed);
+    ctx.new_buffer(type_, BufferUsage::Immutable, data)
+}
+
+pub(crate) fn new_texture(
+    ctx: &mut Box<dyn RenderingBackend>,
+    source: TextureSource,
+    params: TextureParams,
+) -> TextureId {
+    TEXTURES.fetch_add(1, Ordering::Relaxed);
+    ctx.new_texture(TextureAccess::Static, source, params)
+}
+
+pub(crate) fn new_shader(
+    ctx: &mut Box<dyn RenderingBackend>,
+    vertex: &str,
+    fragment: &str,
+    meta: ShaderMeta,
+) -> Result<ShaderId, crate::RendererError> {
+    let shader = ctx
+        .new_shader(ShaderSource::Glsl { vertex, fragment }, meta)
+        .map_err(|e| crate::RendererError::from_shader(e, vertex, fragment))?;
+    SHADERS.fetch_add(1, Ordering::Relaxed);
+    Ok(shader)
+}
+
+/// depth tested with LESS, as all renderers draw, and each attribute in its own buffer as
+/// `MeshHandle` uploads them, blending by source alpha if `blend`
+pub(crate) fn new_pipeline(
+    ctx: &mut Box<dyn RenderingBackend>,
+    attributes: &[(&'static str, VertexFormat)],
+    shader: ShaderId,
+    cull_face: CullFace,
+    blend: bool,
+) -> Pipeline {
+    PIPELINES.fetch_add(1, Ordering::Relaxed);
+    let attributes: Vec<VertexAttribute> = attributes
+        .iter()
+        .enumerate()
+        .map(|(i, (name, format))| VertexAttribute::with_buffer(name, *format, i))
+        .collect();
+    ctx.new_pipeline(
+        &vec![BufferLayout::default(); attributes.len()],
+        &attributes,
+        shader,
+        PipelineParams {
+            cull_face,
+            depth_test: Comparison::Less,
+            depth_write: true,
+            color_blend: blend.then(|| {
+                BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )
+            }),
+            ..PipelineParams::default()
+        },
+    )
+}
+
+pub(crate) fn delete_buffer(ctx: &mut Box<dyn RenderingBackend>, buffer: BufferId) {
+    BUFFERS.fetch_sub(1, Ordering::Relaxed);
+    ctx.delete_buffer(buffer);
+}
+
+pub(crate) fn delete_textures(ctx: &mut Box<dyn RenderingBackend>, textures: &[TextureId]) {
+    for texture in textures {
+        TEXTURES.fetch_sub(1, Ordering::Relaxed);
+        ctx.delete_texture(*texture);
+    }
+}
+
+pub(crate) fn delete_shader(ctx: &mut Box<dyn RenderingBackend>, shader: ShaderId) {
+    SHADERS.fetch_sub(1, Ordering::Relaxed);
+    ctx.delete_shader(shader);
+}
+
+/// Delete a renderer's pipeline and shader.
+pub(crate) fn delete_program(
+    ctx: &mut Box<dyn RenderingBackend>,
+    pipeline: Pipeline,
+    shader: ShaderId,
+) {
+    PIPELINES.fetch_sub(1, Ordering::Relaxed);
+    ctx.delete_pipeline(pipeline);
+    delete_shader(ctx, shader);
+}
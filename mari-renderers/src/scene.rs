@@ -0,0 +1,249 @@
+//! Every actor of a scene, drawn in one pass.
+
+use std::collections::HashMap;
+
+use glam::{Mat4, Vec3};
+use miniquad::*;
+
+use crate::{
+    DynRenderer, FrameContext, MaterialDesc, Registry, RendererError, ResourceCache, Sampler,
+    ToonParams, Transforms,
+};
+
+/// One submesh of an actor with the renderer resolved for its material.
+struct Draw {
+    actor: String,
+    renderer: Box<dyn DynRenderer>,
+    /// blended, so drawn after the opaque draws and back to front
+    transparent: bool,
+    /// of the bounding box of the submesh, in model space
+    center: Vec3,
+}
+
+/// Draws the body and parts of every actor of a `Scene`, each submesh with the renderer of
+/// its material in `Scene::materials`:
+///
+/// - `default` in the color of an unlit material, or the base color of a PBR one,
+/// - `textured` for a textured material, or a PBR one with a base color texture,
+/// - `toon` for a toon material.
+///
+/// Submeshes without a material are drawn `textured` with the texture named after their actor
+/// in `Scene::textures`, else `default`. Opaque submeshes are drawn front to back, then
+/// transparent ones, textured with any alpha below 255, back to front.
+pub struct SceneRenderer {
+    draws: Vec<Draw>,
+    /// model transform by actor name, identity if unset
+    transforms: HashMap<String, Mat4>,
+}
+
+impl SceneRenderer {
+    /// Upload `scene` through `cache`, sharing the meshes and textures already there.
+    pub fn new(
+        ctx: &mut Box<dyn RenderingBackend>,
+        scene: &mari_formats::Scene,
+        cache: &mut ResourceCache,
+        registry: &Registry,
+    ) -> Result<Self, RendererError> {
+        let mut renderer = Self {
+            draws: Vec::new(),
+            transforms: HashMap::new(),
+        };
+        // sorted, so draws at the same depth keep an order from one run to the next
+        let mut actors: Vec<_> = scene.actors.iter().collect();
+        actors.sort_unstable_by_key(|(name, _)| *name);
+        for (name, actor) in actors {
+            let models = std::iter::once(&actor.body).chain(actor.parts.iter().map(|p| &p.model));
+            for model in models {
+                if let Err(e) = renderer.add_model(ctx, scene, cache, registry, name, model) {
+                    renderer.destroy(ctx);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(renderer)
+    }
+
+    fn add_model(
+        &mut self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        scene: &mari_formats::Scene,
+        cache: &mut ResourceCache,
+        registry: &Registry,
+        actor: &str,
+        model: &mari_formats::Model,
+    ) -> Result<(), RendererError> {
+        let mesh = cache.mesh(ctx, model)?;
+        let whole = [mari_formats::Submesh {
+            first: 0,
+            count: model.mesh.len(),
+            material: None,
+        }];
+        let submeshes = match model.submeshes.is_empty() {
+            true => &whole[..],
+            false => &model.submeshes[..],
+        };
+
+        for submesh in submeshes {
+            let mut desc = MaterialDesc::new(mesh.submesh(submesh));
+            let material = submesh.material.as_deref();
+            let (name, transparent) = match resolve(ctx, scene, cache, actor, material, &mut desc) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    desc.release(ctx);
+                    mesh.release(ctx);
+                    return Err(e);
+                }
+            };
+            let renderer = match registry.build(ctx, name, desc) {
+                Ok(renderer) => renderer,
+                Err(e) => {
+                    mesh.release(ctx);
+                    return Err(e);
+                }
+            };
+            self.draws.push(Draw {
+                actor: actor.to_string(),
+                renderer,
+                transparent,
+                center: center(model, submesh),
+            });
+        }
+        mesh.release(ctx);
+        Ok(())
+    }
+
+    /// Place `actor`, whose model space is world space until then.
+    pub fn set_transform(&mut self, actor: &str, model: Mat4) {
+        self.transforms.insert(actor.to_string(), model);
+    }
+
+    /// the number of draws, one per submesh of every body and part
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn render(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        view: Mat4,
+        projection: Mat4,
+        frame: &FrameContext,
+    ) {
+        let transforms = |draw: &Draw| Transforms {
+            model: self
+                .transforms
+                .get(&draw.actor)
+                .copied()
+                .unwrap_or(Mat4::IDENTITY),
+            view,
+            projection,
+        };
+        let transforms: Vec<Transforms> = self.draws.iter().map(transforms).collect();
+        let keys: Vec<_> = self
+            .draws
+            .iter()
+            .zip(&transforms)
+            .map(|(draw, transforms)| (draw.transparent, transforms.model, draw.center))
+            .collect();
+        for i in Self::draw_order(view, &keys) {
+            self.draws[i].renderer.render(ctx, &transforms[i], frame);
+        }
+    }
+
+    /// The order of draws given as whether they blend, their model transform and the center
+    /// of their submesh: opaque ones front to back, then transparent ones back to front, by
+    /// distance along the view direction with the camera looking down -z. Draws at the same
+    /// distance keep their order.
+    pub fn draw_order(view: Mat4, draws: &[(bool, Mat4, Vec3)]) -> Vec<usize> {
+        let depths: Vec<f32> = draws
+            .iter()
+            .map(|(_, model, center)| -(view * *model).transform_point3(*center).z)
+            .collect();
+        let mut order: Vec<usize> = (0..draws.len()).collect();
+        order.sort_by(|&a, &b| {
+            let transparent = draws[a].0;
+            transparent
+                .cmp(&draws[b].0)
+                .then_with(|| match transparent {
+                    true => depths[b].total_cmp(&depths[a]),
+                    false => depths[a].total_cmp(&depths[b]),
+                })
+        });
+        order
+    }
+
+    /// Destroy every renderer, releasing their handles to the cache.
+    pub fn destroy(self, ctx: &mut Box<dyn RenderingBackend>) {
+        for draw in self.draws {
+            draw.renderer.destroy(ctx);
+        }
+    }
+}
+
+/// The renderer for `material` and whether it blends, adding its textures and parameters to
+/// `desc`.
+fn resolve(
+    ctx: &mut Box<dyn RenderingBackend>,
+    scene: &mari_formats::Scene,
+    cache: &mut ResourceCache,
+    actor: &str,
+    material: Option<&str>,
+    desc: &mut MaterialDesc,
+) -> Result<(&'static str, bool), RendererError> {
+    use mari_formats::Material;
+
+    let texture = match material.and_then(|m| scene.materials.get(m)) {
+        Some(Material::Unlit { color }) => {
+            desc.color = *color;
+            return Ok(("default", false));
+        }
+        Some(Material::Textured { texture }) => texture,
+        Some(Material::Toon(toon)) => {
+            for (slot, texture, sampler) in [
+                ("texture", &toon.texture, Sampler::default()),
+                ("ramp_texture", &toon.ramp_texture, Sampler::LOOKUP),
+                ("sdw_texture", &toon.sdw_texture, Sampler::default()),
+            ] {
+                let texture = cache.texture(ctx, texture, sampler)?;
+                desc.textures.insert(slot, texture);
+            }
+            desc.toon = ToonParams::from_material(toon);
+            return Ok(("toon", false));
+        }
+        // without lighting, from the base color alone
+        Some(Material::Pbr(pbr)) => match &pbr.base_color_texture {
+            Some(texture) => texture,
+            None => {
+                desc.color = pbr.base_color;
+                return Ok(("default", false));
+            }
+        },
+        None => match scene.textures.get(actor) {
+            Some(texture) => texture,
+            None => return Ok(("default", false)),
+        },
+    };
+    let handle = cache.texture(ctx, texture, Sampler::default())?;
+    desc.textures.insert("texture", handle);
+    let transparent = texture.data.chunks_exact(4).any(|t| t[3] < 255);
+    Ok(("textured", transparent))
+}
+
+/// the center of the bounding box of the vertices `submesh` indexes
+fn center(model: &mari_formats::Model, submesh: &mari_formats::Submesh) -> Vec3 {
+    let (min, max) = model.mesh[submesh.first..][..submesh.count]
+        .iter()
+        .map(|i| Vec3::from_slice(&model.vertices[*i as usize * 3..]))
+        .fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), v| (min.min(v), max.max(v)),
+        );
+    match submesh.count {
+        0 => Vec3::ZERO,
+        _ => (min + max) * 0.5,
+    }
+}
@@ -0,0 +1,477 @@
+//! Rendering on the CPU into memory, for machines without a GPU and for tests.
+
+use glam::Vec3;
+use mari_formats::TextureRGBA8;
+use miniquad::TextureWrap;
+
+use crate::error::check_model;
+use crate::{
+    DefaultInitParams, Filter, FrameContext, RendererError, Sampler, TextureData,
+    TexturedInitParams, ToonInitParams, ToonParams, Transforms,
+};
+
+/// A color and a depth buffer in memory, rows top-down like `TextureRGBA8`.
+pub struct Framebuffer {
+    pub color: TextureRGBA8,
+    /// window-space depth per pixel, 0 nearest and 1 farthest
+    pub depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u16, height: u16) -> Self {
+        let size = width as usize * height as usize;
+        Self {
+            color: TextureRGBA8 {
+                width,
+                data: vec![0; size * 4],
+            },
+            depth: vec![1.0; size],
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.color.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.color.height()
+    }
+
+    /// Fill color with r,g,b,a and depth with 1.
+    pub fn clear(&mut self, color: [f32; 4]) {
+        let color = color.map(unorm8);
+        for p in self.color.data.chunks_exact_mut(4) {
+            p.copy_from_slice(&color);
+        }
+        self.depth.fill(1.0);
+    }
+}
+
+fn unorm8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// A texture with its mips, sampled like the GPU does with the same `Sampler`.
+struct Sampled {
+    levels: Vec<TextureRGBA8>,
+    sampler: Sampler,
+}
+
+impl Sampled {
+    fn new(texture: &dyn TextureData, sampler: Sampler) -> Self {
+        // textures beyond `TextureRGBA8` sample as white
+        let texture = texture.pixels().unwrap_or(TextureRGBA8 {
+            width: 1,
+            data: vec![255; 4],
+        });
+        let levels = if sampler.filter == Filter::Trilinear {
+            texture.mip_chain(Some(0.5))
+        } else {
+            vec![texture]
+        };
+        Self { levels, sampler }
+    }
+
+    fn wrap(&self, i: i64, size: i64) -> usize {
+        (match self.sampler.wrap {
+            TextureWrap::Clamp => i.clamp(0, size - 1),
+            TextureWrap::Repeat => i.rem_euclid(size),
+            TextureWrap::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size { i } else { 2 * size - 1 - i }
+            }
+        }) as usize
+    }
+
+    fn texel(&self, level: &TextureRGBA8, x: i64, y: i64) -> [f32; 4] {
+        let (w, h) = (level.width as i64, level.height() as i64);
+        let (x, y) = (self.wrap(x, w), self.wrap(y, h));
+        let p = &level.data[(y * level.width as usize + x) * 4..][..4];
+        [p[0], p[1], p[2], p[3]].map(|c| c as f32 / 255.0)
+    }
+
+    fn bilinear(&self, level: &TextureRGBA8, uv: [f32; 2]) -> [f32; 4] {
+        let x = uv[0] * level.width as f32 - 0.5;
+        let y = uv[1] * level.height() as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let [a, b, c, d] = [
+            self.texel(level, x0, y0),
+            self.texel(level, x0 + 1, y0),
+            self.texel(level, x0, y0 + 1),
+            self.texel(level, x0 + 1, y0 + 1),
+        ];
+        std::array::from_fn(|i| {
+            let top = a[i] + (b[i] - a[i]) * fx;
+            let bottom = c[i] + (d[i] - c[i]) * fx;
+            top + (bottom - top) * fy
+        })
+    }
+
+    /// `lod` is the log2 of texels of level 0 per pixel
+    fn sample(&self, uv: [f32; 2], lod: f32) -> [f32; 4] {
+        let base = &self.levels[0];
+        match self.sampler.filter {
+            Filter::Nearest => {
+                let x = (uv[0] * base.width as f32).floor() as i64;
+                let y = (uv[1] * base.height() as f32).floor() as i64;
+                self.texel(base, x, y)
+            }
+            Filter::Bilinear => self.bilinear(base, uv),
+            Filter::Trilinear => {
+                let lod = lod.clamp(0.0, (self.levels.len() - 1) as f32);
+                let (low, t) = (lod.floor() as usize, lod.fract());
+                let a = self.bilinear(&self.levels[low], uv);
+                if t == 0.0 {
+                    return a;
+                }
+                let b = self.bilinear(&self.levels[low + 1], uv);
+                std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+            }
+        }
+    }
+
+    fn size(&self) -> [f32; 2] {
+        [self.levels[0].width as f32, self.levels[0].height() as f32]
+    }
+}
+
+enum Shading {
+    /// the color drawn, r,g,b,a
+    Default([f32; 4]),
+    Textured(Sampled),
+    Toon {
+        texture: Sampled,
+        ramp_texture: Sampled,
+        sdw_texture: Sampled,
+        params: ToonParams,
+    },
+}
+
+/// varyings: u, v and the toon ramp coordinate
+type Varyings = [f32; 3];
+
+/// A clip-space vertex.
+#[derive(Clone, Copy)]
+struct Vertex {
+    position: [f32; 4],
+    varyings: Varyings,
+}
+
+fn lerp_vertex(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    Vertex {
+        position: std::array::from_fn(|i| a.position[i] + (b.position[i] - a.position[i]) * t),
+        varyings: std::array::from_fn(|i| a.varyings[i] + (b.varyings[i] - a.varyings[i]) * t),
+    }
+}
+
+/// What triangles are drawn for.
+#[derive(Clone, Copy)]
+enum Pass {
+    /// the shading of the renderer, back faces culled
+    Shading,
+    /// the toon outline in one opaque color, r,g,b,a, front faces culled
+    Outline([f32; 4]),
+}
+
+/// Clip a polygon to `distance(v) >= 0`.
+fn clip(polygon: Vec<Vertex>, distance: impl Fn(&Vertex) -> f32) -> Vec<Vertex> {
+    let mut ret = Vec::with_capacity(polygon.len() + 1);
+    for (i, a) in polygon.iter().enumerate() {
+        let b = &polygon[(i + 1) % polygon.len()];
+        let (da, db) = (distance(a), distance(b));
+        if da >= 0.0 {
+            ret.push(*a);
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            ret.push(lerp_vertex(a, b, da / (da - db)));
+        }
+    }
+    ret
+}
+
+/// The CPU counterpart of `Default`, `Textured` and `Toon`, taking the same `InitParams`,
+/// `Transforms` and `FrameContext`, except that the viewport is the size of the target. Like
+/// them it culls back faces, CCW being front-facing, or front faces for the toon outline, and
+/// tests depth with LESS. Textured draws are blended by their alpha, the others are opaque.
+pub struct Software {
+    vertices: Vec<f32>,
+    uvs: Vec<f32>,
+    normals: Vec<f32>,
+    colors: Vec<f32>,
+    mesh: Vec<u16>,
+    shading: Shading,
+}
+
+impl Software {
+    fn new(model: &mari_formats::Model, shading: Shading) -> Self {
+        Self {
+            vertices: model.vertices.clone(),
+            uvs: model.uvs.clone(),
+            normals: model.normals.clone(),
+            colors: model.colors.clone(),
+            mesh: model.mesh.clone(),
+            shading,
+        }
+    }
+
+    pub fn new_default(params: DefaultInitParams) -> Result<Self, RendererError> {
+        check_model(params.model, false, false)?;
+        Ok(Self::new(
+            params.model,
+            Shading::Default(crate::Default::COLOR),
+        ))
+    }
+
+    pub fn new_textured(params: TexturedInitParams) -> Result<Self, RendererError> {
+        check_model(params.model, true, false)?;
+        let shading = Shading::Textured(Sampled::new(params.texture, params.sampler));
+        Ok(Self::new(params.model, shading))
+    }
+
+    pub fn new_toon(params: ToonInitParams) -> Result<Self, RendererError> {
+        check_model(params.model, true, true)?;
+        let shading = Shading::Toon {
+            texture: Sampled::new(params.texture, params.sampler),
+            ramp_texture: Sampled::new(params.ramp_texture, Sampler::LOOKUP),
+            sdw_texture: Sampled::new(params.sdw_texture, params.sampler),
+            params: params.params,
+        };
+        Ok(Self::new(params.model, shading))
+    }
+
+    /// Draw with `color` like `Default::set_color`, if made by `new_default`.
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        if let Shading::Default(c) = &mut self.shading {
+            *c = color;
+        }
+    }
+
+    /// the vertex shader, `mvp` in column-major order
+    fn vertex(&self, i: usize, mvp: &[f32; 16], light_pos_in_model_space: Vec3) -> Vertex {
+        let p = &self.vertices[3 * i..3 * i + 3];
+        let position = std::array::from_fn(|r| {
+            mvp[r] * p[0] + mvp[4 + r] * p[1] + mvp[8 + r] * p[2] + mvp[12 + r]
+        });
+        let uv = self.uvs.get(2 * i..2 * i + 2).unwrap_or(&[0.0, 0.0]);
+        let rmp = match (&self.shading, self.normals.get(3 * i..3 * i + 3)) {
+            (Shading::Toon { params, .. }, Some(n)) => {
+                let l = light_pos_in_model_space;
+                let d = [p[0] - l[0], p[1] - l[1], p[2] - l[2]];
+                let len = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+                let scale = params.ramp_scale;
+                scale - scale * (n[0] * d[0] + n[1] * d[1] + n[2] * d[2]) / len
+            }
+            _ => 0.0,
+        };
+        Vertex {
+            position,
+            varyings: [uv[0], uv[1], rmp],
+        }
+    }
+
+    /// the vertex shader of the toon outline, moved `width` pixels times the vertex alpha along
+    /// its normal on screen
+    fn outline_vertex(
+        &self,
+        i: usize,
+        mvp: &[f32; 16],
+        viewport: [f32; 2],
+        params: &ToonParams,
+    ) -> Vertex {
+        let n = &self.normals[3 * i..3 * i + 3];
+        let alpha = self.colors.get(4 * i + 3).copied().unwrap_or(1.0);
+        // pushed out along the normal in model space first, as Unity's toon shaders do
+        let p: [f32; 3] = std::array::from_fn(|r| {
+            self.vertices[3 * i + r] + n[r] * params.outline_offset * alpha
+        });
+        let mut position: [f32; 4] = std::array::from_fn(|r| {
+            mvp[r] * p[0] + mvp[4 + r] * p[1] + mvp[8 + r] * p[2] + mvp[12 + r]
+        });
+        let mut dir: [f32; 2] = std::array::from_fn(|r| {
+            (mvp[r] * n[0] + mvp[4 + r] * n[1] + mvp[8 + r] * n[2]) * viewport[r]
+        });
+        let len = (dir[0] * dir[0] + dir[1] * dir[1]).sqrt();
+        if len > 0.0 {
+            dir = dir.map(|d| d / len);
+        }
+        // back to clip space, where the offset grows with w to stay the same on screen
+        let w = position[3];
+        for r in 0..2 {
+            position[r] += dir[r] * params.outline_width * alpha * 2.0 / viewport[r] * w;
+        }
+        Vertex {
+            position,
+            varyings: [0.0; 3],
+        }
+    }
+
+    /// the fragment shader, `lod` as in `Sampled::sample`
+    fn fragment(&self, varyings: &Varyings, lod: impl Fn([f32; 2]) -> f32) -> [f32; 4] {
+        let uv = [varyings[0], varyings[1]];
+        match &self.shading {
+            Shading::Default(color) => *color,
+            Shading::Textured(texture) => texture.sample(uv, lod(texture.size())),
+            Shading::Toon {
+                texture,
+                ramp_texture,
+                sdw_texture,
+                params,
+            } => {
+                let shadow = sdw_texture.sample(uv, lod(sdw_texture.size()))[3];
+                let rmp_coeff = if shadow > params.shadow_threshold {
+                    ramp_texture.sample([varyings[2], 0.0], 0.0)
+                } else {
+                    [1.0; 4]
+                };
+                let col = texture.sample(uv, lod(texture.size()));
+                [
+                    col[0] * rmp_coeff[0],
+                    col[1] * rmp_coeff[1],
+                    col[2] * rmp_coeff[2],
+                    1.0,
+                ]
+            }
+        }
+    }
+
+    pub fn render(&self, target: &mut Framebuffer, transforms: &Transforms, frame: &FrameContext) {
+        let mvp = transforms.mvp().to_cols_array();
+        let light_pos_in_model_space = transforms.to_model_space(frame.light_pos());
+        self.draw(
+            target,
+            |i| self.vertex(i, &mvp, light_pos_in_model_space),
+            Pass::Shading,
+        );
+        if let Shading::Toon { params, .. } = &self.shading
+            && params.outlined()
+        {
+            let viewport = [target.width() as f32, target.height() as f32];
+            self.draw(
+                target,
+                |i| self.outline_vertex(i, &mvp, viewport, params),
+                Pass::Outline(params.outline_color),
+            );
+        }
+    }
+
+    /// Draw every triangle with the vertex shader `vertex`.
+    fn draw(&self, target: &mut Framebuffer, vertex: impl Fn(usize) -> Vertex, pass: Pass) {
+        for triangle in self.mesh.chunks_exact(3) {
+            let polygon = triangle.iter().map(|i| vertex(*i as usize)).collect();
+            // the near and far planes, x and y are left to the viewport bounds
+            let polygon = clip(polygon, |v| v.position[2] + v.position[3]);
+            let polygon = clip(polygon, |v| v.position[3] - v.position[2]);
+            for i in 1..polygon.len().saturating_sub(1) {
+                self.rasterize(target, [&polygon[0], &polygon[i], &polygon[i + 1]], pass);
+            }
+        }
+    }
+
+    fn rasterize(&self, target: &mut Framebuffer, triangle: [&Vertex; 3], pass: Pass) {
+        let (width, height) = (target.width() as usize, target.height() as usize);
+        let (fw, fh) = (width as f32, height as f32);
+        // window coordinates with y down, depth in 0..1, and 1/w
+        let window = triangle.map(|v| {
+            let [x, y, z, w] = v.position;
+            [
+                (x / w * 0.5 + 0.5) * fw,
+                (0.5 - y / w * 0.5) * fh,
+                z / w * 0.5 + 0.5,
+                1.0 / w,
+            ]
+        });
+        let [a, b, c] = window;
+        let edge = |p: [f32; 4], q: [f32; 4], x: f32, y: f32| {
+            (q[0] - p[0]) * (y - p[1]) - (q[1] - p[1]) * (x - p[0])
+        };
+        // with y down, counter-clockwise triangles have a negative area
+        let area = edge(a, b, c[0], c[1]);
+        let culled = match pass {
+            Pass::Shading => area >= 0.0,
+            Pass::Outline(_) => area <= 0.0,
+        };
+        if culled || !area.is_finite() {
+            return;
+        }
+
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize;
+        let max_x = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as usize).min(width);
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize;
+        let max_y = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as usize).min(height);
+
+        // top-left rule, so pixels on an edge shared by two triangles are drawn once
+        let owns = |p: [f32; 4], q: [f32; 4]| {
+            let (dx, dy) = (q[0] - p[0], q[1] - p[1]);
+            dy > 0.0 || (dy == 0.0 && dx < 0.0)
+        };
+        let bias = [owns(b, c), owns(c, a), owns(a, b)];
+
+        // perspective-correct barycentrics at any point of the window
+        let weights = |x: f32, y: f32| {
+            let l = [
+                edge(b, c, x, y) / area,
+                edge(c, a, x, y) / area,
+                edge(a, b, x, y) / area,
+            ];
+            let q = [l[0] * a[3], l[1] * b[3], l[2] * c[3]];
+            let sum = q[0] + q[1] + q[2];
+            (l, q.map(|q| q / sum))
+        };
+        let interpolate = |q: [f32; 3]| -> Varyings {
+            std::array::from_fn(|i| {
+                q[0] * triangle[0].varyings[i]
+                    + q[1] * triangle[1].varyings[i]
+                    + q[2] * triangle[2].varyings[i]
+            })
+        };
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let (l, q) = weights(px, py);
+                let inside = l
+                    .iter()
+                    .zip(bias)
+                    .all(|(l, bias)| *l > 0.0 || (*l == 0.0 && bias));
+                if !inside {
+                    continue;
+                }
+                let depth = l[0] * a[2] + l[1] * b[2] + l[2] * c[2];
+                let index = y * width + x;
+                if depth.partial_cmp(&target.depth[index]) != Some(std::cmp::Ordering::Less) {
+                    continue;
+                }
+
+                let varyings = interpolate(q);
+                // texture coordinate derivatives from the neighbouring pixels
+                let dx = interpolate(weights(px + 1.0, py).1);
+                let dy = interpolate(weights(px, py + 1.0).1);
+                let lod = |size: [f32; 2]| {
+                    let length = |d: &Varyings| {
+                        let du = (d[0] - varyings[0]) * size[0];
+                        let dv = (d[1] - varyings[1]) * size[1];
+                        (du * du + dv * dv).sqrt()
+                    };
+                    length(&dx).max(length(&dy)).max(f32::MIN_POSITIVE).log2()
+                };
+                let mut color = match pass {
+                    Pass::Shading => self.fragment(&varyings, lod),
+                    Pass::Outline([r, g, b, _]) => [r, g, b, 1.0],
+                };
+                let pixel = &mut target.color.data[index * 4..][..4];
+                if let (Pass::Shading, Shading::Textured(_)) = (pass, &self.shading) {
+                    // blended by source alpha like the `Textured` pipeline, alpha included
+                    let alpha = color[3];
+                    for (c, dst) in color.iter_mut().zip(pixel.iter()) {
+                        *c = *c * alpha + *dst as f32 / 255.0 * (1.0 - alpha);
+                    }
+                }
+
+                target.depth[index] = depth;
+                pixel.copy_from_slice(&color.map(unorm8));
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use mari_formats::{PixelFormat, Texture, TextureLayout, TextureRGBA8};
+use miniquad::*;
+
+use crate::RendererError;
+
+/// missing from `miniquad::gl`
+const GL_TEXTURE_BINDING_2D: u32 = 0x8069;
+
+/// How a texture is sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    /// bilinear within and linear between mip levels
+    Trilinear,
+}
+
+/// Sampler settings of a texture in `InitParams`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sampler {
+    pub wrap: TextureWrap,
+    pub filter: Filter,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            wrap: TextureWrap::Clamp,
+            filter: Filter::Trilinear,
+        }
+    }
+}
+
+impl Sampler {
+    /// for lookup tables like toon ramps, which must neither repeat nor blur across mips
+    pub const LOOKUP: Self = Self {
+        wrap: TextureWrap::Clamp,
+        filter: Filter::Bilinear,
+    };
+
+    fn params(self) -> TextureParams {
+        let filter = match self.filter {
+            Filter::Nearest => FilterMode::Nearest,
+            Filter::Bilinear | Filter::Trilinear => FilterMode::Linear,
+        };
+        let mipmaps = self.filter == Filter::Trilinear;
+        TextureParams {
+            wrap: self.wrap,
+            min_filter: filter,
+            mag_filter: filter,
+            mipmap_filter: if mipmaps {
+                MipmapFilterMode::Linear
+            } else {
+                MipmapFilterMode::None
+            },
+            allocate_mipmaps: mipmaps,
+            ..TextureParams::default()
+        }
+    }
+}
+
+/// The largest width and height of a texture on the backend.
+fn max_texture_size(ctx: &mut Box<dyn RenderingBackend>) -> u32 {
+    match ctx.info().backend {
+        Backend::OpenGl => {
+            let mut max = 0;
+            unsafe { gl::glGetIntegerv(gl::GL_MAX_TEXTURE_SIZE, &mut max) };
+            max as u32
+        }
+        // the minimum of Metal GPU families since Apple2
+        Backend::Metal => 8192,
+    }
+}
+
+fn check_size(
+    ctx: &mut Box<dyn RenderingBackend>,
+    width: u32,
+    height: u32,
+) -> Result<(), RendererError> {
+    let max = max_texture_size(ctx);
+    if width > max || height > max {
+        return Err(RendererError::TextureTooLarge { width, height, max });
+    }
+    Ok(())
+}
+
+/// Textures the renderers can upload.
+pub trait TextureData {
+    fn upload(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        sampler: Sampler,
+    ) -> Result<TextureId, RendererError>;
+    /// the first image as RGBA8 for `Software`, `None` if it doesn't fit `TextureRGBA8`
+    fn pixels(&self) -> Option<TextureRGBA8>;
+    /// a hash of the size, format and pixels, under which `ResourceCache` shares uploads
+    fn content_hash(&self) -> u64;
+}
+
+/// Fill the levels after the first of a 2D RGBA8 texture, or let the GPU generate them where
+/// raw OpenGL isn't available.
+fn upload_mips(ctx: &mut Box<dyn RenderingBackend>, texture: TextureId, levels: &[TextureRGBA8]) {
+    #[allow(unreachable_patterns)]
+    match unsafe { ctx.texture_raw_id(texture) } {
+        RawId::OpenGl(raw) => unsafe {
+            let mut bound = 0;
+            gl::glGetIntegerv(GL_TEXTURE_BINDING_2D, &mut bound);
+            gl::glBindTexture(gl::GL_TEXTURE_2D, raw);
+            gl::glPixelStorei(gl::GL_UNPACK_ALIGNMENT, 1);
+            for (i, level) in levels.iter().enumerate().skip(1) {
+                gl::glTexImage2D(
+                    gl::GL_TEXTURE_2D,
+                    i as _,
+                    gl::GL_RGBA as _,
+                    level.width as _,
+                    level.height() as _,
+                    0,
+                    gl::GL_RGBA,
+                    gl::GL_UNSIGNED_BYTE,
+                    level.data.as_ptr() as _,
+                );
+            }
+            gl::glTexParameteri(
+                gl::GL_TEXTURE_2D,
+                gl::GL_TEXTURE_MAX_LEVEL,
+                levels.len() as i32 - 1,
+            );
+            gl::glBindTexture(gl::GL_TEXTURE_2D, bound as _);
+        },
+        _ => ctx.texture_generate_mipmaps(texture),
+    }
+}
+
+/// With `Filter::Trilinear`, the mips come from `TextureRGBA8::mip_chain` with the 0.5 cutoff
+/// the shaders test alpha against.
+impl TextureData for TextureRGBA8 {
+    fn upload(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        sampler: Sampler,
+    ) -> Result<TextureId, RendererError> {
+        check_size(ctx, self.width as u32, self.height() as u32)?;
+        let texture = crate::resources::new_texture(
+            ctx,
+            TextureSource::Bytes(&self.data),
+            TextureParams {
+                format: TextureFormat::RGBA8,
+                width: self.width as u32,
+                height: self.height() as u32,
+                ..sampler.params()
+            },
+        );
+        if sampler.filter == Filter::Trilinear {
+            upload_mips(ctx, texture, &self.mip_chain(Some(0.5)));
+        }
+        Ok(texture)
+    }
+
+    fn pixels(&self) -> Option<TextureRGBA8> {
+        Some(TextureRGBA8 {
+            width: self.width,
+            data: self.data.clone(),
+        })
+    }
+
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.width, &self.data).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// The miniquad format a texture of `format` goes up as on `backend`, and the pixel format its
+/// data is converted to first.
+///
+/// - R8 is `Alpha`, which reads as red (and alpha) on desktop GL.
+/// - RG8 is widened to `RGBA8`.
+/// - RGBA8 sRGB keeps its encoded bytes in `RGBA8`, as miniquad has no sRGB format.
+/// - R16F, RGBA16F and RGBA32F are `RGBA16F`. Its OpenGL backend reads the data as f32, so
+///   it gets RGBA32F there, while Metal reads half floats.
+/// - Depth is `Depth32`.
+pub fn upload_format(format: PixelFormat, backend: Backend) -> (TextureFormat, PixelFormat) {
+    match format {
+        PixelFormat::R8 => (TextureFormat::Alpha, PixelFormat::R8),
+        PixelFormat::RG8 | PixelFormat::RGBA8 => (TextureFormat::RGBA8, PixelFormat::RGBA8),
+        PixelFormat::RGBA8Srgb => (TextureFormat::RGBA8, PixelFormat::RGBA8Srgb),
+        PixelFormat::R16F | PixelFormat::RGBA16F | PixelFormat::RGBA32F => match backend {
+            Backend::OpenGl => (TextureFormat::RGBA16F, PixelFormat::RGBA32F),
+            Backend::Metal => (TextureFormat::RGBA16F, PixelFormat::RGBA16F),
+        },
+        PixelFormat::Depth => (TextureFormat::Depth32, PixelFormat::Depth),
+    }
+}
+
+/// Each pixel format goes up as `upload_format` gives.
+///
+/// Cube textures become cube maps. miniquad has no array textures, so of an array only the
+/// first layer is uploaded. Mips are generated on the GPU, without gamma correction.
+impl TextureData for Texture {
+    fn upload(
+        &self,
+        ctx: &mut Box<dyn RenderingBackend>,
+        sampler: Sampler,
+    ) -> Result<TextureId, RendererError> {
+        check_size(ctx, self.width, self.height)?;
+        let (format, data_format) = upload_format(self.format, ctx.info().backend);
+        let converted = (data_format != self.format).then(|| self.convert(data_format));
+        let texture = converted.as_ref().unwrap_or(self);
+
+        let params = TextureParams {
+            kind: match self.layout {
+                TextureLayout::Cube => TextureKind::CubeMap,
+                _ => TextureKind::Texture2D,
+            },
+            format,
+            width: self.width,
+            height: self.height,
+            ..sampler.params()
+        };
+        // `Bytes` must be as long as miniquad counts, 8 bytes a pixel for `RGBA16F`, which the f32
+        // data of OpenGL isn't, so that goes through `Array` like the faces of a cube
+        let id = match self.layout {
+            TextureLayout::Cube => {
+                let faces: Vec<[&[u8]; 1]> = (0..6).map(|i| [texture.layer(i)]).collect();
+                let faces: Vec<&[&[u8]]> = faces.iter().map(|f| f.as_slice()).collect();
+                crate::resources::new_texture(ctx, TextureSource::Array(&faces), params)
+            }
+            _ if data_format == PixelFormat::RGBA32F => {
+                let level: &[&[u8]] = &[texture.layer(0)];
+                crate::resources::new_texture(ctx, TextureSource::Array(&[level]), params)
+            }
+            _ => crate::resources::new_texture(ctx, TextureSource::Bytes(texture.layer(0)), params),
+        };
+        if sampler.filter == Filter::Trilinear {
+            ctx.texture_generate_mipmaps(id);
+        }
+        Ok(id)
+    }
+
+    fn pixels(&self) -> Option<TextureRGBA8> {
+        self.to_rgba8().ok()
+    }
+
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
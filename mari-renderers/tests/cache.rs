@@ -0,0 +1,214 @@
+//! `ResourceCache` on a backend that only records what is created and deleted, as sharing and
+//! releasing need no GPU.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use mari_formats::{Model, Submesh, TextureRGBA8};
+use mari_renderers::{RendererError, ResourceCache, Sampler};
+use miniquad::*;
+
+#[derive(Default)]
+struct Log {
+    buffers: usize,
+    textures: usize,
+    deleted_buffers: usize,
+    deleted_textures: usize,
+}
+
+struct Recorder(Rc<RefCell<Log>>);
+
+fn context() -> (Box<dyn RenderingBackend>, Rc<RefCell<Log>>) {
+    let log = Rc::new(RefCell::new(Log::default()));
+    (Box::new(Recorder(log.clone())), log)
+}
+
+impl RenderingBackend for Recorder {
+    fn info(&self) -> ContextInfo {
+        // not OpenGL, which `TextureData::upload` would query through raw GL
+        ContextInfo {
+            backend: Backend::Metal,
+            gl_version_string: String::new(),
+            glsl_support: GlslSupport::default(),
+            features: Features::default(),
+        }
+    }
+    fn new_shader(&mut self, _: ShaderSource, _: ShaderMeta) -> Result<ShaderId, ShaderError> {
+        unimplemented!()
+    }
+    fn new_texture(&mut self, _: TextureAccess, _: TextureSource, _: TextureParams) -> TextureId {
+        let mut log = self.0.borrow_mut();
+        log.textures += 1;
+        TextureId::from_raw_id(RawId::OpenGl(log.textures as u32))
+    }
+    fn texture_params(&self, _: TextureId) -> TextureParams {
+        unimplemented!()
+    }
+    unsafe fn texture_raw_id(&self, _: TextureId) -> RawId {
+        unimplemented!()
+    }
+    fn texture_set_min_filter(&mut self, _: TextureId, _: FilterMode, _: MipmapFilterMode) {}
+    fn texture_set_mag_filter(&mut self, _: TextureId, _: FilterMode) {}
+    fn texture_set_wrap(&mut self, _: TextureId, _: TextureWrap, _: TextureWrap) {}
+    fn texture_generate_mipmaps(&mut self, _: TextureId) {}
+    fn texture_resize(&mut self, _: TextureId, _: u32, _: u32, _: Option<&[u8]>) {}
+    fn texture_read_pixels(&mut self, _: TextureId, _: &mut [u8]) {}
+    fn texture_update_part(&mut self, _: TextureId, _: i32, _: i32, _: i32, _: i32, _: &[u8]) {}
+    fn new_render_pass_mrt(
+        &mut self,
+        _: &[TextureId],
+        _: Option<&[TextureId]>,
+        _: Option<TextureId>,
+    ) -> RenderPass {
+        unimplemented!()
+    }
+    fn render_pass_color_attachments(&self, _: RenderPass) -> &[TextureId] {
+        unimplemented!()
+    }
+    fn delete_render_pass(&mut self, _: RenderPass) {}
+    fn new_pipeline(
+        &mut self,
+        _: &[BufferLayout],
+        _: &[VertexAttribute],
+        _: ShaderId,
+        _: PipelineParams,
+    ) -> Pipeline {
+        unimplemented!()
+    }
+    fn apply_pipeline(&mut self, _: &Pipeline) {}
+    fn delete_pipeline(&mut self, _: Pipeline) {}
+    fn new_buffer(&mut self, _: BufferType, _: BufferUsage, _: BufferSource) -> BufferId {
+        let mut log = self.0.borrow_mut();
+        log.buffers += 1;
+        // miniquad has no public constructor for ids of buffers, which are a usize
+        unsafe { std::mem::transmute::<usize, BufferId>(log.buffers) }
+    }
+    fn buffer_update(&mut self, _: BufferId, _: BufferSource) {}
+    fn buffer_size(&mut self, _: BufferId) -> usize {
+        0
+    }
+    fn delete_buffer(&mut self, _: BufferId) {
+        self.0.borrow_mut().deleted_buffers += 1;
+    }
+    fn delete_texture(&mut self, _: TextureId) {
+        self.0.borrow_mut().deleted_textures += 1;
+    }
+    fn delete_shader(&mut self, _: ShaderId) {}
+    fn apply_viewport(&mut self, _: i32, _: i32, _: i32, _: i32) {}
+    fn apply_scissor_rect(&mut self, _: i32, _: i32, _: i32, _: i32) {}
+    fn apply_bindings_from_slice(&mut self, _: &[BufferId], _: BufferId, _: &[TextureId]) {}
+    fn apply_uniforms_from_bytes(&mut self, _: *const u8, _: usize) {}
+    fn clear(&mut self, _: Option<(f32, f32, f32, f32)>, _: Option<f32>, _: Option<i32>) {}
+    fn begin_default_pass(&mut self, _: PassAction) {}
+    fn begin_pass(&mut self, _: Option<RenderPass>, _: PassAction) {}
+    fn end_render_pass(&mut self) {}
+    fn commit_frame(&mut self) {}
+    fn draw(&self, _: i32, _: i32, _: i32) {}
+}
+
+/// a triangle with uvs and normals, so 5 buffers with the colors and the indices
+fn triangle(x: f32) -> Model {
+    Model {
+        vertices: vec![x, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        mesh: vec![0, 1, 2],
+        uvs: vec![0.0; 6],
+        normals: [0.0, 0.0, 1.0].repeat(3),
+        colors: Vec::new(),
+        joints: Vec::new(),
+        weights: Vec::new(),
+        submeshes: vec![Submesh {
+            first: 0,
+            count: 3,
+            material: None,
+        }],
+    }
+}
+
+fn texture(seed: u8) -> TextureRGBA8 {
+    TextureRGBA8 {
+        width: 1,
+        data: vec![seed, 0, 0, 255],
+    }
+}
+
+#[test]
+fn meshes_are_shared_by_content() {
+    let (mut ctx, log) = context();
+    let mut cache = ResourceCache::new();
+    let a = cache.mesh(&mut ctx, &triangle(0.0)).unwrap();
+    // an equal model, e.g. of another scene, shares the upload
+    let b = cache.mesh(&mut ctx, &triangle(0.0)).unwrap();
+    assert_eq!(log.borrow().buffers, 5);
+    // a different model of the same name doesn't
+    let c = cache.mesh(&mut ctx, &triangle(0.5)).unwrap();
+    assert_eq!(log.borrow().buffers, 10);
+
+    // a model equal on the GPU is still checked for its submeshes
+    let mut bad = triangle(0.0);
+    bad.submeshes[0].count = 6;
+    assert!(matches!(
+        cache.mesh(&mut ctx, &bad),
+        Err(RendererError::SubmeshOutOfRange { .. })
+    ));
+    assert_eq!(log.borrow().buffers, 10);
+
+    for mesh in [a, b, c] {
+        mesh.release(&mut ctx);
+    }
+    cache.clear(&mut ctx);
+    assert_eq!(log.borrow().deleted_buffers, 10);
+}
+
+#[test]
+fn entries_are_collected_once_unused() {
+    let (mut ctx, log) = context();
+    let mut cache = ResourceCache::new();
+    let mesh = cache.mesh(&mut ctx, &triangle(0.0)).unwrap();
+    let held = mesh.clone();
+    let texture = cache
+        .texture(&mut ctx, &texture(1), Sampler::LOOKUP)
+        .unwrap();
+    assert_eq!(cache.collect(&mut ctx), 0);
+
+    // the mesh stays while any handle is out
+    mesh.release(&mut ctx);
+    assert_eq!(cache.collect(&mut ctx), 0);
+    held.release(&mut ctx);
+    assert_eq!(cache.collect(&mut ctx), 1);
+    assert_eq!(log.borrow().deleted_buffers, 5);
+
+    // clearing leaves what renderers hold to them
+    cache.clear(&mut ctx);
+    assert_eq!(log.borrow().deleted_textures, 0);
+    texture.release(&mut ctx);
+    assert_eq!(log.borrow().deleted_textures, 1);
+}
+
+#[test]
+fn textures_are_shared_by_content_and_sampler() {
+    let (mut ctx, log) = context();
+    let mut cache = ResourceCache::new();
+    let handles = [
+        cache.texture(&mut ctx, &texture(1), Sampler::LOOKUP),
+        cache.texture(&mut ctx, &texture(1), Sampler::LOOKUP),
+        cache.texture(&mut ctx, &texture(2), Sampler::LOOKUP),
+        cache.texture(
+            &mut ctx,
+            &texture(1),
+            Sampler {
+                filter: mari_renderers::Filter::Nearest,
+                ..Sampler::LOOKUP
+            },
+        ),
+    ]
+    .map(Result::unwrap);
+    assert_eq!(log.borrow().textures, 3);
+    assert_eq!(handles[0].id(), handles[1].id());
+    assert_ne!(handles[0].id(), handles[2].id());
+
+    for handle in handles {
+        handle.release(&mut ctx);
+    }
+    assert_eq!(cache.collect(&mut ctx), 3);
+    assert_eq!(log.borrow().deleted_textures, 3);
+}
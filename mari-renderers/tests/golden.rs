@@ -0,0 +1,402 @@
+//! Golden-image tests: canonical scenes rendered by `Software` from fixed cameras and compared
+//! against the reference PNGs in `tests/golden/`. `Software` mirrors the GLSL shaders, so a
+//! shading change is made to both and shows up here.
+//!
+//! On a mismatch the rendered image and a diff are written to
+//! `$CARGO_TARGET_TMPDIR/golden/`. After an intended change to the shading, run with
+//! `UPDATE_GOLDEN=1` to rewrite the references, and review them before committing.
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use glam::{Mat4, Vec3};
+use mari_formats::{Model, Submesh, TextureRGBA8};
+use mari_renderers::{
+    DefaultInitParams, FrameContext, Framebuffer, Light, Sampler, Software, TexturedInitParams,
+    ToonInitParams, ToonParams, Transforms,
+};
+
+const SIZE: u16 = 128;
+
+/// a pixel differs when its YIQ distance exceeds this fraction of the largest possible
+const THRESHOLD: f32 = 0.1;
+/// the fraction of differing pixels tolerated, for rasterization and rounding details
+const TOLERANCE: f32 = 0.002;
+
+// ---- scenes ----
+
+fn model(
+    vertices: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    mesh: Vec<u16>,
+) -> Model {
+    Model {
+        vertices: vertices.concat(),
+        uvs: uvs.concat(),
+        normals: normals.concat(),
+        colors: Vec::new(),
+        joints: Vec::new(),
+        weights: Vec::new(),
+        submeshes: vec![Submesh {
+            first: 0,
+            count: mesh.len(),
+            material: None,
+        }],
+        mesh,
+    }
+}
+
+/// a unit cube with each face mapping the whole texture, upright on the sides
+fn cube() -> Model {
+    // normal, right and up of each face, right x up = normal so the corners go CCW
+    let faces = [
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+    ];
+    let (mut vertices, mut uvs, mut normals, mut mesh) = (vec![], vec![], vec![], vec![]);
+    for (n, r, t) in faces {
+        let first = vertices.len() as u16;
+        // top left, bottom left, bottom right, top right
+        for (corner, uv) in [
+            (n - r + t, [0.0, 0.0]),
+            (n - r - t, [0.0, 1.0]),
+            (n + r - t, [1.0, 1.0]),
+            (n + r + t, [1.0, 0.0]),
+        ] {
+            vertices.push((corner * 0.5).to_array());
+            uvs.push(uv);
+            normals.push(n.to_array());
+        }
+        mesh.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+    }
+    model(vertices, uvs, normals, mesh)
+}
+
+/// a unit sphere, u around the equator from +z towards +x and v from the north pole
+fn sphere(rings: u16, segments: u16) -> Model {
+    let (mut vertices, mut uvs, mut normals, mut mesh) = (vec![], vec![], vec![], vec![]);
+    for r in 0..=rings {
+        let v = r as f32 / rings as f32;
+        let theta = v * PI;
+        for s in 0..=segments {
+            let u = s as f32 / segments as f32;
+            let phi = u * 2.0 * PI;
+            let n = [
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                theta.sin() * phi.cos(),
+            ];
+            vertices.push(n);
+            uvs.push([u, v]);
+            normals.push(n);
+        }
+    }
+    let stride = segments + 1;
+    for r in 0..rings {
+        for s in 0..segments {
+            let i = r * stride + s;
+            let (top_left, bottom_left) = (i, i + stride);
+            let (bottom_right, top_right) = (i + stride + 1, i + 1);
+            mesh.extend([top_left, bottom_left, bottom_right]);
+            mesh.extend([top_left, bottom_right, top_right]);
+        }
+    }
+    model(vertices, uvs, normals, mesh)
+}
+
+fn checker(size: u16, cells: u16) -> TextureRGBA8 {
+    let cell = size / cells;
+    let data = (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x / cell + y / cell).is_multiple_of(2)))
+        .flat_map(|light| {
+            if light {
+                [230, 220, 200, 255]
+            } else {
+                [60, 90, 160, 255]
+            }
+        })
+        .collect();
+    TextureRGBA8 { width: size, data }
+}
+
+/// a 3-step toon ramp, lit on the left and darkest on the right
+fn ramp() -> TextureRGBA8 {
+    let data = (0..32u16)
+        .flat_map(|x| match x {
+            0..12 => [255, 255, 255, 255],
+            12..22 => [200, 170, 190, 255],
+            _ => [130, 100, 140, 255],
+        })
+        .collect();
+    TextureRGBA8 { width: 32, data }
+}
+
+fn solid(rgba: [u8; 4]) -> TextureRGBA8 {
+    TextureRGBA8 {
+        width: 1,
+        data: rgba.to_vec(),
+    }
+}
+
+/// Draw with the fixed camera: perspective with a 45 degree fov, looking at the origin from
+/// `eye`.
+fn draw(target: &mut Framebuffer, renderer: &Software, eye: Vec3, model: Mat4, lights: &[Light]) {
+    let transforms = Transforms {
+        model,
+        view: Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y),
+        projection: Mat4::perspective_rh_gl(PI / 4.0, 1.0, 0.1, 100.0),
+    };
+    let frame = FrameContext {
+        time: 0.0,
+        viewport: [SIZE as f32; 2],
+        lights,
+        camera_pos: eye,
+    };
+    renderer.render(target, &transforms, &frame);
+}
+
+fn light(x: f32, y: f32, z: f32) -> Light {
+    Light {
+        position: Vec3::new(x, y, z),
+    }
+}
+
+fn new_target() -> Framebuffer {
+    let mut target = Framebuffer::new(SIZE, SIZE);
+    target.clear([0.1, 0.1, 0.15, 1.0]);
+    target
+}
+
+// ---- comparison ----
+
+fn yiq(p: &[u8]) -> [f32; 3] {
+    // blend over white so transparent pixels compare by what they would show
+    let a = p[3] as f32 / 255.0;
+    let [r, g, b] = [p[0], p[1], p[2]].map(|c| 255.0 + (c as f32 - 255.0) * a);
+    [
+        0.298_895_3 * r + 0.586_622_5 * g + 0.114_482_23 * b,
+        0.595_978 * r - 0.274_176_1 * g - 0.321_801_9 * b,
+        0.211_470_17 * r - 0.522_617_1 * g + 0.311_146_94 * b,
+    ]
+}
+
+/// the perceived color difference of two pixels, 0 to 35215
+fn distance(p: &[u8], q: &[u8]) -> f32 {
+    let ([y0, i0, q0], [y1, i1, q1]) = (yiq(p), yiq(q));
+    let (y, i, q) = (y0 - y1, i0 - i1, q0 - q1);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+/// the number of differing pixels and an image marking them red over a faded `actual`
+fn diff(expected: &TextureRGBA8, actual: &TextureRGBA8) -> (usize, TextureRGBA8) {
+    let max = 35215.0 * THRESHOLD * THRESHOLD;
+    let mut count = 0;
+    let data = expected
+        .data
+        .chunks_exact(4)
+        .zip(actual.data.chunks_exact(4))
+        .flat_map(|(e, a)| {
+            if distance(e, a) > max {
+                count += 1;
+                [255, 0, 0, 255]
+            } else {
+                let y = (yiq(a)[0] * 0.1 + 255.0 * 0.9) as u8;
+                [y, y, y, 255]
+            }
+        })
+        .collect();
+    let image = TextureRGBA8 {
+        width: actual.width,
+        data,
+    };
+    (count, image)
+}
+
+fn write(path: &Path, image: &TextureRGBA8) {
+    image
+        .write_png(BufWriter::new(File::create(path).unwrap()))
+        .unwrap();
+}
+
+fn check(name: &str, actual: &Framebuffer) {
+    let actual = &actual.color;
+    let reference = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.png"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write(&reference, actual);
+        return;
+    }
+    let expected = match File::open(&reference) {
+        Ok(file) => TextureRGBA8::new_from_png(BufReader::new(file)).unwrap(),
+        Err(e) => panic!(
+            "{}: {e}, run with UPDATE_GOLDEN=1 to create it",
+            reference.display()
+        ),
+    };
+
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    let (actual_path, diff_path) = (
+        out.join(format!("{name}.actual.png")),
+        out.join(format!("{name}.diff.png")),
+    );
+    if (expected.width, expected.height()) != (actual.width, actual.height()) {
+        std::fs::create_dir_all(&out).unwrap();
+        write(&actual_path, actual);
+        panic!(
+            "{name}: {}x{} rendered, {}x{} expected, see {}",
+            actual.width,
+            actual.height(),
+            expected.width,
+            expected.height(),
+            actual_path.display()
+        );
+    }
+    let (count, image) = diff(&expected, actual);
+    let pixels = actual.data.len() / 4;
+    if count as f32 > pixels as f32 * TOLERANCE {
+        std::fs::create_dir_all(&out).unwrap();
+        write(&actual_path, actual);
+        write(&diff_path, &image);
+        panic!(
+            "{name}: {count} of {pixels} pixels differ, see {} and {}",
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+// ---- tests ----
+
+#[test]
+fn harness_tolerates_small_changes_only() {
+    let a = checker(16, 4);
+    let mut b = checker(16, 4);
+    // a barely visible change everywhere
+    b.data
+        .iter_mut()
+        .step_by(4)
+        .for_each(|r| *r = r.saturating_add(3));
+    assert_eq!(diff(&a, &b).0, 0);
+    // a visible one in a single pixel
+    b.data[0..4].copy_from_slice(&[0, 255, 0, 255]);
+    let (count, image) = diff(&a, &b);
+    assert_eq!(count, 1);
+    assert_eq!(&image.data[0..4], [255, 0, 0, 255]);
+}
+
+#[test]
+fn primitives() {
+    let eye = Vec3::new(0.5, 2.0, 5.0);
+    let (cube, sphere) = (cube(), sphere(16, 24));
+    let mut target = new_target();
+    for (model, translation) in [
+        (&cube, Vec3::new(-1.0, 0.0, 0.0)),
+        (&sphere, Vec3::new(1.0, 0.0, 0.0)),
+    ] {
+        let renderer = Software::new_default(DefaultInitParams { model }).unwrap();
+        let model = Mat4::from_translation(translation);
+        draw(&mut target, &renderer, eye, model, &[]);
+    }
+    check("primitives", &target);
+}
+
+#[test]
+fn textured_cube() {
+    let eye = Vec3::new(1.5, 1.2, 2.0);
+    let (cube, texture) = (cube(), checker(64, 8));
+    let renderer = Software::new_textured(TexturedInitParams {
+        model: &cube,
+        texture: &texture,
+        sampler: Sampler::default(),
+    })
+    .unwrap();
+    let mut target = new_target();
+    draw(&mut target, &renderer, eye, Mat4::from_rotation_y(0.3), &[]);
+    check("textured_cube", &target);
+}
+
+#[test]
+fn toon_sphere() {
+    let eye = Vec3::new(0.0, 0.5, 3.0);
+    let (sphere, texture, ramp) = (sphere(24, 32), checker(64, 8), ramp());
+    // shadowed everywhere, so the ramp applies to the whole sphere
+    let sdw = solid([0, 0, 0, 255]);
+    let renderer = Software::new_toon(ToonInitParams {
+        model: &sphere,
+        texture: &texture,
+        ramp_texture: &ramp,
+        sdw_texture: &sdw,
+        sampler: Sampler::default(),
+        params: ToonParams::default(),
+    })
+    .unwrap();
+    let mut target = new_target();
+    let lights = [light(-3.0, 3.0, 3.0)];
+    draw(&mut target, &renderer, eye, Mat4::IDENTITY, &lights);
+    check("toon_sphere", &target);
+}
+
+#[test]
+fn toon_shadow_mask() {
+    let eye = Vec3::new(1.5, 1.2, 2.0);
+    let (cube, texture, ramp) = (cube(), solid([240, 200, 180, 255]), ramp());
+    // the ramp applies only on the shadowed checker cells
+    let mut sdw = checker(4, 2);
+    sdw.data
+        .chunks_exact_mut(4)
+        .for_each(|p| p[3] = if p[0] > 128 { 255 } else { 0 });
+    let renderer = Software::new_toon(ToonInitParams {
+        model: &cube,
+        texture: &texture,
+        ramp_texture: &ramp,
+        sdw_texture: &sdw,
+        sampler: Sampler {
+            filter: mari_renderers::Filter::Nearest,
+            ..Sampler::default()
+        },
+        params: ToonParams::default(),
+    })
+    .unwrap();
+    let mut target = new_target();
+    let lights = [light(3.0, 4.0, 1.0)];
+    draw(&mut target, &renderer, eye, Mat4::IDENTITY, &lights);
+    check("toon_shadow_mask", &target);
+}
+
+#[test]
+fn toon_outline() {
+    let eye = Vec3::new(0.0, 0.5, 3.0);
+    let (mut sphere, texture, ramp) = (sphere(24, 32), solid([240, 200, 180, 255]), ramp());
+    // the outline thins out towards the poles
+    sphere.colors = sphere
+        .vertices
+        .chunks_exact(3)
+        .flat_map(|v| [1.0, 1.0, 1.0, 1.0 - v[1].abs()])
+        .collect();
+    let sdw = solid([0, 0, 0, 255]);
+    let renderer = Software::new_toon(ToonInitParams {
+        model: &sphere,
+        texture: &texture,
+        ramp_texture: &ramp,
+        sdw_texture: &sdw,
+        sampler: Sampler::default(),
+        params: ToonParams {
+            outline_width: 4.0,
+            outline_color: [0.55, 0.15, 0.3, 1.0],
+            ..ToonParams::default()
+        },
+    })
+    .unwrap();
+    let mut target = new_target();
+    let lights = [light(-3.0, 3.0, 3.0)];
+    draw(&mut target, &renderer, eye, Mat4::IDENTITY, &lights);
+    check("toon_outline", &target);
+}
@@ -0,0 +1,20 @@
+use mari_renderers::{DynRenderer, Registry};
+
+#[test]
+fn builtin_renderers_are_registered() {
+    let mut registry = Registry::default();
+    assert_eq!(registry.names(), ["default", "textured", "toon"]);
+
+    registry.register("overlay", Box::new(|_, _| unreachable!()));
+    assert_eq!(registry.names(), ["default", "overlay", "textured", "toon"]);
+    assert!(Registry::new().names().is_empty());
+}
+
+#[test]
+fn renderers_are_trait_objects() {
+    fn is_dyn_renderer<T: DynRenderer>() {}
+    is_dyn_renderer::<mari_renderers::Default>();
+    is_dyn_renderer::<mari_renderers::Textured>();
+    is_dyn_renderer::<mari_renderers::Toon>();
+    let _: Option<Box<dyn DynRenderer>> = None;
+}
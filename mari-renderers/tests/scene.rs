@@ -0,0 +1,47 @@
+use glam::{Mat4, Vec3};
+use mari_renderers::SceneRenderer;
+
+#[test]
+fn opaque_front_to_back_then_transparent_back_to_front() {
+    let at = |transparent, z| (transparent, Mat4::IDENTITY, Vec3::new(0.0, 0.0, z));
+    let draws = [
+        at(true, -1.0),
+        at(false, -5.0),
+        at(true, -5.0),
+        at(false, -1.0),
+        at(false, -3.0),
+    ];
+    assert_eq!(
+        SceneRenderer::draw_order(Mat4::IDENTITY, &draws),
+        [3, 4, 1, 2, 0]
+    );
+
+    // the depth is of the center moved by the model transform and seen through the view
+    let model = Mat4::from_translation(Vec3::new(0.0, 0.0, -10.0));
+    let draws = [
+        (false, model, Vec3::ZERO),
+        (false, Mat4::IDENTITY, Vec3::new(0.0, 0.0, -2.0)),
+    ];
+    assert_eq!(SceneRenderer::draw_order(Mat4::IDENTITY, &draws), [1, 0]);
+    // a camera turned around, looking down +z, sees the other way
+    let view = Mat4::from_rotation_y(std::f32::consts::PI);
+    let draws = [
+        (false, Mat4::IDENTITY, Vec3::new(0.0, 0.0, 2.0)),
+        (false, Mat4::IDENTITY, Vec3::new(0.0, 0.0, 1.0)),
+    ];
+    assert_eq!(SceneRenderer::draw_order(view, &draws), [1, 0]);
+}
+
+#[test]
+fn equal_depths_keep_their_order() {
+    let draws = [
+        (true, Mat4::IDENTITY, Vec3::NEG_Z),
+        (false, Mat4::IDENTITY, Vec3::NEG_Z),
+        (true, Mat4::IDENTITY, Vec3::NEG_Z),
+        (false, Mat4::IDENTITY, Vec3::NEG_Z),
+    ];
+    assert_eq!(
+        SceneRenderer::draw_order(Mat4::IDENTITY, &draws),
+        [1, 3, 0, 2]
+    );
+}
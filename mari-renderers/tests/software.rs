@@ -0,0 +1,425 @@
+use glam::{Mat4, Vec3};
+use mari_formats::{Material, Model, Submesh, TextureRGBA8, ToonMaterial};
+use mari_renderers::{
+    DefaultInitParams, Filter, FrameContext, Framebuffer, Light, RendererError, Sampler, Software,
+    TexturedInitParams, ToonInitParams, ToonParams, Transforms,
+};
+
+/// identity transforms and an empty frame, so model space is clip space
+const IDENTITY: (Transforms, FrameContext) = (
+    Transforms {
+        model: Mat4::IDENTITY,
+        view: Mat4::IDENTITY,
+        projection: Mat4::IDENTITY,
+    },
+    FrameContext {
+        time: 0.0,
+        viewport: [0.0; 2],
+        lights: &[],
+        camera_pos: Vec3::ZERO,
+    },
+);
+
+/// triangles of x,y,z corners with u,v and a normal towards +z
+fn model(corners: &[[f32; 5]], mesh: Vec<u16>) -> Model {
+    Model {
+        vertices: corners.iter().flat_map(|c| [c[0], c[1], c[2]]).collect(),
+        uvs: corners.iter().flat_map(|c| [c[3], c[4]]).collect(),
+        normals: corners.iter().flat_map(|_| [0.0, 0.0, 1.0]).collect(),
+        colors: Vec::new(),
+        joints: Vec::new(),
+        weights: Vec::new(),
+        submeshes: vec![Submesh {
+            first: 0,
+            count: mesh.len(),
+            material: None,
+        }],
+        mesh,
+    }
+}
+
+/// a quad over the whole viewport at depth `z`, UV 0,0 at the top left
+fn quad(z: f32) -> Model {
+    model(
+        &[
+            [-1.0, 1.0, z, 0.0, 0.0],
+            [-1.0, -1.0, z, 0.0, 1.0],
+            [1.0, -1.0, z, 1.0, 1.0],
+            [1.0, 1.0, z, 1.0, 0.0],
+        ],
+        vec![0, 1, 2, 0, 2, 3],
+    )
+}
+
+fn pixel(target: &Framebuffer, x: usize, y: usize) -> [u8; 4] {
+    let i = (y * target.width() as usize + x) * 4;
+    target.color.data[i..i + 4].try_into().unwrap()
+}
+
+#[test]
+fn default_fills_and_culls() {
+    let mut target = Framebuffer::new(8, 6);
+    target.clear([0.0, 0.0, 1.0, 1.0]);
+    let front = quad(0.0);
+    Software::new_default(DefaultInitParams { model: &front })
+        .unwrap()
+        .render(&mut target, &IDENTITY.0, &IDENTITY.1);
+    assert!(
+        target
+            .color
+            .data
+            .chunks_exact(4)
+            .all(|p| p == [179, 179, 179, 255])
+    );
+    assert!(target.depth.iter().all(|d| (d - 0.5).abs() < 1e-6));
+
+    // the same quad wound clockwise faces away
+    let mut back = quad(0.0);
+    back.mesh = vec![0, 2, 1, 0, 3, 2];
+    target.clear([0.0, 0.0, 1.0, 1.0]);
+    Software::new_default(DefaultInitParams { model: &back })
+        .unwrap()
+        .render(&mut target, &IDENTITY.0, &IDENTITY.1);
+    assert!(
+        target
+            .color
+            .data
+            .chunks_exact(4)
+            .all(|p| p == [0, 0, 255, 255])
+    );
+}
+
+#[test]
+fn default_draws_an_unlit_material() {
+    let material = Material::Unlit {
+        color: [1.0, 0.0, 0.5, 1.0],
+    };
+    let Material::Unlit { color } = material else {
+        unreachable!()
+    };
+    let mut target = Framebuffer::new(4, 4);
+    target.clear([0.0, 0.0, 1.0, 1.0]);
+    let mut renderer = Software::new_default(DefaultInitParams { model: &quad(0.0) }).unwrap();
+    renderer.set_color(color);
+    renderer.render(&mut target, &IDENTITY.0, &IDENTITY.1);
+    assert!(
+        target
+            .color
+            .data
+            .chunks_exact(4)
+            .all(|p| p == [255, 0, 128, 255])
+    );
+}
+
+#[test]
+fn depth_test_is_less() {
+    let texture = |c: u8| TextureRGBA8 {
+        width: 1,
+        data: vec![c, c, c, 255],
+    };
+    let (near, far) = (texture(200), texture(50));
+    let (near_quad, far_quad) = (quad(-0.5), quad(0.5));
+    let sampler = Sampler::default();
+    let renderers = [
+        Software::new_textured(TexturedInitParams {
+            model: &near_quad,
+            texture: &near,
+            sampler,
+        })
+        .unwrap(),
+        Software::new_textured(TexturedInitParams {
+            model: &far_quad,
+            texture: &far,
+            sampler,
+        })
+        .unwrap(),
+    ];
+    for order in [[0, 1], [1, 0]] {
+        let mut target = Framebuffer::new(4, 4);
+        for i in order {
+            renderers[i].render(&mut target, &IDENTITY.0, &IDENTITY.1);
+        }
+        assert_eq!(pixel(&target, 1, 1), [200, 200, 200, 255]);
+        assert!((target.depth[5] - 0.25).abs() < 1e-6);
+    }
+
+    // equal depth doesn't pass LESS, so the first quad drawn stays
+    let mut target = Framebuffer::new(4, 4);
+    let same = quad(-0.5);
+    Software::new_textured(TexturedInitParams {
+        model: &same,
+        texture: &far,
+        sampler,
+    })
+    .unwrap()
+    .render(&mut target, &IDENTITY.0, &IDENTITY.1);
+    renderers[0].render(&mut target, &IDENTITY.0, &IDENTITY.1);
+    assert_eq!(pixel(&target, 1, 1), [50, 50, 50, 255]);
+}
+
+#[test]
+fn textured_maps_uv_from_the_top_left() {
+    let texture = TextureRGBA8 {
+        width: 2,
+        data: [
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [9, 9, 9, 255],
+        ]
+        .concat(),
+    };
+    let model = quad(0.0);
+    let mut target = Framebuffer::new(4, 4);
+    Software::new_textured(TexturedInitParams {
+        model: &model,
+        texture: &texture,
+        sampler: Sampler {
+            filter: Filter::Nearest,
+            ..Sampler::default()
+        },
+    })
+    .unwrap()
+    .render(&mut target, &IDENTITY.0, &IDENTITY.1);
+    assert_eq!(pixel(&target, 0, 0), [255, 0, 0, 255]);
+    assert_eq!(pixel(&target, 3, 0), [0, 255, 0, 255]);
+    assert_eq!(pixel(&target, 0, 3), [0, 0, 255, 255]);
+    assert_eq!(pixel(&target, 3, 3), [9, 9, 9, 255]);
+}
+
+#[test]
+fn textured_blends_by_alpha() {
+    let texture = TextureRGBA8 {
+        width: 1,
+        data: vec![255, 0, 0, 51],
+    };
+    let model = quad(0.0);
+    let mut target = Framebuffer::new(2, 2);
+    target.clear([0.0, 0.0, 1.0, 1.0]);
+    Software::new_textured(TexturedInitParams {
+        model: &model,
+        texture: &texture,
+        sampler: Sampler::default(),
+    })
+    .unwrap()
+    .render(&mut target, &IDENTITY.0, &IDENTITY.1);
+    assert_eq!(pixel(&target, 0, 0), [51, 0, 204, 214]);
+}
+
+#[test]
+fn toon_ramp_only_where_shadowed() {
+    let white = TextureRGBA8 {
+        width: 1,
+        data: vec![255, 200, 100, 255],
+    };
+    let ramp = TextureRGBA8 {
+        width: 1,
+        data: vec![128, 128, 128, 255],
+    };
+    let model = quad(0.0);
+    let (lit, shadowed) = ([255, 200, 100, 255], [128, 100, 50, 255]);
+    for (shadow, shadow_threshold, expected) in [
+        (0, 0.5, lit),
+        (255, 0.5, shadowed),
+        (128, 0.5, shadowed),
+        (128, 0.6, lit),
+    ] {
+        let sdw = TextureRGBA8 {
+            width: 1,
+            data: vec![0, 0, 0, shadow],
+        };
+        let renderer = Software::new_toon(ToonInitParams {
+            model: &model,
+            texture: &white,
+            ramp_texture: &ramp,
+            sdw_texture: &sdw,
+            sampler: Sampler::default(),
+            params: ToonParams {
+                shadow_threshold,
+                ..ToonParams::default()
+            },
+        })
+        .unwrap();
+        let frame = FrameContext {
+            lights: &[Light {
+                position: Vec3::new(0.0, 0.0, 5.0),
+            }],
+            ..IDENTITY.1
+        };
+        let mut target = Framebuffer::new(4, 4);
+        renderer.render(&mut target, &IDENTITY.0, &frame);
+        assert_eq!(pixel(&target, 2, 2), expected);
+    }
+}
+
+#[test]
+fn toon_outline_surrounds_the_silhouette() {
+    let texture = TextureRGBA8 {
+        width: 1,
+        data: vec![255, 200, 100, 255],
+    };
+    // a quad over the middle half of the viewport, closed by its back face, with the normals of
+    // a smooth hull pointing out through the corners
+    let mut hull = model(
+        &[
+            [-0.5, 0.5, 0.0, 0.0, 0.0],
+            [-0.5, -0.5, 0.0, 0.0, 1.0],
+            [0.5, -0.5, 0.0, 1.0, 1.0],
+            [0.5, 0.5, 0.0, 1.0, 0.0],
+        ],
+        vec![0, 1, 2, 0, 2, 3, 0, 2, 1, 0, 3, 2],
+    );
+    let half = 0.5f32.sqrt();
+    hull.normals = vec![
+        -half, half, 0.0, -half, -half, 0.0, half, -half, 0.0, half, half, 0.0,
+    ];
+    let outline_color = [0.2, 0.4, 0.6, 0.5];
+    let width = |outline_width| ToonParams {
+        outline_width,
+        ..ToonParams::default()
+    };
+    // 2 pixels are 0.25 along x and y, so 0.25 / half along the normals, as a material has it
+    let blank = || TextureRGBA8 {
+        width: 0,
+        data: Vec::new(),
+    };
+    let material = ToonMaterial {
+        texture: blank(),
+        ramp_texture: blank(),
+        sdw_texture: blank(),
+        shadow_threshold: 1.0,
+        ramp_scale: ToonMaterial::RAMP_SCALE,
+        outline_width: 0.25 / half / ToonParams::OUTLINE_UNIT,
+        outline_color,
+    };
+    for (params, alpha, outlined) in [
+        (width(2.0), 1.0, true),
+        (width(0.0), 1.0, false),
+        (width(2.0), 0.0, false),
+        (ToonParams::from_material(&material), 1.0, true),
+        (ToonParams::from_material(&material), 0.0, false),
+    ] {
+        hull.colors = [1.0, 1.0, 1.0, alpha].repeat(4);
+        let renderer = Software::new_toon(ToonInitParams {
+            model: &hull,
+            texture: &texture,
+            ramp_texture: &texture,
+            sdw_texture: &texture,
+            sampler: Sampler::default(),
+            params: ToonParams {
+                shadow_threshold: 1.0,
+                outline_color,
+                ..params
+            },
+        })
+        .unwrap();
+        let mut target = Framebuffer::new(16, 16);
+        renderer.render(&mut target, &IDENTITY.0, &IDENTITY.1);
+        // the quad covers pixels 4 to 11, its outline the ones around, opaque
+        assert_eq!(pixel(&target, 8, 8), [255, 200, 100, 255]);
+        let expected = match outlined {
+            true => [51, 102, 153, 255],
+            false => [0; 4],
+        };
+        assert_eq!(pixel(&target, 3, 8), expected);
+        assert_eq!(pixel(&target, 8, 12), expected);
+        assert_eq!(pixel(&target, 1, 8), [0; 4]);
+    }
+}
+
+#[test]
+fn triangles_behind_the_camera_are_clipped() {
+    // a perspective projection looking down -z, near 0.1 and far 10
+    let (n, f) = (0.1f32, 10.0f32);
+    let transforms = Transforms {
+        projection: Mat4::from_cols_array_2d(&[
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, (f + n) / (n - f), -1.0],
+            [0.0, 0.0, 2.0 * f * n / (n - f), 0.0],
+        ]),
+        ..IDENTITY.0
+    };
+    // a floor reaching from in front of the camera to behind it
+    let floor = model(
+        &[
+            [-1.0, -0.5, -5.0, 0.0, 0.0],
+            [-1.0, -0.5, 5.0, 0.0, 1.0],
+            [1.0, -0.5, 5.0, 1.0, 1.0],
+            [1.0, -0.5, -5.0, 1.0, 0.0],
+        ],
+        vec![0, 1, 2, 0, 2, 3],
+    );
+    let mut target = Framebuffer::new(16, 16);
+    Software::new_default(DefaultInitParams { model: &floor })
+        .unwrap()
+        .render(&mut target, &transforms, &IDENTITY.1);
+    // the lower half shows the floor, the upper half stays empty
+    assert_eq!(pixel(&target, 8, 15), [179, 179, 179, 255]);
+    assert_eq!(pixel(&target, 8, 2), [0, 0, 0, 0]);
+    assert!(target.depth.iter().all(|d| (0.0..=1.0).contains(d)));
+}
+
+#[test]
+fn mismatched_models_are_rejected() {
+    let mut missing_normals = quad(0.0);
+    missing_normals.normals.truncate(9);
+    let texture = TextureRGBA8 {
+        width: 1,
+        data: vec![255; 4],
+    };
+    let toon = Software::new_toon(ToonInitParams {
+        model: &missing_normals,
+        texture: &texture,
+        ramp_texture: &texture,
+        sdw_texture: &texture,
+        sampler: Sampler::default(),
+        params: ToonParams::default(),
+    });
+    assert!(matches!(
+        toon,
+        Err(RendererError::AttributeLength {
+            attribute: "normals",
+            len: 9,
+            expected: 12,
+        })
+    ));
+    let mut missing_colors = quad(0.0);
+    missing_colors.colors = vec![1.0; 8];
+    assert!(matches!(
+        Software::new_default(DefaultInitParams {
+            model: &missing_colors
+        }),
+        Err(RendererError::AttributeLength {
+            attribute: "colors",
+            len: 8,
+            expected: 16,
+        })
+    ));
+    // normals aren't used without toon shading
+    assert!(
+        Software::new_default(DefaultInitParams {
+            model: &missing_normals
+        })
+        .is_ok()
+    );
+
+    let mut out_of_range = quad(0.0);
+    out_of_range.mesh[4] = 4;
+    assert!(matches!(
+        Software::new_default(DefaultInitParams {
+            model: &out_of_range
+        }),
+        Err(RendererError::IndexOutOfRange {
+            index: 4,
+            vertices: 4
+        })
+    ));
+
+    let mut open = quad(0.0);
+    open.mesh.pop();
+    assert!(matches!(
+        Software::new_default(DefaultInitParams { model: &open }),
+        Err(RendererError::NotTriangles { len: 5 })
+    ));
+}
@@ -0,0 +1,51 @@
+use mari_formats::PixelFormat;
+use mari_renderers::upload_format;
+use miniquad::{Backend, TextureFormat};
+
+const FORMATS: [PixelFormat; 8] = [
+    PixelFormat::R8,
+    PixelFormat::RG8,
+    PixelFormat::RGBA8,
+    PixelFormat::RGBA8Srgb,
+    PixelFormat::R16F,
+    PixelFormat::RGBA16F,
+    PixelFormat::RGBA32F,
+    PixelFormat::Depth,
+];
+
+#[test]
+fn uploads_are_as_long_as_miniquad_reads() {
+    let (width, height) = (3, 5);
+    for format in FORMATS {
+        for backend in [Backend::OpenGl, Backend::Metal] {
+            let (to, data) = upload_format(format, backend);
+            let len = data.bytes_per_pixel() as u32 * width * height;
+            if to == TextureFormat::RGBA16F && backend == Backend::OpenGl {
+                // read as GL_FLOAT, through `TextureSource::Array` which has no length check
+                assert_eq!(data, PixelFormat::RGBA32F);
+                assert_eq!(len, 16 * width * height);
+            } else {
+                // the length `Texture::new` asserts for `TextureSource::Bytes`
+                assert_eq!(len, to.size(width, height), "{format:?} on {backend:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn float_formats_go_up_as_half_floats() {
+    for format in [
+        PixelFormat::R16F,
+        PixelFormat::RGBA16F,
+        PixelFormat::RGBA32F,
+    ] {
+        assert_eq!(
+            upload_format(format, Backend::Metal),
+            (TextureFormat::RGBA16F, PixelFormat::RGBA16F)
+        );
+        assert_eq!(
+            upload_format(format, Backend::OpenGl).0,
+            TextureFormat::RGBA16F
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::env;
+use std::path::PathBuf;
+
+use mari_tools::convert::{Options, load, process, save};
+
+const USAGE: &str = "[--cleanup] [--weld <epsilon>] [--optimize] [--max-texture-size <pixels>] \
+<input>... -o <output.gltf|output.glb|output.obj|output.mari>";
+
+fn main() -> Result<(), mari_tools::Error> {
+    let args: Vec<String> = env::args().collect();
+    let mut options = Options::default();
+    let mut inputs = Vec::new();
+    let mut output = None;
+
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        let mut value = |name: &str| rest.next().ok_or(format!("{name} needs a value."));
+        match arg.as_str() {
+            "--cleanup" => options.cleanup = true,
+            "--optimize" => options.optimize = true,
+            "--weld" => options.weld = Some(value(arg)?.parse()?),
+            "--max-texture-size" => options.max_texture_size = Some(value(arg)?.parse()?),
+            "-o" | "--output" => output = Some(PathBuf::from(value(arg)?)),
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    let Some(output) = output.filter(|_| !inputs.is_empty()) else {
+        eprintln!("Usage: {} {USAGE}", args[0]);
+        std::process::exit(1);
+    };
+
+    let mut scene = load(&inputs)?;
+    for line in process(&mut scene, &options) {
+        println!("{line}");
+    }
+    save(&scene, &output)?;
+    println!(
+        "Wrote {} actors to {}.",
+        scene.actors.len(),
+        output.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::env;
+use std::path::Path;
+
+use mari_tools::inspect::{inspect, to_text};
+
+fn main() -> Result<(), mari_tools::Error> {
+    let args: Vec<String> = env::args().collect();
+    let json = args.iter().any(|a| a == "--json");
+    let files: Vec<&String> = args[1..].iter().filter(|a| *a != "--json").collect();
+    if files.is_empty() {
+        eprintln!(
+            "Usage: {} [--json] <unity3d|obj|gltf|glb|pmx file>...",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+
+    let mut summaries = Vec::new();
+    for file in files {
+        let path = Path::new(file);
+        let data = std::fs::read(path)?;
+        let format =
+            mari_tools::sniff(path, &data).ok_or(format!("{file}: Unknown file format."))?;
+        let summary = inspect(path, format, data)?;
+        if json {
+            summaries.push(summary);
+        } else {
+            println!("{file}");
+            print!("{}", to_text(&summary));
+        }
+    }
+    if json {
+        let out = match summaries.len() {
+            1 => summaries.remove(0),
+            _ => serde_json::Value::Array(summaries),
+        };
+        println!("{}", serde_json::to_string_pretty(&out)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,268 @@
+//! Loading any supported input into a `Scene`, optional processing and writing it back out.
+
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use mari_formats::pmx::Pmx;
+use mari_formats::unity::{self, CLASS_MATERIAL, assemble_actor};
+use mari_formats::{Actor, Model, Scene, TextureRGBA8};
+
+use crate::{Error, Format, load_bundle, sniff};
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// drop degenerate triangles and unused vertices
+    pub cleanup: bool,
+    /// merge vertices falling in the same cell of a grid this fine
+    pub weld: Option<f32>,
+    /// reorder for the vertex cache
+    pub optimize: bool,
+    /// take the largest mip level with both dimensions at most this
+    pub max_texture_size: Option<u16>,
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("actor")
+        .to_string()
+}
+
+/// extensions of the image files `TextureRGBA8::load` may read
+const IMAGE_EXTENSIONS: [&str; 6] = [".png", ".tga", ".jpg", ".jpeg", ".dds", ".ktx2"];
+
+fn load_image(path: &Path) -> Result<TextureRGBA8, Error> {
+    Ok(TextureRGBA8::load(&std::fs::read(path)?)?)
+}
+
+fn insert(scene: &mut Scene, name: String, actor: Actor, texture: Option<TextureRGBA8>) {
+    let mut unique = name.clone();
+    let mut i = 1;
+    while scene.actors.contains_key(&unique) {
+        unique = format!("{name}{i}");
+        i += 1;
+    }
+    if let Some(texture) = texture {
+        scene.textures.insert(unique.clone(), texture);
+    }
+    scene.actors.insert(unique, actor);
+}
+
+/// Read every input into one scene.
+///
+/// All Unity bundles together make up one actor, the first of them being the body. Every other
+/// input adds its own actors.
+pub fn load(inputs: &[PathBuf]) -> Result<Scene, Error> {
+    let mut scene = Scene::default();
+    let mut bundles = Vec::new();
+    let mut bundle_name = None;
+
+    for path in inputs {
+        let data = std::fs::read(path)?;
+        let format =
+            sniff(path, &data).ok_or(format!("{}: Unknown file format.", path.display()))?;
+        let dir = path.parent();
+        match format {
+            Format::Unity => {
+                bundle_name.get_or_insert_with(|| stem(path));
+                bundles.push(load_bundle(path, data)?);
+            }
+            Format::Obj => {
+                let model = Model::new_from_obj(BufReader::new(data.as_slice()))?;
+                let texture = obj_texture(&data, &model, dir)?;
+                insert(&mut scene, stem(path), Actor::new(model), texture);
+            }
+            Format::Pmx => {
+                let pmx = Pmx::new(BufReader::new(data.as_slice()))?;
+                let texture = pmx
+                    .materials
+                    .first()
+                    .and_then(|m| m.texture)
+                    .and_then(|t| pmx.textures.get(t))
+                    .filter(|t| {
+                        let t = t.to_ascii_lowercase();
+                        IMAGE_EXTENSIONS.iter().any(|e| t.ends_with(e))
+                    })
+                    .map(|t| load_image(&dir.unwrap_or(Path::new(".")).join(t)))
+                    .transpose()?;
+                insert(&mut scene, stem(path), pmx.to_actor()?, texture);
+            }
+            Format::Gltf | Format::Glb | Format::Native => {
+                let other = match format {
+                    Format::Native => Scene::new_from_native(&data)?,
+                    _ => Scene::new_from_gltf(&data, dir)?,
+                };
+                let mut textures = other.textures;
+                for (name, actor) in other.actors {
+                    let texture = textures.remove(&name);
+                    insert(&mut scene, name, actor, texture);
+                }
+                scene.clips.extend(other.clips);
+                scene.materials.extend(other.materials);
+            }
+        }
+    }
+
+    if let Some(name) = bundle_name {
+        let actor = assemble_actor(&bundles)?;
+        let texture = bundle_texture(&bundles[0], &actor)?;
+        insert(&mut scene, name, actor, texture);
+    }
+    Ok(scene)
+}
+
+/// the base texture of the material of the body's first submesh, failing on undecodable formats
+fn bundle_texture(bundle: &unity::Bundle, actor: &Actor) -> Result<Option<TextureRGBA8>, Error> {
+    let Some(name) = actor
+        .body
+        .submeshes
+        .first()
+        .and_then(|s| s.material.as_ref())
+    else {
+        return Ok(None);
+    };
+    for (file, object) in bundle.objects_of_class(CLASS_MATERIAL) {
+        let material = unity::Material::new(&file.read(object)?)?;
+        if &material.name == name {
+            return match material.base_texture(bundle, file) {
+                Ok(texture) => Ok(Some(texture)),
+                // an ETC2 or ASTC texture can't be decoded, don't silently drop it
+                Err(e @ unity::Error::Unsupported(_)) => Err(e.into()),
+                Err(_) => Ok(None),
+            };
+        }
+    }
+    Ok(None)
+}
+
+/// the `map_Kd` of the first material of `model` in the `mtllib` of an OBJ
+fn obj_texture(
+    obj: &[u8],
+    model: &Model,
+    dir: Option<&Path>,
+) -> Result<Option<TextureRGBA8>, Error> {
+    let dir = dir.unwrap_or(Path::new("."));
+    let text = String::from_utf8_lossy(obj);
+    let Some(mtllib) = text.lines().find_map(|l| l.trim().strip_prefix("mtllib ")) else {
+        return Ok(None);
+    };
+    let Ok(mtl) = std::fs::read_to_string(dir.join(mtllib.trim())) else {
+        return Ok(None);
+    };
+
+    let wanted = model.submeshes.iter().find_map(|s| s.material.as_deref());
+    let mut current = None;
+    let mut first = None;
+    for line in mtl.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("newmtl ") {
+            current = Some(name.trim());
+        } else if let Some(map) = line.strip_prefix("map_Kd ") {
+            // options like `-s 1 1 1` come before the file name
+            let map = map.split_whitespace().last().unwrap_or_default();
+            if current == wanted || wanted.is_none() {
+                return Ok(Some(load_image(&dir.join(map))?));
+            }
+            first.get_or_insert(map);
+        }
+    }
+    first.map(|map| load_image(&dir.join(map))).transpose()
+}
+
+/// Run the requested steps over every model and texture, returning what was done.
+pub fn process(scene: &mut Scene, options: &Options) -> Vec<String> {
+    let mut report = Vec::new();
+    let mut names: Vec<String> = scene.actors.keys().cloned().collect();
+    names.sort();
+    for name in &names {
+        let actor = scene.actors.get_mut(name).unwrap();
+        let models = std::iter::once((name.clone(), &mut actor.body)).chain(
+            actor
+                .parts
+                .iter_mut()
+                .map(|p| (format!("{name}/{}", p.name), &mut p.model)),
+        );
+        for (label, model) in models {
+            if options.cleanup {
+                let dropped = model.cleanup();
+                report.push(format!("{label}: dropped {dropped} degenerate triangles"));
+            }
+            if let Some(epsilon) = options.weld {
+                let merged = model.weld(epsilon);
+                report.push(format!("{label}: welded {merged} vertices"));
+            }
+            if options.optimize {
+                model.optimize();
+                report.push(format!("{label}: optimized for the vertex cache"));
+            }
+        }
+    }
+
+    if let Some(max) = options.max_texture_size {
+        for name in &names {
+            let Some(texture) = scene.textures.get_mut(name) else {
+                continue;
+            };
+            let before = (texture.width, texture.height());
+            if before.0 <= max && before.1 <= max {
+                continue;
+            }
+            // the chain ends at 1x1, which fits any limit
+            let fits = |t: &TextureRGBA8| t.width <= max && t.height() <= max;
+            if let Some(level) = texture.mip_chain(None).into_iter().find(fits) {
+                *texture = level;
+            }
+            if before != (texture.width, texture.height()) {
+                report.push(format!(
+                    "{name}: texture {}x{} -> {}x{}",
+                    before.0,
+                    before.1,
+                    texture.width,
+                    texture.height()
+                ));
+            }
+        }
+    }
+    report
+}
+
+/// Write the scene in the format given by the extension of `output`.
+///
+/// A `.gltf` gets its buffer written next to it as `.bin`, an `.obj` its `.mtl` and textures.
+/// `.mari` is the native container.
+pub fn save(scene: &Scene, output: &Path) -> Result<(), Error> {
+    let extension = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "glb" => std::fs::write(output, scene.write_glb()?)?,
+        "mari" => std::fs::write(output, scene.write_native())?,
+        "obj" => {
+            let mtl = output.with_extension("mtl");
+            let name = mtl
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("scene.mtl");
+            let files = scene.write_obj(name)?;
+            std::fs::write(output, files.obj)?;
+            std::fs::write(&mtl, files.mtl)?;
+            let dir = output.parent().unwrap_or(Path::new("."));
+            for (name, png) in files.textures {
+                std::fs::write(dir.join(name), png)?;
+            }
+        }
+        "gltf" => {
+            let bin = output.with_extension("bin");
+            let uri = bin
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("scene.bin");
+            let (json, data) = scene.write_gltf(uri)?;
+            std::fs::write(output, json)?;
+            std::fs::write(&bin, data)?;
+        }
+        _ => return Err(format!("{}: Unknown output format.", output.display()).into()),
+    }
+    Ok(())
+}
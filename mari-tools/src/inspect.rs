@@ -0,0 +1,413 @@
+//! Structured summaries of model files, as JSON values that also render as indented text.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::BufReader;
+use std::path::Path;
+
+use mari_formats::pmx::Pmx;
+use mari_formats::unity::{
+    self, Bundle, CLASS_ANIMATION_CLIP, CLASS_MATERIAL, CLASS_MESH, CLASS_TEXTURE2D, Mesh, Value,
+};
+use mari_formats::{MaterialView, Model, SceneView};
+use serde_json::{Map, Value as Json, json};
+
+use crate::{Error, Format, load_bundle};
+
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a;
+
+/// Summarize the file at `path` holding `data`.
+pub fn inspect(path: &Path, format: Format, data: Vec<u8>) -> Result<Json, Error> {
+    match format {
+        Format::Unity => inspect_bundle(&load_bundle(path, data)?),
+        Format::Obj => inspect_obj(path, &data),
+        Format::Gltf => inspect_gltf(&serde_json::from_slice(&data)?),
+        Format::Glb => inspect_gltf(&glb_json(&data)?),
+        Format::Pmx => inspect_pmx(&Pmx::new(BufReader::new(data.as_slice()))?),
+        Format::Native => inspect_native(&SceneView::new(&data)?),
+    }
+}
+
+fn mesh_stats(
+    name: &str,
+    vertices: usize,
+    triangles: usize,
+    submeshes: usize,
+    bones: usize,
+) -> Json {
+    json!({
+        "name": name,
+        "vertices": vertices,
+        "triangles": triangles,
+        "submeshes": submeshes,
+        "bones": bones,
+    })
+}
+
+pub fn inspect_native(scene: &SceneView) -> Result<Json, Error> {
+    let mut meshes = Vec::new();
+    for actor in &scene.actors {
+        let bones = actor.skeleton.as_ref().map_or(0, |s| s.bones.len());
+        let models = std::iter::once((actor.name.to_string(), &actor.body)).chain(
+            actor
+                .parts
+                .iter()
+                .map(|(name, model)| (format!("{}/{name}", actor.name), model)),
+        );
+        for (name, model) in models {
+            meshes.push(mesh_stats(
+                &name,
+                model.vertices.len() / 3,
+                model.mesh.len() / 3,
+                model.submeshes.len(),
+                bones,
+            ));
+        }
+    }
+    let textures: Vec<Json> = scene
+        .textures
+        .iter()
+        .map(|(name, t)| json!({ "name": name, "width": t.width, "height": t.height }))
+        .collect();
+    let materials: Vec<Json> = scene
+        .materials
+        .iter()
+        .map(|(name, material)| match material {
+            MaterialView::Unlit { .. } => json!({ "name": name, "kind": "unlit" }),
+            MaterialView::Textured { .. } => json!({ "name": name, "kind": "textured" }),
+            MaterialView::Toon(toon) => {
+                json!({ "name": name, "kind": "toon", "outline width": toon.outline_width })
+            }
+            MaterialView::Pbr(_) => json!({ "name": name, "kind": "pbr" }),
+        })
+        .collect();
+    let animations: Vec<Json> = scene
+        .clips
+        .iter()
+        .map(|c| {
+            json!({
+                "name": c.name,
+                "length": c.times.last().copied().unwrap_or(0.0),
+                "tracks": c.tracks.len(),
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "format": "mari",
+        "version": format!("{}.{}", scene.version.0, scene.version.1),
+        "meshes": meshes,
+        "textures": textures,
+        "materials": materials,
+        "animations": animations,
+    }))
+}
+
+pub fn inspect_bundle(bundle: &Bundle) -> Result<Json, Error> {
+    let mut files = Vec::new();
+    for file in &bundle.files {
+        let mut types = BTreeMap::<String, usize>::new();
+        for object in &file.objects {
+            let name = file
+                .type_name(object)
+                .map_or_else(|| format!("class {}", object.class_id), str::to_string);
+            *types.entry(name).or_default() += 1;
+        }
+        files.push(json!({
+            "name": file.name,
+            "objects": file.objects.len(),
+            "types": types,
+        }));
+    }
+
+    let mut meshes = Vec::new();
+    for (file, object) in bundle.objects_of_class(CLASS_MESH) {
+        let value = file.read(object)?;
+        meshes.push(match Mesh::new(bundle, &value) {
+            Ok(mesh) => mesh_stats(
+                &mesh.name,
+                mesh.vertex_cnt,
+                mesh.submeshes.iter().map(|s| s.len() / 3).sum(),
+                mesh.submeshes.len(),
+                mesh.bind_poses.len(),
+            ),
+            Err(e) => json!({ "name": name_of(&value), "error": format!("{e:?}") }),
+        });
+    }
+
+    let mut textures = Vec::new();
+    for (file, object) in bundle.objects_of_class(CLASS_TEXTURE2D) {
+        let value = file.read(object)?;
+        let int = |name| value.get(name).and_then(Value::as_i64);
+        let format = int("m_TextureFormat").unwrap_or(0);
+        textures.push(json!({
+            "name": name_of(&value),
+            "width": int("m_Width"),
+            "height": int("m_Height"),
+            "format": unity::texture_format_name(format)
+                .map_or_else(|| format!("TextureFormat {format}"), str::to_string),
+            "mips": int("m_MipCount"),
+        }));
+    }
+
+    let mut materials = Vec::new();
+    for (file, object) in bundle.objects_of_class(CLASS_MATERIAL) {
+        let material = unity::Material::new(&file.read(object)?)?;
+        let mut bindings = Map::new();
+        for (property, env) in &material.textures {
+            if let Some((f, o)) = bundle.resolve(file, &env.texture) {
+                bindings.insert(property.clone(), json!(name_of(&f.read(o)?)));
+            }
+        }
+        materials.push(json!({ "name": material.name, "textures": bindings }));
+    }
+
+    let mut animations = Vec::new();
+    for (file, object) in bundle.objects_of_class(CLASS_ANIMATION_CLIP) {
+        let value = file.read(object)?;
+        let clip = value.get("m_MuscleClip");
+        let time = |name| clip.and_then(|c| c.get(name)).and_then(Value::as_f32);
+        let length = time("m_StopTime").map(|stop| stop - time("m_StartTime").unwrap_or(0.0));
+        animations.push(json!({ "name": name_of(&value), "length": length }));
+    }
+
+    Ok(json!({
+        "format": "unity3d",
+        "unity_version": bundle.unity_version,
+        "files": files,
+        "meshes": meshes,
+        "textures": textures,
+        "materials": materials,
+        "animations": animations,
+    }))
+}
+
+fn name_of(value: &Value) -> &str {
+    value
+        .get("m_Name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+pub fn inspect_obj(path: &Path, data: &[u8]) -> Result<Json, Error> {
+    let model = Model::new_from_obj(BufReader::new(data))?;
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    Ok(json!({
+        "format": "obj",
+        "meshes": [mesh_stats(
+            name,
+            model.vertices.len() / 3,
+            model.mesh.len() / 3,
+            model.submeshes.len(),
+            0,
+        )],
+    }))
+}
+
+pub fn inspect_pmx(pmx: &Pmx) -> Result<Json, Error> {
+    let texture = |i: Option<usize>| i.and_then(|i| pmx.textures.get(i));
+    let materials: Vec<Json> = pmx
+        .materials
+        .iter()
+        .map(|m| {
+            json!({
+                "name": m.name,
+                "triangles": m.index_count / 3,
+                "textures": {
+                    "texture": texture(m.texture),
+                    "sphere": texture(m.sphere_texture),
+                    "toon": texture(m.toon_texture),
+                },
+            })
+        })
+        .collect();
+    Ok(json!({
+        "format": "pmx",
+        "name": pmx.name,
+        "meshes": [mesh_stats(
+            &pmx.name,
+            pmx.positions.len() / 3,
+            pmx.indices.len() / 3,
+            pmx.materials.len(),
+            pmx.bones.len(),
+        )],
+        "textures": pmx.textures.iter().map(|t| json!({ "name": t })).collect::<Vec<_>>(),
+        "materials": materials,
+    }))
+}
+
+/// the JSON chunk of a binary glTF
+fn glb_json(data: &[u8]) -> Result<Json, Error> {
+    let u32_at = |at: usize| -> Result<u32, Error> {
+        let b = data.get(at..at + 4).ok_or("Truncated GLB.")?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    if u32_at(4)? != 2 {
+        return Err("Only GLB version 2 is supported.".into());
+    }
+    let len = u32_at(12)? as usize;
+    if u32_at(16)? != GLB_CHUNK_JSON {
+        return Err("GLB without a leading JSON chunk.".into());
+    }
+    let chunk = data.get(20..20 + len).ok_or("Truncated GLB.")?;
+    Ok(serde_json::from_slice(chunk)?)
+}
+
+pub fn inspect_gltf(gltf: &Json) -> Result<Json, Error> {
+    let list = |name: &str| gltf[name].as_array().cloned().unwrap_or_default();
+    let accessors = list("accessors");
+    let count = |i: &Json| -> usize {
+        i.as_u64()
+            .and_then(|i| accessors.get(i as usize))
+            .and_then(|a| a["count"].as_u64())
+            .unwrap_or(0) as usize
+    };
+    let nodes = list("nodes");
+    let skins = list("skins");
+
+    let mut meshes = Vec::new();
+    for (i, mesh) in list("meshes").iter().enumerate() {
+        let primitives = mesh["primitives"].as_array().cloned().unwrap_or_default();
+        let mut vertices = 0;
+        let mut triangles = 0;
+        for p in &primitives {
+            let v = count(&p["attributes"]["POSITION"]);
+            vertices += v;
+            if p["mode"].as_u64().unwrap_or(4) == 4 {
+                triangles += if p["indices"].is_null() {
+                    v
+                } else {
+                    count(&p["indices"])
+                } / 3;
+            }
+        }
+        let bones = nodes
+            .iter()
+            .find(|n| n["mesh"].as_u64() == Some(i as u64))
+            .and_then(|n| n["skin"].as_u64())
+            .and_then(|s| skins.get(s as usize))
+            .and_then(|s| s["joints"].as_array())
+            .map_or(0, |j| j.len());
+        meshes.push(mesh_stats(
+            mesh["name"].as_str().unwrap_or_default(),
+            vertices,
+            triangles,
+            primitives.len(),
+            bones,
+        ));
+    }
+
+    let images = list("images");
+    let image_name = |texture: &Json| -> Json {
+        let image = texture
+            .as_u64()
+            .and_then(|t| gltf["textures"].get(t as usize))
+            .and_then(|t| t["source"].as_u64())
+            .and_then(|s| images.get(s as usize));
+        match image {
+            Some(i) => i["name"]
+                .as_str()
+                .or(i["uri"].as_str())
+                .map_or(Json::Null, |n| json!(n)),
+            None => Json::Null,
+        }
+    };
+    let textures: Vec<Json> = images
+        .iter()
+        .map(|i| {
+            json!({
+                "name": i["name"].as_str().or(i["uri"].as_str()),
+                "format": i["mimeType"].as_str(),
+            })
+        })
+        .collect();
+    let materials: Vec<Json> = list("materials")
+        .iter()
+        .map(|m| {
+            let pbr = &m["pbrMetallicRoughness"];
+            json!({
+                "name": m["name"].as_str().unwrap_or_default(),
+                "textures": {
+                    "baseColor": image_name(&pbr["baseColorTexture"]["index"]),
+                    "metallicRoughness": image_name(&pbr["metallicRoughnessTexture"]["index"]),
+                    "normal": image_name(&m["normalTexture"]["index"]),
+                },
+            })
+        })
+        .collect();
+    let animations: Vec<Json> = list("animations")
+        .iter()
+        .map(|a| {
+            let length = a["samplers"]
+                .as_array()
+                .unwrap_or(&Vec::new())
+                .iter()
+                .filter_map(|s| accessors.get(s["input"].as_u64()? as usize)?["max"][0].as_f64())
+                .fold(0.0, f64::max);
+            json!({ "name": a["name"].as_str().unwrap_or_default(), "length": length })
+        })
+        .collect();
+
+    Ok(json!({
+        "format": "gltf",
+        "generator": gltf["asset"]["generator"],
+        "meshes": meshes,
+        "textures": textures,
+        "materials": materials,
+        "animations": animations,
+    }))
+}
+
+/// Render a summary as indented `key: value` lines.
+pub fn to_text(summary: &Json) -> String {
+    let mut out = String::new();
+    write_text(&mut out, summary, 0);
+    out
+}
+
+fn scalar(value: &Json) -> Option<String> {
+    match value {
+        Json::Null => Some("-".to_string()),
+        Json::String(s) => Some(s.clone()),
+        Json::Array(a) if a.is_empty() => Some("none".to_string()),
+        Json::Object(o) if o.is_empty() => Some("none".to_string()),
+        Json::Array(_) | Json::Object(_) => None,
+        v => Some(v.to_string()),
+    }
+}
+
+fn write_text(out: &mut String, value: &Json, indent: usize) {
+    let pad = "  ".repeat(indent);
+    match value {
+        Json::Object(map) => {
+            for (k, v) in map {
+                match scalar(v) {
+                    Some(s) => writeln!(out, "{pad}{k}: {s}").unwrap(),
+                    None => {
+                        writeln!(out, "{pad}{k}:").unwrap();
+                        write_text(out, v, indent + 1);
+                    }
+                }
+            }
+        }
+        Json::Array(items) => {
+            for item in items {
+                match scalar(item) {
+                    Some(s) => writeln!(out, "{pad}- {s}").unwrap(),
+                    None => {
+                        // the first line of the item goes right after the dash
+                        let mut nested = String::new();
+                        write_text(&mut nested, item, indent + 1);
+                        out.push_str(&pad);
+                        out.push_str("- ");
+                        out.push_str(nested.trim_start());
+                    }
+                }
+            }
+        }
+        v => writeln!(out, "{pad}{}", scalar(v).unwrap_or_default()).unwrap(),
+    }
+}
@@ -0,0 +1,70 @@
+//! Command line tools over `mari-formats`.
+
+pub mod convert;
+pub mod inspect;
+
+use std::path::Path;
+
+use mari_formats::gakumas::{Deobfuscator, deobfuscate_bundle};
+use mari_formats::unity::Bundle;
+
+pub type Error = Box<dyn std::error::Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// UnityFS bundle, possibly obfuscated by the game
+    Unity,
+    Obj,
+    Gltf,
+    Glb,
+    Pmx,
+    /// the native `.mari` scene container
+    Native,
+}
+
+/// Tell the format of a file by its magic bytes, else by its extension.
+pub fn sniff(path: &Path, data: &[u8]) -> Option<Format> {
+    if data.starts_with(b"UnityFS\0") {
+        return Some(Format::Unity);
+    }
+    if data.starts_with(b"glTF") {
+        return Some(Format::Glb);
+    }
+    if data.starts_with(b"PMX ") {
+        return Some(Format::Pmx);
+    }
+    if data.starts_with(b"MARI") {
+        return Some(Format::Native);
+    }
+    // the game's cache stores obfuscated bundles under their bundle name
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let mut signature = data.iter().take(8).copied().collect::<Vec<_>>();
+    Deobfuscator::new(name).apply(&mut signature, 0);
+    if signature == b"UnityFS\0" {
+        return Some(Format::Unity);
+    }
+
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "unity3d" | "bundle" => Some(Format::Unity),
+        "obj" => Some(Format::Obj),
+        "gltf" => Some(Format::Gltf),
+        "glb" => Some(Format::Glb),
+        "pmx" => Some(Format::Pmx),
+        "mari" => Some(Format::Native),
+        _ => None,
+    }
+}
+
+/// Read a bundle, deobfuscating it with its file name as the bundle name if needed.
+pub fn load_bundle(path: &Path, data: Vec<u8>) -> Result<Bundle, Error> {
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let data = deobfuscate_bundle(name, data)?;
+    Ok(Bundle::new_from_bytes(&data)?)
+}
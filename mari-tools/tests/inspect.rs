@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use mari_formats::{Actor, Model, Scene, Submesh};
+use mari_tools::Format;
+use mari_tools::inspect::{inspect, inspect_gltf, inspect_obj, to_text};
+use serde_json::json;
+
+/// a quad of two triangles in one submesh
+fn quad() -> Model {
+    Model {
+        vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+        mesh: vec![0, 1, 2, 0, 2, 3],
+        uvs: Vec::new(),
+        normals: Vec::new(),
+        colors: Vec::new(),
+        joints: Vec::new(),
+        weights: Vec::new(),
+        submeshes: vec![Submesh {
+            first: 0,
+            count: 6,
+            material: None,
+        }],
+    }
+}
+
+#[test]
+fn obj_mesh_stats() {
+    let obj = quad().write_obj();
+    let summary = inspect_obj(Path::new("models/quad.obj"), obj.as_bytes()).unwrap();
+    assert_eq!(summary["format"], "obj");
+    assert_eq!(
+        summary["meshes"],
+        json!([{ "name": "quad", "vertices": 4, "triangles": 2, "submeshes": 1, "bones": 0 }])
+    );
+}
+
+#[test]
+fn gltf_mesh_stats_and_clip_lengths() {
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "test" },
+        "nodes": [{ "mesh": 0, "skin": 0 }, {}, {}],
+        "skins": [{ "joints": [1, 2] }],
+        "meshes": [{
+            "name": "body",
+            "primitives": [
+                { "attributes": { "POSITION": 0 }, "indices": 1 },
+                // without indices, every three vertices a triangle
+                { "attributes": { "POSITION": 2 } },
+                // lines, no triangles
+                { "attributes": { "POSITION": 2 }, "mode": 1 }
+            ]
+        }],
+        "accessors": [
+            { "count": 4 },
+            { "count": 6 },
+            { "count": 3 },
+            { "count": 2, "max": [2.5] },
+            { "count": 2, "max": [0.5] }
+        ],
+        "animations": [{
+            "name": "wave",
+            "samplers": [{ "input": 3, "output": 0 }, { "input": 4, "output": 0 }]
+        }]
+    });
+    let summary = inspect_gltf(&gltf).unwrap();
+    assert_eq!(summary["generator"], "test");
+    assert_eq!(
+        summary["meshes"],
+        json!([{ "name": "body", "vertices": 10, "triangles": 3, "submeshes": 3, "bones": 2 }])
+    );
+    assert_eq!(
+        summary["animations"],
+        json!([{ "name": "wave", "length": 2.5 }])
+    );
+}
+
+#[test]
+fn glb_is_summarized_from_its_json_chunk() {
+    let mut scene = Scene::default();
+    scene
+        .actors
+        .insert("Temari".to_string(), Actor::new(quad()));
+    let glb = scene.write_glb().unwrap();
+    let path = Path::new("temari.glb");
+    let summary = inspect(path, Format::Glb, glb.clone()).unwrap();
+    assert_eq!(summary["format"], "gltf");
+    assert_eq!(summary["meshes"][0]["vertices"], 4);
+    assert_eq!(summary["meshes"][0]["triangles"], 2);
+
+    // cut inside the JSON chunk, and inside the header
+    for len in [24, 10] {
+        assert!(inspect(path, Format::Glb, glb[..len].to_vec()).is_err());
+    }
+}
+
+#[test]
+fn text_output() {
+    let summary = json!({
+        "format": "obj",
+        "meshes": [
+            { "name": "quad", "vertices": 4 },
+            { "name": "hat", "vertices": 3 }
+        ],
+        "textures": [],
+        "generator": null,
+        "tags": ["a", "b"]
+    });
+    assert_eq!(
+        to_text(&summary),
+        "format: obj\n\
+         meshes:\n\
+         \x20 - name: quad\n\
+         \x20   vertices: 4\n\
+         \x20 - name: hat\n\
+         \x20   vertices: 3\n\
+         textures: none\n\
+         generator: -\n\
+         tags:\n\
+         \x20 - a\n\
+         \x20 - b\n"
+    );
+}